@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use argh::FromArgs;
+
+use crate::log::LogLevel;
+
+/// Fed, a toy editor inspired by Neovim.
+#[derive(FromArgs, Debug, Default)]
+pub struct Args {
+    /// files to open, as `path` or `path:line:col`, with `+LINE` to start on a line of the first
+    /// one, or `+` for its last line
+    #[argh(positional, arg_name = "file")]
+    pub args: Vec<String>,
+
+    /// theme to use instead of the one in the config, by name or path
+    #[argh(option)]
+    pub theme: Option<String>,
+
+    /// config file to read instead of ~/.config/fed.toml
+    #[argh(option)]
+    pub config: Option<String>,
+
+    /// open the files read-only, so writing them back needs `:w!`
+    #[argh(switch, short = 'R')]
+    pub readonly: bool,
+
+    /// open two files side by side, showing how they differ
+    #[argh(switch, short = 'd')]
+    pub diff: bool,
+
+    /// recover the files from their swap files, or list the swap files when no file is given
+    #[argh(switch, short = 'r')]
+    pub recover: bool,
+
+    /// how much to log: off, error, warn, info (the default), debug or trace
+    #[argh(option)]
+    pub log_level: Option<LogLevel>,
+
+    /// file to log to instead of /tmp/fed.log
+    #[argh(option)]
+    pub log_file: Option<String>,
+
+    /// run ex commands on the files without opening the screen, separated by `|` or newlines,
+    /// or the ones in a script file when given its path
+    #[argh(option, short = 'c')]
+    pub batch: Option<String>,
+
+    /// print the version and exit
+    #[argh(switch, short = 'v')]
+    pub version: bool,
+}
+
+impl Args {
+    /// The files to open, each with the zero-based line and column to start on when given.
+    pub fn files(&self) -> Vec<(String, Option<(usize, usize)>)> {
+        let mut files: Vec<_> = self
+            .args
+            .iter()
+            .filter(|arg| !arg.starts_with('+'))
+            .map(|arg| split_position(arg))
+            .collect();
+
+        if let (Some(line), Some((_, position))) = (self.line(), files.first_mut()) {
+            *position = Some((line, 0));
+        }
+        files
+    }
+
+    /// The zero-based line from the last `+LINE`, where `+` alone is the last line.
+    fn line(&self) -> Option<usize> {
+        let line = self
+            .args
+            .iter()
+            .rev()
+            .find_map(|arg| arg.strip_prefix('+'))?;
+        if line.is_empty() {
+            return Some(usize::MAX);
+        }
+        line.parse::<usize>().ok().map(|n| n.saturating_sub(1))
+    }
+}
+
+/// Splits a `path:line` or `path:line:col` argument, as printed by compilers and grep, into the
+/// path and a zero-based position. A file that exists under the full name is taken as is.
+fn split_position(arg: &str) -> (String, Option<(usize, usize)>) {
+    if Path::new(arg).exists() {
+        return (arg.to_string(), None);
+    }
+
+    let number = |s: &str| s.parse::<usize>().ok().filter(|&n| n > 0).map(|n| n - 1);
+    let mut parts = arg.rsplitn(3, ':');
+    let (last, middle, rest) = (parts.next(), parts.next(), parts.next());
+
+    match (rest, middle.and_then(number), last.and_then(number)) {
+        (Some(path), Some(line), Some(column)) if !path.is_empty() => {
+            (path.to_string(), Some((line, column)))
+        }
+        (_, _, Some(line)) => match arg.rsplit_once(':') {
+            Some((path, _)) if !path.is_empty() => (path.to_string(), Some((line, 0))),
+            _ => (arg.to_string(), None),
+        },
+        _ => (arg.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, argh::EarlyExit> {
+        Args::from_args(&["fed"], args)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&["a.rs", "+12", "b.rs", "--theme", "GitHub", "-R"]).unwrap();
+        assert_eq!(
+            args.files(),
+            vec![
+                ("a.rs".to_string(), Some((11, 0))),
+                ("b.rs".to_string(), None)
+            ]
+        );
+        assert_eq!(args.theme.as_deref(), Some("GitHub"));
+        assert!(args.readonly);
+        assert_eq!(args.log_level, None);
+
+        let args = parse(&["+", "--log-level", "off", "--log-file", "fed.log"]).unwrap();
+        assert!(args.files().is_empty());
+        assert_eq!(args.line(), Some(usize::MAX));
+        assert_eq!(args.log_level, Some(LogLevel::Off));
+        assert_eq!(args.log_file.as_deref(), Some("fed.log"));
+
+        let args = parse(&["+", "a.rs:3"]).unwrap();
+        assert_eq!(
+            args.files(),
+            vec![("a.rs".to_string(), Some((usize::MAX, 0)))]
+        );
+
+        let args = parse(&["--diff", "a.rs", "b.rs"]).unwrap();
+        assert!(args.diff);
+        assert_eq!(args.files().len(), 2);
+
+        let args = parse(&["-c", "%s/a/b/g | w", "a.rs"]).unwrap();
+        assert_eq!(args.batch.as_deref(), Some("%s/a/b/g | w"));
+        assert_eq!(args.files(), vec![("a.rs".to_string(), None)]);
+
+        assert!(parse(&["--log-level", "loud"]).is_err());
+        assert!(parse(&["--help"]).is_err());
+    }
+
+    #[test]
+    fn test_split_position() {
+        let split = |arg| split_position(arg);
+        assert_eq!(split("a.rs:120:5"), ("a.rs".to_string(), Some((119, 4))));
+        assert_eq!(split("a.rs:120"), ("a.rs".to_string(), Some((119, 0))));
+        assert_eq!(split("a.rs:120:"), ("a.rs:120:".to_string(), None));
+        assert_eq!(
+            split("dir:x/a.rs:2:1"),
+            ("dir:x/a.rs".to_string(), Some((1, 0)))
+        );
+        assert_eq!(split("a.rs"), ("a.rs".to_string(), None));
+        assert_eq!(split(":3"), (":3".to_string(), None));
+
+        // existing files keep their full name
+        assert_eq!(split("src/main.rs"), ("src/main.rs".to_string(), None));
+    }
+}
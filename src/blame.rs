@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Who last changed a line and in which commit, from `git blame`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameLine {
+    /// The abbreviated commit hash, all zeros for lines that aren't committed yet.
+    pub commit: String,
+    pub author: String,
+    /// The author date as `YYYY-MM-DD`, in the author's time zone.
+    pub date: String,
+    pub summary: String,
+}
+
+impl BlameLine {
+    /// The annotation shown after the line, like `1a2b3c4 Jane Doe, 2024-03-01 · Fix typo`.
+    pub fn annotation(&self) -> String {
+        if self.commit.bytes().all(|b| b == b'0') {
+            return "Not committed yet".to_string();
+        }
+        format!(
+            "{} {}, {} · {}",
+            self.commit, self.author, self.date, self.summary
+        )
+    }
+}
+
+/// The blame of each line of `text`, the unsaved contents of the file at `path`, so lines edited
+/// since the last write show as not committed.
+pub fn blame(path: &Path, text: &str) -> anyhow::Result<Vec<BlameLine>> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let Some(name) = path.file_name() else {
+        anyhow::bail!("Not a file: {}", path.display());
+    };
+
+    let mut child = Command::new("git")
+        .current_dir(dir.unwrap_or(Path::new(".")))
+        .args(["blame", "--porcelain", "--contents", "-", "--"])
+        .arg(name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow::anyhow!("Can't run git: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{}", error.lines().next().unwrap_or("git blame failed"));
+    }
+    Ok(parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Reads the output of `git blame --porcelain`, where each line of the file starts with a header
+/// naming its commit, followed by the details of that commit the first time it appears.
+fn parse(porcelain: &str) -> Vec<BlameLine> {
+    let mut commits: HashMap<&str, BlameLine> = HashMap::new();
+    let mut lines = vec![];
+    let mut commit = None;
+    let mut time = None;
+
+    for line in porcelain.lines() {
+        if line.starts_with('\t') {
+            // the line of the file ends each entry
+            if let Some(blame) = commit.and_then(|commit| commits.get(commit)) {
+                lines.push(blame.clone());
+            }
+            continue;
+        }
+
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "author" | "author-time" | "author-tz" | "summary" => {
+                let Some(blame) = commit.and_then(|commit| commits.get_mut(commit)) else {
+                    continue;
+                };
+                match key {
+                    "author" => blame.author = value.to_string(),
+                    "author-time" => time = value.parse::<i64>().ok(),
+                    "author-tz" => blame.date = date(time.unwrap_or_default(), value),
+                    _ => blame.summary = value.to_string(),
+                }
+            }
+            hash if hash.len() == 40 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                commits.entry(hash).or_insert_with(|| BlameLine {
+                    commit: hash[..7].to_string(),
+                    author: String::new(),
+                    date: String::new(),
+                    summary: String::new(),
+                });
+                commit = Some(hash);
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+/// Formats the unix `time` as a `YYYY-MM-DD` date in the time zone `tz`, given like `+0130`.
+fn date(time: i64, tz: &str) -> String {
+    let offset = match tz.split_at_checked(1) {
+        Some((sign, hhmm)) if hhmm.len() == 4 => {
+            let minutes =
+                hhmm[..2].parse::<i64>().unwrap_or(0) * 60 + hhmm[2..].parse::<i64>().unwrap_or(0);
+            if sign == "-" {
+                -minutes * 60
+            } else {
+                minutes * 60
+            }
+        }
+        _ => 0,
+    };
+
+    // converts days since the epoch to a civil date, as in Howard Hinnant's `civil_from_days`
+    let days = (time + offset).div_euclid(86_400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let a = "a".repeat(40);
+        let zero = "0".repeat(40);
+        let porcelain = format!(
+            "{a} 1 1 2\nauthor Jane Doe\nauthor-mail <jane@example.com>\nauthor-time 1709251200\n\
+             author-tz -0500\nsummary Fix typo\nfilename f.txt\n\tone\n\
+             {a} 2 2\n\ttwo\n\
+             {zero} 3 3 1\nauthor Not Committed Yet\nauthor-time 1709251200\nauthor-tz +0000\n\
+             summary Version of f.txt from f.txt\nfilename f.txt\n\tthree\n"
+        );
+        let lines = parse(&porcelain);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], lines[1]);
+        assert_eq!(
+            lines[0].annotation(),
+            "aaaaaaa Jane Doe, 2024-02-29 · Fix typo"
+        );
+        assert_eq!(lines[2].annotation(), "Not committed yet");
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(date(0, "+0000"), "1970-01-01");
+        assert_eq!(date(1709251200, "+0000"), "2024-03-01");
+        assert_eq!(date(1709251200, "-0030"), "2024-02-29");
+        assert_eq!(date(951782400, "+0000"), "2000-02-29");
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    env,
+    io::{stdout, Write},
+    process::{Command, Stdio},
+};
+
+/// Commands that copy their stdin to the system clipboard and paste it to their stdout, in the
+/// order they are tried on this system.
+fn backends() -> Vec<(&'static [&'static str], &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![(&["pbcopy"], &["pbpaste"])];
+    }
+    if cfg!(windows) {
+        return vec![(
+            &["clip.exe"],
+            &["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard"],
+        )];
+    }
+
+    let mut backends = vec![];
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        backends.push((&["wl-copy"][..], &["wl-paste", "--no-newline"][..]));
+    }
+    if env::var_os("DISPLAY").is_some() {
+        backends.push((
+            &["xclip", "-selection", "clipboard"][..],
+            &["xclip", "-selection", "clipboard", "-o"][..],
+        ));
+        backends.push((
+            &["xsel", "--clipboard", "--input"][..],
+            &["xsel", "--clipboard", "--output"][..],
+        ));
+    }
+    backends
+}
+
+/// Copies `text` to the system clipboard. It goes to the terminal as an OSC 52 escape, which
+/// reaches the clipboard of the machine the terminal runs on even over ssh, and to the first
+/// clipboard command that works here. Fails only when neither way is available.
+pub fn copy(text: &str) -> anyhow::Result<()> {
+    let osc52 = write_osc52(text);
+    let native = backends()
+        .into_iter()
+        .any(|(command, _)| run_copy(command, text).is_ok());
+
+    match osc52 {
+        Ok(()) => Ok(()),
+        Err(_) if native => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads the system clipboard through the first clipboard command that works here. Terminals
+/// rarely answer OSC 52 reads, so there is no fallback for those.
+pub fn paste() -> anyhow::Result<String> {
+    for (_, command) in backends() {
+        let Ok(output) = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+        else {
+            continue;
+        };
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+        }
+    }
+
+    anyhow::bail!("No clipboard command found, install xclip, xsel or wl-clipboard")
+}
+
+fn write_osc52(text: &str) -> anyhow::Result<()> {
+    let mut out = stdout();
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn run_copy(command: &[&str], text: &str) -> anyhow::Result<()> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", command[0], status);
+    }
+    Ok(())
+}
+
+/// Encodes `bytes` as standard base64 with padding, as OSC 52 expects.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("héllo\n".as_bytes()), "aMOpbGxvCg==");
+    }
+}
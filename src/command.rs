@@ -1,50 +1,647 @@
-use std::io::{stdout, Write};
+use std::iter;
 
-use crossterm::{
-    cursor::MoveTo,
-    event::{read, Event, KeyEvent},
-    style::{PrintStyledContent, Stylize},
-    QueueableCommand,
-};
+use crossterm::style::{Color, Stylize};
 
-use crate::Editor;
+use crate::{core::action::Action, Editor, Message};
 
-pub fn get_command(e: &Editor) -> anyhow::Result<Option<String>> {
+/// How many entered command lines the history keeps.
+const MAX_HISTORY: usize = 100;
+
+/// The text typed after `:`, edited in place around a cursor, with the history of command lines
+/// entered before.
+#[derive(Debug, Default)]
+pub struct CommandLine {
+    text: Vec<char>,
+    /// Char index of the cursor in `text`.
+    cursor: usize,
+    history: Vec<String>,
+    /// The history entry shown while going through the history, with the text typed before it
+    /// started, which the entries it goes through begin with.
+    browsing: Option<(usize, String)>,
+}
+
+impl CommandLine {
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += 1;
+        self.browsing = None;
+    }
+
+    /// Deletes the char before the cursor. Returns false when the line was already empty, which
+    /// leaves command mode like in vim.
+    pub fn backspace(&mut self) -> bool {
+        if self.text.is_empty() {
+            return false;
+        }
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+            self.browsing = None;
+        }
+        true
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+            self.browsing = None;
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.len());
+    }
+
+    pub fn move_to_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Shows the previous command line in the history that starts with the typed text.
+    pub fn previous(&mut self) {
+        let (end, prefix) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => (self.history.len(), self.text()),
+        };
+        let found = (0..end)
+            .rev()
+            .find(|&i| self.history[i].starts_with(&prefix));
+        self.browse(
+            found.or(Some(end).filter(|&i| i < self.history.len())),
+            prefix,
+        );
+    }
+
+    /// Shows the next command line in the history that starts with the typed text, or the typed
+    /// text again past the newest one.
+    pub fn next(&mut self) {
+        let Some((start, prefix)) = self.browsing.take() else {
+            return;
+        };
+        let found = (start + 1..self.history.len()).find(|&i| self.history[i].starts_with(&prefix));
+        self.browse(found, prefix);
+    }
+
+    fn browse(&mut self, entry: Option<usize>, prefix: String) {
+        let text = match entry {
+            Some(i) => self.history[i].clone(),
+            None => prefix.clone(),
+        };
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+        self.browsing = entry.map(|i| (i, prefix));
+    }
+
+    /// Empties the line, returning what it had and adding it to the history unless it's blank or
+    /// the same as the last entry.
+    pub fn submit(&mut self) -> String {
+        let text = self.text();
+        self.clear();
+        if !text.trim().is_empty() && self.history.last() != Some(&text) {
+            self.history.push(text.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        text
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.browsing = None;
+    }
+
+    /// The part of `:` and the text that fits in `width` columns, scrolled to keep the cursor
+    /// visible, with the column of the cursor.
+    pub fn view(&self, width: usize) -> (String, usize) {
+        let line: Vec<char> = iter::once(':').chain(self.text.iter().copied()).collect();
+        let first = (self.cursor + 2).saturating_sub(width);
+        let view = line.iter().skip(first).take(width).collect();
+        (view, self.cursor + 1 - first)
+    }
+}
+
+/// Draws the command line being typed in place of the message line.
+pub fn draw_commandline(e: &mut Editor) -> anyhow::Result<()> {
     let (fg, bg) = e.theme.default_colors();
-    let mut command = String::new();
-
-    loop {
-        clear_commandline(&e)?;
-        stdout().queue(MoveTo(0, e.command_y() as u16))?;
-        stdout().queue(PrintStyledContent(format!(":{command}").with(fg).on(bg)))?;
-        stdout().flush()?;
-
-        match read()? {
-            Event::Key(KeyEvent { code, .. }) => match code {
-                crossterm::event::KeyCode::Esc => break,
-                crossterm::event::KeyCode::Enter => break,
-                crossterm::event::KeyCode::Backspace => {
-                    command.pop();
-                }
-                crossterm::event::KeyCode::Char(c) => {
-                    command.push(c);
-                }
-                _ => {}
+    let (text, _) = e.command_line.view(e.width);
+
+    clear_commandline(e)?;
+    e.screen.move_to(0, e.command_y());
+    e.screen.print_styled(text.with(fg).on(bg));
+
+    Ok(())
+}
+
+/// An ex command the command line accepts.
+struct Spec {
+    name: &'static str,
+    /// Length of the shortest accepted abbreviation, `q` for `quit` or `bn` for `bnext`.
+    min: usize,
+    /// Whether the command acts on a line range.
+    range: bool,
+    /// Whether the command accepts `!` to force it.
+    bang: bool,
+}
+
+const fn spec(name: &'static str, min: usize, range: bool, bang: bool) -> Spec {
+    Spec {
+        name,
+        min,
+        range,
+        bang,
+    }
+}
+
+/// Every command, checked in order so a shorter command wins an ambiguous abbreviation.
+const COMMANDS: &[Spec] = &[
+    spec("quit", 1, false, true),
+    spec("qall", 2, false, true),
+    spec("write", 1, false, true),
+    spec("wq", 2, false, true),
+    spec("xit", 1, false, true),
+    spec("edit", 1, false, true),
+    spec("files", 3, false, false),
+    spec("bnext", 2, false, false),
+    spec("bprevious", 2, false, false),
+    spec("buffer", 1, false, false),
+    spec("buffers", 7, false, false),
+    spec("ls", 2, false, false),
+    spec("new", 3, false, false),
+    spec("help", 1, false, false),
+    spec("delete", 1, true, false),
+    spec("substitute", 1, true, false),
+    spec("theme", 2, false, false),
+    spec("colorscheme", 4, false, false),
+    spec("set", 2, false, false),
+    spec("iabbrev", 3, false, false),
+    spec("iunabbrev", 4, false, false),
+    spec("jumps", 2, false, false),
+    spec("messages", 3, false, false),
+    spec("grep", 2, false, false),
+    spec("make", 3, false, false),
+    spec("cd", 2, false, false),
+    spec("lcd", 2, false, false),
+    spec("pwd", 2, false, false),
+    spec("cnext", 2, false, false),
+    spec("cprevious", 2, false, false),
+    spec("copen", 4, false, false),
+    spec("blame", 3, false, false),
+    spec("highlight", 2, false, false),
+    spec("Inspect", 7, false, false),
+    spec("fmt", 3, false, false),
+    spec("diffsplit", 5, false, false),
+    spec("diffoff", 5, false, false),
+    spec("diffreload", 5, false, false),
+    spec("diffget", 5, true, false),
+    spec("diffput", 6, true, false),
+    spec("ours", 4, false, false),
+    spec("theirs", 6, false, false),
+    spec("both", 4, false, false),
+];
+
+/// Every command with the part that can be left out in brackets, like `:q[uit]`.
+pub fn command_names() -> Vec<String> {
+    COMMANDS
+        .iter()
+        .map(|spec| match spec.name.split_at(spec.min) {
+            (name, "") => format!(":{name}"),
+            (abbreviation, rest) => format!(":{abbreviation}[{rest}]"),
+        })
+        .collect()
+}
+
+/// A parsed command line, like `3,9d`, `w! path` or `%s/a/b/g`.
+#[derive(Debug, PartialEq)]
+pub struct Command<'a> {
+    /// Zero-based inclusive lines the command applies to, when given.
+    pub range: Option<(usize, usize)>,
+    /// Full name of the command, or empty for a bare range like `:42` that moves to a line.
+    pub name: &'static str,
+    pub force: bool,
+    pub args: Option<&'a str>,
+}
+
+impl<'a> Command<'a> {
+    /// Parses `line`, resolving abbreviations to the full command name. `current` and `last` are
+    /// the lines `.` and `$` refer to, and `mark` gives the line of a mark like `'<`.
+    pub fn parse(
+        line: &'a str,
+        current: usize,
+        last: usize,
+        mark: &dyn Fn(char) -> Option<usize>,
+    ) -> anyhow::Result<Self> {
+        let line = line.trim();
+        let (range, rest) = parse_range(line, current, last, mark)?;
+
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let (name, rest) = rest.split_at(end);
+        let (force, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let args = Some(rest.trim_start()).filter(|args| !args.is_empty());
+
+        // `:!cmd` runs a shell command, `:{range}!cmd` filters the range through it
+        if name.is_empty() && force {
+            let Some(args) = args else {
+                anyhow::bail!("E471: Argument required");
+            };
+            return Ok(Command {
+                range,
+                name: "!",
+                force: false,
+                args: Some(args),
+            });
+        }
+        if name.is_empty() {
+            if range.is_none() || force || args.is_some() {
+                anyhow::bail!("E492: Not an editor command: {line}");
+            }
+            return Ok(Command {
+                range,
+                name: "",
+                force,
+                args,
+            });
+        }
+
+        let Some(spec) = COMMANDS
+            .iter()
+            .find(|spec| name.len() >= spec.min && spec.name.starts_with(name))
+        else {
+            anyhow::bail!("E492: Not an editor command: {line}");
+        };
+
+        if range.is_some() && !spec.range {
+            anyhow::bail!("E481: No range allowed");
+        }
+        if force && !spec.bang {
+            anyhow::bail!("E477: No ! allowed");
+        }
+
+        Ok(Command {
+            range,
+            name: spec.name,
+            force,
+            args,
+        })
+    }
+
+    /// The action the command runs.
+    pub fn action(&self) -> anyhow::Result<Action> {
+        let arg = self.args.map(str::to_string);
+
+        let action = match self.name {
+            "" => Action::GoToLine(self.range.map_or(0, |(_, end)| end)),
+            "!" => match self.range {
+                Some(range) => Action::Filter(arg.unwrap_or_default(), range),
+                None => Action::Shell(arg.unwrap_or_default()),
+            },
+            "quit" | "qall" => Action::Quit(self.force),
+            "write" => Action::Write(arg, self.force),
+            "wq" => Action::WriteQuit(arg, self.force),
+            "xit" => Action::Exit(arg, self.force),
+            "edit" => Action::Edit(arg, self.force),
+            "files" => Action::OpenPicker,
+            "bnext" => Action::NextBuffer,
+            "bprevious" => Action::PreviousBuffer,
+            "buffer" => match self.args.and_then(|arg| arg.parse().ok()) {
+                Some(n) => Action::SwitchBuffer(n),
+                None => anyhow::bail!(
+                    "E86: Buffer {} does not exist",
+                    self.args.unwrap_or_default()
+                ),
+            },
+            "buffers" | "ls" => Action::ListBuffers,
+            "new" => Action::NewBuffer,
+            "help" => Action::Help,
+            "delete" => Action::DeleteLines(self.range),
+            "substitute" => Action::Substitute(arg.unwrap_or_default(), self.range),
+            "theme" | "colorscheme" => Action::Theme(arg),
+            "set" => Action::Set(arg.unwrap_or_default()),
+            "iabbrev" => Action::Abbreviate(arg),
+            "iunabbrev" => match arg {
+                Some(arg) => Action::Unabbreviate(arg),
+                None => anyhow::bail!("E471: Argument required"),
+            },
+            "jumps" => Action::ListJumps,
+            "messages" => Action::ShowMessages,
+            "grep" => Action::Grep(arg.unwrap_or_default()),
+            "make" => Action::Make(arg),
+            "cd" => Action::ChangeDir(arg, false),
+            "lcd" => Action::ChangeDir(arg, true),
+            "pwd" => Action::PrintDir,
+            "cnext" => Action::NextQuickfix,
+            "cprevious" => Action::PreviousQuickfix,
+            "copen" => Action::OpenQuickfix,
+            "blame" => Action::ToggleBlame,
+            "highlight" | "Inspect" => Action::InspectHighlight,
+            "fmt" => Action::Format,
+            "diffsplit" => match arg {
+                Some(arg) => Action::DiffSplit(arg),
+                None => anyhow::bail!("E471: Argument required"),
             },
-            _ => {}
+            "diffoff" => Action::DiffOff,
+            "diffreload" => Action::DiffReload,
+            "diffget" => Action::DiffGet(self.range),
+            "diffput" => Action::DiffPut(self.range),
+            "ours" => Action::AcceptOurs,
+            "theirs" => Action::AcceptTheirs,
+            "both" => Action::AcceptBoth,
+            name => anyhow::bail!("E492: Not an editor command: {name}"),
+        };
+
+        Ok(action)
+    }
+}
+
+/// Splits a script into command lines at newlines and at `|`, as in `%s/a/b/g | w`, where `\|`
+/// passes a `|` on to the command. A `:!cmd` takes the rest of its line, bars and all.
+pub fn split_commands(script: &str) -> Vec<String> {
+    let mut commands = vec![];
+    for line in script.lines() {
+        let mut command = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                _ if is_shell(&command) => command.push(c),
+                '\\' if chars.peek() == Some(&'|') => command.push(chars.next().unwrap()),
+                '|' => commands.push(std::mem::take(&mut command)),
+                c => command.push(c),
+            }
         }
+        commands.push(command);
     }
+    commands
+        .into_iter()
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+/// Whether `command` runs a shell command, whose text goes on to the end of the line.
+fn is_shell(command: &str) -> bool {
+    parse_range(command.trim_start(), 0, 0, &|_| Some(0))
+        .is_ok_and(|(_, rest)| rest.trim_start().starts_with('!'))
+}
 
-    clear_commandline(&e)?;
-    Ok(Some(command))
+/// Splits a leading line range off `cmd`, returning it as zero-based inclusive lines. Supports
+/// `%`, a single line and `a,b`, where each address is a number, `.`, `$` or a mark like `'<`
+/// whose line `mark` gives.
+pub fn parse_range<'a>(
+    cmd: &'a str,
+    current: usize,
+    last: usize,
+    mark: &dyn Fn(char) -> Option<usize>,
+) -> anyhow::Result<(Option<(usize, usize)>, &'a str)> {
+    if let Some(rest) = cmd.strip_prefix('%') {
+        return Ok((Some((0, last)), rest));
+    }
+
+    let Some((a, rest)) = parse_address(cmd, current, last, mark)? else {
+        return Ok((None, cmd));
+    };
+    let (b, rest) = match rest.strip_prefix(',') {
+        Some(rest) => match parse_address(rest, current, last, mark)? {
+            Some(address) => address,
+            None => return Ok((None, cmd)),
+        },
+        None => (a, rest),
+    };
+    Ok((Some((a.min(b), a.max(b))), rest))
+}
+
+/// Splits the address at the start of `cmd` off, returning its line.
+fn parse_address<'a>(
+    cmd: &'a str,
+    current: usize,
+    last: usize,
+    mark: &dyn Fn(char) -> Option<usize>,
+) -> anyhow::Result<Option<(usize, &'a str)>> {
+    let mut chars = cmd.chars();
+    let address = match chars.next() {
+        Some('.') => Some((current, chars.as_str())),
+        Some('$') => Some((last, chars.as_str())),
+        Some('\'') => match chars.next() {
+            Some(name) => match mark(name) {
+                Some(line) => Some((line.min(last), chars.as_str())),
+                None => anyhow::bail!("E20: Mark not set"),
+            },
+            None => None,
+        },
+        _ => {
+            let end = cmd.find(|c: char| !c.is_ascii_digit()).unwrap_or(cmd.len());
+            let (number, rest) = cmd.split_at(end);
+            number
+                .parse::<usize>()
+                .ok()
+                .map(|n| (n.saturating_sub(1).min(last), rest))
+        }
+    };
+    Ok(address)
 }
 
-pub fn clear_commandline(e: &Editor) -> anyhow::Result<()> {
+pub fn clear_commandline(e: &mut Editor) -> anyhow::Result<()> {
     let (fg, bg) = e.theme.default_colors();
     let width = e.width;
 
-    stdout().queue(MoveTo(0, e.command_y() as u16))?;
-    stdout().queue(PrintStyledContent(" ".repeat(width).with(fg).on(bg)))?;
+    e.screen.move_to(0, e.command_y());
+    e.screen.print_styled(" ".repeat(width).with(fg).on(bg));
 
     Ok(())
 }
+
+pub fn draw_message(e: &mut Editor) -> anyhow::Result<()> {
+    let Some(message) = &e.message else {
+        return Ok(());
+    };
+
+    let (fg, bg) = e.theme.default_colors();
+    let (text, fg) = match message {
+        Message::Info(text) => (text, fg),
+        Message::Error(text) => (text, Color::Red),
+    };
+
+    let text: String = text.chars().take(e.width).collect();
+    e.screen.move_to(0, e.command_y());
+    e.screen.print_styled(text.with(fg).on(bg));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_commands() {
+        assert_eq!(split_commands("%s/foo/bar/g | w"), ["%s/foo/bar/g", "w"]);
+        assert_eq!(
+            split_commands("set ts=2\n\n1d|wq"),
+            ["set ts=2", "1d", "wq"]
+        );
+        assert_eq!(split_commands("s/a\\|b/c/"), ["s/a|b/c/"]);
+        assert_eq!(split_commands("'<,'>!sort | uniq"), ["'<,'>!sort | uniq"]);
+        assert_eq!(split_commands("w | !ls | wc"), ["w", "!ls | wc"]);
+        assert_eq!(split_commands("!grep a\\|b"), ["!grep a\\|b"]);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let mark = |name| (name == '<').then_some(4);
+        let range = |cmd| parse_range(cmd, 3, 9, &mark).unwrap();
+        assert_eq!(range("%s/a/b/"), (Some((0, 9)), "s/a/b/"));
+        assert_eq!(
+            parse_range("10,20s/a/b/", 3, 99, &mark).unwrap(),
+            (Some((9, 19)), "s/a/b/")
+        );
+        assert_eq!(range(".,$d"), (Some((3, 9)), "d"));
+        assert_eq!(range("5,2s"), (Some((1, 4)), "s"));
+        assert_eq!(range("s/a/b/"), (None, "s/a/b/"));
+        assert_eq!(range("'<,$!sort"), (Some((4, 9)), "!sort"));
+        assert!(parse_range("'>d", 3, 9, &mark).is_err());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let command = Command::parse("3,9d", 0, 20, &|_| None).unwrap();
+        assert_eq!(command.range, Some((2, 8)));
+        assert_eq!(command.name, "delete");
+
+        let command = Command::parse("w! out.txt ", 0, 20, &|_| None).unwrap();
+        assert_eq!(command.name, "write");
+        assert!(command.force);
+        assert_eq!(command.args, Some("out.txt"));
+
+        let command = Command::parse("%s/a b/c/g", 0, 20, &|_| None).unwrap();
+        assert_eq!(command.name, "substitute");
+        assert_eq!(command.args, Some("/a b/c/g"));
+
+        assert_eq!(Command::parse("qa", 0, 20, &|_| None).unwrap().name, "qall");
+        assert_eq!(
+            Command::parse("e src/main.rs", 0, 20, &|_| None)
+                .unwrap()
+                .name,
+            "edit"
+        );
+        assert_eq!(
+            Command::parse("b2", 0, 20, &|_| None).unwrap().args,
+            Some("2")
+        );
+        assert_eq!(
+            Command::parse("buffers", 0, 20, &|_| None).unwrap().name,
+            "buffers"
+        );
+        assert_eq!(
+            Command::parse("$", 4, 20, &|_| None).unwrap().range,
+            Some((20, 20))
+        );
+
+        let action = |line| {
+            Command::parse(line, 4, 20, &|_| None)
+                .unwrap()
+                .action()
+                .unwrap()
+        };
+        assert_eq!(action("q!"), Action::Quit(true));
+        assert_eq!(action("h"), Action::Help);
+        assert_eq!(
+            action("w a.txt"),
+            Action::Write(Some("a.txt".to_string()), false)
+        );
+        assert_eq!(action("12"), Action::GoToLine(11));
+        assert_eq!(action("d"), Action::DeleteLines(None));
+        assert_eq!(action("se ff=dos"), Action::Set("ff=dos".to_string()));
+        assert_eq!(action("ju"), Action::ListJumps);
+        assert_eq!(
+            action("iab teh the"),
+            Action::Abbreviate(Some("teh the".to_string()))
+        );
+        assert_eq!(action("iuna teh"), Action::Unabbreviate("teh".to_string()));
+        assert_eq!(action("mes"), Action::ShowMessages);
+        assert_eq!(action("gr fo+ bar"), Action::Grep("fo+ bar".to_string()));
+        assert_eq!(action("mak"), Action::Make(None));
+        assert_eq!(action("cd"), Action::ChangeDir(None, false));
+        assert_eq!(
+            action("lcd src"),
+            Action::ChangeDir(Some("src".to_string()), true)
+        );
+        assert_eq!(action("pw"), Action::PrintDir);
+        assert_eq!(
+            action("make --release"),
+            Action::Make(Some("--release".to_string()))
+        );
+        assert_eq!(action("cn"), Action::NextQuickfix);
+        assert_eq!(action("cope"), Action::OpenQuickfix);
+        assert_eq!(action("hi"), Action::InspectHighlight);
+        assert_eq!(action("Inspect"), Action::InspectHighlight);
+        assert_eq!(action("diffs b.rs"), Action::DiffSplit("b.rs".to_string()));
+        assert_eq!(action("2,3diffg"), Action::DiffGet(Some((1, 2))));
+        assert_eq!(action("diffpu"), Action::DiffPut(None));
+        assert_eq!(action("theirs"), Action::AcceptTheirs);
+        assert_eq!(action("!ls -la"), Action::Shell("ls -la".to_string()));
+        assert_eq!(
+            action("%!sort"),
+            Action::Filter("sort".to_string(), (0, 20))
+        );
+        assert!(Command::parse("!", 0, 20, &|_| None).is_err());
+        assert!(Command::parse("b x", 0, 20, &|_| None)
+            .unwrap()
+            .action()
+            .is_err());
+
+        assert!(Command::parse("nope", 0, 20, &|_| None).is_err());
+        assert!(Command::parse("1,2w", 0, 20, &|_| None).is_err());
+        assert!(Command::parse("bn!", 0, 20, &|_| None).is_err());
+    }
+
+    #[test]
+    fn test_command_line() {
+        let mut line = CommandLine::default();
+        "wq".chars().for_each(|c| line.insert(c));
+        line.move_left();
+        line.insert('!');
+        assert_eq!(line.text(), "w!q");
+        line.move_to_start();
+        line.delete();
+        assert_eq!(line.view(80), (":!q".to_string(), 1));
+        assert_eq!(line.submit(), "!q");
+
+        for text in ["set wrap", "grep foo", "set nowrap"] {
+            text.chars().for_each(|c| line.insert(c));
+            line.submit();
+        }
+
+        // going through the history only stops at lines starting with the typed text
+        line.insert('s');
+        line.previous();
+        assert_eq!(line.text(), "set nowrap");
+        line.previous();
+        assert_eq!(line.text(), "set wrap");
+        line.previous();
+        assert_eq!(line.text(), "set wrap");
+        line.next();
+        line.next();
+        assert_eq!(line.text(), "s");
+
+        // long lines scroll to keep the cursor in view
+        assert_eq!(line.view(2), ("s".to_string(), 1));
+        assert!(line.backspace());
+        assert!(!line.backspace());
+    }
+}
@@ -1,55 +1,335 @@
+use std::{collections::HashMap, path::PathBuf};
+
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    log::{LogConfig, LogSettings},
+    tui::{
+        background::Background,
+        colors::ColorSupport,
+        cursor_style::{CursorConfig, CursorStyles},
+        keymap::Keymap,
+        statusline::{Statusline, StatuslineConfig},
+    },
+};
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ConfigFile {
     pub faded_line_numbers: Option<bool>,
+    pub number: Option<bool>,
+    pub number_width: Option<u8>,
+    pub gutter_separator: Option<String>,
+    pub gutter_padding: Option<u8>,
     pub tab_size: Option<u8>,
     pub tab_to_spaces: Option<bool>,
+    pub detect_indent: Option<bool>,
     pub mouse_scroll_lines: Option<u8>,
+    pub scroll_off: Option<u8>,
+    pub wrap: Option<bool>,
+    pub list: Option<bool>,
+    pub tabline: Option<bool>,
+    pub sequence_timeout_ms: Option<u64>,
+    pub key_hints: Option<bool>,
+    pub autopairs: Option<bool>,
+    pub smart_home: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub ensure_final_newline: Option<bool>,
     pub theme: Option<String>,
+    pub light_theme: Option<String>,
+    pub dark_theme: Option<String>,
+    pub background: Option<String>,
+    pub colors: Option<String>,
+    pub transparent_background: Option<bool>,
+    pub scrollbar: Option<bool>,
+    pub scrollbar_marks: Option<bool>,
+    pub autosave_interval_secs: Option<u64>,
+    pub autoread: Option<bool>,
+    pub keys: Option<KeysConfig>,
+    pub lsp: Option<HashMap<String, String>>,
+    pub format: Option<HashMap<String, String>>,
+    pub format_on_save: Option<bool>,
+    pub cd_to_root: Option<bool>,
+    pub make: Option<HashMap<String, String>>,
+    pub plugins: Option<HashMap<String, String>>,
+    pub abbreviations: Option<HashMap<String, String>>,
+    pub spell: Option<bool>,
+    pub spell_dictionary: Option<String>,
+    pub statusline: Option<StatuslineConfig>,
+    pub cursor: Option<CursorConfig>,
+    pub log: Option<LogConfig>,
+    pub languages: Option<HashMap<String, LanguageConfig>>,
 }
 
-impl From<ConfigFile> for Config {
-    fn from(config: ConfigFile) -> Self {
-        Self {
+/// A `[languages.<id>]` table, like `[languages.python]`, with settings for the buffers of that
+/// language over the global ones.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LanguageConfig {
+    pub tab_size: Option<u8>,
+    pub tab_to_spaces: Option<bool>,
+    /// Replaces the language's `[format]` command.
+    pub formatter: Option<String>,
+    /// The marker `gc` toggles line comments with, like `"--"`.
+    pub comment: Option<String>,
+    /// Replaces the language's `[lsp]` command.
+    pub lsp: Option<String>,
+}
+
+/// The `[keys.normal]`, `[keys.insert]` and `[keys.command]` tables, mapping keys to action
+/// names.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct KeysConfig {
+    #[serde(default)]
+    pub normal: HashMap<String, String>,
+    #[serde(default)]
+    pub insert: HashMap<String, String>,
+    #[serde(default)]
+    pub command: HashMap<String, String>,
+}
+
+impl TryFrom<ConfigFile> for Config {
+    type Error = anyhow::Error;
+
+    fn try_from(config: ConfigFile) -> anyhow::Result<Self> {
+        let keys = config.keys.unwrap_or_default();
+        Ok(Self {
             faded_line_numbers: config.faded_line_numbers.unwrap_or(true),
+            number: config.number.unwrap_or(true),
+            number_width: config.number_width.unwrap_or(3).max(1),
+            gutter_separator: config.gutter_separator.unwrap_or_else(|| "▎".to_string()),
+            gutter_padding: config.gutter_padding.unwrap_or(1),
             tab_size: config.tab_size.unwrap_or(4),
             tab_to_spaces: config.tab_to_spaces.unwrap_or(true),
+            detect_indent: config.detect_indent.unwrap_or(true),
             mouse_scroll_lines: config.mouse_scroll_lines.unwrap_or(3),
+            scroll_off: config.scroll_off.unwrap_or(0),
+            wrap: config.wrap.unwrap_or(false),
+            list: config.list.unwrap_or(false),
+            tabline: config.tabline.unwrap_or(false),
+            sequence_timeout_ms: config.sequence_timeout_ms.unwrap_or(3000),
+            key_hints: config.key_hints.unwrap_or(true),
+            autopairs: config.autopairs.unwrap_or(false),
+            smart_home: config.smart_home.unwrap_or(false),
+            trim_trailing_whitespace: config.trim_trailing_whitespace.unwrap_or(false),
+            ensure_final_newline: config.ensure_final_newline.unwrap_or(true),
             theme: config.theme,
-        }
+            light_theme: config.light_theme,
+            dark_theme: config.dark_theme,
+            background: config
+                .background
+                .map(|background| {
+                    background.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid background {background}, expected light or dark")
+                    })
+                })
+                .transpose()?,
+            colors: config
+                .colors
+                .as_deref()
+                .map(ColorSupport::parse)
+                .transpose()?,
+            transparent_background: config.transparent_background.unwrap_or(false),
+            scrollbar: config.scrollbar.unwrap_or(false),
+            scrollbar_marks: config.scrollbar_marks.unwrap_or(true),
+            autosave_interval_secs: config.autosave_interval_secs,
+            autoread: config.autoread.unwrap_or(true),
+            lsp: config.lsp.unwrap_or_default(),
+            format: config.format.unwrap_or_default(),
+            format_on_save: config.format_on_save.unwrap_or(false),
+            cd_to_root: config.cd_to_root.unwrap_or(false),
+            make: config.make.unwrap_or_default(),
+            plugins: config.plugins.unwrap_or_default(),
+            abbreviations: config.abbreviations.unwrap_or_default(),
+            spell: config.spell.unwrap_or(false),
+            spell_dictionary: config.spell_dictionary,
+            statusline: config.statusline.unwrap_or_default().try_into()?,
+            cursor: config.cursor.unwrap_or_default().try_into()?,
+            log: config.log.unwrap_or_default().try_into()?,
+            languages: config.languages.unwrap_or_default(),
+            keymap: Keymap::with_bindings(&keys.normal, &keys.insert, &keys.command)?,
+        })
     }
 }
 
+#[allow(unused)]
 #[derive(Debug)]
 pub struct Config {
     pub faded_line_numbers: bool,
+    /// Shows line numbers in the gutter, which is left with the sign column and the separator
+    /// without them.
+    pub number: bool,
+    /// The fewest digits the line numbers take, the gutter grows past it for longer buffers.
+    pub number_width: u8,
+    /// What the gutter ends in, between the line numbers and the text. Empty for nothing.
+    pub gutter_separator: String,
+    /// How many spaces go between the line numbers and the separator.
+    pub gutter_padding: u8,
     pub tab_size: u8,
     pub tab_to_spaces: bool,
+    /// Follows the indentation a file already uses, tabs or a number of spaces, over `tab_size`
+    /// and `tab_to_spaces`.
+    pub detect_indent: bool,
     pub mouse_scroll_lines: u8,
+    /// How many lines to keep in view above and below the cursor, like vim's `scrolloff`.
+    pub scroll_off: u8,
+    /// Wraps long lines across multiple screen rows instead of cutting them off.
+    pub wrap: bool,
+    /// Shows tabs, spaces and line ends as visible marks, like vim's `list`.
+    pub list: bool,
+    /// Shows the open buffers in a line at the top while there is more than one.
+    pub tabline: bool,
+    /// Cancels a key sequence like `d` or `g` when its next key doesn't come within this many
+    /// milliseconds, or never when 0. Like vim's `timeoutlen`.
+    pub sequence_timeout_ms: u64,
+    /// Shows the keys that can follow a key sequence that waits for its next key.
+    pub key_hints: bool,
+    /// Closes brackets and quotes as they are typed in insert mode.
+    pub autopairs: bool,
+    /// Makes `0` and `Home` go to the first non-blank char, or to column 0 when already there.
+    pub smart_home: bool,
+    /// Removes whitespace at the end of lines before writing a buffer to its file.
+    pub trim_trailing_whitespace: bool,
+    /// Ends the last line of a file in a line ending when writing it, even if it didn't have one.
+    /// When off, files keep whether they had one.
+    pub ensure_final_newline: bool,
     pub theme: Option<String>,
+    /// The themes for terminals with a light and a dark background, over `theme`.
+    pub light_theme: Option<String>,
+    pub dark_theme: Option<String>,
+    /// Whether the terminal's background is `light` or `dark`, over what it tells when asked.
+    pub background: Option<Background>,
+    /// The colors the terminal can show, `truecolor`, `256` or `16`, over what is detected from
+    /// `COLORTERM` and terminfo. Theme colors are fit to the nearest one the terminal has.
+    pub colors: Option<ColorSupport>,
+    /// Leaves the theme's background off the text and empty space, showing the terminal's own
+    /// background, and its transparency, through. Colored parts like selections keep theirs.
+    pub transparent_background: bool,
+    /// Shows a scrollbar on the right edge of the buffer.
+    pub scrollbar: bool,
+    /// Marks the lines with search matches and diagnostics on the scrollbar.
+    pub scrollbar_marks: bool,
+    /// Writes modified buffers to their files after this many seconds without input.
+    pub autosave_interval_secs: Option<u64>,
+    /// Reloads buffers without unsaved changes when their files change on disk. Buffers with
+    /// changes, or all of them when off, get a warning instead.
+    pub autoread: bool,
+    /// Language server commands by language, like `rust = "rust-analyzer"`.
+    pub lsp: HashMap<String, String>,
+    /// Formatter commands by language, like `rust = "rustfmt"`, that get the text on stdin and
+    /// print it formatted.
+    pub format: HashMap<String, String>,
+    /// Formats buffers that have a formatter before writing them.
+    pub format_on_save: bool,
+    /// Changes to the root of the git repository of a file when it's opened, the nearest
+    /// directory up from it with a `.git`.
+    pub cd_to_root: bool,
+    /// Build commands by the file that marks a project, like `"Cargo.toml" = "cargo clippy"`,
+    /// run by `:make` in the nearest directory up with that file.
+    pub make: HashMap<String, String>,
+    /// Plugin commands by name, like `words = "python3 words.py"`, started once at startup.
+    pub plugins: HashMap<String, String>,
+    /// Words replaced in insert mode once the char after them is typed, like `teh = "the"`.
+    pub abbreviations: HashMap<String, String>,
+    /// Underlines misspelled words in comments, strings, Markdown and plain text.
+    pub spell: bool,
+    /// The hunspell `.dic` file, next to its `.aff`, or word list to check spelling with,
+    /// instead of the first system dictionary found.
+    pub spell_dictionary: Option<String>,
+    pub statusline: Statusline,
+    /// The shape of the cursor in each mode and whether it blinks.
+    pub cursor: CursorStyles,
+    /// What to log and where, read once at startup.
+    pub log: LogSettings,
+    /// Settings by language id, like `rust`, over the global ones.
+    pub languages: HashMap<String, LanguageConfig>,
+    pub keymap: Keymap,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             faded_line_numbers: true,
+            number: true,
+            number_width: 3,
+            gutter_separator: "▎".to_string(),
+            gutter_padding: 1,
             tab_size: 4,
             tab_to_spaces: true,
+            detect_indent: true,
             mouse_scroll_lines: 3,
+            scroll_off: 0,
+            wrap: false,
+            list: false,
+            tabline: false,
+            sequence_timeout_ms: 3000,
+            key_hints: true,
+            autopairs: false,
+            smart_home: false,
+            trim_trailing_whitespace: false,
+            ensure_final_newline: true,
             theme: None,
+            light_theme: None,
+            dark_theme: None,
+            background: None,
+            colors: None,
+            transparent_background: false,
+            scrollbar: false,
+            scrollbar_marks: true,
+            autosave_interval_secs: None,
+            autoread: true,
+            lsp: HashMap::new(),
+            format: HashMap::new(),
+            format_on_save: false,
+            cd_to_root: false,
+            make: HashMap::new(),
+            plugins: HashMap::new(),
+            abbreviations: HashMap::new(),
+            spell: false,
+            spell_dictionary: None,
+            statusline: Statusline::default(),
+            cursor: CursorStyles::default(),
+            log: LogSettings::default(),
+            languages: HashMap::new(),
+            keymap: Keymap::default(),
         }
     }
 }
 
 impl Config {
-    pub fn read() -> anyhow::Result<Self> {
-        let mut path = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("No home directory"))?;
-        path.push(".config");
+    /// The theme for a terminal with a `background` of that kind: `light_theme` or `dark_theme`,
+    /// or else `theme`.
+    pub fn theme_for(&self, background: Background) -> Option<&String> {
+        let theme = match background {
+            Background::Light => &self.light_theme,
+            Background::Dark => &self.dark_theme,
+        };
+        theme.as_ref().or(self.theme.as_ref())
+    }
+
+    /// The `[languages.<id>]` settings of the language `id`, if it has any.
+    pub fn language(&self, id: &str) -> Option<&LanguageConfig> {
+        self.languages.get(id)
+    }
 
-        let config = path.join("fed.toml");
-        let config = config.to_str().unwrap();
-        Self::read_from_file(config)
+    /// The language server command for the language `id`, from its `[languages]` table or else
+    /// from `[lsp]`.
+    pub fn lsp_command(&self, id: &str) -> Option<&String> {
+        let command = self.language(id).and_then(|language| language.lsp.as_ref());
+        command.or_else(|| self.lsp.get(id))
+    }
+
+    /// The formatter command for the language `id`, from its `[languages]` table or else from
+    /// `[format]`.
+    pub fn formatter(&self, id: &str) -> Option<&String> {
+        let command = self
+            .language(id)
+            .and_then(|language| language.formatter.as_ref());
+        command.or_else(|| self.format.get(id))
+    }
+
+    /// The config file, `~/.config/fed.toml`.
+    pub fn path() -> anyhow::Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("No home directory"))?;
+        Ok(home.join(".config").join("fed.toml"))
     }
 
     pub fn read_from_file(file: &str) -> anyhow::Result<Self> {
@@ -59,6 +339,251 @@ impl Config {
         let config = std::fs::read_to_string(file)
             .map_err(|e| anyhow::anyhow!("error opening config file: {}", e))?;
         let config = toml::from_str::<ConfigFile>(&config)?;
-        Ok(config.into())
+        config.try_into()
+    }
+}
+
+/// The options `:set` changes, by full name and abbreviation, and whether each is a switch
+/// rather than a number.
+const OPTIONS: &[(&str, &str, bool)] = &[
+    ("fadednumbers", "fn", true),
+    ("number", "nu", true),
+    ("numberwidth", "nuw", false),
+    ("tabsize", "ts", false),
+    ("tabtospaces", "tts", true),
+    ("scrolllines", "sl", false),
+    ("scrolloff", "so", false),
+    ("wrap", "wrap", true),
+    ("list", "list", true),
+    ("tabline", "tal", true),
+    ("timeoutlen", "tm", false),
+    ("keyhints", "kh", true),
+    ("autopairs", "ap", true),
+    ("smarthome", "smarthome", true),
+    ("trimtrailing", "trim", true),
+    ("finalnewline", "fnl", true),
+    ("autosave", "as", false),
+    ("autoread", "ar", true),
+    ("formatonsave", "fos", true),
+    ("spell", "spell", true),
+    ("transparentbg", "tbg", true),
+    ("scrollbar", "sb", true),
+    ("scrollbarmarks", "sbm", true),
+];
+
+impl Config {
+    /// Applies a `:set` argument: `wrap` or `nowrap` for switches, `tabsize=2` for numbers, and
+    /// `tabsize?` to query. Returns the value as `name=value` when queried, which a number is
+    /// also when given without a value, like vim does.
+    pub fn set(&mut self, arg: &str) -> anyhow::Result<Option<String>> {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg, None),
+        };
+        let (name, query) = match name.strip_suffix('?') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+
+        let find = |name: &str| {
+            OPTIONS
+                .iter()
+                .find(|(full, short, _)| name == *full || name == *short)
+        };
+        let (option, negated) = match (find(name), name.strip_prefix("no").map(find)) {
+            (Some(option), _) => (option, false),
+            (None, Some(Some(option))) if option.2 => (option, true),
+            _ => anyhow::bail!("E518: Unknown option: {name}"),
+        };
+        let &(name, _, switch) = option;
+
+        if query || (value.is_none() && !switch) {
+            if negated || value.is_some() {
+                anyhow::bail!("E474: Invalid argument: {arg}");
+            }
+            return Ok(Some(self.get(name)));
+        }
+
+        let invalid = || anyhow::anyhow!("E474: Invalid argument: {arg}");
+        let number = || -> anyhow::Result<u64> {
+            let value = value.ok_or_else(invalid)?;
+            value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("E521: Number required after =: {arg}"))
+        };
+        if switch && value.is_some() {
+            return Err(invalid());
+        }
+
+        match name {
+            "fadednumbers" => self.faded_line_numbers = !negated,
+            "tabtospaces" => self.tab_to_spaces = !negated,
+            "wrap" => self.wrap = !negated,
+            "list" => self.list = !negated,
+            "tabline" => self.tabline = !negated,
+            "keyhints" => self.key_hints = !negated,
+            "autopairs" => self.autopairs = !negated,
+            "smarthome" => self.smart_home = !negated,
+            "trimtrailing" => self.trim_trailing_whitespace = !negated,
+            "finalnewline" => self.ensure_final_newline = !negated,
+            "formatonsave" => self.format_on_save = !negated,
+            "autoread" => self.autoread = !negated,
+            "spell" => self.spell = !negated,
+            "transparentbg" => self.transparent_background = !negated,
+            "scrollbar" => self.scrollbar = !negated,
+            "scrollbarmarks" => self.scrollbar_marks = !negated,
+            "tabsize" => {
+                self.tab_size = u8::try_from(number()?)
+                    .ok()
+                    .filter(|&size| size > 0)
+                    .ok_or_else(|| anyhow::anyhow!("E487: Argument must be positive: {arg}"))?
+            }
+            "number" => self.number = !negated,
+            "numberwidth" => {
+                self.number_width = u8::try_from(number()?)
+                    .ok()
+                    .filter(|&width| width > 0)
+                    .ok_or_else(|| anyhow::anyhow!("E487: Argument must be positive: {arg}"))?
+            }
+            "scrolllines" => {
+                self.mouse_scroll_lines = u8::try_from(number()?).map_err(|_| invalid())?
+            }
+            "scrolloff" => self.scroll_off = u8::try_from(number()?).map_err(|_| invalid())?,
+            "timeoutlen" => self.sequence_timeout_ms = number()?,
+            // 0 turns autosaving off
+            "autosave" => self.autosave_interval_secs = Some(number()?).filter(|&secs| secs > 0),
+            _ => unreachable!("option without a setter: {name}"),
+        }
+        Ok(None)
+    }
+
+    /// The value of an option as `:set name?` shows it.
+    fn get(&self, name: &str) -> String {
+        let switch = |name: &str, on: bool| {
+            if on {
+                name.to_string()
+            } else {
+                format!("no{name}")
+            }
+        };
+        match name {
+            "fadednumbers" => switch(name, self.faded_line_numbers),
+            "tabtospaces" => switch(name, self.tab_to_spaces),
+            "wrap" => switch(name, self.wrap),
+            "list" => switch(name, self.list),
+            "tabline" => switch(name, self.tabline),
+            "keyhints" => switch(name, self.key_hints),
+            "autopairs" => switch(name, self.autopairs),
+            "smarthome" => switch(name, self.smart_home),
+            "trimtrailing" => switch(name, self.trim_trailing_whitespace),
+            "finalnewline" => switch(name, self.ensure_final_newline),
+            "formatonsave" => switch(name, self.format_on_save),
+            "autoread" => switch(name, self.autoread),
+            "spell" => switch(name, self.spell),
+            "transparentbg" => switch(name, self.transparent_background),
+            "scrollbar" => switch(name, self.scrollbar),
+            "scrollbarmarks" => switch(name, self.scrollbar_marks),
+            "tabsize" => format!("{name}={}", self.tab_size),
+            "number" => switch(name, self.number),
+            "numberwidth" => format!("{name}={}", self.number_width),
+            "scrolllines" => format!("{name}={}", self.mouse_scroll_lines),
+            "scrolloff" => format!("{name}={}", self.scroll_off),
+            "timeoutlen" => format!("{name}={}", self.sequence_timeout_ms),
+            "autosave" => format!("{name}={}", self.autosave_interval_secs.unwrap_or(0)),
+            _ => unreachable!("option without a getter: {name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut config = Config::default();
+        assert_eq!(config.set("wrap").unwrap(), None);
+        assert!(config.wrap);
+        assert_eq!(config.set("wrap?").unwrap(), Some("wrap".to_string()));
+        config.set("nofn").unwrap();
+        assert_eq!(
+            config.set("fn?").unwrap(),
+            Some("nofadednumbers".to_string())
+        );
+
+        config.set("ts=2").unwrap();
+        assert_eq!(config.tab_size, 2);
+        assert_eq!(
+            config.set("tabsize").unwrap(),
+            Some("tabsize=2".to_string())
+        );
+        config.set("autosave=5").unwrap();
+        assert_eq!(config.autosave_interval_secs, Some(5));
+        config.set("autosave=0").unwrap();
+        assert_eq!(config.autosave_interval_secs, None);
+
+        assert!(config.set("tabsize=0").is_err());
+        config.set("so=3").unwrap();
+        assert_eq!(config.set("so").unwrap(), Some("scrolloff=3".to_string()));
+        config.set("tm=500").unwrap();
+        assert_eq!(config.sequence_timeout_ms, 500);
+        config.set("nokh").unwrap();
+        assert!(!config.key_hints);
+        config.set("ap").unwrap();
+        assert!(config.autopairs);
+        config.set("smarthome").unwrap();
+        assert!(config.smart_home);
+        config.set("nuw=5").unwrap();
+        assert_eq!(config.number_width, 5);
+        assert!(config.set("nuw=0").is_err());
+        assert!(config.set("tabsize=x").is_err());
+        assert!(config.set("notabsize").is_err());
+        config.set("fos").unwrap();
+        assert!(config.format_on_save);
+        config.set("list").unwrap();
+        assert!(config.list);
+        assert_eq!(
+            config.set("trim?").unwrap(),
+            Some("notrimtrailing".to_string())
+        );
+        assert!(config.set("wrap=1").is_err());
+        assert!(config.set("nope").is_err());
+        assert_eq!(config.tab_size, 2);
+    }
+
+    #[test]
+    fn test_languages() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [lsp]
+            rust = "rust-analyzer"
+            python = "pylsp"
+
+            [format]
+            rust = "rustfmt"
+
+            [languages.python]
+            tab_size = 2
+            lsp = "pyright-langserver --stdio"
+            formatter = "black -q -"
+            comment = "--"
+            "#,
+        )
+        .unwrap();
+        let config = Config::try_from(config).unwrap();
+
+        assert_eq!(config.language("python").unwrap().tab_size, Some(2));
+        assert_eq!(
+            config.lsp_command("python").unwrap(),
+            "pyright-langserver --stdio"
+        );
+        assert_eq!(config.lsp_command("rust").unwrap(), "rust-analyzer");
+        assert_eq!(config.formatter("python").unwrap(), "black -q -");
+        assert_eq!(
+            config.language("python").unwrap().comment.as_deref(),
+            Some("--")
+        );
+        assert_eq!(config.formatter("rust").unwrap(), "rustfmt");
+        assert!(config.language("rust").is_none());
     }
 }
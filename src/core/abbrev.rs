@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use crate::core::word::is_word_char;
+
+/// The abbreviation that ends at column `x` of `line`, as the column it starts at and what it
+/// stands for. Abbreviations are whole words, so one that is the end of a longer word doesn't
+/// count.
+pub fn find<'a>(
+    abbreviations: &'a HashMap<String, String>,
+    line: &str,
+    x: usize,
+) -> Option<(usize, &'a str)> {
+    let before: Vec<char> = line.chars().take(x).collect();
+    let start = before
+        .iter()
+        .rposition(|&c| !is_word_char(c))
+        .map_or(0, |i| i + 1);
+    if start == before.len() {
+        return None;
+    }
+
+    let word: String = before[start..].iter().collect();
+    abbreviations
+        .get(&word)
+        .map(|expansion| (start, expansion.as_str()))
+}
+
+/// Splits the arguments of `:iabbrev teh the` into the abbreviation and what it stands for,
+/// which is the rest of the line.
+pub fn parse(args: &str) -> anyhow::Result<(String, String)> {
+    let Some((abbreviation, expansion)) = args.trim().split_once(char::is_whitespace) else {
+        anyhow::bail!("E474: Invalid argument: {args}");
+    };
+    if !abbreviation.chars().all(is_word_char) {
+        anyhow::bail!("E474: Invalid argument: {abbreviation}");
+    }
+    Ok((abbreviation.to_string(), expansion.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let abbreviations = HashMap::from([("teh".to_string(), "the".to_string())]);
+        assert_eq!(find(&abbreviations, "teh", 3), Some((0, "the")));
+        assert_eq!(find(&abbreviations, "(teh cat", 4), Some((1, "the")));
+        assert_eq!(find(&abbreviations, "steh", 4), None);
+        assert_eq!(find(&abbreviations, "teh ", 4), None);
+        assert_eq!(find(&abbreviations, "", 0), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("adn  and so on").unwrap(),
+            ("adn".to_string(), "and so on".to_string())
+        );
+        assert!(parse("adn").is_err());
+        assert!(parse("a-b c").is_err());
+    }
+}
@@ -0,0 +1,425 @@
+use strum_macros::{Display, EnumString};
+
+use crate::core::{find::CharSearch, word::WordMotion};
+
+/// An editor operation. Key bindings and ex commands both resolve to actions, which the editor
+/// runs through a single dispatcher.
+///
+/// Keys can be bound to actions by name from the `[keys.normal]`, `[keys.insert]` and
+/// `[keys.command]` sections of `fed.toml`, like `"<C-d>" = "page_down"`. Binding a key to `nop`
+/// disables it. Actions that take arguments use their defaults when bound to a key, so
+/// `"<C-s>" = "write"` writes the buffer to its own file.
+#[derive(Debug, Clone, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Action {
+    Nop,
+    MoveLeft,
+    MoveDown,
+    MoveUp,
+    MoveRight,
+    /// Moves down one screen row, within a wrapped line when wrapping.
+    DisplayLineDown,
+    /// Moves up one screen row, within a wrapped line when wrapping.
+    DisplayLineUp,
+    NextWord,
+    PreviousWord,
+    EndOfWord,
+    /// Moves to the end of the previous word, `ge` by default.
+    PreviousEndOfWord,
+    /// Like `NextWord`, but a WORD is any run of non-blank chars.
+    NextBigWord,
+    PreviousBigWord,
+    EndOfBigWord,
+    PreviousEndOfBigWord,
+    /// Moves to column 0, `0` by default. With `smart_home` it moves to the first non-blank char
+    /// instead, or to column 0 when already there.
+    StartOfLine,
+    /// Moves to the first non-blank char of the line, `^` by default.
+    FirstNonBlank,
+    EndOfLine,
+    /// Jumps between a bracket and its match, `%` by default.
+    MatchingBracket,
+    StartOfBuffer,
+    EndOfBuffer,
+    MiddleOfViewport,
+    PageDown,
+    PageUp,
+    /// Scrolls half a viewport down, moving the cursor as many lines, `<C-d>` by default.
+    HalfPageDown,
+    /// Scrolls half a viewport up, moving the cursor as many lines, `<C-u>` by default.
+    HalfPageUp,
+    ScrollDown,
+    ScrollUp,
+    /// Scrolls so the cursor line is in the middle of the viewport.
+    CenterLine,
+    /// Scrolls so the cursor line is at the top of the viewport, `zt`.
+    LineToTop,
+    /// Scrolls so the cursor line is at the bottom of the viewport, `zb`.
+    LineToBottom,
+    /// Moves to a zero-based line.
+    GoToLine(usize),
+    /// Moves to the next typed char in the line, `f` by default.
+    FindChar,
+    FindCharBackward,
+    /// Moves to just before the next typed char in the line, `t` by default.
+    Till,
+    TillBackward,
+    /// Repeats the last char search, `;` by default.
+    RepeatFind,
+    /// Repeats the last char search in the other direction, `,` by default.
+    RepeatFindBackward,
+    /// Goes back to where the cursor was before its last jump, `<C-o>` by default.
+    JumpBack,
+    /// Goes forward again through the jumps `JumpBack` went back through, `<Tab>` (`<C-i>`) by
+    /// default.
+    JumpForward,
+    /// Shows the jump list of the current buffer, `:jumps`.
+    ListJumps,
+    /// Shows the latest messages and log lines, `:messages`.
+    ShowMessages,
+    /// Sets a mark at the cursor from the typed letter, `m` by default.
+    SetMark,
+    /// Moves to the typed mark, `` ` `` by default. `` ` `` and `'` are where the last jump
+    /// started.
+    GoToMark,
+    /// Moves to the first non-blank of the line of the typed mark, `'` by default.
+    GoToMarkLine,
+    /// Asks the language server where the symbol under the cursor is defined and jumps there,
+    /// `gd` by default.
+    GoToDefinition,
+    /// Shows what the language server knows about the symbol under the cursor, like its type
+    /// and docs, `K` by default.
+    Hover,
+    /// Starts `gg`, `gd`, `gj` and `gk`.
+    GotoPrefix,
+    /// Starts `zz` and `z=`.
+    ScrollPrefix,
+    /// Starts `]c`, `]m`, `]n`, `]s` and `]x`.
+    NextPrefix,
+    /// Starts `[c`, `[m`, `[n`, `[s` and `[x`.
+    PreviousPrefix,
+    /// Moves to the next block of lines that differ from git's `HEAD`, or from the other side
+    /// of a diff, `]c` by default.
+    NextHunk,
+    /// Moves to the previous block of lines that differ from git's `HEAD`, or from the other
+    /// side of a diff, `[c` by default.
+    PreviousHunk,
+    /// Moves to the next merge conflict, `]x` by default.
+    NextConflict,
+    /// Moves to the previous merge conflict, `[x` by default.
+    PreviousConflict,
+    /// Moves to the start of the next function or class, `]m` by default.
+    NextFunction,
+    /// Moves to the start of the previous function or class, `[m` by default.
+    PreviousFunction,
+    /// Moves to the start of the syntax node around the cursor, then of the ones around it,
+    /// `[n` by default.
+    StartOfNode,
+    /// Moves to the end of the syntax node around the cursor, then of the ones around it, `]n`
+    /// by default.
+    EndOfNode,
+    /// Moves to the next misspelled word while `spell` is on, `]s` by default.
+    NextMisspelling,
+    /// Moves to the previous misspelled word while `spell` is on, `[s` by default.
+    PreviousMisspelling,
+    /// Shows the words the one under the cursor may have meant to be, to replace it with the
+    /// one whose number is typed next, `z=` by default.
+    SuggestSpelling,
+    /// Selects the syntax node under the cursor, then the node around the selection, `<A-o>` by
+    /// default.
+    ExpandSelection,
+    /// Starts leader commands like `<Space>f`.
+    Leader,
+    /// Starts `<C-w>w`, `<C-w>h` and `<C-w>l`.
+    WindowPrefix,
+    Delete,
+    Change,
+    Yank,
+    /// Puts the register after the cursor, or below the cursor line when it holds whole lines,
+    /// `p` by default.
+    PutAfter,
+    /// Puts the register before the cursor, or above the cursor line, `P` by default.
+    PutBefore,
+    /// Picks the register the next yank, delete or put uses from the typed name, `"` by
+    /// default. `+` and `*` are the system clipboard.
+    SelectRegister,
+    Indent,
+    Dedent,
+    /// Indents lines by the syntax around them, `=` by default.
+    Reindent,
+    /// Lowercases the text of a motion, `gu` by default.
+    Lowercase,
+    /// Uppercases the text of a motion, `gU` by default.
+    Uppercase,
+    /// Toggles line comments on the lines of a motion, `gc` by default.
+    Comment,
+    /// Switches the case of the char under the cursor and moves past it, `~` by default.
+    ToggleCase,
+    Insert,
+    Append,
+    /// Replaces the char under the cursor with the next typed char, `r` by default.
+    ReplaceChar,
+    /// Types over the chars after the cursor until `<Esc>`, `R` by default.
+    ReplaceMode,
+    OpenBelow,
+    OpenAbove,
+    DeleteChar,
+    /// Deletes to the end of the line, `D` by default.
+    DeleteToEndOfLine,
+    /// Changes to the end of the line, `C` by default.
+    ChangeToEndOfLine,
+    /// Adds the count to the number under or after the cursor, `<C-a>` by default.
+    Increment,
+    /// Subtracts the count from the number under or after the cursor, `<C-x>` by default.
+    Decrement,
+    /// Moves to the next occurrence of the identifier under the cursor, `*` by default.
+    NextOccurrence,
+    /// Moves to the previous occurrence of the identifier under the cursor, `#` by default.
+    PreviousOccurrence,
+    /// Changes the char under the cursor, `s` by default.
+    SubstituteChar,
+    /// Changes the whole line, `S` by default.
+    SubstituteLine,
+    /// Deletes a zero-based inclusive range of lines, or the cursor line.
+    DeleteLines(Option<(usize, usize)>),
+    JoinLines,
+    /// Runs `:s` arguments like `/foo/bar/g` over a range of lines, or the cursor line.
+    Substitute(String, Option<(usize, usize)>),
+    CommandMode,
+    VisualMode,
+    NormalMode,
+    Backspace,
+    Newline,
+    Tab,
+    /// Goes back to the previous tab stop of the snippet being filled in.
+    PreviousTabStop,
+    /// Inserts the next key as it is, without expanding an abbreviation or closing a pair.
+    InsertLiteral,
+    /// Writes the buffer to a path, or to its own file. Forcing it writes read-only buffers.
+    Write(Option<String>, bool),
+    WriteQuit(Option<String>, bool),
+    /// Writes the buffer only if it changed, then quits.
+    Exit(Option<String>, bool),
+    /// Quits, refusing when a buffer has unsaved changes unless forced.
+    Quit(bool),
+    /// Loads a file into the current buffer, or reloads it, discarding changes when forced.
+    Edit(Option<String>, bool),
+    NextBuffer,
+    PreviousBuffer,
+    /// Switches to a buffer by its one-based number.
+    SwitchBuffer(usize),
+    ListBuffers,
+    /// Opens an empty scratch buffer for notes, `:new`.
+    NewBuffer,
+    /// Opens the help buffer listing the key bindings and commands, `:help`.
+    Help,
+    OpenPicker,
+    /// Searches the files under the current directory for a regex, `:grep`.
+    Grep(String),
+    /// Changes the directory of the editor, or of the window of the buffer when local, `:cd` and
+    /// `:lcd`. Without a directory it goes home, and to the previous one for `-`.
+    ChangeDir(Option<String>, bool),
+    /// Shows the directory the editor works in, `:pwd`.
+    PrintDir,
+    /// Builds the project in the background, listing the errors in the quickfix list, `:make`.
+    /// Any arguments are added to the build command.
+    Make(Option<String>),
+    /// Runs a command through the shell and shows what it printed, `:!cmd`.
+    Shell(String),
+    /// Tells a plugin, by name, that a key it bound without an action was pressed.
+    Plugin(String, String),
+    /// Replaces the lines in the range with what a shell command prints when given them,
+    /// `:{range}!cmd`.
+    Filter(String, (usize, usize)),
+    /// Moves to the next `:grep` match, `:cnext`.
+    NextQuickfix,
+    PreviousQuickfix,
+    /// Shows the `:grep` matches again, `:copen`.
+    OpenQuickfix,
+    /// Pipes the buffer through the formatter of its language, `:fmt`.
+    Format,
+    /// Shows or hides who last changed each line after its text, `:blame`.
+    ToggleBlame,
+    /// Shows the highlight capture, theme scope and colors of the text under the cursor,
+    /// `:highlight` or `:Inspect`.
+    InspectHighlight,
+    /// Keeps our side of the merge conflict under the cursor, `:ours`.
+    AcceptOurs,
+    /// Keeps their side of the merge conflict under the cursor, `:theirs`.
+    AcceptTheirs,
+    /// Keeps both sides of the merge conflict under the cursor, ours first, `:both`.
+    AcceptBoth,
+    /// Opens a file side by side with the current buffer, showing how they differ,
+    /// `:diffsplit`.
+    DiffSplit(String),
+    /// Goes back to showing a single buffer, `:diffoff`.
+    DiffOff,
+    /// Compares the buffer with what its file holds on disk now, `:diffreload`.
+    DiffReload,
+    /// Takes the other side's version of the changes in a range of lines, or at the cursor
+    /// line, `:diffget`.
+    DiffGet(Option<(usize, usize)>),
+    /// Gives the other side this side's version of the changes in a range of lines, or at the
+    /// cursor line, `:diffput`.
+    DiffPut(Option<(usize, usize)>),
+    /// Moves to the other side of a diff, `<C-w>w` by default.
+    NextWindow,
+    /// Moves to the left side of a diff, `<C-w>h` by default.
+    LeftWindow,
+    /// Moves to the right side of a diff, `<C-w>l` by default.
+    RightWindow,
+    /// Switches to a theme by name, or shows the name of the current one.
+    Theme(Option<String>),
+    /// Sets an option like `ff=dos`, or shows the value of one given by name.
+    Set(String),
+    /// Adds an insert mode abbreviation like `teh the`, or lists them, `:iabbrev`.
+    Abbreviate(Option<String>),
+    /// Removes an insert mode abbreviation, `:iunabbrev`.
+    Unabbreviate(String),
+    /// Starts recording keys into a register, or stops the recording in progress.
+    RecordMacro,
+    /// Replays the keys recorded in a register, `@@` replays the last one.
+    PlayMacro,
+    /// Repeats the last command that changed the buffer, `.` by default.
+    RepeatChange,
+}
+
+impl Action {
+    /// Whether the action only moves the cursor, so it can extend a visual selection.
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self,
+            Action::MoveLeft
+                | Action::MoveDown
+                | Action::MoveUp
+                | Action::MoveRight
+                | Action::DisplayLineDown
+                | Action::DisplayLineUp
+                | Action::NextWord
+                | Action::PreviousWord
+                | Action::EndOfWord
+                | Action::PreviousEndOfWord
+                | Action::NextBigWord
+                | Action::PreviousBigWord
+                | Action::EndOfBigWord
+                | Action::PreviousEndOfBigWord
+                | Action::StartOfLine
+                | Action::FirstNonBlank
+                | Action::EndOfLine
+                | Action::MatchingBracket
+                | Action::StartOfBuffer
+                | Action::EndOfBuffer
+                | Action::MiddleOfViewport
+                | Action::PageDown
+                | Action::PageUp
+                | Action::HalfPageDown
+                | Action::HalfPageUp
+                | Action::GoToLine(_)
+                | Action::FindChar
+                | Action::FindCharBackward
+                | Action::Till
+                | Action::TillBackward
+                | Action::RepeatFind
+                | Action::RepeatFindBackward
+                | Action::JumpBack
+                | Action::JumpForward
+                | Action::GoToMark
+                | Action::GoToMarkLine
+                | Action::NextOccurrence
+                | Action::PreviousOccurrence
+                | Action::GotoPrefix
+                | Action::NextPrefix
+                | Action::PreviousPrefix
+                | Action::NextHunk
+                | Action::PreviousHunk
+                | Action::NextConflict
+                | Action::PreviousConflict
+                | Action::NextFunction
+                | Action::PreviousFunction
+                | Action::StartOfNode
+                | Action::EndOfNode
+        )
+    }
+
+    /// Whether the action changes the text, or starts an operator or mode that does.
+    pub fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Action::Delete
+                | Action::Change
+                | Action::PutAfter
+                | Action::PutBefore
+                | Action::Indent
+                | Action::Dedent
+                | Action::Reindent
+                | Action::Lowercase
+                | Action::Uppercase
+                | Action::Comment
+                | Action::ToggleCase
+                | Action::Insert
+                | Action::Append
+                | Action::ReplaceChar
+                | Action::ReplaceMode
+                | Action::OpenBelow
+                | Action::OpenAbove
+                | Action::DeleteChar
+                | Action::DeleteToEndOfLine
+                | Action::ChangeToEndOfLine
+                | Action::Increment
+                | Action::Decrement
+                | Action::SubstituteChar
+                | Action::SubstituteLine
+                | Action::DeleteLines(_)
+                | Action::JoinLines
+                | Action::Substitute(..)
+                | Action::SuggestSpelling
+                | Action::Filter(..)
+                | Action::Format
+                | Action::AcceptOurs
+                | Action::AcceptTheirs
+                | Action::AcceptBoth
+                | Action::DiffGet(_)
+                | Action::RepeatChange
+        )
+    }
+
+    /// Whether the action moves far enough that the position it leaves goes into the jump list.
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            Action::StartOfBuffer
+                | Action::EndOfBuffer
+                | Action::PageDown
+                | Action::PageUp
+                | Action::GoToLine(_)
+        )
+    }
+
+    /// The word motion the action moves by, and whether it moves by WORDs.
+    pub fn word_motion(&self) -> Option<(WordMotion, bool)> {
+        let motion = match self {
+            Action::NextWord => (WordMotion::NextStart, false),
+            Action::PreviousWord => (WordMotion::PreviousStart, false),
+            Action::EndOfWord => (WordMotion::NextEnd, false),
+            Action::PreviousEndOfWord => (WordMotion::PreviousEnd, false),
+            Action::NextBigWord => (WordMotion::NextStart, true),
+            Action::PreviousBigWord => (WordMotion::PreviousStart, true),
+            Action::EndOfBigWord => (WordMotion::NextEnd, true),
+            Action::PreviousEndOfBigWord => (WordMotion::PreviousEnd, true),
+            _ => return None,
+        };
+        Some(motion)
+    }
+
+    /// The char search the action starts, waiting for the char to search for.
+    pub fn char_search(&self) -> Option<CharSearch> {
+        let (forward, till) = match self {
+            Action::FindChar => (true, false),
+            Action::FindCharBackward => (false, false),
+            Action::Till => (true, true),
+            Action::TillBackward => (false, true),
+            _ => return None,
+        };
+        Some(CharSearch { forward, till })
+    }
+}
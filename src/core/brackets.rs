@@ -0,0 +1,181 @@
+use crate::core::{buffer::Buffer, syntax::Highlights};
+
+const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+fn is_bracket(c: char) -> bool {
+    PAIRS.iter().any(|&(open, close)| c == open || c == close)
+}
+
+/// Finds the bracket matching the one at `(x, y)`.
+///
+/// When the buffer's highlights are current, brackets inside strings and comments only match
+/// each other, so a `"{"` doesn't throw off the code around it. Otherwise every bracket counts.
+pub fn matching_bracket(buffer: &Buffer, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+    let idx = buffer.char_idx(x, y);
+    let c = buffer.char(idx)?;
+    let (open, close) = PAIRS
+        .into_iter()
+        .find(|&(open, close)| c == open || c == close)?;
+
+    let highlights = buffer
+        .highlights
+        .is_current(buffer)
+        .then_some(&buffer.highlights);
+    let literal = |pos| highlights.is_some_and(|h: &Highlights| h.is_literal(pos));
+    let in_literal = literal((x, y));
+
+    let mut depth = 0;
+    let mut check = |i: usize, ch: char| {
+        if ch != open && ch != close {
+            return None;
+        }
+        let pos = buffer.position(i);
+        if literal(pos) != in_literal {
+            return None;
+        }
+        if ch == c {
+            depth += 1;
+        } else if depth == 0 {
+            return Some(pos);
+        } else {
+            depth -= 1;
+        }
+        None
+    };
+
+    if c == open {
+        buffer
+            .chars_at(idx + 1)
+            .zip(idx + 1..)
+            .find_map(|(ch, i)| check(i, ch))
+    } else {
+        let mut chars = buffer.chars_at(idx);
+        let mut i = idx;
+        while let Some(ch) = chars.prev() {
+            i -= 1;
+            if let Some(pos) = check(i, ch) {
+                return Some(pos);
+            }
+        }
+        None
+    }
+}
+
+/// Where `%` jumps from `(x, y)`: the match of the bracket under the cursor, or of the next one
+/// on the line.
+pub fn jump_target(buffer: &Buffer, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+    let line = buffer.line(y)?;
+    let (x, _) = line
+        .chars()
+        .enumerate()
+        .skip(x)
+        .find(|&(_, c)| is_bracket(c))?;
+    matching_bracket(buffer, (x, y))
+}
+
+/// The pairs auto-pairs closes as their opening char is typed.
+const AUTO_PAIRS: [(char, char); 5] =
+    [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// What typing a char does with auto-pairs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoPair {
+    /// Inserts the char as usual.
+    Insert,
+    /// Inserts the char followed by its closing char, with the cursor between them.
+    Pair(char),
+    /// Moves over the same closing char that already follows the cursor.
+    Skip,
+}
+
+/// What typing `c` at column `x` of `line` does with auto-pairs on. Brackets are closed when
+/// followed by a blank or a closing char, quotes when neither side is a word char, so the `'`
+/// of `don't` stays single.
+pub fn auto_pair(line: &str, x: usize, c: char) -> AutoPair {
+    let mut chars = line.chars().skip(x.saturating_sub(1));
+    let before = if x > 0 { chars.next() } else { None };
+    let after = chars.next();
+
+    if after == Some(c) && AUTO_PAIRS.iter().any(|&(_, close)| close == c) {
+        return AutoPair::Skip;
+    }
+    let Some(&(open, close)) = AUTO_PAIRS.iter().find(|&&(open, _)| open == c) else {
+        return AutoPair::Insert;
+    };
+
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let closes = if open == close {
+        !is_word(before) && !is_word(after)
+    } else {
+        after.is_none_or(|c| c.is_whitespace() || AUTO_PAIRS.iter().any(|&(_, close)| close == c))
+    };
+    if closes {
+        AutoPair::Pair(close)
+    } else {
+        AutoPair::Insert
+    }
+}
+
+/// Whether the cursor at column `x` of `line` sits inside an empty pair like `(|)`, which
+/// backspace deletes as a whole.
+pub fn in_empty_pair(line: &str, x: usize) -> bool {
+    if x == 0 {
+        return false;
+    }
+    let mut chars = line.chars().skip(x - 1);
+    let (Some(before), Some(after)) = (chars.next(), chars.next()) else {
+        return false;
+    };
+    AUTO_PAIRS.contains(&(before, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::syntax::Language;
+
+    #[test]
+    fn test_matching_bracket() {
+        let buffer = Buffer::from_contents("fn a(b: [u8]) {\n    c(\"}\");\n}");
+
+        assert_eq!(matching_bracket(&buffer, (4, 0)), Some((12, 0)));
+        assert_eq!(matching_bracket(&buffer, (11, 0)), Some((8, 0)));
+        assert_eq!(matching_bracket(&buffer, (0, 0)), None);
+        assert_eq!(jump_target(&buffer, (0, 0)), Some((12, 0)));
+
+        // without highlights the brace in the string closes the block
+        assert_eq!(matching_bracket(&buffer, (14, 0)), Some((7, 1)));
+    }
+
+    #[test]
+    fn test_auto_pair() {
+        assert_eq!(auto_pair("foo", 3, '('), AutoPair::Pair(')'));
+        assert_eq!(auto_pair("a  b", 2, '['), AutoPair::Pair(']'));
+        assert_eq!(auto_pair("()", 1, '{'), AutoPair::Pair('}'));
+        assert_eq!(auto_pair("foo", 0, '('), AutoPair::Insert);
+        assert_eq!(auto_pair("()", 1, ')'), AutoPair::Skip);
+        assert_eq!(auto_pair("(]", 1, ')'), AutoPair::Insert);
+
+        assert_eq!(auto_pair("x = ", 4, '"'), AutoPair::Pair('"'));
+        assert_eq!(auto_pair("\"\"", 1, '"'), AutoPair::Skip);
+        assert_eq!(auto_pair("don", 3, '\''), AutoPair::Insert);
+        assert_eq!(auto_pair("a", 0, '\''), AutoPair::Insert);
+        assert_eq!(auto_pair("x", 1, 'y'), AutoPair::Insert);
+
+        assert!(in_empty_pair("f()", 2));
+        assert!(in_empty_pair("''", 1));
+        assert!(!in_empty_pair("(x)", 1));
+        assert!(!in_empty_pair("()", 0));
+    }
+
+    #[test]
+    fn test_matching_bracket_skips_strings() {
+        let mut buffer = Buffer::from_contents("fn a() {\n    c(\"}\");\n}");
+        buffer.language = Some(Language::Rust);
+        buffer.highlights = Highlights::compute(&buffer).unwrap();
+
+        assert_eq!(matching_bracket(&buffer, (7, 0)), Some((0, 2)));
+        assert_eq!(matching_bracket(&buffer, (0, 2)), Some((7, 0)));
+        assert_eq!(matching_bracket(&buffer, (5, 1)), Some((9, 1)));
+    }
+}
@@ -0,0 +1,899 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufRead, BufReader},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use ropey::{Rope, RopeBuilder};
+use strum_macros::{Display, EnumString};
+use tree_sitter::Tree;
+
+use crate::{
+    blame::BlameLine,
+    core::{
+        conflict::{self, Conflict},
+        encoding::{Encoding, UTF8_BOM},
+        indent::{self, Indentation},
+        jumplist::JumpList,
+        signs::{moved_by_insert, moved_by_remove, Sign, Signs},
+        syntax::{Highlights, Language},
+        word,
+    },
+    gitdiff::{GitDiff, Hunk},
+    log,
+    lsp::{self, Diagnostic},
+    watcher,
+};
+
+/// Files bigger than this many bytes are read straight into the rope in chunks and are not
+/// syntax highlighted.
+pub const LARGE_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// How lines end in a file, named like vim's `fileformat`. Buffers always hold `\n` and the
+/// original ending is put back when writing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Unix,
+    /// `\r\n`
+    Dos,
+    /// `\r`
+    Mac,
+}
+
+impl LineEnding {
+    /// Detects the line ending from the first line break in `contents`, falling back to unix.
+    pub fn detect(contents: &[u8]) -> Self {
+        match contents.iter().position(|&b| b == b'\n' || b == b'\r') {
+            Some(i) if contents[i] == b'\n' => LineEnding::Unix,
+            Some(i) if contents.get(i + 1) == Some(&b'\n') => LineEnding::Dos,
+            Some(_) => LineEnding::Mac,
+            None => LineEnding::Unix,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Unix => "\n",
+            LineEnding::Dos => "\r\n",
+            LineEnding::Mac => "\r",
+        }
+    }
+}
+
+/// The text being edited, stored as a rope so edits and line lookups stay cheap on large files,
+/// along with the file it came from and the view state to restore when switching back to it.
+///
+/// Lines are separated by `\n` and positions are `(x, y)` pairs where `x` is a char index within
+/// line `y`. There is always at least one (possibly empty) line.
+#[derive(Debug, Clone, Default)]
+pub struct Buffer {
+    text: Rope,
+    /// Bumped on every edit, so derived data like highlights can tell when it is stale.
+    revision: u64,
+    pub file: Option<String>,
+    pub name: String,
+    pub dirty: bool,
+    /// Refuses writes to its own file unless forced, as when opened with `--readonly`.
+    pub readonly: bool,
+    /// Not backed by a file, like the buffers of `:new` and `:help`, so quitting or editing
+    /// another file never asks to write its changes.
+    pub scratch: bool,
+    /// Refuses changes to its text, like the `:help` buffer, as vim's `nomodifiable` does.
+    pub locked: bool,
+    pub language: Option<Language>,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    /// How the file indents its lines, as detected when it was read.
+    pub indentation: Option<Indentation>,
+    /// The tab settings `:set` gave the buffer, over the detected ones and the config's.
+    pub tab_size: Option<u8>,
+    pub tab_to_spaces: Option<bool>,
+    /// The file didn't end in a line ending, which writing it keeps unless
+    /// `ensure_final_newline` is on.
+    pub missing_newline: bool,
+    /// Set for files over [`LARGE_FILE_SIZE`], which only get the visible lines highlighted, as
+    /// plain text.
+    pub large: bool,
+    /// Whether unsaved changes go to a swap file. Off for buffers without a file, large ones and
+    /// ones whose file already had a swap file waiting to be recovered.
+    pub swappable: bool,
+    /// The revision last saved to the swap file, if there is one.
+    pub swapped: Option<u64>,
+    /// The revision autosaving last failed to write, which isn't tried again until an edit.
+    pub autosave_failed: Option<u64>,
+    /// Cursor and scroll position `(cx, cy, vtop)` saved while the buffer is in the background.
+    pub view: (usize, usize, usize),
+    /// When the file was last modified as of reading or writing it, to tell when something else
+    /// changes it.
+    pub modified: Option<SystemTime>,
+    /// Set once the file changed on disk in a way the buffer didn't follow, so it is only warned
+    /// about once.
+    pub conflict: bool,
+    /// The directory `:lcd` set for the window of the buffer, which the editor works in while the
+    /// buffer is current.
+    pub dir: Option<PathBuf>,
+    /// Where the cursor jumped from, for `Ctrl-o` and `Ctrl-i`.
+    pub jumps: JumpList,
+    /// Problems the language server found in the text, with char columns.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The revision the language server last got, once the buffer was opened with it.
+    pub synced: Option<u64>,
+    /// Marks set with `m`, as char indices so they follow the text around them as it is edited.
+    marks: HashMap<char, usize>,
+    /// Signs shown in the gutter next to lines, moving with them as the text is edited.
+    signs: Signs,
+    /// How the text differs from the file at git's `HEAD`, shown as signs.
+    pub git: GitDiff,
+    /// Who last changed each line while `:blame` is on, with the revision it was found for.
+    pub blame: Option<(u64, Vec<BlameLine>)>,
+    pub highlights: Highlights,
+    /// The syntax tree of the text with the revision and language it was parsed for.
+    tree: Option<(u64, Language, Tree)>,
+    /// The merge conflicts in the text with the revision they were found in.
+    conflicts: Option<(u64, Vec<Conflict>)>,
+    /// Where a word is in the text, found for the word and revision in `word_searched`.
+    word_positions: Vec<(usize, usize)>,
+    word_searched: Option<(String, u64)>,
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self {
+            name: "No Name".to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Loads `file` into a new buffer. A missing file opens an empty buffer that will create it
+    /// on the first write.
+    pub fn open(file: &str) -> anyhow::Result<Self> {
+        log!("opening file: {}", file);
+        let large = fs::metadata(file).is_ok_and(|m| m.len() > LARGE_FILE_SIZE);
+        if large {
+            log!("large file, skipping syntax highlighting: {}", file);
+        }
+
+        let mut buffer = match Self::read(file, large) {
+            Ok(buffer) => buffer,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                log!("new file: {}", file);
+                Self::default()
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        buffer.file = Some(file.to_string());
+        buffer.name = file.to_string();
+        buffer.modified = watcher::modified(Path::new(file));
+        buffer.detect_indentation();
+        buffer.large = large;
+        buffer.language = Language::from_path(file);
+        Ok(buffer)
+    }
+
+    /// Reads `file`, decoding it from whatever encoding it is in. Large UTF-8 files are streamed
+    /// instead, and only read whole when they turn out to be in another encoding.
+    fn read(file: &str, large: bool) -> io::Result<Self> {
+        if large {
+            match fs::File::open(file).and_then(|f| Self::stream(BufReader::new(f))) {
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    log!("not utf-8, reading it whole: {}", file);
+                }
+                result => return result,
+            }
+        }
+
+        let bytes = fs::read(file)?;
+        let encoding = Encoding::detect(&bytes);
+        let contents = encoding.decode(&bytes);
+        Ok(Self {
+            encoding,
+            missing_newline: !contents.is_empty() && !contents.ends_with(['\n', '\r']),
+            ..Self::from_contents(&contents)
+        })
+    }
+
+    /// Reads everything from `reader` into an unnamed buffer, as when text is piped into the
+    /// editor. Invalid UTF-8 is replaced rather than refused.
+    pub fn from_reader(mut reader: impl io::Read) -> anyhow::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(Self {
+            name: "No Name".to_string(),
+            ..Self::from_contents(&String::from_utf8_lossy(&bytes))
+        })
+    }
+
+    /// A scratch buffer called `name` holding `contents`.
+    pub fn scratch(name: &str, contents: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            scratch: true,
+            ..Self::from_contents(contents)
+        }
+    }
+
+    /// Whether the buffer has changes that quitting would lose, which scratch buffers never do.
+    pub fn unsaved(&self) -> bool {
+        self.dirty && !self.scratch
+    }
+
+    /// Builds a buffer from file contents, converting its line endings to `\n` and dropping the
+    /// final one so that `"a\nb\n"` holds the two lines `a` and `b`.
+    pub fn from_contents(contents: &str) -> Self {
+        let line_ending = LineEnding::detect(contents.as_bytes());
+        let contents = contents
+            .strip_suffix(line_ending.as_str())
+            .unwrap_or(contents);
+        let text = match line_ending {
+            LineEnding::Unix => Rope::from_str(contents),
+            _ => Rope::from_str(&contents.replace(line_ending.as_str(), "\n")),
+        };
+
+        Self {
+            text,
+            line_ending,
+            ..Self::default()
+        }
+    }
+
+    /// Builds a buffer from `reader` a line at a time, so the contents are never held twice like
+    /// they are by [`Buffer::from_contents`].
+    fn stream(mut reader: impl BufRead) -> io::Result<Self> {
+        let encoding = match Encoding::from_bom(reader.fill_buf()?) {
+            Some(Encoding::Utf8Bom) => {
+                reader.consume(UTF8_BOM.len());
+                Encoding::Utf8Bom
+            }
+            Some(encoding) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("can't stream {encoding}"),
+                ))
+            }
+            None => Encoding::Utf8,
+        };
+        let line_ending = LineEnding::detect(reader.fill_buf()?);
+        let separator = match line_ending {
+            LineEnding::Mac => b'\r',
+            _ => b'\n',
+        };
+
+        let mut text = RopeBuilder::new();
+        let mut line = vec![];
+        let mut missing_newline = false;
+        for i in 0.. {
+            line.clear();
+            if reader.read_until(separator, &mut line)? == 0 {
+                break;
+            }
+            missing_newline = line.last() != Some(&separator);
+            if !missing_newline {
+                line.pop();
+            }
+            if line_ending == LineEnding::Dos && line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            let line = std::str::from_utf8(&line)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+            if i > 0 {
+                text.append("\n");
+            }
+            text.append(line);
+        }
+
+        Ok(Self {
+            text: text.finish(),
+            encoding,
+            line_ending,
+            missing_newline,
+            ..Self::default()
+        })
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Number of lines in the buffer.
+    pub fn len(&self) -> usize {
+        self.text.len_lines()
+    }
+
+    pub fn line(&self, y: usize) -> Option<String> {
+        if y >= self.len() {
+            return None;
+        }
+
+        let mut line = self.text.line(y).to_string();
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        Some(line)
+    }
+
+    /// Number of chars in line `y`, not counting the line break.
+    pub fn line_len(&self, y: usize) -> usize {
+        if y >= self.len() {
+            return 0;
+        }
+
+        let line = self.text.line(y);
+        let len = line.len_chars();
+        if len > 0 && line.char(len - 1) == '\n' {
+            len - 1
+        } else {
+            len
+        }
+    }
+
+    /// Number of chars in the buffer, including line breaks.
+    pub fn len_chars(&self) -> usize {
+        self.text.len_chars()
+    }
+
+    /// Returns the char at the absolute char index `idx`.
+    pub fn char(&self, idx: usize) -> Option<char> {
+        self.text.get_char(idx)
+    }
+
+    /// Iterates over the chars from the absolute char index `idx`. Calling `prev` on it walks
+    /// backwards from `idx` instead.
+    pub fn chars_at(&self, idx: usize) -> ropey::iter::Chars<'_> {
+        self.text.chars_at(idx.min(self.text.len_chars()))
+    }
+
+    /// The text as written to a file, with every line ending in the buffer's line ending, the
+    /// last one too unless the file was missing it.
+    pub fn file_contents(&self) -> String {
+        let mut contents = String::with_capacity(self.text.len_bytes() + self.len());
+        for chunk in self.text.chunks() {
+            match self.line_ending {
+                LineEnding::Unix => contents.push_str(chunk),
+                line_ending => contents.push_str(&chunk.replace('\n', line_ending.as_str())),
+            }
+        }
+        if !self.missing_newline {
+            contents.push_str(self.line_ending.as_str());
+        }
+        contents
+    }
+
+    /// Writes the buffer to `path` in its encoding and line ending, returning the number of bytes
+    /// written.
+    pub fn write_to(&self, path: &str) -> anyhow::Result<usize> {
+        let contents = self.encoding.encode(&self.file_contents())?;
+        fs::write(path, &contents)
+            .map_err(|err| anyhow::anyhow!("E212: Can't open file for writing: {path}: {err}"))?;
+        Ok(contents.len())
+    }
+
+    /// Converts an absolute char index back into a position.
+    pub fn position(&self, idx: usize) -> (usize, usize) {
+        let idx = idx.min(self.text.len_chars());
+        let y = self.text.char_to_line(idx);
+        (idx - self.text.line_to_char(y), y)
+    }
+
+    /// Converts a position into an absolute char index, clamping `x` to the line length.
+    pub fn char_idx(&self, x: usize, y: usize) -> usize {
+        let y = y.min(self.len() - 1);
+        self.text.line_to_char(y) + x.min(self.line_len(y))
+    }
+
+    /// Finds how the lines of the buffer are indented.
+    fn detect_indentation(&mut self) {
+        let lines = self.text.lines().map(|line| line.to_string());
+        self.indentation = indent::detect(lines);
+    }
+
+    /// Whether the file was modified since the buffer last read or wrote it. A removed file
+    /// doesn't count, writing it back is safe.
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(file) = &self.file else {
+            return false;
+        };
+        let modified = watcher::modified(Path::new(file));
+        modified.is_some() && modified != self.modified
+    }
+
+    /// Notes that the file was just written with the text of the buffer.
+    pub fn written(&mut self) {
+        self.dirty = false;
+        self.conflict = false;
+        self.modified = self
+            .file
+            .as_deref()
+            .and_then(|f| watcher::modified(Path::new(f)));
+        self.git.expire();
+    }
+
+    /// Reads the file again after it changed on disk, keeping the cursor, the jump list and the
+    /// other state of the buffer that isn't about the text.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let fresh = Self::open(file)?;
+        self.text = fresh.text;
+        self.marks.clear();
+        self.signs.clear();
+        self.revision += 1;
+        self.encoding = fresh.encoding;
+        self.line_ending = fresh.line_ending;
+        self.missing_newline = fresh.missing_newline;
+        self.indentation = fresh.indentation;
+        self.large = fresh.large;
+        self.written();
+        Ok(())
+    }
+
+    /// Replaces the whole text, as when recovering it from a swap file.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = Rope::from_str(text);
+        self.marks.clear();
+        self.signs.clear();
+        self.revision += 1;
+    }
+
+    /// Inserts `text` at char index `idx`, moving the marks and signs after it along.
+    fn insert_text(&mut self, idx: usize, text: &str) {
+        self.text.insert(idx, text);
+        let len = text.chars().count();
+        for mark in self.marks.values_mut() {
+            *mark = moved_by_insert(*mark, idx, len);
+        }
+        self.signs.insert(idx, len);
+        self.revision += 1;
+    }
+
+    /// Removes the chars in `range`, moving the marks and signs after it back and the ones inside
+    /// it to where it started.
+    fn remove_text(&mut self, range: Range<usize>) {
+        self.text.remove(range.clone());
+        for mark in self.marks.values_mut() {
+            *mark = moved_by_remove(*mark, &range);
+        }
+        self.signs.remove(range);
+        self.revision += 1;
+    }
+
+    pub fn insert_char(&mut self, x: usize, y: usize, c: char) {
+        let idx = self.char_idx(x, y);
+        self.insert_text(idx, c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Removes the char at `(x, y)`, returning it. Line breaks are never removed.
+    pub fn remove_char(&mut self, x: usize, y: usize) -> Option<char> {
+        if x >= self.line_len(y) {
+            return None;
+        }
+
+        let idx = self.char_idx(x, y);
+        let c = self.text.char(idx);
+        self.remove_text(idx..idx + 1);
+        Some(c)
+    }
+
+    /// Inserts `text` at `(x, y)`, returning the position of its last char.
+    pub fn insert(&mut self, (x, y): (usize, usize), text: &str) -> (usize, usize) {
+        let idx = self.char_idx(x, y);
+        self.insert_text(idx, text);
+        self.position(idx + text.chars().count().saturating_sub(1))
+    }
+
+    /// Inserts a new line with `text` before line `y`, or at the end when `y == len()`.
+    pub fn insert_line(&mut self, y: usize, text: &str) {
+        if y >= self.len() {
+            let end = self.text.len_chars();
+            self.insert_text(end, &format!("\n{text}"));
+        } else {
+            let idx = self.text.line_to_char(y);
+            self.insert_text(idx, &format!("{text}\n"));
+        }
+    }
+
+    /// Replaces the contents of line `y` with `text`.
+    pub fn set_line(&mut self, y: usize, text: &str) {
+        if y >= self.len() {
+            return;
+        }
+
+        let start = self.text.line_to_char(y);
+        let end = start + self.line_len(y);
+        self.remove_text(start..end);
+        self.insert_text(start, text);
+    }
+
+    /// Removes the spaces and tabs at the end of every line. Returns whether any were removed.
+    pub fn trim_trailing_whitespace(&mut self) -> bool {
+        let mut trimmed = false;
+        for y in 0..self.len() {
+            let Some(line) = self.line(y) else {
+                continue;
+            };
+            let len = line.trim_end_matches([' ', '\t']).chars().count();
+            let end = line.chars().count();
+            if len < end {
+                let start = self.text.line_to_char(y);
+                self.remove_text(start + len..start + end);
+                trimmed = true;
+            }
+        }
+        trimmed
+    }
+
+    /// Removes line `y`, returning its contents. Removing the only line leaves it empty.
+    pub fn remove_line(&mut self, y: usize) -> Option<String> {
+        let line = self.line(y)?;
+
+        let start = self.text.line_to_char(y);
+        let end = start + self.text.line(y).len_chars();
+        if y + 1 < self.len() || y == 0 {
+            self.remove_text(start..end);
+        } else {
+            // last line: takes the line break before it
+            self.remove_text(start - 1..end);
+        }
+
+        Some(line)
+    }
+
+    /// Replaces the lines in `range` with `lines`. An empty range inserts them before its start,
+    /// and no lines removes the range.
+    pub fn replace_lines(&mut self, range: Range<usize>, lines: &[&str]) {
+        if range.is_empty() {
+            for (i, line) in lines.iter().enumerate() {
+                self.insert_line(range.start + i, line);
+            }
+            return;
+        }
+        if lines.is_empty() {
+            for y in range.rev() {
+                self.remove_line(y);
+            }
+            return;
+        }
+
+        let from = self.text.line_to_char(range.start);
+        let to = self.text.line_to_char(range.end - 1) + self.line_len(range.end - 1);
+        self.remove_text(from..to);
+        self.insert_text(from, &lines.join("\n"));
+    }
+
+    /// Splits line `y` at `x`, moving everything after the cursor to a new line below.
+    pub fn split_line(&mut self, x: usize, y: usize) {
+        self.insert_char(x, y, '\n');
+    }
+
+    /// Joins line `y` with the line below it using `separator`.
+    pub fn join_lines(&mut self, y: usize, separator: &str) {
+        if y + 1 >= self.len() {
+            return;
+        }
+
+        let idx = self.text.line_to_char(y) + self.line_len(y);
+        self.remove_text(idx..idx + 1);
+        self.insert_text(idx, separator);
+    }
+
+    /// Returns the text between `start` (inclusive) and `end` (exclusive).
+    pub fn slice(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let start = self.char_idx(start.0, start.1);
+        let end = self.char_idx(end.0, end.1).max(start);
+        self.text.slice(start..end).to_string()
+    }
+
+    /// Removes the text between `start` (inclusive) and `end` (exclusive), returning it.
+    pub fn remove(&mut self, start: (usize, usize), end: (usize, usize)) -> String {
+        let start = self.char_idx(start.0, start.1);
+        let end = self.char_idx(end.0, end.1).max(start);
+        let text = self.text.slice(start..end).to_string();
+        self.remove_text(start..end);
+        text
+    }
+
+    /// Replaces the diagnostics with ones from a language server, converting their UTF-16
+    /// columns to chars.
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        let column = |(x, y): (usize, usize)| {
+            let line = self.line(y).unwrap_or_default();
+            (lsp::char_column(&line, x), y)
+        };
+
+        self.diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| Diagnostic {
+                start: column(diagnostic.start),
+                end: column(diagnostic.end),
+                ..diagnostic
+            })
+            .collect();
+    }
+
+    pub fn set_mark(&mut self, mark: char, (x, y): (usize, usize)) {
+        let idx = self.char_idx(x, y);
+        self.marks.insert(mark, idx);
+    }
+
+    /// The position of `mark`, if it was set.
+    pub fn mark(&self, mark: char) -> Option<(usize, usize)> {
+        self.marks.get(&mark).map(|&idx| self.position(idx))
+    }
+
+    /// Replaces the signs of `group`, given by line.
+    pub fn set_signs(
+        &mut self,
+        group: &'static str,
+        signs: impl IntoIterator<Item = (usize, Sign)>,
+    ) {
+        let signs = signs
+            .into_iter()
+            .filter(|&(y, _)| y < self.len())
+            .map(|(y, sign)| (self.text.line_to_char(y), sign))
+            .collect();
+        self.signs.set(group, signs);
+    }
+
+    /// Compares the text to its file at `HEAD` if either changed since the last call, placing the
+    /// `"git"` signs for the lines that differ. Returns whether they changed.
+    pub fn update_git(&mut self) -> bool {
+        let Some(file) = self.file.as_deref().filter(|_| !self.large) else {
+            return false;
+        };
+        let text = &self.text;
+        if !self
+            .git
+            .update(Path::new(file), self.revision, || text.to_string())
+        {
+            return false;
+        }
+
+        let signs = self
+            .git
+            .hunks
+            .iter()
+            .flat_map(Hunk::signs)
+            .collect::<Vec<_>>();
+        self.set_signs("git", signs);
+        true
+    }
+
+    /// The syntax tree of the text, parsed again only after edits. `None` for buffers without a
+    /// language and large ones.
+    pub fn syntax_tree(&mut self) -> Option<Tree> {
+        let language = self.language.filter(|_| !self.large)?;
+        let current = self
+            .tree
+            .as_ref()
+            .is_some_and(|(revision, parsed, _)| *revision == self.revision && *parsed == language);
+        if !current {
+            let tree = language.parse(&self.to_string())?;
+            self.tree = Some((self.revision, language, tree));
+        }
+        self.tree.as_ref().map(|(_, _, tree)| tree.clone())
+    }
+
+    /// The merge conflicts in the text, found again only after edits.
+    pub fn conflicts(&mut self) -> Vec<Conflict> {
+        if !matches!(&self.conflicts, Some((revision, _)) if *revision == self.revision) {
+            self.conflicts = Some((self.revision, conflict::find(self)));
+        }
+        self.conflicts
+            .as_ref()
+            .map(|(_, conflicts)| conflicts.clone())
+            .unwrap_or_default()
+    }
+
+    /// The positions of `word` as a whole word, as `(y, x)` so they compare by line first. They
+    /// are looked for again only after edits or for another word.
+    pub fn word_positions(&mut self, word: &str) -> &[(usize, usize)] {
+        let current = matches!(
+            &self.word_searched,
+            Some((searched, revision)) if searched == word && *revision == self.revision
+        );
+        if !current {
+            self.word_positions = (0..self.len())
+                .flat_map(|y| {
+                    let line = self.line(y).unwrap_or_default();
+                    word::occurrences(&line, word)
+                        .into_iter()
+                        .map(move |x| (y, x))
+                })
+                .collect();
+            self.word_searched = Some((word.to_string(), self.revision));
+        }
+        &self.word_positions
+    }
+
+    /// The sign shown next to each line that has one.
+    pub fn signs(&self) -> HashMap<usize, Sign> {
+        self.signs.by_line(|idx| self.text.char_to_line(idx))
+    }
+}
+
+impl fmt::Display for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.text.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_contents() {
+        let buffer = Buffer::from_contents("fn main() {\n    println!();\n}\n");
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.line(1).unwrap(), "    println!();");
+        assert_eq!(buffer.line_len(1), 15);
+        assert_eq!(buffer.line(3), None);
+
+        let buffer = Buffer::from_reader(&b"diff\n+\xff\n"[..]).unwrap();
+        assert_eq!(buffer.to_string(), "diff\n+\u{fffd}");
+        assert_eq!(buffer.file, None);
+
+        let buffer = Buffer::new();
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.line(0).unwrap(), "");
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let buffer = Buffer::from_contents("one\r\ntwo\r\n");
+        assert_eq!(buffer.line_ending, LineEnding::Dos);
+        assert_eq!(buffer.to_string(), "one\ntwo");
+        assert_eq!(buffer.file_contents(), "one\r\ntwo\r\n");
+
+        let mut buffer = Buffer::stream(&b"one\rtwo\r"[..]).unwrap();
+        assert_eq!(buffer.line_ending, LineEnding::Mac);
+        assert_eq!(buffer.to_string(), "one\ntwo");
+        buffer.line_ending = LineEnding::Unix;
+        assert_eq!(buffer.file_contents(), "one\ntwo\n");
+
+        let buffer = Buffer::stream(&b"a\r\nb\r\n\r\n"[..]).unwrap();
+        assert_eq!(buffer.to_string(), "a\nb\n");
+        assert!(!buffer.missing_newline);
+        let mut buffer = Buffer::stream(&b"a\nb"[..]).unwrap();
+        assert_eq!(buffer.to_string(), "a\nb");
+        assert_eq!(buffer.file_contents(), "a\nb");
+        buffer.missing_newline = false;
+        assert_eq!(buffer.file_contents(), "a\nb\n");
+        assert_eq!(LineEnding::detect(b"no breaks"), LineEnding::Unix);
+        assert_eq!("dos".parse::<LineEnding>().unwrap(), LineEnding::Dos);
+    }
+
+    #[test]
+    fn test_char_editing() {
+        let mut buffer = Buffer::from_contents("héllo\nworld");
+        buffer.insert_char(2, 0, 'x');
+        assert_eq!(buffer.line(0).unwrap(), "héxllo");
+        assert_eq!(buffer.remove_char(1, 0), Some('é'));
+        assert_eq!(buffer.remove_char(5, 0), None);
+        assert_eq!(buffer.to_string(), "hxllo\nworld");
+    }
+
+    #[test]
+    fn test_line_editing() {
+        let mut buffer = Buffer::from_contents("one\ntwo\nthree");
+        buffer.insert_line(1, "new");
+        buffer.insert_line(4, "last");
+        assert_eq!(buffer.to_string(), "one\nnew\ntwo\nthree\nlast");
+
+        assert_eq!(buffer.remove_line(4).unwrap(), "last");
+        assert_eq!(buffer.remove_line(0).unwrap(), "one");
+        assert_eq!(buffer.to_string(), "new\ntwo\nthree");
+
+        buffer.split_line(1, 1);
+        assert_eq!(buffer.to_string(), "new\nt\nwo\nthree");
+
+        buffer.join_lines(1, " ");
+        assert_eq!(buffer.to_string(), "new\nt wo\nthree");
+
+        buffer.set_line(2, "3");
+        assert_eq!(buffer.to_string(), "new\nt wo\n3");
+
+        buffer.replace_lines(0..2, &["a", "b", "c"]);
+        assert_eq!(buffer.to_string(), "a\nb\nc\n3");
+        buffer.replace_lines(1..4, &[]);
+        assert_eq!(buffer.to_string(), "a");
+        buffer.replace_lines(1..1, &["b", "c"]);
+        buffer.replace_lines(0..0, &["0"]);
+        assert_eq!(buffer.to_string(), "0\na\nb\nc");
+
+        let mut buffer = Buffer::from_contents("a  \n\t\nb c\t ");
+        assert!(buffer.trim_trailing_whitespace());
+        assert_eq!(buffer.to_string(), "a\n\nb c");
+        assert!(!buffer.trim_trailing_whitespace());
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut buffer = Buffer::from_contents("one\ntwo\nthree\nfour");
+        buffer.set_mark('a', (1, 2));
+        buffer.set_mark('b', (2, 1));
+        assert_eq!(buffer.mark('c'), None);
+
+        buffer.insert_line(0, "zero");
+        buffer.insert_char(0, 3, 'x');
+        assert_eq!(buffer.mark('a'), Some((2, 3)));
+        buffer.remove_line(4);
+        assert_eq!(buffer.mark('a'), Some((2, 3)));
+        buffer.remove_line(1);
+        assert_eq!(buffer.mark('a'), Some((2, 2)));
+        assert_eq!(buffer.mark('b'), Some((2, 1)));
+
+        // marks in removed text go to where it was
+        buffer.remove_line(1);
+        assert_eq!(buffer.mark('b'), Some((0, 1)));
+        assert_eq!(buffer.mark('a'), Some((2, 1)));
+    }
+
+    #[test]
+    fn test_signs() {
+        let sign = |priority| Sign {
+            text: '●',
+            kind: crate::core::signs::SignKind::Error,
+            priority,
+        };
+        let mut buffer = Buffer::from_contents("one\ntwo\nthree\nfour");
+        buffer.set_signs("lsp", [(1, sign(1)), (2, sign(1)), (9, sign(1))]);
+        buffer.set_signs("git", [(2, sign(2))]);
+        let lines = |buffer: &Buffer| {
+            let mut lines: Vec<_> = buffer
+                .signs()
+                .into_iter()
+                .map(|(y, sign)| (y, sign.priority))
+                .collect();
+            lines.sort();
+            lines
+        };
+        assert_eq!(lines(&buffer), vec![(1, 1), (2, 2)]);
+
+        // signs move with their lines
+        buffer.insert_line(0, "zero");
+        buffer.insert_char(0, 2, 'x');
+        assert_eq!(lines(&buffer), vec![(2, 1), (3, 2)]);
+        buffer.remove_line(0);
+        buffer.set_signs("git", []);
+        assert_eq!(lines(&buffer), vec![(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut buffer = Buffer::from_contents("<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> b");
+        assert_eq!(buffer.conflicts().len(), 1);
+        assert_eq!(buffer.conflicts().len(), 1);
+        buffer.remove_line(0);
+        assert!(buffer.conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_remove_range() {
+        let mut buffer = Buffer::from_contents("one\ntwo\nthree");
+        assert_eq!(buffer.slice((1, 0), (2, 2)), "ne\ntwo\nth");
+        assert_eq!(buffer.remove((1, 0), (2, 2)), "ne\ntwo\nth");
+        assert_eq!(buffer.to_string(), "oree");
+    }
+
+    #[test]
+    fn test_position() {
+        let buffer = Buffer::from_contents("one\ntwo");
+        assert_eq!(buffer.char_idx(1, 1), 5);
+        assert_eq!(buffer.position(5), (1, 1));
+        assert_eq!(buffer.position(3), (3, 0));
+        assert_eq!(buffer.char(4), Some('t'));
+        assert_eq!(buffer.char(7), None);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::core::indent::leading_whitespace;
+
+/// Toggles `marker` line comments on `lines`. When every non-blank line is already commented the
+/// markers are removed, along with the space after them. Otherwise each non-blank line gets the
+/// marker at the indentation of the least indented one, so the comments line up. Blank lines are
+/// left alone either way.
+pub fn toggle(lines: &[String], marker: &str) -> Vec<String> {
+    let code: Vec<_> = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let commented = !code.is_empty()
+        && code
+            .iter()
+            .all(|line| line.trim_start().starts_with(marker));
+
+    if commented {
+        return lines
+            .iter()
+            .map(|line| {
+                let indent = leading_whitespace(line);
+                match line[indent.len()..].strip_prefix(marker) {
+                    Some(rest) => format!("{indent}{}", rest.strip_prefix(' ').unwrap_or(rest)),
+                    None => line.clone(),
+                }
+            })
+            .collect();
+    }
+
+    let indent = code
+        .iter()
+        .map(|line| leading_whitespace(line).len())
+        .min()
+        .unwrap_or_default();
+    lines
+        .iter()
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.clone()
+            } else {
+                let (indent, rest) = line.split_at(indent);
+                format!("{indent}{marker} {rest}")
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_toggle() {
+        let code = lines("fn a() {\n    b();\n\n    c();\n}");
+        let commented = toggle(&code[1..4], "//");
+        assert_eq!(commented, lines("    // b();\n\n    // c();"));
+        assert_eq!(toggle(&commented, "//"), &code[1..4]);
+
+        // mixed lines get commented, lining up with the least indented
+        let mixed = lines("    # a\n  b");
+        assert_eq!(toggle(&mixed, "#"), lines("  #   # a\n  # b"));
+        // markers without a space after them come off too
+        assert_eq!(toggle(&lines("#a\n# b"), "#"), lines("a\nb"));
+        assert_eq!(toggle(&lines(""), "#"), lines(""));
+    }
+}
@@ -0,0 +1,214 @@
+use std::ops::Range;
+
+use crate::core::buffer::Buffer;
+
+/// A merge conflict left in a file by git, as the lines of its markers:
+///
+/// ```text
+/// <<<<<<< HEAD
+/// our lines
+/// ||||||| base
+/// the lines both started from, with `merge.conflictStyle = diff3`
+/// =======
+/// their lines
+/// >>>>>>> branch
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub start: usize,
+    pub base: Option<usize>,
+    pub separator: usize,
+    pub end: usize,
+}
+
+/// Which side of a conflict to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    /// Our lines followed by theirs.
+    Both,
+}
+
+/// What a line of a conflict is, to draw each part apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Marker,
+    Ours,
+    Base,
+    Theirs,
+}
+
+impl Conflict {
+    /// Our lines, between the start marker and the base or the separator.
+    pub fn ours(&self) -> Range<usize> {
+        self.start + 1..self.base.unwrap_or(self.separator)
+    }
+
+    /// Their lines, between the separator and the end marker.
+    pub fn theirs(&self) -> Range<usize> {
+        self.separator + 1..self.end
+    }
+
+    /// The part of the conflict line `y` is in, `None` outside of it.
+    pub fn region(&self, y: usize) -> Option<Region> {
+        if y < self.start || y > self.end {
+            return None;
+        }
+        let markers = [
+            Some(self.start),
+            self.base,
+            Some(self.separator),
+            Some(self.end),
+        ];
+        let region = if markers.contains(&Some(y)) {
+            Region::Marker
+        } else if self.ours().contains(&y) {
+            Region::Ours
+        } else if y < self.separator {
+            Region::Base
+        } else {
+            Region::Theirs
+        };
+        Some(region)
+    }
+
+    /// Replaces the conflict in `buffer` with the lines of the side kept.
+    pub fn resolve(&self, buffer: &mut Buffer, resolution: Resolution) {
+        let ranges = match resolution {
+            Resolution::Ours => vec![self.ours()],
+            Resolution::Theirs => vec![self.theirs()],
+            Resolution::Both => vec![self.ours(), self.theirs()],
+        };
+        let lines: Vec<String> = ranges
+            .into_iter()
+            .flatten()
+            .filter_map(|y| buffer.line(y))
+            .collect();
+        let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+        buffer.replace_lines(self.start..self.end + 1, &lines);
+    }
+}
+
+/// Whether `line` is a conflict marker made of seven `c`, alone or followed by a space and a
+/// label like the branch name.
+fn is_marker(line: &str, c: char) -> bool {
+    let Some(rest) = line.strip_prefix(&c.to_string().repeat(7)) else {
+        return false;
+    };
+    rest.is_empty() || rest.starts_with(' ')
+}
+
+/// The conflicts in `buffer` from top to bottom. Markers that don't make up a whole conflict are
+/// left out.
+pub fn find(buffer: &Buffer) -> Vec<Conflict> {
+    let mut conflicts = vec![];
+    let mut start = None;
+    let mut base = None;
+    let mut separator = None;
+
+    for y in 0..buffer.len() {
+        let line = buffer.line(y).unwrap_or_default();
+        if is_marker(&line, '<') {
+            (start, base, separator) = (Some(y), None, None);
+        } else if is_marker(&line, '|') && start.is_some() && separator.is_none() {
+            base = Some(y);
+        } else if is_marker(&line, '=') && start.is_some() && separator.is_none() {
+            separator = Some(y);
+        } else if is_marker(&line, '>') {
+            if let (Some(start), Some(separator)) = (start, separator) {
+                conflicts.push(Conflict {
+                    start,
+                    base,
+                    separator,
+                    end: y,
+                });
+            }
+            (start, base, separator) = (None, None, None);
+        }
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "\
+a
+<<<<<<< HEAD
+ours
+||||||| base
+base
+=======
+theirs 1
+theirs 2
+>>>>>>> topic
+b
+=======
+<<<<<<<
+x
+=======
+>>>>>>>";
+
+    #[test]
+    fn test_find() {
+        let buffer = Buffer::from_contents(TEXT);
+        let conflicts = find(&buffer);
+        assert_eq!(
+            conflicts,
+            vec![
+                Conflict {
+                    start: 1,
+                    base: Some(3),
+                    separator: 5,
+                    end: 8
+                },
+                Conflict {
+                    start: 11,
+                    base: None,
+                    separator: 13,
+                    end: 14
+                },
+            ]
+        );
+
+        let regions: Vec<_> = (0..10).map(|y| conflicts[0].region(y)).collect();
+        assert_eq!(
+            regions,
+            [
+                None,
+                Some(Region::Marker),
+                Some(Region::Ours),
+                Some(Region::Marker),
+                Some(Region::Base),
+                Some(Region::Marker),
+                Some(Region::Theirs),
+                Some(Region::Theirs),
+                Some(Region::Marker),
+                None
+            ]
+        );
+        assert!(!is_marker("<<<<<<<< eight", '<'));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolved = |resolution| {
+            let mut buffer = Buffer::from_contents(TEXT);
+            let conflicts = find(&buffer);
+            conflicts[1].resolve(&mut buffer, resolution);
+            conflicts[0].resolve(&mut buffer, resolution);
+            buffer.to_string()
+        };
+        assert_eq!(resolved(Resolution::Ours), "a\nours\nb\n=======\nx");
+        assert_eq!(
+            resolved(Resolution::Theirs),
+            "a\ntheirs 1\ntheirs 2\nb\n======="
+        );
+        assert_eq!(
+            resolved(Resolution::Both),
+            "a\nours\ntheirs 1\ntheirs 2\nb\n=======\nx"
+        );
+    }
+}
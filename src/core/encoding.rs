@@ -0,0 +1,126 @@
+use strum_macros::Display;
+
+pub const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+
+/// The encoding of a file on disk. Buffers always hold UTF-8, so files are decoded when read
+/// and encoded back when written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display)]
+pub enum Encoding {
+    #[default]
+    #[strum(serialize = "utf-8")]
+    Utf8,
+    #[strum(serialize = "utf-8-bom")]
+    Utf8Bom,
+    #[strum(serialize = "utf-16le")]
+    Utf16Le,
+    #[strum(serialize = "utf-16be")]
+    Utf16Be,
+    #[strum(serialize = "latin1")]
+    Latin1,
+}
+
+impl Encoding {
+    /// The encoding announced by a byte order mark at the start of `bytes`, if any.
+    pub fn from_bom(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(UTF8_BOM) {
+            Some(Encoding::Utf8Bom)
+        } else if bytes.starts_with(UTF16LE_BOM) {
+            Some(Encoding::Utf16Le)
+        } else if bytes.starts_with(UTF16BE_BOM) {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the encoding of `bytes` from its byte order mark. Without one it is UTF-8 when
+    /// valid, and Latin-1 otherwise since any byte sequence is valid Latin-1.
+    pub fn detect(bytes: &[u8]) -> Self {
+        match Self::from_bom(bytes) {
+            Some(encoding) => encoding,
+            None if std::str::from_utf8(bytes).is_ok() => Encoding::Utf8,
+            None => Encoding::Latin1,
+        }
+    }
+
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8Bom => UTF8_BOM,
+            Encoding::Utf16Le => UTF16LE_BOM,
+            Encoding::Utf16Be => UTF16BE_BOM,
+            Encoding::Utf8 | Encoding::Latin1 => &[],
+        }
+    }
+
+    /// Decodes `bytes`, skipping the byte order mark. Invalid sequences are replaced.
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        let bytes = bytes.strip_prefix(self.bom()).unwrap_or(bytes);
+        let utf16 = |unit: fn([u8; 2]) -> u16| {
+            let units = bytes
+                .chunks(2)
+                .map(|c| unit([c[0], *c.get(1).unwrap_or(&0)]));
+            char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect()
+        };
+
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le => utf16(u16::from_le_bytes),
+            Encoding::Utf16Be => utf16(u16::from_be_bytes),
+            Encoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        }
+    }
+
+    /// Encodes `text` along with the byte order mark, failing when it has chars the encoding
+    /// can't represent.
+    pub fn encode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = self.bom().to_vec();
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => bytes.extend_from_slice(text.as_bytes()),
+            Encoding::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Encoding::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+            Encoding::Latin1 => {
+                for c in text.chars() {
+                    let Ok(b) = u8::try_from(c) else {
+                        anyhow::bail!("E513: Write error, conversion failed for {self}");
+                    };
+                    bytes.push(b);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Encoding::detect("héllo".as_bytes()), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"h\xe9llo"), Encoding::Latin1);
+        assert_eq!(Encoding::detect(b"\xef\xbb\xbfhi"), Encoding::Utf8Bom);
+        assert_eq!(Encoding::detect(b"\xff\xfeh\0"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\xfe\xff\0h"), Encoding::Utf16Be);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (encoding, bytes) in [
+            (Encoding::Latin1, &b"h\xe9\n"[..]),
+            (Encoding::Utf8Bom, b"\xef\xbb\xbfh\xc3\xa9\n"),
+            (Encoding::Utf16Le, b"\xff\xfeh\0\xe9\0\n\0"),
+            (Encoding::Utf16Be, b"\xfe\xff\0h\0\xe9\0\n"),
+        ] {
+            let text = encoding.decode(bytes);
+            assert_eq!(text, "hé\n");
+            assert_eq!(encoding.encode(&text).unwrap(), bytes);
+        }
+
+        assert!(Encoding::Latin1.encode("€").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::core::operator::MotionKind;
+
+/// A search for a char within the cursor line: `f` and `t` forward, `F` and `T` backward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharSearch {
+    pub forward: bool,
+    /// Stops next to the char instead of on it, as `t` and `T` do.
+    pub till: bool,
+}
+
+impl CharSearch {
+    /// The same search in the other direction, which `,` repeats the last one with.
+    pub fn reversed(self) -> Self {
+        CharSearch {
+            forward: !self.forward,
+            ..self
+        }
+    }
+
+    /// How much text the search covers after an operator: `dfx` deletes the `x` while `dFx`
+    /// leaves the char under the cursor alone.
+    pub fn kind(&self) -> MotionKind {
+        if self.forward {
+            MotionKind::Inclusive
+        } else {
+            MotionKind::Exclusive
+        }
+    }
+
+    /// Finds the column `search` for `c` moves to from column `x` of `line`. A repeated till
+    /// search skips the char it would stop next to, so `;` keeps moving after `t`.
+    pub fn find(&self, line: &str, x: usize, c: char, repeat: bool) -> Option<usize> {
+        let chars: Vec<char> = line.chars().collect();
+        let skip = usize::from(self.till && repeat);
+
+        if self.forward {
+            let i = (x + 1 + skip..chars.len()).find(|&i| chars[i] == c)?;
+            Some(if self.till { i - 1 } else { i })
+        } else {
+            let i = (0..x.saturating_sub(skip)).rev().find(|&i| chars[i] == c)?;
+            Some(if self.till { i + 1 } else { i })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let search = |forward, till| CharSearch { forward, till };
+        let line = "foo(bar, baz)";
+
+        assert_eq!(search(true, false).find(line, 0, 'a', false), Some(5));
+        assert_eq!(search(true, true).find(line, 0, 'a', false), Some(4));
+        assert_eq!(search(true, true).find(line, 4, 'a', false), Some(4));
+        assert_eq!(search(true, true).find(line, 4, 'a', true), Some(9));
+        assert_eq!(search(true, false).find(line, 0, 'x', false), None);
+
+        assert_eq!(search(false, false).find(line, 12, 'a', false), Some(10));
+        assert_eq!(search(false, true).find(line, 12, 'a', false), Some(11));
+        assert_eq!(search(false, true).find(line, 11, 'a', true), Some(6));
+        assert_eq!(search(false, false).find(line, 0, 'f', false), None);
+        assert_eq!(search(true, true).reversed(), search(false, true));
+    }
+}
@@ -0,0 +1,106 @@
+/// A number found in a line, as the char range it spans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Number {
+    start: usize,
+    end: usize,
+    hex: bool,
+}
+
+/// The numbers of `line` in order: hex ones with their `0x` prefix, and decimal ones with a `-`
+/// right before them as their sign.
+fn numbers(line: &[char]) -> Vec<Number> {
+    let mut numbers = vec![];
+    let mut i = 0;
+    while i < line.len() {
+        let hex = line[i] == '0'
+            && matches!(line.get(i + 1), Some('x' | 'X'))
+            && line.get(i + 2).is_some_and(|c| c.is_ascii_hexdigit());
+        let (start, mut end) = if hex {
+            (i, i + 2)
+        } else if line[i].is_ascii_digit() {
+            let signed = i > 0 && line[i - 1] == '-';
+            (if signed { i - 1 } else { i }, i)
+        } else {
+            i += 1;
+            continue;
+        };
+
+        while end < line.len()
+            && (line[end].is_ascii_digit() || hex && line[end].is_ascii_hexdigit())
+        {
+            end += 1;
+        }
+        numbers.push(Number { start, end, hex });
+        i = end;
+    }
+    numbers
+}
+
+/// Adds `delta` to the number under or after column `x` of `line`, as `Ctrl-a` and `Ctrl-x` do,
+/// returning the char range it spans and the text to put there.
+///
+/// Hex numbers wrap around as unsigned and keep the case of their digits. Numbers with leading
+/// zeros keep their width, so `007` goes to `008` and `0x0f` to `0x10`.
+pub fn increment(line: &str, x: usize, delta: i64) -> Option<(usize, usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let number = numbers(&chars).into_iter().find(|n| n.end > x)?;
+    let text: String = chars[number.start..number.end].iter().collect();
+
+    let replacement = if number.hex {
+        let (prefix, digits) = text.split_at(2);
+        let value = u64::from_str_radix(digits, 16).ok()?;
+        let value = value.wrapping_add(delta as u64);
+        let width = digits.len();
+        if digits.chars().any(|c| c.is_ascii_uppercase()) {
+            format!("{prefix}{value:0width$X}")
+        } else {
+            format!("{prefix}{value:0width$x}")
+        }
+    } else {
+        let value = text.parse::<i64>().ok()?.saturating_add(delta);
+        let digits = text.trim_start_matches('-');
+        if digits.len() > 1 && digits.starts_with('0') {
+            let sign = if value < 0 { "-" } else { "" };
+            format!(
+                "{sign}{:0width$}",
+                value.unsigned_abs(),
+                width = digits.len()
+            )
+        } else {
+            value.to_string()
+        }
+    };
+
+    Some((number.start, number.end, replacement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increment() {
+        let line = "port = 8080, retries = -1";
+        assert_eq!(increment(line, 0, 1), Some((7, 11, "8081".to_string())));
+        assert_eq!(increment(line, 9, -81), Some((7, 11, "7999".to_string())));
+        assert_eq!(increment(line, 12, 2), Some((23, 25, "1".to_string())));
+        assert_eq!(increment(line, 25, 1), None);
+
+        assert_eq!(increment("x0", 0, 1), Some((1, 2, "1".to_string())));
+        assert_eq!(increment("007", 2, 1), Some((0, 3, "008".to_string())));
+        assert_eq!(increment("0099", 0, 1), Some((0, 4, "0100".to_string())));
+        assert_eq!(increment("-01", 0, 3), Some((0, 3, "02".to_string())));
+
+        assert_eq!(
+            increment("c = 0x0f", 0, 1),
+            Some((4, 8, "0x10".to_string()))
+        );
+        assert_eq!(increment("0xFF", 3, 1), Some((0, 4, "0x100".to_string())));
+        assert_eq!(
+            increment("0x00", 1, -1),
+            Some((0, 4, "0xffffffffffffffff".to_string()))
+        );
+        // a `0x` with no hex digits after it is just a zero
+        assert_eq!(increment("0xg", 0, 1), Some((0, 1, "1".to_string())));
+    }
+}
@@ -0,0 +1,264 @@
+use std::ops::RangeInclusive;
+
+use tree_sitter::{Node, Point, Tree};
+
+use crate::core::syntax::Language;
+
+/// How many lines from the top of a file [`detect`] looks at.
+const DETECT_LINES: usize = 1000;
+
+/// How a file indents its lines, as [`detect`] found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indentation {
+    Tabs,
+    Spaces(u8),
+}
+
+/// Returns the spaces and tabs `line` starts with.
+pub fn leading_whitespace(line: &str) -> &str {
+    let end = line.len() - line.trim_start_matches([' ', '\t']).len();
+    &line[..end]
+}
+
+/// One level of indentation: `tab_size` spaces, or a tab when `tab_to_spaces` is off.
+pub fn unit(tab_size: u8, tab_to_spaces: bool) -> String {
+    if tab_to_spaces {
+        " ".repeat(tab_size as usize)
+    } else {
+        "\t".to_string()
+    }
+}
+
+/// Adds a level of indentation to `line`, leaving empty lines alone.
+pub fn indent_line(line: &str, unit: &str) -> String {
+    if line.trim().is_empty() {
+        return line.to_string();
+    }
+    format!("{unit}{line}")
+}
+
+/// Removes a level of indentation from `line`: a tab, or up to `tab_size` spaces.
+pub fn dedent_line(line: &str, tab_size: u8) -> String {
+    if let Some(rest) = line.strip_prefix('\t') {
+        return rest.to_string();
+    }
+
+    let spaces = line
+        .chars()
+        .take(tab_size as usize)
+        .take_while(|&c| c == ' ')
+        .count();
+    line[spaces..].to_string()
+}
+
+/// Guesses how the `lines` of a file indent: with tabs when more lines start with one than with
+/// spaces, otherwise by the most common step between the indentation of one line and the next,
+/// the smaller on a tie. Returns `None` when no line is indented.
+pub fn detect(lines: impl IntoIterator<Item = impl AsRef<str>>) -> Option<Indentation> {
+    let (mut tabs, mut spaces) = (0, 0);
+    let mut steps = [0usize; 9];
+    let mut previous = 0;
+    for line in lines.into_iter().take(DETECT_LINES) {
+        let line = line.as_ref();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+
+        let width = line.len() - line.trim_start_matches(' ').len();
+        if width > 0 {
+            spaces += 1;
+        }
+        // the ` * ` lines of block comments are one off from the code around them
+        if line[width..].starts_with('*') {
+            continue;
+        }
+        let step = width.abs_diff(previous);
+        if step >= 2 && step < steps.len() {
+            steps[step] += 1;
+        }
+        previous = width;
+    }
+
+    if tabs > spaces {
+        return Some(Indentation::Tabs);
+    }
+    let (step, count) = steps
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|&(_, count)| count)?;
+    (*count > 0).then_some(Indentation::Spaces(step as u8))
+}
+
+/// The indentation for a line opened below `line`. It keeps the indentation of `line` and adds
+/// a level when `line` ends by opening a block in `language`.
+pub fn next_line_indent(line: &str, language: Option<&Language>, unit: &str) -> String {
+    let mut indent = leading_whitespace(line).to_string();
+
+    let opens_block = match (language, line.trim_end().chars().last()) {
+        (Some(language), Some(last)) => language.block_openers().contains(&last),
+        _ => false,
+    };
+    if opens_block {
+        indent.push_str(unit);
+    }
+
+    indent
+}
+
+/// The indentation level of each of `lines` in `text`, from how many of the language's indent
+/// nodes, like blocks, contain the line after starting on an earlier one. A line starting with
+/// the bracket that closes such a node goes with the line that opened it. Lines inside a syntax
+/// error, or inside a string or comment spanning lines, get `None`, as do all lines in languages
+/// without indent nodes.
+pub fn levels(
+    language: &Language,
+    tree: &Tree,
+    text: &str,
+    lines: RangeInclusive<usize>,
+) -> Vec<Option<usize>> {
+    if language.indent_nodes().is_empty() {
+        return lines.map(|_| None).collect();
+    }
+
+    let text: Vec<_> = text.split('\n').collect();
+    lines
+        .map(|y| {
+            let line = text.get(y)?;
+            let column = line.len() - line.trim_start().len();
+            level(language, tree.root_node(), Point::new(y, column))
+        })
+        .collect()
+}
+
+/// The indentation level of the line whose first non-blank char is at `point`.
+fn level(language: &Language, root: Node, point: Point) -> Option<usize> {
+    let mut node = root.descendant_for_point_range(point, point)?;
+    let mut level = 0;
+    // nodes opened on the same line only indent once, as in `foo(|x| {`
+    let mut counted_row = None;
+    loop {
+        if node.is_error() || node.is_missing() {
+            return None;
+        }
+
+        let start = node.start_position();
+        if start.row < point.row {
+            if language.literal_nodes().contains(&node.kind()) {
+                return None;
+            }
+            if language.indent_nodes().contains(&node.kind()) && counted_row != Some(start.row) {
+                let closes = node
+                    .child(node.child_count().saturating_sub(1))
+                    .is_some_and(|last| !last.is_named() && last.start_position() == point);
+                if !closes {
+                    level += 1;
+                }
+                counted_row = Some(start.row);
+            }
+        }
+
+        match node.parent() {
+            Some(parent) => node = parent,
+            None => return Some(level),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed_levels(
+        language: Language,
+        text: &str,
+        lines: RangeInclusive<usize>,
+    ) -> Vec<Option<usize>> {
+        let tree = language.parse(text).unwrap();
+        levels(&language, &tree, text, lines)
+    }
+
+    #[test]
+    fn test_next_line_indent() {
+        let rust = Some(&Language::Rust);
+        assert_eq!(next_line_indent("    let a = 1;", rust, "    "), "    ");
+        assert_eq!(next_line_indent("\tfn main() {", rust, "\t"), "\t\t");
+        assert_eq!(next_line_indent("  foo(  ", rust, "  "), "    ");
+        assert_eq!(next_line_indent("fn main() {", None, "    "), "");
+        assert_eq!(next_line_indent("", rust, "    "), "");
+
+        assert_eq!(unit(2, true), "  ");
+        assert_eq!(unit(2, false), "\t");
+    }
+
+    #[test]
+    fn test_detect() {
+        let rust = "fn main() {\n    if a {\n        b();\n    }\n}\n";
+        assert_eq!(detect(rust.lines()), Some(Indentation::Spaces(4)));
+        let yaml = "a:\n  b:\n    c: 1\n  d: 2\ne:\n  - f\n";
+        assert_eq!(detect(yaml.lines()), Some(Indentation::Spaces(2)));
+        let go = "func main() {\n\tif a {\n\t\tb()\n\t}\n}\n/*\n * c\n */";
+        assert_eq!(detect(go.lines()), Some(Indentation::Tabs));
+        let c = "/*\n * a\n */\nint b;\n";
+        assert_eq!(detect(c.lines()), None);
+        assert_eq!(detect(["a", "", "b"]), None);
+    }
+
+    #[test]
+    fn test_shift_line() {
+        assert_eq!(indent_line("foo", "    "), "    foo");
+        assert_eq!(indent_line("\tfoo", "\t"), "\t\tfoo");
+        assert_eq!(indent_line("  ", "    "), "  ");
+
+        assert_eq!(dedent_line("      foo", 4), "  foo");
+        assert_eq!(dedent_line("  foo", 4), "foo");
+        assert_eq!(dedent_line("\t\tfoo", 4), "\tfoo");
+        assert_eq!(dedent_line("foo", 4), "foo");
+    }
+
+    #[test]
+    fn test_levels() {
+        let text = "fn main() {\n\
+                    let v = vec![\n\
+                    1,\n\
+                    ];\n\
+                    foo(|x| {\n\
+                    x\n\
+                    })\n\
+                    .bar();\n\
+                    if a {\n\
+                    } else {\n\
+                    let s = \"a\n\
+                    b\";\n\
+                    \n\
+                    }\n\
+                    }";
+        let expected = [0, 1, 2, 1, 1, 2, 1, 2, 1, 1, 2, 0, 2, 1, 0].map(Some);
+        let mut expected = expected.to_vec();
+        // the second line of the string keeps its indentation
+        expected[11] = None;
+        assert_eq!(parsed_levels(Language::Rust, text, 0..=14), expected);
+
+        let text = "function f(a) {\nswitch (a) {\ncase 1:\nreturn {\nb: [\n],\n};\n}\n}";
+        let expected = [0, 1, 2, 3, 4, 4, 3, 1, 0].map(Some);
+        assert_eq!(parsed_levels(Language::JavaScript, text, 0..=8), expected);
+
+        let broken = parsed_levels(Language::Rust, "fn main() {\nfoo(", 1..=1);
+        assert_eq!(broken, [None]);
+    }
+
+    #[cfg(all(feature = "go", feature = "python"))]
+    #[test]
+    fn test_levels_other_languages() {
+        let text = "func f(a int) {\nswitch a {\ncase 1:\ng(\na,\n)\n}\n}";
+        let expected = [0, 1, 1, 2, 3, 2, 1, 0].map(Some);
+        assert_eq!(parsed_levels(Language::Go, text, 0..=7), expected);
+
+        let text = "def f():\nreturn 1";
+        assert_eq!(parsed_levels(Language::Python, text, 0..=1), [None, None]);
+    }
+}
@@ -0,0 +1,84 @@
+/// How many jumps a buffer remembers, dropping the oldest ones past it.
+const MAX_JUMPS: usize = 100;
+
+/// The positions a buffer's cursor jumped away from, with `gg`, `G`, `:N` or a page, which
+/// `Ctrl-o` goes back through and `Ctrl-i` forward again.
+#[derive(Debug, Clone, Default)]
+pub struct JumpList {
+    jumps: Vec<(usize, usize)>,
+    /// The jump `Ctrl-o` and `Ctrl-i` last moved to, or the end of the list after a new jump.
+    current: usize,
+}
+
+impl JumpList {
+    /// Remembers `position` before a jump. Like vim, a line only appears once in the list, at
+    /// its latest jump.
+    pub fn push(&mut self, position: (usize, usize)) {
+        self.jumps.retain(|&(_, y)| y != position.1);
+        self.jumps.push(position);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.current = self.jumps.len();
+    }
+
+    /// Moves back to the previous jump from `position`, which is remembered first when leaving
+    /// the end of the list so `forward` can return to it.
+    pub fn back(&mut self, position: (usize, usize)) -> Option<(usize, usize)> {
+        if self.current == self.jumps.len() {
+            self.push(position);
+            self.current = self.jumps.len() - 1;
+        }
+        if self.current == 0 {
+            return None;
+        }
+
+        self.current -= 1;
+        Some(self.jumps[self.current])
+    }
+
+    pub fn forward(&mut self) -> Option<(usize, usize)> {
+        if self.current + 1 >= self.jumps.len() {
+            return None;
+        }
+
+        self.current += 1;
+        Some(self.jumps[self.current])
+    }
+
+    /// The jumps from oldest to newest, with whether each is the one last moved to.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), bool)> + '_ {
+        self.jumps
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| (position, i == self.current))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jump_list() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.back((0, 0)), None);
+
+        jumps.push((2, 0));
+        jumps.push((0, 10));
+        assert_eq!(jumps.back((3, 50)), Some((0, 10)));
+        assert_eq!(jumps.back((0, 10)), Some((2, 0)));
+        assert_eq!(jumps.back((2, 0)), None);
+        assert_eq!(jumps.forward(), Some((0, 10)));
+        assert_eq!(jumps.forward(), Some((3, 50)));
+        assert_eq!(jumps.forward(), None);
+
+        // jumping again from the middle of the list moves its line to the end
+        jumps.back((3, 50));
+        jumps.push((1, 10));
+        let list: Vec<_> = jumps.iter().map(|(position, _)| position).collect();
+        assert_eq!(list, vec![(2, 0), (3, 50), (1, 10)]);
+        assert_eq!(jumps.forward(), None);
+        assert_eq!(jumps.back((0, 80)), Some((1, 10)));
+    }
+}
@@ -0,0 +1,27 @@
+//! The pieces of the editing engine: buffers and the text they hold, the modes, actions and
+//! operators that change it, and what they need to find their way around it, like words, brackets
+//! and syntax trees.
+//! Nothing in this module draws or reads the terminal, which is left to [`crate::tui`]. The
+//! `Editor` in `main.rs` that runs them still handles crossterm events itself.
+
+pub mod abbrev;
+pub mod action;
+pub mod brackets;
+pub mod buffer;
+pub mod comment;
+pub mod conflict;
+pub mod encoding;
+pub mod find;
+pub mod increment;
+pub mod indent;
+pub mod jumplist;
+pub mod mode;
+pub mod operator;
+pub mod register;
+pub mod signs;
+pub mod snippet;
+pub mod spell;
+pub mod structure;
+pub mod substitute;
+pub mod syntax;
+pub mod word;
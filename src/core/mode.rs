@@ -0,0 +1,28 @@
+/// What keys do, switched between with actions like `InsertMode` and `NormalMode`.
+#[derive(Default, Debug)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Command,
+    Visual,
+    /// Typing over the chars after the cursor, started with `R`.
+    Replace,
+}
+
+impl Mode {
+    pub fn is_command(&self) -> bool {
+        matches!(self, Mode::Command)
+    }
+
+    /// The lowercase name of the mode, as plugins get it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Insert => "insert",
+            Mode::Command => "command",
+            Mode::Visual => "visual",
+            Mode::Replace => "replace",
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use crate::core::{action::Action, buffer::Buffer, find::CharSearch, word};
+
+/// An action applied to the text covered by a motion or text object, as in `dw`, `c$` or `yi(`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    /// Shifts the covered lines right by a level, `>`.
+    Indent,
+    /// Shifts the covered lines left by a level, `<`.
+    Dedent,
+    /// Indents the covered lines by the syntax around them, `=`.
+    Reindent,
+    /// Lowercases the covered text, `gu`.
+    Lowercase,
+    /// Uppercases the covered text, `gU`.
+    Uppercase,
+    /// Switches the case of each char, as `~` does to the chars under the cursor.
+    ToggleCase,
+    /// Toggles line comments on the covered lines, `gc`.
+    Comment,
+}
+
+impl Operator {
+    /// The operator an action starts. Repeating it, as in `dd`, `cc` or `>>`, acts on the whole
+    /// line.
+    pub fn from_action(action: &Action) -> Option<Self> {
+        match action {
+            Action::Delete => Some(Operator::Delete),
+            Action::Change => Some(Operator::Change),
+            Action::Yank => Some(Operator::Yank),
+            Action::Indent => Some(Operator::Indent),
+            Action::Dedent => Some(Operator::Dedent),
+            Action::Reindent => Some(Operator::Reindent),
+            Action::Lowercase => Some(Operator::Lowercase),
+            Action::Uppercase => Some(Operator::Uppercase),
+            Action::Comment => Some(Operator::Comment),
+            _ => None,
+        }
+    }
+
+    /// The key that repeats a `g` operator to act on whole lines, as the second `u` of `guu` or
+    /// `c` of `gcc`.
+    pub fn line_key(&self) -> Option<char> {
+        match self {
+            Operator::Lowercase => Some('u'),
+            Operator::Uppercase => Some('U'),
+            Operator::Comment => Some('c'),
+            _ => None,
+        }
+    }
+
+    /// `text` with its case changed by a case operator, or `None` for the other operators. Chars
+    /// map by their Unicode case, so `ß` uppercases to `SS`.
+    pub fn change_case(&self, text: &str) -> Option<String> {
+        match self {
+            Operator::Lowercase => Some(text.to_lowercase()),
+            Operator::Uppercase => Some(text.to_uppercase()),
+            Operator::ToggleCase => Some(
+                text.chars()
+                    .flat_map(|c| {
+                        if c.is_lowercase() {
+                            c.to_uppercase().collect::<Vec<_>>()
+                        } else {
+                            c.to_lowercase().collect()
+                        }
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// How much of the text a motion covers when used after an operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionKind {
+    /// Up to, but not including, the position the motion moves to (`w`, `h`, `0`).
+    Exclusive,
+    /// Up to and including the position the motion moves to (`$`, `t`).
+    Inclusive,
+    /// Every line between the cursor and where the motion moves to (`j`, `k`, `G`).
+    Linewise,
+}
+
+/// The keys typed so far of a command that takes more than one key in normal mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Pending {
+    #[default]
+    None,
+    /// `g`, `z` or `<C-w>` waiting for its second key, possibly as the motion of an operator
+    /// (`dgg`).
+    Prefix(Option<Operator>, char),
+    /// `f`, `t`, `F` or `T` waiting for the char to move to, possibly as the motion of an
+    /// operator (`dt)`).
+    FindChar(Option<Operator>, CharSearch),
+    /// An operator waiting for its motion.
+    Operator(Operator),
+    /// `i` (inner) or `a` (around) after an operator or in visual mode, waiting for the text
+    /// object.
+    TextObject(Option<Operator>, bool),
+    /// `m` waiting for the mark to set.
+    SetMark,
+    /// `` ` `` or `'` (linewise) waiting for the mark to move to, possibly as the motion of an
+    /// operator.
+    GoToMark(Option<Operator>, bool),
+    /// `"` waiting for the name of the register to use.
+    Register,
+    /// `q` waiting for the register to record a macro into.
+    Record,
+    /// `@` waiting for the register of the macro to replay, and how many times.
+    Play(usize),
+    /// `r` waiting for the char to replace that many chars with.
+    Replace(usize),
+    /// `z=` waiting for the number of the suggestion to replace the word from the start
+    /// position to the end column with.
+    Suggestion((usize, usize), usize),
+}
+
+/// The char `<C-w>` waits as in `Pending::Prefix`, the one Ctrl-W types.
+pub const WINDOW_PREFIX: char = '\u{17}';
+
+/// Finds the range of the text object `object` around `(x, y)`, returning its start (inclusive)
+/// and end (exclusive). Inner objects leave out the delimiters, or the blanks around a word or
+/// paragraph.
+///
+/// Objects are words (`w`, `W`), paragraphs (`p`), brackets and quotes. Brackets may span lines
+/// and nest, quotes are only matched within the cursor line.
+pub fn text_object(
+    buffer: &Buffer,
+    (x, y): (usize, usize),
+    object: char,
+    inner: bool,
+) -> Option<((usize, usize), (usize, usize))> {
+    let (start, end) = match object {
+        'w' | 'W' => return word::word_object(buffer, (x, y), object == 'W', !inner),
+        'p' => return paragraph(buffer, y, !inner),
+        '(' | ')' | 'b' => bracket_pair(buffer, (x, y), '(', ')')?,
+        '[' | ']' => bracket_pair(buffer, (x, y), '[', ']')?,
+        '{' | '}' | 'B' => bracket_pair(buffer, (x, y), '{', '}')?,
+        '<' | '>' => bracket_pair(buffer, (x, y), '<', '>')?,
+        '"' | '\'' | '`' => quote_pair(buffer, (x, y), object)?,
+        _ => return None,
+    };
+
+    let (start, end) = if inner {
+        (start + 1, end)
+    } else {
+        (start, end + 1)
+    };
+    Some((buffer.position(start), buffer.position(end)))
+}
+
+/// The lines of the paragraph around line `y`, a run of non-blank lines or of blank lines when
+/// `y` is blank. Around a paragraph also takes the lines after it up to the next one, or the ones
+/// before it when it ends the buffer.
+fn paragraph(buffer: &Buffer, y: usize, around: bool) -> Option<((usize, usize), (usize, usize))> {
+    let blank = |y: usize| buffer.line(y).is_some_and(|line| line.trim().is_empty());
+    let len = buffer.len();
+    if y >= len {
+        return None;
+    }
+
+    let kind = blank(y);
+    let (mut first, mut last) = (y, y);
+    while first > 0 && blank(first - 1) == kind {
+        first -= 1;
+    }
+    while last + 1 < len && blank(last + 1) == kind {
+        last += 1;
+    }
+
+    if around && last + 1 < len {
+        while last + 1 < len && blank(last + 1) != kind {
+            last += 1;
+        }
+    } else if around {
+        while first > 0 && blank(first - 1) != kind {
+            first -= 1;
+        }
+    }
+
+    // whole lines, taking the line break before them when they run to the end of the buffer
+    if last + 1 < len {
+        Some(((0, first), (0, last + 1)))
+    } else if first > 0 {
+        let end = (buffer.line_len(last), last);
+        Some(((buffer.line_len(first - 1), first - 1), end))
+    } else {
+        Some(((0, 0), (buffer.line_len(last), last)))
+    }
+}
+
+/// Char indices of the innermost `open`/`close` pair enclosing `(x, y)`. When the buffer's
+/// highlights are current, brackets in strings and comments are skipped from code and the other
+/// way around, as for `%`.
+fn bracket_pair(
+    buffer: &Buffer,
+    (x, y): (usize, usize),
+    open: char,
+    close: char,
+) -> Option<(usize, usize)> {
+    let cursor = buffer.char_idx(x, y);
+    let highlights = buffer
+        .highlights
+        .is_current(buffer)
+        .then_some(&buffer.highlights);
+    let literal = |i: usize| highlights.is_some_and(|h| h.is_literal(buffer.position(i)));
+    let in_literal = literal(cursor);
+    let bracket =
+        |i: usize, bracket: char| buffer.char(i) == Some(bracket) && literal(i) == in_literal;
+
+    let start = if bracket(cursor, open) {
+        cursor
+    } else {
+        let mut depth = 0;
+        let mut found = None;
+        for i in (0..cursor).rev() {
+            if bracket(i, close) {
+                depth += 1;
+            } else if bracket(i, open) && depth == 0 {
+                found = Some(i);
+                break;
+            } else if bracket(i, open) {
+                depth -= 1;
+            }
+        }
+        found?
+    };
+
+    let mut depth = 0;
+    for i in start + 1..buffer.len_chars() {
+        if bracket(i, open) {
+            depth += 1;
+        } else if bracket(i, close) && depth == 0 {
+            return Some((start, i));
+        } else if bracket(i, close) {
+            depth -= 1;
+        }
+    }
+
+    None
+}
+
+/// Char indices of the pair of `quote`s on line `y` around `x`. Like vim, when the cursor is
+/// before the first quote the first quoted string on the line is used.
+fn quote_pair(buffer: &Buffer, (x, y): (usize, usize), quote: char) -> Option<(usize, usize)> {
+    let line = buffer.line(y)?;
+    let quotes: Vec<usize> = line
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| *c == quote)
+        .map(|(i, _)| i)
+        .collect();
+
+    let (a, b) = if let Some(k) = quotes.iter().position(|&i| i == x) {
+        // on a quote: quotes pair up from the start of the line
+        if k % 2 == 0 {
+            (*quotes.get(k)?, *quotes.get(k + 1)?)
+        } else {
+            (quotes[k - 1], quotes[k])
+        }
+    } else {
+        match quotes.iter().rposition(|&i| i < x) {
+            Some(k) => (quotes[k], *quotes.get(k + 1)?),
+            None => (*quotes.first()?, *quotes.get(1)?),
+        }
+    };
+
+    let offset = buffer.char_idx(0, y);
+    Some((offset + a, offset + b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::syntax::{Highlights, Language};
+
+    #[test]
+    fn test_bracket_text_object() {
+        let buffer = Buffer::from_contents("call(a, (b), c)\nfn x() {\n    y\n}");
+
+        // nested brackets pick the innermost pair around the cursor
+        assert_eq!(
+            text_object(&buffer, (9, 0), '(', true),
+            Some(((9, 0), (10, 0)))
+        );
+        assert_eq!(
+            text_object(&buffer, (6, 0), '(', true),
+            Some(((5, 0), (14, 0)))
+        );
+        assert_eq!(
+            text_object(&buffer, (4, 0), ')', false),
+            Some(((4, 0), (15, 0)))
+        );
+
+        // brackets can span lines
+        assert_eq!(
+            text_object(&buffer, (4, 2), '{', true),
+            Some(((8, 1), (0, 3)))
+        );
+        assert_eq!(text_object(&buffer, (0, 1), '[', true), None);
+    }
+
+    #[test]
+    fn test_bracket_in_string() {
+        let mut buffer = Buffer::from_contents(r#"f(")", g(x))"#);
+        buffer.language = Some(Language::Rust);
+        buffer.highlights = Highlights::compute(&buffer).unwrap();
+
+        assert_eq!(
+            text_object(&buffer, (1, 0), '(', true),
+            Some(((2, 0), (11, 0)))
+        );
+    }
+
+    #[test]
+    fn test_word_and_paragraph_text_objects() {
+        let buffer = Buffer::from_contents(
+            "one two  three
+
+four
+five
+
+
+six",
+        );
+
+        assert_eq!(
+            text_object(&buffer, (5, 0), 'w', true),
+            Some(((4, 0), (7, 0)))
+        );
+        assert_eq!(
+            text_object(&buffer, (5, 0), 'w', false),
+            Some(((4, 0), (9, 0)))
+        );
+        // the last word takes the blanks before it
+        assert_eq!(
+            text_object(&buffer, (10, 0), 'w', false),
+            Some(((7, 0), (14, 0)))
+        );
+
+        assert_eq!(
+            text_object(&buffer, (0, 3), 'p', true),
+            Some(((0, 2), (0, 4)))
+        );
+        assert_eq!(
+            text_object(&buffer, (0, 3), 'p', false),
+            Some(((0, 2), (0, 6)))
+        );
+        // the last paragraph takes the line breaks before it
+        assert_eq!(
+            text_object(&buffer, (1, 6), 'p', false),
+            Some(((4, 3), (3, 6)))
+        );
+    }
+
+    #[test]
+    fn test_change_case() {
+        assert_eq!(
+            Operator::Uppercase.change_case("straße ok"),
+            Some("STRASSE OK".to_string())
+        );
+        assert_eq!(
+            Operator::Lowercase.change_case("ÉCOLE"),
+            Some("école".to_string())
+        );
+        assert_eq!(
+            Operator::ToggleCase.change_case("Hello, Ωmega"),
+            Some("hELLO, ωMEGA".to_string())
+        );
+        assert_eq!(Operator::Delete.change_case("x"), None);
+    }
+
+    #[test]
+    fn test_quote_text_object() {
+        let buffer = Buffer::from_contents(r#"let s = "one" + "two";"#);
+
+        assert_eq!(
+            text_object(&buffer, (10, 0), '"', true),
+            Some(((9, 0), (12, 0)))
+        );
+        assert_eq!(
+            text_object(&buffer, (12, 0), '"', false),
+            Some(((8, 0), (13, 0)))
+        );
+        assert_eq!(
+            text_object(&buffer, (17, 0), '"', true),
+            Some(((17, 0), (20, 0)))
+        );
+        // before the first quote uses the first quoted string
+        assert_eq!(
+            text_object(&buffer, (0, 0), '"', true),
+            Some(((9, 0), (12, 0)))
+        );
+        assert_eq!(text_object(&buffer, (0, 0), '\'', true), None);
+    }
+}
@@ -0,0 +1,57 @@
+/// Text yanked or deleted for `p` and `P` to put back, and whether it is whole lines, which go
+/// below or above the cursor line rather than next to the cursor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+impl Register {
+    pub fn charwise(text: String) -> Self {
+        Register {
+            text,
+            linewise: false,
+        }
+    }
+
+    /// Takes whole lines, each ending with a line break.
+    pub fn linewise(text: String) -> Self {
+        Register {
+            text,
+            linewise: true,
+        }
+    }
+
+    /// Takes text from the system clipboard, which is whole lines when it ends with a line
+    /// break, as vim decides.
+    pub fn from_clipboard(text: String) -> Self {
+        let text = text.replace("\r\n", "\n");
+        Register {
+            linewise: text.ends_with('\n'),
+            text,
+        }
+    }
+
+    /// The lines of a linewise register, without the line break after the last one.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.text
+            .strip_suffix('\n')
+            .unwrap_or(&self.text)
+            .split('\n')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_clipboard() {
+        let register = Register::from_clipboard("one\r\ntwo\r\n".to_string());
+        assert!(register.linewise);
+        assert_eq!(register.lines().collect::<Vec<_>>(), vec!["one", "two"]);
+
+        let register = Register::from_clipboard("word".to_string());
+        assert!(!register.linewise);
+    }
+}
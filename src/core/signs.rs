@@ -0,0 +1,94 @@
+use std::{collections::HashMap, ops::Range};
+
+/// What a sign marks, which decides the color it's drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignKind {
+    Error,
+    Warning,
+    Information,
+    Added,
+    Changed,
+    Removed,
+}
+
+/// A char drawn in the sign column next to a line, like the `●` of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sign {
+    pub text: char,
+    pub kind: SignKind,
+    /// Decides which sign shows when a line has several, the highest wins.
+    pub priority: u8,
+}
+
+/// The signs of a buffer, placed in groups so each part of the editor that places signs, like the
+/// language server diagnostics, replaces its own without touching the others.
+///
+/// Signs are kept at the char index where their line starts, so they move with the line as text
+/// is added or removed above it until their group places them again.
+#[derive(Debug, Clone, Default)]
+pub struct Signs {
+    groups: HashMap<&'static str, Vec<(usize, Sign)>>,
+}
+
+impl Signs {
+    /// Replaces the signs of `group` with `signs`, given at char indices.
+    pub fn set(&mut self, group: &'static str, signs: Vec<(usize, Sign)>) {
+        if signs.is_empty() {
+            self.groups.remove(group);
+        } else {
+            self.groups.insert(group, signs);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+    }
+
+    /// Moves the signs after `idx` along for `len` chars inserted there.
+    pub fn insert(&mut self, idx: usize, len: usize) {
+        for (sign_idx, _) in self.groups.values_mut().flatten() {
+            *sign_idx = moved_by_insert(*sign_idx, idx, len);
+        }
+    }
+
+    /// Moves the signs after `range` back for the chars removed there.
+    pub fn remove(&mut self, range: Range<usize>) {
+        for (sign_idx, _) in self.groups.values_mut().flatten() {
+            *sign_idx = moved_by_remove(*sign_idx, &range);
+        }
+    }
+
+    /// The sign to show for each line, the one with the highest priority, where `line` gives the
+    /// line of a char index.
+    pub fn by_line(&self, line: impl Fn(usize) -> usize) -> HashMap<usize, Sign> {
+        let mut lines: HashMap<usize, Sign> = HashMap::new();
+        for &(idx, sign) in self.groups.values().flatten() {
+            let shown = lines.entry(line(idx)).or_insert(sign);
+            if sign.priority > shown.priority {
+                *shown = sign;
+            }
+        }
+        lines
+    }
+}
+
+/// Where the char at `pos` ends up after inserting `len` chars at `idx`.
+pub fn moved_by_insert(pos: usize, idx: usize, len: usize) -> usize {
+    if pos >= idx {
+        pos + len
+    } else {
+        pos
+    }
+}
+
+/// Where the char at `pos` ends up after removing `range`, which is where the range started for
+/// chars inside it.
+pub fn moved_by_remove(pos: usize, range: &Range<usize>) -> usize {
+    if pos >= range.end {
+        pos - range.len()
+    } else if pos > range.start {
+        range.start
+    } else {
+        pos
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::core::buffer::Buffer;
+
+/// The directory snippets are read from, `~/.config/fed/snippets`, with a `<language>.toml`
+/// for each language and an `all.toml` for every buffer.
+pub fn snippets_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("fed").join("snippets"))
+}
+
+/// The body of the snippet `trigger` for `language` from its file in `dir`, or from `all.toml`
+/// when the language has none by that name. The files map triggers to bodies:
+///
+/// ```toml
+/// fn = "fn ${1:name}($2) {\n    $0\n}"
+/// ```
+pub fn find(dir: &Path, language: Option<&str>, trigger: &str) -> anyhow::Result<Option<String>> {
+    for name in language.into_iter().chain(["all"]) {
+        let path = dir.join(format!("{name}.toml"));
+        if !path.exists() {
+            continue;
+        }
+        let snippets: HashMap<String, String> = toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?;
+        if let Some(body) = snippets.get(trigger) {
+            return Ok(Some(body.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// A tab stop of a snippet, or a mirror of one, covering a range of chars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// Which stop it belongs to, by the order Tab visits them in.
+    pub stop: usize,
+    pub range: Range<usize>,
+}
+
+/// A snippet body with its markup taken out.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// The tab stops in the order they appear in the text, where the first field of each stop
+    /// is the one typed in and the others mirror it.
+    pub fields: Vec<Field>,
+    /// How many stops there are, the last one being `$0`, or the end of the text without one.
+    pub stops: usize,
+}
+
+enum Token {
+    Text(String),
+    Stop(usize, Option<String>),
+}
+
+impl Snippet {
+    /// Parses a body with tab stops like `$1` and placeholders like `${1:name}`. Stops with the
+    /// same number mirror each other and share the placeholder of the one that has it. `\$`,
+    /// `\}` and `\\` stand for the chars themselves.
+    pub fn parse(body: &str) -> Self {
+        let tokens = tokenize(body);
+
+        let mut numbers: Vec<usize> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Stop(n, _) if *n > 0 => Some(*n),
+                _ => None,
+            })
+            .collect();
+        numbers.sort();
+        numbers.dedup();
+        // $0 goes last, where the cursor ends up
+        numbers.push(0);
+
+        let mut placeholders = HashMap::new();
+        for token in &tokens {
+            if let Token::Stop(n, Some(placeholder)) = token {
+                placeholders.entry(*n).or_insert(placeholder.as_str());
+            }
+        }
+
+        let mut text = String::new();
+        let mut len = 0;
+        let mut fields = vec![];
+        for token in &tokens {
+            let (stop, part) = match token {
+                Token::Text(part) => (None, part.as_str()),
+                Token::Stop(n, _) => {
+                    let stop = numbers.iter().position(|m| m == n);
+                    (stop, placeholders.get(n).copied().unwrap_or_default())
+                }
+            };
+            let start = len;
+            text.push_str(part);
+            len += part.chars().count();
+            if let Some(stop) = stop {
+                fields.push(Field {
+                    stop,
+                    range: start..len,
+                });
+            }
+        }
+
+        let stops = numbers.len();
+        if !fields.iter().any(|field| field.stop == stops - 1) {
+            fields.push(Field {
+                stop: stops - 1,
+                range: len..len,
+            });
+        }
+
+        Snippet {
+            text,
+            fields,
+            stops,
+        }
+    }
+}
+
+fn tokenize(body: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut text = String::new();
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some('$' | '}' | '\\')) => {
+                text.extend(chars.next());
+            }
+            '$' => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let mut digits = String::new();
+                while let Some(d) = chars.next_if(|c| c.is_ascii_digit()) {
+                    digits.push(d);
+                }
+                let Ok(n) = digits.parse() else {
+                    text.push('$');
+                    if braced {
+                        text.push('{');
+                    }
+                    text.push_str(&digits);
+                    continue;
+                };
+
+                let mut placeholder = None;
+                if braced {
+                    if chars.next_if_eq(&':').is_some() {
+                        let mut value = String::new();
+                        while let Some(c) = chars.next_if(|&c| c != '}') {
+                            if c == '\\' && matches!(chars.peek(), Some('$' | '}' | '\\')) {
+                                value.extend(chars.next());
+                            } else {
+                                value.push(c);
+                            }
+                        }
+                        placeholder = Some(value);
+                    }
+                    chars.next_if_eq(&'}');
+                }
+
+                tokens.push(Token::Text(std::mem::take(&mut text)));
+                tokens.push(Token::Stop(n, placeholder));
+            }
+            c => text.push(c),
+        }
+    }
+
+    tokens.push(Token::Text(text));
+    tokens
+}
+
+/// A snippet being filled in, with its fields as char indices of the buffer it was expanded
+/// in. Typing at the current stop grows its field and moves the fields after it along.
+#[derive(Debug)]
+pub struct Session {
+    fields: Vec<Field>,
+    stops: usize,
+    current: usize,
+    /// The length of the buffer when the fields last matched it, telling how much an edit
+    /// added or removed.
+    len: usize,
+    /// Whether the placeholder of the current stop is still there untouched, so typing replaces
+    /// it.
+    fresh: bool,
+}
+
+impl Session {
+    /// Starts on `snippet`, whose text was just inserted at char index `start` of `buffer`,
+    /// at its first stop.
+    pub fn new(snippet: &Snippet, start: usize, buffer: &Buffer) -> Self {
+        let fields = snippet
+            .fields
+            .iter()
+            .map(|field| Field {
+                stop: field.stop,
+                range: field.range.start + start..field.range.end + start,
+            })
+            .collect();
+        Session {
+            fields,
+            stops: snippet.stops,
+            current: 0,
+            len: buffer.len_chars(),
+            fresh: true,
+        }
+    }
+
+    fn primary(&self) -> usize {
+        self.fields
+            .iter()
+            .position(|field| field.stop == self.current)
+            .expect("every stop has a field")
+    }
+
+    /// The chars of the current stop.
+    pub fn range(&self) -> Range<usize> {
+        self.fields[self.primary()].range.clone()
+    }
+
+    /// Whether the current stop is the last one, where the snippet is done.
+    pub fn is_done(&self) -> bool {
+        self.current == self.stops - 1
+    }
+
+    /// Moves to the next stop. Returns false when already at the last one.
+    pub fn next(&mut self) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        self.current += 1;
+        self.fresh = true;
+        true
+    }
+
+    /// Moves to the previous stop. Returns false when already at the first one.
+    pub fn previous(&mut self) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        self.current -= 1;
+        self.fresh = true;
+        true
+    }
+
+    /// The placeholder of the current stop when the cursor at char index `cursor` is on its
+    /// start and nothing was typed over it yet, for typing to replace.
+    pub fn take_placeholder(&mut self, cursor: usize) -> Option<Range<usize>> {
+        let range = self.range();
+        if !self.fresh || range.is_empty() || cursor != range.start {
+            return None;
+        }
+        self.fresh = false;
+        Some(range)
+    }
+
+    /// Catches up with an edit at the current stop, growing or shrinking its field and copying
+    /// its text to the mirrors. Takes the cursor after the edit as a char index and returns
+    /// where it is once the mirrors changed, or `None` when the edit happened outside the
+    /// current stop, which ends the snippet.
+    pub fn sync(&mut self, buffer: &mut Buffer, cursor: usize) -> Option<usize> {
+        let len = buffer.len_chars();
+        if len == self.len {
+            return Some(cursor);
+        }
+
+        let primary = self.primary();
+        let range = self.fields[primary].range.clone();
+        let end = range.end as isize + len as isize - self.len as isize;
+        if end < range.start as isize || cursor < range.start || cursor as isize > end {
+            return None;
+        }
+        self.fresh = false;
+        self.resize(primary, end as usize - range.start);
+        let offset = cursor - range.start;
+
+        let range = self.fields[primary].range.clone();
+        let text = buffer.slice(buffer.position(range.start), buffer.position(range.end));
+        let mirrors: Vec<_> = (0..self.fields.len())
+            .filter(|&i| i != primary && self.fields[i].stop == self.current)
+            .collect();
+        for i in mirrors {
+            let range = self.fields[i].range.clone();
+            buffer.remove(buffer.position(range.start), buffer.position(range.end));
+            buffer.insert(buffer.position(range.start), &text);
+            self.resize(i, text.chars().count());
+        }
+
+        self.len = buffer.len_chars();
+        Some(self.range().start + offset)
+    }
+
+    /// Makes field `i` `len` chars long, moving the fields after it along.
+    fn resize(&mut self, i: usize, len: usize) {
+        let range = &mut self.fields[i].range;
+        let delta = (range.start + len) as isize - range.end as isize;
+        range.end = range.start + len;
+        for field in &mut self.fields[i + 1..] {
+            field.range.start = (field.range.start as isize + delta) as usize;
+            field.range.end = (field.range.end as isize + delta) as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(stop: usize, range: Range<usize>) -> Field {
+        Field { stop, range }
+    }
+
+    #[test]
+    fn test_parse() {
+        let snippet = Snippet::parse("fn ${1:name}($2) {\n    $0\n}");
+        assert_eq!(snippet.text, "fn name() {\n    \n}");
+        assert_eq!(
+            snippet.fields,
+            vec![field(0, 3..7), field(1, 8..8), field(2, 16..16)]
+        );
+        assert_eq!(snippet.stops, 3);
+
+        // the end of the text is the last stop without $0, and mirrors share the placeholder
+        let snippet = Snippet::parse("<$1>$2</${1:div}> \\$5");
+        assert_eq!(snippet.text, "<div></div> $5");
+        assert_eq!(
+            snippet.fields,
+            vec![
+                field(0, 1..4),
+                field(1, 5..5),
+                field(0, 7..10),
+                field(2, 14..14)
+            ]
+        );
+
+        let snippet = Snippet::parse("cost: $ ${x} ${2:a\\}b}");
+        assert_eq!(snippet.text, "cost: $ ${x} a}b");
+        assert_eq!(snippet.fields, vec![field(0, 13..16), field(1, 16..16)]);
+    }
+
+    #[test]
+    fn test_session() {
+        let snippet = Snippet::parse("<${1:div}>$2</$1>");
+        let mut buffer = Buffer::from_contents("x ");
+        buffer.insert((2, 0), &snippet.text);
+        let mut session = Session::new(&snippet, 2, &buffer);
+        assert_eq!(session.range(), 3..6);
+
+        // typing replaces the placeholder, and the mirror follows
+        let range = session.take_placeholder(3).unwrap();
+        assert_eq!(session.take_placeholder(3), None);
+        buffer.remove(buffer.position(range.start), buffer.position(range.end));
+        buffer.insert((3, 0), "p");
+        assert_eq!(session.sync(&mut buffer, 4), Some(4));
+        assert_eq!(buffer.to_string(), "x <p></p>");
+        assert_eq!(session.range(), 3..4);
+
+        assert!(session.next());
+        assert_eq!(session.range(), 5..5);
+        buffer.insert((5, 0), "hi");
+        assert_eq!(session.sync(&mut buffer, 7), Some(7));
+        assert!(session.next());
+        assert!(session.is_done());
+        assert_eq!(session.range(), 11..11);
+        assert!(!session.next());
+
+        // editing outside the current stop ends the snippet
+        buffer.insert((0, 0), "a");
+        assert_eq!(session.sync(&mut buffer, 1), None);
+    }
+}
@@ -0,0 +1,562 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::core::{
+    buffer::Buffer,
+    syntax::{ChunkType, Language},
+    word::is_word_char,
+};
+
+/// Where the dictionary is looked for when the config doesn't name one: hunspell's English
+/// dictionary, then the system's word list.
+const DICTIONARIES: &[&str] = &[
+    "/usr/share/hunspell/en_US.dic",
+    "/usr/share/myspell/en_US.dic",
+    "/usr/share/myspell/dicts/en_US.dic",
+    "/usr/share/dict/words",
+];
+
+/// At most this many suggestions are offered, so each can be picked with a single digit.
+pub const MAX_SUGGESTIONS: usize = 9;
+
+/// Chars that make a word part of code rather than prose, like a path, an address or a call.
+const CODE_CHARS: &[char] = &['/', '\\', '@', '.', ':', '`', '<', '>', '=', '#', '$', '_'];
+
+/// How the flags after a word in a hunspell dictionary are written, from the affix file's
+/// `FLAG`.
+#[derive(Debug, Clone, Copy)]
+enum FlagType {
+    /// A char each, the default.
+    Char,
+    /// Two chars each, `FLAG long`.
+    Long,
+    /// Numbers separated by commas, `FLAG num`.
+    Num,
+}
+
+impl FlagType {
+    fn split(self, flags: &str) -> Vec<String> {
+        match self {
+            FlagType::Char => flags.chars().map(String::from).collect(),
+            FlagType::Long => {
+                let chars: Vec<char> = flags.chars().collect();
+                chars.chunks(2).map(|flag| flag.iter().collect()).collect()
+            }
+            FlagType::Num => flags
+                .split(',')
+                .map(str::trim)
+                .filter(|flag| !flag.is_empty())
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// What an affix condition accepts at one position, like `e`, `.` or `[^aeiou]`.
+#[derive(Debug)]
+enum CharClass {
+    Any,
+    Of(Vec<char>),
+    NotOf(Vec<char>),
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharClass::Any => true,
+            CharClass::Of(chars) => chars.contains(&c),
+            CharClass::NotOf(chars) => !chars.contains(&c),
+        }
+    }
+}
+
+fn parse_condition(condition: &str) -> Vec<CharClass> {
+    let mut classes = vec![];
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        let class = match c {
+            '.' => CharClass::Any,
+            '[' => {
+                let set: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                match set.strip_prefix('^') {
+                    Some(set) => CharClass::NotOf(set.chars().collect()),
+                    None => CharClass::Of(set.chars().collect()),
+                }
+            }
+            c => CharClass::Of(vec![c]),
+        };
+        classes.push(class);
+    }
+    classes
+}
+
+/// A prefix or suffix rule of a hunspell affix file: words with its flag take it by having
+/// `strip` taken off their start or end and `add` put there, when they meet the condition.
+#[derive(Debug)]
+struct Affix {
+    flag: String,
+    strip: String,
+    add: String,
+    condition: Vec<CharClass>,
+    /// Whether it combines with affixes of the other kind, hunspell's cross product.
+    cross: bool,
+}
+
+impl Affix {
+    /// The word `word` would be made from by this affix as a suffix.
+    fn suffix_stem(&self, word: &str) -> Option<String> {
+        let rest = word.strip_suffix(self.add.as_str())?;
+        if rest.is_empty() {
+            return None;
+        }
+        let stem = format!("{rest}{}", self.strip);
+        let chars: Vec<char> = stem.chars().collect();
+        let start = chars.len().checked_sub(self.condition.len())?;
+        let meets = chars[start..]
+            .iter()
+            .zip(&self.condition)
+            .all(|(&c, class)| class.matches(c));
+        meets.then_some(stem)
+    }
+
+    /// The word `word` would be made from by this affix as a prefix.
+    fn prefix_stem(&self, word: &str) -> Option<String> {
+        let rest = word.strip_prefix(self.add.as_str())?;
+        if rest.is_empty() {
+            return None;
+        }
+        let stem = format!("{}{rest}", self.strip);
+        let meets = stem.chars().count() >= self.condition.len()
+            && stem
+                .chars()
+                .zip(&self.condition)
+                .all(|(c, class)| class.matches(c));
+        meets.then_some(stem)
+    }
+}
+
+/// The words spelled right: a hunspell dictionary, whose words take the prefixes and suffixes
+/// of its affix file, or a plain list with a word per line.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    /// The flags of the affixes each word takes.
+    words: HashMap<String, Vec<String>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    /// The chars tried when looking for suggestions, from the affix file's `TRY`.
+    try_chars: Vec<char>,
+}
+
+impl Dictionary {
+    /// Loads the dictionary at `path`, with the `.aff` file next to it for a `.dic`, or the
+    /// first of the usual system dictionaries that exists.
+    pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => Path::new(path),
+            None => DICTIONARIES
+                .iter()
+                .map(Path::new)
+                .find(|path| path.exists())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("E756: Spell checking is not possible, no dictionary found")
+                })?,
+        };
+        let read = |path: &Path| {
+            fs::read_to_string(path)
+                .map_err(|err| anyhow::anyhow!("error reading {}: {err}", path.display()))
+        };
+
+        let words = read(path)?;
+        let affixes = path.with_extension("aff");
+        let affixes = if affixes.exists() {
+            Some(read(&affixes)?)
+        } else {
+            None
+        };
+        Ok(Dictionary::parse(&words, affixes.as_deref()))
+    }
+
+    /// Reads the words of a dictionary with their affix flags, and the rules of its affix
+    /// file. Morphological fields and options other than `FLAG` and `TRY` are left out.
+    pub fn parse(words: &str, affixes: Option<&str>) -> Self {
+        let mut dictionary = Dictionary::default();
+        let mut flag_type = FlagType::Char;
+        // whether the affixes of each flag combine with the other kind
+        let mut cross = HashMap::new();
+
+        for line in affixes.unwrap_or_default().lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => flag_type = FlagType::Long,
+                ["FLAG", "num", ..] => flag_type = FlagType::Num,
+                ["TRY", chars, ..] => dictionary.try_chars = chars.chars().collect(),
+                [kind @ ("PFX" | "SFX"), flag, combines @ ("Y" | "N"), count]
+                    if count.parse::<usize>().is_ok() =>
+                {
+                    cross.insert((*kind, *flag), *combines == "Y");
+                }
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    let affix = Affix {
+                        flag: flag.to_string(),
+                        strip: empty_if_zero(strip),
+                        add: empty_if_zero(add.split('/').next().unwrap_or_default()),
+                        condition: parse_condition(rest.first().unwrap_or(&".")),
+                        cross: cross.get(&(*kind, *flag)).copied().unwrap_or(false),
+                    };
+                    match *kind {
+                        "PFX" => dictionary.prefixes.push(affix),
+                        _ => dictionary.suffixes.push(affix),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for (i, line) in words.lines().enumerate() {
+            let Some(entry) = line.split_whitespace().next() else {
+                continue;
+            };
+            // hunspell dictionaries start with their number of words
+            if i == 0 && entry.parse::<usize>().is_ok() {
+                continue;
+            }
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            dictionary
+                .words
+                .entry(word.to_string())
+                .or_default()
+                .extend(flag_type.split(flags));
+        }
+
+        dictionary
+    }
+
+    fn has_flag(&self, word: &str, flag: &str) -> bool {
+        self.words
+            .get(word)
+            .is_some_and(|flags| flags.iter().any(|f| f == flag))
+    }
+
+    /// Whether `word` is in the dictionary as it is written, or made from one of its words
+    /// with the affixes it takes.
+    fn known(&self, word: &str) -> bool {
+        if self.words.contains_key(word) {
+            return true;
+        }
+        let suffixed = |word: &str, prefix: Option<&Affix>| {
+            self.suffixes
+                .iter()
+                .filter(|suffix| prefix.is_none() || suffix.cross)
+                .any(|suffix| {
+                    suffix.suffix_stem(word).is_some_and(|stem| {
+                        self.has_flag(&stem, &suffix.flag)
+                            && prefix.is_none_or(|prefix| self.has_flag(&stem, &prefix.flag))
+                    })
+                })
+        };
+        if suffixed(word, None) {
+            return true;
+        }
+        self.prefixes.iter().any(|prefix| {
+            prefix.prefix_stem(word).is_some_and(|stem| {
+                self.has_flag(&stem, &prefix.flag)
+                    || (prefix.cross && suffixed(&stem, Some(prefix)))
+            })
+        })
+    }
+
+    /// Whether `word` is spelled right. Words starting a sentence with a capital, or written in
+    /// capitals, are also right when their lowercase form is.
+    pub fn check(&self, word: &str) -> bool {
+        if self.known(word) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        match case(word) {
+            Case::Lower => false,
+            Case::Capitalized => self.known(&lower),
+            Case::Upper => self.known(&lower) || self.known(&capitalize(&lower)),
+        }
+    }
+
+    /// Words spelled like `word`, the closest first: the ones a single change away, a missing,
+    /// extra, swapped or wrong char, then the words of the dictionary two changes away. They
+    /// take the case `word` is written in.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let chars: Vec<char> = lower.chars().collect();
+        let mut alphabet = self.try_chars.clone();
+        if alphabet.is_empty() {
+            alphabet = ('a'..='z').chain(['\'']).collect();
+        }
+        alphabet.retain(|c| !c.is_uppercase());
+
+        let mut candidates = vec![];
+        for i in 0..=chars.len() {
+            for &c in &alphabet {
+                let mut candidate = chars.clone();
+                candidate.insert(i, c);
+                candidates.push(candidate);
+            }
+            if i == chars.len() {
+                break;
+            }
+            let mut candidate = chars.clone();
+            candidate.remove(i);
+            candidates.push(candidate);
+            if i + 1 < chars.len() {
+                let mut candidate = chars.clone();
+                candidate.swap(i, i + 1);
+                candidates.push(candidate);
+            }
+            for &c in &alphabet {
+                let mut candidate = chars.clone();
+                candidate[i] = c;
+                candidates.push(candidate);
+            }
+        }
+
+        let mut found: Vec<String> = candidates
+            .into_iter()
+            .map(|candidate| candidate.into_iter().collect::<String>())
+            .filter(|candidate| *candidate != lower && self.known(candidate))
+            .collect();
+        found.extend(
+            self.words
+                .keys()
+                .filter(|stem| stem.chars().count().abs_diff(chars.len()) <= 2)
+                .filter(|stem| distance(&chars, &stem.chars().collect::<Vec<_>>()) <= 4)
+                .cloned(),
+        );
+
+        let first = chars.first().copied();
+        let mut found: Vec<(usize, bool, String)> = found
+            .into_iter()
+            .map(|candidate| {
+                let candidate_chars: Vec<char> = candidate.chars().collect();
+                let cost = distance(&chars, &candidate_chars);
+                (cost, candidate_chars.first().copied() != first, candidate)
+            })
+            .filter(|(cost, ..)| *cost > 0)
+            .collect();
+        found.sort();
+        found.dedup_by(|a, b| a.2 == b.2);
+
+        let case = case(word);
+        found
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, _, candidate)| match case {
+                Case::Lower => candidate,
+                Case::Capitalized => capitalize(&candidate),
+                Case::Upper => candidate.to_uppercase(),
+            })
+            .collect()
+    }
+}
+
+/// Affix files write an empty strip or affix as `0`.
+fn empty_if_zero(text: &str) -> String {
+    match text {
+        "0" => String::new(),
+        text => text.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    Lower,
+    Capitalized,
+    Upper,
+}
+
+fn case(word: &str) -> Case {
+    let mut chars = word.chars();
+    let first = chars.next().is_some_and(char::is_uppercase);
+    let rest_upper = chars.clone().any(char::is_uppercase);
+    let rest_lower = chars.any(char::is_lowercase);
+    match (first, rest_upper, rest_lower) {
+        (true, _, false) => Case::Upper,
+        (true, false, true) => Case::Capitalized,
+        _ => Case::Lower,
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// How far apart `a` and `b` are, counting 2 for each char inserted, removed or replaced and 1
+/// for two chars swapped, as those are the likeliest typos.
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).map(|j| j * 2).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i * 2; b.len() + 1];
+        for j in 1..=b.len() {
+            let replace = rows[i - 1][j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 2 };
+            row[j] = replace.min(rows[i - 1][j] + 2).min(row[j - 1] + 2);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// The words of `line` to check, as start and exclusive end columns: runs of letters with
+/// apostrophes inside them. Words that look like code are left out, like the ones written in
+/// camelCase, with digits or underscores, or in a path, an address or a call.
+pub fn words(line: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut words = vec![];
+    let mut x = 0;
+
+    while x < chars.len() {
+        if !is_word_char(chars[x]) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < chars.len()
+            && (is_word_char(chars[x])
+                || (chars[x] == '\''
+                    && x > start
+                    && chars.get(x + 1).is_some_and(|c| c.is_alphabetic())))
+        {
+            x += 1;
+        }
+
+        let word = &chars[start..x];
+        let camel_case =
+            word.iter().any(|c| c.is_lowercase()) && word.iter().skip(1).any(|c| c.is_uppercase());
+        let alphabetic = word.iter().all(|&c| c.is_alphabetic() || c == '\'');
+        if word.len() > 1 && alphabetic && !camel_case && !in_code(&chars, start, x) {
+            words.push((start, x));
+        }
+    }
+
+    words
+}
+
+/// Whether the blank separated run of chars around `start..end` looks like code once the
+/// punctuation around it is left out.
+fn in_code(chars: &[char], start: usize, end: usize) -> bool {
+    let first = chars[..start]
+        .iter()
+        .rposition(|c| c.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let last = chars[end..]
+        .iter()
+        .position(|c| c.is_whitespace())
+        .map_or(chars.len(), |i| end + i);
+    let punctuation = |c: &char| "!\"'(),.:;?[]{}*".contains(*c);
+    let run = &chars[first..last];
+    let run_start = run.iter().take_while(|c| punctuation(c)).count();
+    let run_end = run.len() - run.iter().rev().take_while(|c| punctuation(c)).count();
+    run[run_start..run_end.max(run_start)]
+        .iter()
+        .any(|c| CODE_CHARS.contains(c))
+}
+
+/// Whether text highlighted as `typ` in a buffer of `language` is prose to check: all of plain
+/// text, Markdown but for its code and links, and the comments and strings of code.
+pub fn is_checked(language: Option<Language>, typ: ChunkType) -> bool {
+    match language {
+        None => true,
+        #[cfg(feature = "markdown")]
+        Some(Language::Markdown) => !matches!(
+            typ,
+            ChunkType::MarkupRaw
+                | ChunkType::MarkupRawBlock
+                | ChunkType::MarkupRawInline
+                | ChunkType::MarkupLinkUrl
+        ),
+        Some(_) => typ.is_literal(),
+    }
+}
+
+/// The misspelled words on line `y` of `buffer`, as start and exclusive end columns, in the
+/// text its highlights say is prose.
+pub fn misspellings(dictionary: &Dictionary, buffer: &Buffer, y: usize) -> Vec<(usize, usize)> {
+    let Some(line) = buffer.line(y) else {
+        return vec![];
+    };
+    let chars: Vec<char> = line.chars().collect();
+    words(&line)
+        .into_iter()
+        .filter(|&(start, _)| {
+            buffer
+                .highlights
+                .chunk_type((start, y))
+                .is_some_and(|typ| is_checked(buffer.language, typ))
+        })
+        .filter(|&(start, end)| !dictionary.check(&chars[start..end].iter().collect::<String>()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFFIXES: &str = "SET UTF-8
+TRY esianrtolcdugmphbyfvkwz'
+PFX U Y 1
+PFX U 0 un .
+SFX S Y 3
+SFX S y ies [^aeiou]y
+SFX S 0 s [aeiou]y
+SFX S 0 s [^y]
+SFX D Y 3
+SFX D 0 d e
+SFX D y ied [^aeiou]y
+SFX D 0 ed [^e]
+";
+
+    const WORDS: &str = "5
+the
+word/S
+try/SD
+do/U
+like/UD
+";
+
+    #[test]
+    fn test_check() {
+        let dictionary = Dictionary::parse(WORDS, Some(AFFIXES));
+        for word in [
+            "the", "words", "tries", "tried", "undo", "unliked", "The", "WORDS",
+        ] {
+            assert!(dictionary.check(word), "{word}");
+        }
+        for word in ["teh", "trys", "untry", "dos", "wordd", "tHe"] {
+            assert!(!dictionary.check(word), "{word}");
+        }
+    }
+
+    #[test]
+    fn test_suggest() {
+        let dictionary = Dictionary::parse(WORDS, Some(AFFIXES));
+        assert_eq!(dictionary.suggest("teh")[0], "the");
+        assert_eq!(dictionary.suggest("Wrods")[0], "Words");
+        assert!(dictionary.suggest("triesd").contains(&"tries".to_string()));
+
+        // plain word lists have no affixes
+        let dictionary = Dictionary::parse("apple\nbanana\n", None);
+        assert!(dictionary.check("banana"));
+        assert_eq!(dictionary.suggest("banan"), ["banana"]);
+    }
+
+    #[test]
+    fn test_words() {
+        let line = "// Teh fooBar isn't in src/main.rs, e.g. `code` or snake_case2 (see)";
+        let words: Vec<_> = words(line)
+            .into_iter()
+            .map(|(start, end)| &line[start..end])
+            .collect();
+        assert_eq!(words, ["Teh", "isn't", "in", "or", "see"]);
+    }
+}
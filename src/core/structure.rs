@@ -0,0 +1,186 @@
+use tree_sitter::{Node, Point, Tree};
+
+use crate::core::{buffer::Buffer, syntax::Language};
+
+/// The start of the next definition after `position`, like a function or class, or of the
+/// previous one before it when going backward, for `]m` and `[m`.
+pub fn definition(
+    buffer: &Buffer,
+    language: &Language,
+    tree: &Tree,
+    position: (usize, usize),
+    forward: bool,
+) -> Option<(usize, usize)> {
+    let point = point(buffer, position);
+    let kinds = language.definition_nodes();
+
+    // nodes come in the order they start in, parents before their children
+    let mut starts = vec![];
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        if kinds.contains(&node.kind()) {
+            starts.push(node.start_position());
+        }
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+
+    let start = if forward {
+        starts.into_iter().find(|&start| start > point)
+    } else {
+        starts.into_iter().rev().find(|&start| start < point)
+    };
+    start.map(|start| self::position(buffer, start))
+}
+
+/// The start of the smallest node around `position` that starts before it, so going again
+/// moves out to the node around that one, for `[n`.
+pub fn node_start(
+    buffer: &Buffer,
+    tree: &Tree,
+    position: (usize, usize),
+) -> Option<(usize, usize)> {
+    let point = point(buffer, position);
+    let mut node = tree
+        .root_node()
+        .named_descendant_for_point_range(point, point)?;
+    while node.start_position() >= point {
+        node = node.parent()?;
+    }
+    Some(self::position(buffer, node.start_position()))
+}
+
+/// The last char of the smallest node around `position` that ends after it, so going again moves
+/// out to the node around that one, for `]n`.
+pub fn node_end(buffer: &Buffer, tree: &Tree, position: (usize, usize)) -> Option<(usize, usize)> {
+    let point = point(buffer, position);
+    let mut node = tree
+        .root_node()
+        .named_descendant_for_point_range(point, point)?;
+    loop {
+        let end = last_char(buffer, &node);
+        if end > point {
+            return Some(self::position(buffer, end));
+        }
+        node = node.parent()?;
+    }
+}
+
+/// The kind of the smallest named node at `position`, like `identifier`.
+pub fn node_kind(buffer: &Buffer, tree: &Tree, position: (usize, usize)) -> Option<&'static str> {
+    let point = point(buffer, position);
+    let node = tree
+        .root_node()
+        .named_descendant_for_point_range(point, point)?;
+    Some(node.kind())
+}
+
+/// The smallest node covering more than the chars from `start` to `end`, inclusive, as the
+/// exclusive range a visual selection expands to.
+pub fn expand(
+    buffer: &Buffer,
+    tree: &Tree,
+    start: (usize, usize),
+    end: (usize, usize),
+) -> Option<((usize, usize), (usize, usize))> {
+    let (start, end) = (point(buffer, start), point(buffer, end));
+    let after = point(buffer, (position(buffer, end).0 + 1, end.row));
+    let mut node = tree
+        .root_node()
+        .named_descendant_for_point_range(start, after)?;
+    loop {
+        let (first, last) = (node.start_position(), last_char(buffer, &node));
+        if first <= start && last >= end && (first < start || last > end) {
+            return Some((
+                position(buffer, first),
+                position(buffer, node.end_position()),
+            ));
+        }
+        node = node.parent()?;
+    }
+}
+
+/// The point of the last char of `node`, which is its start for empty nodes.
+fn last_char(buffer: &Buffer, node: &Node) -> Point {
+    let end = node.end_position();
+    let last = match end.column {
+        // ends with the line break of the line before
+        0 if end.row > 0 => {
+            let line = buffer.line(end.row - 1).unwrap_or_default();
+            Point::new(end.row - 1, line.len())
+        }
+        0 => end,
+        column => {
+            let line = buffer.line(end.row).unwrap_or_default();
+            let width = line
+                .get(..column)
+                .and_then(|line| line.chars().last())
+                .map_or(1, char::len_utf8);
+            Point::new(end.row, column.saturating_sub(width))
+        }
+    };
+    last.max(node.start_position())
+}
+
+/// The tree-sitter point at the char position `(x, y)`, whose column counts bytes.
+fn point(buffer: &Buffer, (x, y): (usize, usize)) -> Point {
+    let line = buffer.line(y).unwrap_or_default();
+    let column = line.char_indices().nth(x).map_or(line.len(), |(i, _)| i);
+    Point::new(y, column)
+}
+
+/// The char position of a tree-sitter point.
+fn position(buffer: &Buffer, point: Point) -> (usize, usize) {
+    let line = buffer.line(point.row).unwrap_or_default();
+    let x = line
+        .get(..point.column)
+        .map_or(0, |line| line.chars().count());
+    (x, point.row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_navigation() {
+        let mut buffer = Buffer::from_contents(
+            "struct A;\n\nimpl A {\n    fn a(&self) {\n        foo(1, \"é\", bar(2));\n    }\n}",
+        );
+        buffer.language = Some(Language::Rust);
+        let tree = buffer.syntax_tree().unwrap();
+        let rust = Language::Rust;
+
+        let next = |position| definition(&buffer, &rust, &tree, position, true);
+        let previous = |position| definition(&buffer, &rust, &tree, position, false);
+        assert_eq!(next((0, 0)), Some((0, 2)));
+        assert_eq!(next((0, 2)), Some((4, 3)));
+        assert_eq!(next((4, 3)), None);
+        assert_eq!(previous((2, 4)), Some((4, 3)));
+        assert_eq!(previous((4, 3)), Some((0, 2)));
+        assert_eq!(previous((0, 0)), None);
+
+        // from the `2` in `bar(2)`, out through its arguments and the call
+        assert_eq!(node_start(&buffer, &tree, (24, 4)), Some((23, 4)));
+        assert_eq!(node_start(&buffer, &tree, (23, 4)), Some((20, 4)));
+        assert_eq!(node_end(&buffer, &tree, (24, 4)), Some((25, 4)));
+        assert_eq!(node_end(&buffer, &tree, (25, 4)), Some((26, 4)));
+
+        // the string, then the arguments it's in
+        assert_eq!(
+            expand(&buffer, &tree, (16, 4), (16, 4)),
+            Some(((15, 4), (18, 4)))
+        );
+        assert_eq!(
+            expand(&buffer, &tree, (15, 4), (17, 4)),
+            Some(((11, 4), (27, 4)))
+        );
+    }
+}
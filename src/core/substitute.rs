@@ -0,0 +1,171 @@
+use regex::Regex;
+
+use crate::core::buffer::Buffer;
+
+/// A parsed `:s/pattern/replacement/flags` command.
+///
+/// The pattern uses Rust regex syntax. The replacement follows vim: `&` is the whole match,
+/// `\1` to `\9` are capture groups and `\&` is a literal ampersand.
+#[derive(Debug)]
+pub struct Substitute {
+    pattern: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl Substitute {
+    /// Parses the part after `s`, like `/foo/bar/g`. Any non-alphanumeric char can be the
+    /// delimiter, and the trailing one may be left out.
+    pub fn parse(args: &str) -> anyhow::Result<Self> {
+        let mut chars = args.chars();
+        let delimiter = match chars.next() {
+            Some(c) if !c.is_alphanumeric() && !c.is_whitespace() => c,
+            _ => anyhow::bail!("E146: Regular expressions can't be delimited by letters"),
+        };
+
+        let parts = split_unescaped(chars.as_str(), delimiter);
+        let pattern = parts.first().cloned().unwrap_or_default();
+        let replacement = parts.get(1).cloned().unwrap_or_default();
+        let flags = parts.get(2).cloned().unwrap_or_default();
+
+        if pattern.is_empty() {
+            anyhow::bail!("E35: No previous regular expression");
+        }
+        let pattern = Regex::new(&pattern)
+            .map_err(|_| anyhow::anyhow!("E383: Invalid search string: {pattern}"))?;
+
+        Ok(Substitute {
+            pattern,
+            replacement: to_regex_replacement(&replacement),
+            global: flags.contains('g'),
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// Replaces matches on lines `start..=end`, returning the number of replacements made, the
+    /// number of lines they were made on and the last line that changed.
+    pub fn apply(
+        &self,
+        buffer: &mut Buffer,
+        start: usize,
+        end: usize,
+    ) -> (usize, usize, Option<usize>) {
+        let mut count = 0;
+        let mut lines = 0;
+        let mut last = None;
+
+        // replacements can add lines, so the range is walked from the bottom up
+        for y in (start..=end.min(buffer.len() - 1)).rev() {
+            let Some(line) = buffer.line(y) else {
+                continue;
+            };
+
+            let matches = self.pattern.find_iter(&line).count();
+            if matches == 0 {
+                continue;
+            }
+
+            let (text, replaced) = if self.global {
+                (self.pattern.replace_all(&line, &self.replacement), matches)
+            } else {
+                (self.pattern.replacen(&line, 1, &self.replacement), 1)
+            };
+            buffer.set_line(y, &text);
+            count += replaced;
+            lines += 1;
+            last = last.or(Some(y));
+        }
+
+        (count, lines, last)
+    }
+}
+
+/// Splits `s` on `delimiter`, keeping `\` escapes except for an escaped delimiter.
+fn split_unescaped(s: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => parts.last_mut().unwrap().push('\\'),
+            },
+            _ if c == delimiter => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(c),
+        }
+    }
+
+    parts
+}
+
+/// Converts a vim replacement string into the `$` syntax the regex crate expects.
+fn to_regex_replacement(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => result.push_str(&format!("${{{d}}}")),
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('$') => result.push_str("$$"),
+                Some(next) => result.push(next),
+                None => result.push('\\'),
+            },
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let sub = Substitute::parse(r"/a\/b/[&]/g").unwrap();
+        assert_eq!(sub.pattern.as_str(), "a/b");
+        assert_eq!(sub.replacement, "[${0}]");
+        assert!(sub.global);
+
+        let sub = Substitute::parse(r"#(\w+) (\w+)#\2 \1").unwrap();
+        assert_eq!(sub.replacement, "${2} ${1}");
+        assert!(!sub.global);
+
+        let sub = Substitute::parse(r"/x/\$5 \$name/").unwrap();
+        assert_eq!(sub.replacement, "$$5 $$name");
+
+        assert!(Substitute::parse("/(/x/").is_err());
+        assert!(Substitute::parse("//x/").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut buffer = Buffer::from_contents("foo foo\nbar\nfoo");
+
+        let sub = Substitute::parse("/foo/baz/").unwrap();
+        assert_eq!(sub.apply(&mut buffer, 0, 2), (2, 2, Some(2)));
+        assert_eq!(buffer.to_string(), "baz foo\nbar\nbaz");
+
+        let sub = Substitute::parse("/[a-z]+/$&/g").unwrap();
+        assert_eq!(sub.apply(&mut buffer, 0, 1), (3, 2, Some(1)));
+        assert_eq!(buffer.to_string(), "$baz $foo\n$bar\nbaz");
+
+        let sub = Substitute::parse("/nothing/x/").unwrap();
+        assert_eq!(sub.apply(&mut buffer, 0, 2), (0, 0, None));
+    }
+}
@@ -0,0 +1,1064 @@
+use once_cell::sync::Lazy;
+use std::{cmp, path::Path, str::FromStr};
+use strum_macros::{Display, EnumIter, EnumString};
+use tree_sitter::{Parser, Tree};
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
+
+use crate::core::buffer::Buffer;
+
+const HIGHLIGHT_NAMES: [&str; 52] = [
+    "attribute",
+    "boolean",
+    "carriage-return",
+    "comment",
+    "comment.documentation",
+    "constant",
+    "constant.builtin",
+    "constructor",
+    "constructor.builtin",
+    "embedded",
+    "error",
+    "escape",
+    "function",
+    "function.builtin",
+    "keyword",
+    "markup",
+    "markup.bold",
+    "markup.heading",
+    "markup.italic",
+    "markup.link",
+    "markup.link.url",
+    "markup.list",
+    "markup.list.checked",
+    "markup.list.numbered",
+    "markup.list.unchecked",
+    "markup.list.unnumbered",
+    "markup.quote",
+    "markup.raw",
+    "markup.raw.block",
+    "markup.raw.inline",
+    "markup.strikethrough",
+    "module",
+    "number",
+    "operator",
+    "property",
+    "property.builtin",
+    "punctuation",
+    "punctuation.bracket",
+    "punctuation.delimiter",
+    "punctuation.special",
+    "string",
+    "string.escape",
+    "string.regexp",
+    "string.special",
+    "string.special.symbol",
+    "tag",
+    "type",
+    "type.builtin",
+    "variable",
+    "variable.builtin",
+    "variable.member",
+    "variable.parameter",
+];
+
+#[derive(Clone, Debug, PartialEq, EnumString, EnumIter, Display)]
+#[strum(serialize_all = "snake_case")]
+/// The kind of syntax a piece of text is, named after the tree-sitter highlight it comes from.
+pub enum ChunkType {
+    None,
+    Attribute,
+    Boolean,
+    CarriageReturn,
+    Comment,
+    #[strum(serialize = "comment.documentation")]
+    CommentDocumentation,
+    Constant,
+    #[strum(serialize = "constant.builtin")]
+    ConstantBuiltin,
+    Constructor,
+    #[strum(serialize = "constructor.builtin")]
+    ConstructorBuiltin,
+    Embedded,
+    Error,
+    Escape,
+    Function,
+    #[strum(serialize = "function.builtin")]
+    FunctionBuiltin,
+    Keyword,
+    Markup,
+    #[strum(serialize = "markup.bold")]
+    MarkupBold,
+    #[strum(serialize = "markup.heading")]
+    MarkupHeading,
+    #[strum(serialize = "markup.italic")]
+    MarkupItalic,
+    #[strum(serialize = "markup.link")]
+    MarkupLink,
+    #[strum(serialize = "markup.link.url")]
+    MarkupLinkUrl,
+    #[strum(serialize = "markup.list")]
+    MarkupList,
+    #[strum(serialize = "markup.list.checked")]
+    MarkupListChecked,
+    #[strum(serialize = "markup.list.numbered")]
+    MarkupListNumbered,
+    #[strum(serialize = "markup.list.unchecked")]
+    MarkupListUnchecked,
+    #[strum(serialize = "markup.list.unnumbered")]
+    MarkupListUnnumbered,
+    #[strum(serialize = "markup.quote")]
+    MarkupQuote,
+    #[strum(serialize = "markup.raw")]
+    MarkupRaw,
+    #[strum(serialize = "markup.raw.block")]
+    MarkupRawBlock,
+    #[strum(serialize = "markup.raw.inline")]
+    MarkupRawInline,
+    #[strum(serialize = "markup.strikethrough")]
+    MarkupStrikethrough,
+    Module,
+    Number,
+    Operator,
+    Property,
+    #[strum(serialize = "property.builtin")]
+    PropertyBuiltin,
+    Punctuation,
+    #[strum(serialize = "punctuation.bracket")]
+    PunctuationBracket,
+    #[strum(serialize = "punctuation.delimiter")]
+    PunctuationDelimiter,
+    #[strum(serialize = "punctuation.special")]
+    PunctuationSpecial,
+    String,
+    #[strum(serialize = "string.escape")]
+    StringEscape,
+    #[strum(serialize = "string.regexp")]
+    StringRegexp,
+    #[strum(serialize = "string.special")]
+    StringSpecial,
+    #[strum(serialize = "string.special.symbol")]
+    StringSpecialSymbol,
+    Tag,
+    Type,
+    #[strum(serialize = "type.builtin")]
+    TypeBuiltin,
+    Variable,
+    #[strum(serialize = "variable.builtin")]
+    VariableBuiltin,
+    #[strum(serialize = "variable.member")]
+    VariableMember,
+    #[strum(serialize = "variable.parameter")]
+    VariableParameter,
+}
+
+impl ChunkType {
+    /// Whether the text is part of a string or a comment.
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            ChunkType::Comment
+                | ChunkType::CommentDocumentation
+                | ChunkType::String
+                | ChunkType::StringEscape
+                | ChunkType::StringRegexp
+                | ChunkType::StringSpecial
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Chunk<'a> {
+    contents: &'a str,
+    start: usize,
+    end: usize,
+    typ: ChunkType,
+}
+
+impl Default for Chunk<'_> {
+    fn default() -> Self {
+        Chunk {
+            contents: "",
+            start: 0,
+            end: 0,
+            typ: ChunkType::None,
+        }
+    }
+}
+
+impl<'a> Chunk<'a> {
+    fn with_type(typ: ChunkType) -> Self {
+        Chunk {
+            typ,
+            ..Chunk::default()
+        }
+    }
+
+    fn from_source(start: usize, end: usize, contents: &'a str) -> Self {
+        Chunk {
+            contents,
+            start,
+            end,
+            ..Chunk::default()
+        }
+    }
+
+    fn split(&self, ch: char) -> Vec<Chunk<'a>> {
+        let mut chunks = vec![];
+
+        for (i, line) in self.contents.split(ch).enumerate() {
+            let mut chunk = Chunk::from_source(self.start, self.end, line);
+            if i == 0 {
+                chunk.typ = self.typ.clone();
+            }
+            chunks.push(chunk);
+        }
+
+        chunks
+    }
+}
+
+/// An owned piece of highlighted text, so highlighted lines can outlive the source they were
+/// parsed from.
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub contents: String,
+    pub typ: ChunkType,
+}
+
+/// The highlighted lines of a buffer, along with the buffer revision they were computed from.
+#[derive(Debug, Clone, Default)]
+pub struct Highlights {
+    revision: Option<u64>,
+    language: Option<Language>,
+    /// The buffer line `lines` starts at, which is only past zero for the window of a large
+    /// buffer.
+    top: usize,
+    lines: Vec<Vec<Span>>,
+}
+
+impl Highlights {
+    pub fn compute(buffer: &Buffer) -> anyhow::Result<Self> {
+        // parses the text with the line break files end with, which grammars like Markdown's
+        // need to end the last block
+        let source = buffer.to_string() + "\n";
+        let chunks = match buffer.language {
+            Some(language) => parse(&source, language.parser())?,
+            None => vec![Chunk::from_source(0, source.len(), &source)],
+        };
+
+        let lines = split_chunks(chunks)
+            .into_iter()
+            .map(|line| {
+                line.into_iter()
+                    .map(|chunk| Span {
+                        contents: chunk.contents.to_string(),
+                        typ: chunk.typ,
+                    })
+                    .collect()
+            })
+            .take(buffer.len())
+            .collect();
+
+        Ok(Highlights {
+            revision: Some(buffer.revision()),
+            language: buffer.language,
+            top: 0,
+            lines,
+        })
+    }
+
+    /// Plain, unparsed spans for the `height` lines from `top`, so a large buffer never gets
+    /// copied or parsed as a whole.
+    pub fn compute_window(buffer: &Buffer, top: usize, height: usize) -> Self {
+        let end = cmp::min(top + height, buffer.len());
+        let lines = (top..end)
+            .map(|y| {
+                vec![Span {
+                    contents: buffer.line(y).unwrap_or_default(),
+                    typ: ChunkType::None,
+                }]
+            })
+            .collect();
+
+        Highlights {
+            revision: Some(buffer.revision()),
+            language: buffer.language,
+            top,
+            lines,
+        }
+    }
+
+    /// Returns true if these highlights still match the buffer's contents and language.
+    pub fn is_current(&self, buffer: &Buffer) -> bool {
+        self.revision == Some(buffer.revision()) && self.language == buffer.language
+    }
+
+    /// Returns true if the lines shown in `viewport` are all here.
+    fn covers(&self, buffer: &Buffer, viewport: &Viewport) -> bool {
+        let end = cmp::min(viewport.top + viewport.height, buffer.len());
+        self.top <= viewport.top && end <= self.top + self.lines.len()
+    }
+
+    /// The lines shown in `viewport`.
+    pub fn visible(&self, viewport: &Viewport) -> &[Vec<Span>] {
+        let start = cmp::min(viewport.top.saturating_sub(self.top), self.lines.len());
+        let end = cmp::min(
+            (viewport.top + viewport.height).saturating_sub(self.top),
+            self.lines.len(),
+        );
+        &self.lines[start..end]
+    }
+
+    /// Returns true if the char at `(x, y)` is part of a string or a comment.
+    pub fn is_literal(&self, position: (usize, usize)) -> bool {
+        self.chunk_type(position)
+            .is_some_and(|typ| typ.is_literal())
+    }
+
+    /// How the char at `(x, y)` is highlighted, `None` past the lines or the chars computed.
+    pub fn chunk_type(&self, (x, y): (usize, usize)) -> Option<ChunkType> {
+        let line = y.checked_sub(self.top).and_then(|y| self.lines.get(y))?;
+
+        let mut end = 0;
+        for span in line {
+            end += span.contents.chars().count();
+            if x < end {
+                return Some(span.typ.clone());
+            }
+        }
+
+        None
+    }
+}
+
+/// Recomputes the buffer's highlights when it changed since they were last computed. Large
+/// buffers are left unhighlighted and only get the lines in `viewport`.
+pub fn update_highlights(buffer: &mut Buffer, viewport: &Viewport) -> anyhow::Result<()> {
+    if buffer.large {
+        let highlights = &buffer.highlights;
+        if !highlights.is_current(buffer) || !highlights.covers(buffer, viewport) {
+            buffer.highlights = Highlights::compute_window(buffer, viewport.top, viewport.height);
+        }
+        return Ok(());
+    }
+
+    // only edits invalidate the highlights, cursor movement and scrolling reuse them
+    if !buffer.highlights.is_current(buffer) {
+        buffer.highlights = Highlights::compute(buffer)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Viewport {
+    pub top: usize,
+    pub left: usize,
+    pub width: usize,
+    pub height: usize,
+    pub wrap: bool,
+    /// The first column shown when lines don't wrap, after scrolling right along long lines.
+    pub scroll: usize,
+}
+
+impl Viewport {
+    pub fn new(top: usize, left: usize, width: usize, height: usize, wrap: bool) -> Self {
+        Viewport {
+            top,
+            left,
+            width,
+            height,
+            wrap,
+            scroll: 0,
+        }
+    }
+
+    /// The viewport scrolled right to show lines from column `scroll` on.
+    pub fn with_scroll(self, scroll: usize) -> Self {
+        Viewport { scroll, ..self }
+    }
+
+    /// Number of screen rows a line of `len` chars takes up.
+    pub fn rows(&self, len: usize) -> usize {
+        if !self.wrap || self.width == 0 {
+            return 1;
+        }
+
+        cmp::max(1, len.div_ceil(self.width))
+    }
+}
+
+/// A charwise selection over the buffer, with both ends inclusive and `start` never after `end`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Selection {
+    pub fn new(anchor: (usize, usize), cursor: (usize, usize)) -> Self {
+        let (ax, ay) = anchor;
+        let (cx, cy) = cursor;
+        if (ay, ax) <= (cy, cx) {
+            Selection {
+                start: anchor,
+                end: cursor,
+            }
+        } else {
+            Selection {
+                start: cursor,
+                end: anchor,
+            }
+        }
+    }
+
+    /// Returns the selected column range `[start, end)` on line `y`, where `end` may go past the
+    /// end of the line when the selection continues on the next line.
+    pub fn columns(&self, y: usize) -> Option<(usize, usize)> {
+        let (sx, sy) = self.start;
+        let (ex, ey) = self.end;
+        if y < sy || y > ey {
+            return None;
+        }
+
+        let start = if y == sy { sx } else { 0 };
+        let end = if y == ey { ex + 1 } else { usize::MAX };
+        Some((start, end))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    JavaScript,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "markdown")]
+    Markdown,
+    #[cfg(feature = "python")]
+    Python,
+    #[cfg(feature = "go")]
+    Go,
+    #[cfg(feature = "c")]
+    C,
+}
+
+impl Language {
+    pub fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("rs") => Some(Language::Rust),
+            Some("js" | "mjs" | "cjs" | "jsx") => Some(Language::JavaScript),
+            #[cfg(feature = "toml")]
+            Some("toml") => Some(Language::Toml),
+            #[cfg(feature = "json")]
+            Some("json") => Some(Language::Json),
+            #[cfg(feature = "markdown")]
+            Some("md" | "markdown") => Some(Language::Markdown),
+            #[cfg(feature = "python")]
+            Some("py" | "pyi") => Some(Language::Python),
+            #[cfg(feature = "go")]
+            Some("go") => Some(Language::Go),
+            #[cfg(feature = "c")]
+            Some("c" | "h") => Some(Language::C),
+            _ => None,
+        }
+    }
+
+    /// The language's identifier for language servers, also used to configure one for it.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::JavaScript => "javascript",
+            #[cfg(feature = "toml")]
+            Language::Toml => "toml",
+            #[cfg(feature = "json")]
+            Language::Json => "json",
+            #[cfg(feature = "markdown")]
+            Language::Markdown => "markdown",
+            #[cfg(feature = "python")]
+            Language::Python => "python",
+            #[cfg(feature = "go")]
+            Language::Go => "go",
+            #[cfg(feature = "c")]
+            Language::C => "c",
+        }
+    }
+
+    /// Chars that open a block when they end a line, so the next line is indented a level
+    /// deeper.
+    pub fn block_openers(&self) -> &'static [char] {
+        match self {
+            Language::Rust | Language::JavaScript => &['{', '(', '['],
+            #[cfg(feature = "toml")]
+            Language::Toml => &['{', '['],
+            #[cfg(feature = "json")]
+            Language::Json => &['{', '['],
+            #[cfg(feature = "markdown")]
+            Language::Markdown => &[],
+            #[cfg(feature = "python")]
+            Language::Python => &[':', '{', '(', '['],
+            #[cfg(feature = "go")]
+            Language::Go => &['{', '(', '['],
+            #[cfg(feature = "c")]
+            Language::C => &['{', '(', '['],
+        }
+    }
+
+    /// The marker that starts a line comment, used by `gc` to toggle comments. JSON has no
+    /// comments and Markdown only block ones.
+    pub fn line_comment(&self) -> Option<&'static str> {
+        match self {
+            Language::Rust | Language::JavaScript => Some("//"),
+            #[cfg(feature = "toml")]
+            Language::Toml => Some("#"),
+            #[cfg(feature = "json")]
+            Language::Json => None,
+            #[cfg(feature = "markdown")]
+            Language::Markdown => None,
+            #[cfg(feature = "python")]
+            Language::Python => Some("#"),
+            #[cfg(feature = "go")]
+            Language::Go => Some("//"),
+            #[cfg(feature = "c")]
+            Language::C => Some("//"),
+        }
+    }
+
+    pub fn grammar(&self) -> tree_sitter::Language {
+        match self {
+            Language::Rust => tree_sitter_rust::language(),
+            Language::JavaScript => tree_sitter_javascript::language(),
+            #[cfg(feature = "toml")]
+            Language::Toml => tree_sitter_toml::language(),
+            #[cfg(feature = "json")]
+            Language::Json => tree_sitter_json::language(),
+            #[cfg(feature = "markdown")]
+            Language::Markdown => tree_sitter_md::language(),
+            #[cfg(feature = "python")]
+            Language::Python => tree_sitter_python::language(),
+            #[cfg(feature = "go")]
+            Language::Go => tree_sitter_go::language(),
+            #[cfg(feature = "c")]
+            Language::C => tree_sitter_c::language(),
+        }
+    }
+
+    /// Syntax nodes whose lines after the first are indented a level deeper than the line they
+    /// start on, like blocks and argument lists. Languages indented by other rules, like Python,
+    /// have none and aren't indented from their syntax tree.
+    pub fn indent_nodes(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &[
+                "block",
+                "declaration_list",
+                "field_declaration_list",
+                "enum_variant_list",
+                "match_block",
+                "match_arm",
+                "arguments",
+                "parameters",
+                "type_arguments",
+                "type_parameters",
+                "array_expression",
+                "tuple_expression",
+                "field_initializer_list",
+                "use_list",
+                "token_tree",
+                "let_declaration",
+                "field_expression",
+                "where_clause",
+            ],
+            Language::JavaScript => &[
+                "statement_block",
+                "class_body",
+                "switch_body",
+                "switch_case",
+                "switch_default",
+                "object",
+                "object_pattern",
+                "array",
+                "array_pattern",
+                "arguments",
+                "formal_parameters",
+                "named_imports",
+                "member_expression",
+                "jsx_element",
+            ],
+            #[cfg(feature = "toml")]
+            Language::Toml => &["array", "inline_table"],
+            #[cfg(feature = "json")]
+            Language::Json => &["object", "array"],
+            #[cfg(feature = "markdown")]
+            Language::Markdown => &[],
+            #[cfg(feature = "python")]
+            Language::Python => &[],
+            // gofmt lines up `case` with its `switch`, so only the cases indent
+            #[cfg(feature = "go")]
+            Language::Go => &[
+                "block",
+                "literal_value",
+                "argument_list",
+                "parameter_list",
+                "field_declaration_list",
+                "interface_type",
+                "expression_case",
+                "type_case",
+                "communication_case",
+                "default_case",
+                "import_spec_list",
+                "const_declaration",
+                "var_declaration",
+            ],
+            #[cfg(feature = "c")]
+            Language::C => &[
+                "compound_statement",
+                "field_declaration_list",
+                "enumerator_list",
+                "argument_list",
+                "parameter_list",
+                "initializer_list",
+                "case_statement",
+            ],
+        }
+    }
+
+    /// Syntax nodes `]m` and `[m` move between, like functions and classes.
+    pub fn definition_nodes(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &[
+                "function_item",
+                "impl_item",
+                "trait_item",
+                "struct_item",
+                "enum_item",
+                "union_item",
+                "mod_item",
+                "macro_definition",
+            ],
+            Language::JavaScript => &[
+                "function_declaration",
+                "generator_function_declaration",
+                "function",
+                "method_definition",
+                "class_declaration",
+                "class",
+            ],
+            #[cfg(feature = "toml")]
+            Language::Toml => &["table", "table_array_element"],
+            #[cfg(feature = "json")]
+            Language::Json => &[],
+            #[cfg(feature = "markdown")]
+            Language::Markdown => &["atx_heading", "setext_heading"],
+            #[cfg(feature = "python")]
+            Language::Python => &["function_definition", "class_definition"],
+            #[cfg(feature = "go")]
+            Language::Go => &[
+                "function_declaration",
+                "method_declaration",
+                "type_declaration",
+            ],
+            #[cfg(feature = "c")]
+            Language::C => &["function_definition", "type_definition"],
+        }
+    }
+
+    /// Parses `text` into its syntax tree, `None` if the grammar can't be loaded.
+    pub fn parse(&self, text: &str) -> Option<Tree> {
+        let mut parser = Parser::new();
+        parser.set_language(self.grammar()).ok()?;
+        parser.parse(text, None)
+    }
+
+    /// Syntax nodes whose text spans lines as written, like strings and comments, so their lines
+    /// keep their indentation.
+    pub fn literal_nodes(&self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["string_literal", "raw_string_literal", "block_comment"],
+            Language::JavaScript => &["string", "template_string", "comment"],
+            #[cfg(feature = "toml")]
+            Language::Toml => &["string"],
+            #[cfg(feature = "json")]
+            Language::Json => &["string"],
+            #[cfg(feature = "markdown")]
+            Language::Markdown => &[],
+            #[cfg(feature = "python")]
+            Language::Python => &[],
+            #[cfg(feature = "go")]
+            Language::Go => &["raw_string_literal", "comment"],
+            #[cfg(feature = "c")]
+            Language::C => &["string_literal", "comment"],
+        }
+    }
+
+    /// Returns the highlight configuration for the language, which is built once and shared.
+    pub fn parser(&self) -> &'static HighlightConfiguration {
+        static RUST: Lazy<HighlightConfiguration> = Lazy::new(rust_parser);
+        static JAVASCRIPT: Lazy<HighlightConfiguration> = Lazy::new(javascript_parser);
+        #[cfg(feature = "toml")]
+        static TOML: Lazy<HighlightConfiguration> = Lazy::new(|| {
+            highlight_config(
+                tree_sitter_toml::language(),
+                tree_sitter_toml::HIGHLIGHT_QUERY,
+            )
+        });
+        #[cfg(feature = "json")]
+        static JSON: Lazy<HighlightConfiguration> = Lazy::new(|| {
+            highlight_config(
+                tree_sitter_json::language(),
+                tree_sitter_json::HIGHLIGHT_QUERY,
+            )
+        });
+        #[cfg(feature = "markdown")]
+        static MARKDOWN: Lazy<HighlightConfiguration> = Lazy::new(markdown_parser);
+        #[cfg(feature = "python")]
+        static PYTHON: Lazy<HighlightConfiguration> = Lazy::new(|| {
+            highlight_config(
+                tree_sitter_python::language(),
+                tree_sitter_python::HIGHLIGHT_QUERY,
+            )
+        });
+        #[cfg(feature = "go")]
+        static GO: Lazy<HighlightConfiguration> = Lazy::new(|| {
+            highlight_config(tree_sitter_go::language(), tree_sitter_go::HIGHLIGHT_QUERY)
+        });
+        #[cfg(feature = "c")]
+        static C: Lazy<HighlightConfiguration> = Lazy::new(|| {
+            highlight_config(tree_sitter_c::language(), tree_sitter_c::HIGHLIGHT_QUERY)
+        });
+
+        match self {
+            Language::Rust => &RUST,
+            Language::JavaScript => &JAVASCRIPT,
+            #[cfg(feature = "toml")]
+            Language::Toml => &TOML,
+            #[cfg(feature = "json")]
+            Language::Json => &JSON,
+            #[cfg(feature = "markdown")]
+            Language::Markdown => &MARKDOWN,
+            #[cfg(feature = "python")]
+            Language::Python => &PYTHON,
+            #[cfg(feature = "go")]
+            Language::Go => &GO,
+            #[cfg(feature = "c")]
+            Language::C => &C,
+        }
+    }
+}
+
+pub fn rust_parser() -> HighlightConfiguration {
+    let rust_language = tree_sitter_rust::language();
+
+    let mut rust_config = HighlightConfiguration::new(
+        rust_language,
+        tree_sitter_rust::HIGHLIGHT_QUERY,
+        tree_sitter_rust::INJECTIONS_QUERY,
+        "",
+    )
+    .unwrap();
+
+    rust_config.configure(&HIGHLIGHT_NAMES);
+    rust_config
+}
+
+pub fn javascript_parser() -> HighlightConfiguration {
+    let mut javascript_config = HighlightConfiguration::new(
+        tree_sitter_javascript::language(),
+        tree_sitter_javascript::HIGHLIGHT_QUERY,
+        tree_sitter_javascript::INJECTION_QUERY,
+        tree_sitter_javascript::LOCALS_QUERY,
+    )
+    .unwrap();
+
+    javascript_config.configure(&HIGHLIGHT_NAMES);
+    javascript_config
+}
+
+/// The highlight configuration for a grammar that only comes with a highlights query.
+#[cfg(any(
+    feature = "toml",
+    feature = "json",
+    feature = "markdown",
+    feature = "python",
+    feature = "go",
+    feature = "c"
+))]
+fn highlight_config(grammar: tree_sitter::Language, query: &str) -> HighlightConfiguration {
+    let mut config = HighlightConfiguration::new(grammar, query, "", "").unwrap();
+    config.configure(&HIGHLIGHT_NAMES);
+    config
+}
+
+/// The Markdown grammar names its captures in the older `text.*` scheme, which is renamed to
+/// the `markup.*` names themes are matched with.
+#[cfg(feature = "markdown")]
+fn markdown_parser() -> HighlightConfiguration {
+    let query = [
+        ("@text.title", "@markup.heading"),
+        ("@text.literal", "@markup.raw"),
+        ("@text.uri", "@markup.link.url"),
+        ("@text.reference", "@markup.link"),
+        ("@text.emphasis", "@markup.italic"),
+        ("@text.strong", "@markup.bold"),
+    ]
+    .iter()
+    .fold(
+        tree_sitter_md::HIGHLIGHTS_QUERY.to_string(),
+        |query, (from, to)| query.replace(from, to),
+    );
+    highlight_config(tree_sitter_md::language(), &query)
+}
+
+fn split_chunks(chunks: Vec<Chunk>) -> Vec<Vec<Chunk>> {
+    let mut lines: Vec<Vec<Chunk>> = vec![];
+    let mut current_line: Vec<Chunk> = vec![];
+
+    for chunk in chunks {
+        if chunk.contents.contains('\n') {
+            let mut chunks = chunk.split('\n');
+            // pushes the first chunk to the current line
+            current_line.push(chunks.remove(0));
+            // this line is done, because the previous chunk ended with \n
+            lines.push(current_line);
+
+            // pushes all the intermediary items alone to lines
+            for chunk in chunks[0..chunks.len() - 1].iter() {
+                lines.push(vec![chunk.clone()]);
+            }
+
+            // and the last item to current_line
+            current_line = vec![chunks[chunks.len() - 1].clone()];
+        } else {
+            current_line.push(chunk);
+        }
+    }
+
+    lines.push(current_line);
+
+    lines
+}
+
+fn parse<'a>(
+    source: &'a str,
+    lang_config: &'a HighlightConfiguration,
+) -> anyhow::Result<Vec<Chunk<'a>>> {
+    let mut highlighter = Highlighter::new();
+    let highlights = highlighter
+        .highlight(lang_config, source.as_bytes(), None, |_| None)
+        .unwrap();
+
+    let mut chunks = vec![];
+    let mut chunk: Option<Chunk<'_>> = None;
+
+    for event in highlights {
+        let event = event?;
+        match event {
+            HighlightEvent::Source { start, end } => {
+                if let Some(ref mut chunk) = chunk {
+                    chunk.contents = &source[start..end];
+                    chunk.start = start;
+                    chunk.end = end;
+                } else {
+                    chunk = Some(Chunk::from_source(start, end, &source[start..end]));
+                }
+            }
+            HighlightEvent::HighlightStart(s) => {
+                if let Some(chunk) = chunk.take() {
+                    // Push the previous chunk if it has content
+                    chunks.push(chunk);
+                }
+
+                chunk = Some(Chunk::with_type(
+                    ChunkType::from_str(HIGHLIGHT_NAMES[s.0]).expect("Invalid highlighting type"),
+                ));
+            }
+            HighlightEvent::HighlightEnd => {
+                if let Some(chunk) = chunk.take() {
+                    chunks.push(chunk);
+                }
+                chunk = None;
+            }
+        }
+    }
+
+    if let Some(chunk) = chunk.take() {
+        chunks.push(chunk);
+    }
+
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(all(
+        feature = "toml",
+        feature = "json",
+        feature = "markdown",
+        feature = "python",
+        feature = "go",
+        feature = "c"
+    ))]
+    #[test]
+    fn test_bundled_languages() {
+        let files = [
+            ("Cargo.toml", "[package]\nname = \"fed\"", ChunkType::String),
+            ("a.json", "{\"a\": \"b\"}", ChunkType::String),
+            ("README.md", "# Title", ChunkType::MarkupHeading),
+            ("a.py", "def f(): pass", ChunkType::Keyword),
+            ("a.go", "package main", ChunkType::Keyword),
+            ("a.h", "int x; // note", ChunkType::Comment),
+        ];
+        for (file, text, typ) in files {
+            let mut buffer = Buffer::from_contents(text);
+            buffer.language = Language::from_path(file);
+            assert!(buffer.language.is_some(), "{file}");
+
+            let highlights = Highlights::compute(&buffer).unwrap();
+            let spans = highlights.lines.concat();
+            assert!(
+                spans.iter().any(|span| span.typ == typ),
+                "{file}: {spans:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let javascript_language = tree_sitter_javascript::language();
+
+        let mut javascript_config = HighlightConfiguration::new(
+            javascript_language,
+            tree_sitter_javascript::HIGHLIGHT_QUERY,
+            tree_sitter_javascript::INJECTION_QUERY,
+            tree_sitter_javascript::LOCALS_QUERY,
+        )
+        .unwrap();
+
+        javascript_config.configure(&HIGHLIGHT_NAMES);
+
+        let source = r#"
+        function x() { 
+            let x = 1 + 2; 
+        }
+        "#;
+
+        let chunks = parse(source, &javascript_config).unwrap();
+        assert_eq!(chunks.len(), 24);
+        assert_eq!(chunks[0].typ, ChunkType::None); // space and return before function
+        assert_eq!(chunks[1].typ, ChunkType::Keyword);
+        assert_eq!(chunks[1].contents, "function");
+        assert_eq!(chunks[23].typ, ChunkType::None); // trailing return and indentation
+    }
+
+    #[test]
+    fn test_highlights_cache() {
+        let mut buffer = Buffer::from_contents("fn main() {}");
+        buffer.language = Some(Language::Rust);
+
+        let highlights = Highlights::compute(&buffer).unwrap();
+        assert!(highlights.is_current(&buffer));
+        assert_eq!(highlights.lines[0][0].typ, ChunkType::Keyword);
+
+        buffer.insert_char(0, 0, ' ');
+        assert!(!highlights.is_current(&buffer));
+
+        let highlights = Highlights::compute(&buffer).unwrap();
+        buffer.language = Some(Language::JavaScript);
+        assert!(!highlights.is_current(&buffer));
+    }
+
+    #[test]
+    fn test_highlights_window() {
+        let mut buffer = Buffer::from_contents("fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}");
+        buffer.language = Some(Language::Rust);
+        buffer.large = true;
+
+        let viewport = Viewport::new(1, 0, 20, 2, false);
+        update_highlights(&mut buffer, &viewport).unwrap();
+        let lines = buffer.highlights.visible(&viewport);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0][0].contents, "fn b() {}");
+        assert_eq!(lines[0][0].typ, ChunkType::None);
+
+        let viewport = Viewport::new(2, 0, 20, 2, false);
+        assert!(!buffer.highlights.covers(&buffer, &viewport));
+        update_highlights(&mut buffer, &viewport).unwrap();
+        assert_eq!(
+            buffer.highlights.visible(&viewport)[1][0].contents,
+            "fn d() {}"
+        );
+    }
+
+    #[test]
+    fn test_selection_columns() {
+        let selection = Selection::new((4, 2), (1, 0));
+        assert_eq!(selection.start, (1, 0));
+        assert_eq!(selection.end, (4, 2));
+
+        assert_eq!(selection.columns(0), Some((1, usize::MAX)));
+        assert_eq!(selection.columns(1), Some((0, usize::MAX)));
+        assert_eq!(selection.columns(2), Some((0, 5)));
+        assert_eq!(selection.columns(3), None);
+
+        let selection = Selection::new((2, 0), (5, 0));
+        assert_eq!(selection.columns(0), Some((2, 6)));
+    }
+
+    #[test]
+    fn test_split_chunk() {
+        let chunk = Chunk {
+            contents: "Hello, world!\nThis is a test",
+            start: 0,
+            end: 0,
+            typ: ChunkType::None,
+        };
+
+        let chunks = chunk.split('\n');
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].contents, "Hello, world!");
+        assert_eq!(chunks[1].contents, "This is a test");
+    }
+
+    #[test]
+    fn test_split_chunks() {
+        let chunks = vec![
+            Chunk {
+                contents: "function main() {\n    ",
+                start: 0,
+                end: 0,
+                typ: ChunkType::None,
+            },
+            Chunk {
+                contents: "println!(\"Hello, world!\");\n}",
+                start: 0,
+                end: 0,
+                typ: ChunkType::None,
+            },
+        ];
+
+        let lines = split_chunks(chunks);
+        println!("lines: {:?}", lines);
+        let line = lines[0]
+            .iter()
+            .map(|c| c.contents)
+            .collect::<Vec<&str>>()
+            .join("");
+        assert_eq!(line, "function main() {");
+
+        let line = lines[1]
+            .iter()
+            .map(|c| c.contents)
+            .collect::<Vec<&str>>()
+            .join("");
+        assert_eq!(line, "    println!(\"Hello, world!\");");
+
+        let line = lines[2]
+            .iter()
+            .map(|c| c.contents)
+            .collect::<Vec<&str>>()
+            .join("");
+        assert_eq!(line, "}");
+    }
+}
@@ -0,0 +1,277 @@
+use crate::core::{buffer::Buffer, operator::MotionKind};
+
+/// Where a word motion moves to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordMotion {
+    /// The start of the next word, `w`.
+    NextStart,
+    /// The start of the current or previous word, `b`.
+    PreviousStart,
+    /// The end of the current or next word, `e`.
+    NextEnd,
+    /// The end of the previous word, `ge`.
+    PreviousEnd,
+}
+
+impl WordMotion {
+    /// How much text the motion covers after an operator: `dw` stops before the next word while
+    /// `de` deletes the last char of the word.
+    pub fn kind(&self) -> MotionKind {
+        match self {
+            WordMotion::NextStart | WordMotion::PreviousStart => MotionKind::Exclusive,
+            WordMotion::NextEnd | WordMotion::PreviousEnd => MotionKind::Inclusive,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Blank,
+    Punctuation,
+    Word,
+}
+
+/// Words are runs of letters, digits and underscores, or runs of other non-blank chars. WORDs,
+/// when `big`, are runs of any non-blank chars.
+fn class(c: char, big: bool) -> Class {
+    if c.is_whitespace() {
+        Class::Blank
+    } else if big || c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+/// Whether `c` can be part of a word, being a letter, a digit or an underscore.
+pub fn is_word_char(c: char) -> bool {
+    class(c, false) == Class::Word
+}
+
+/// Finds where `motion` moves from `(x, y)`, by words or by WORDs when `big`. Motions cross
+/// lines, and `w`, `b` and `ge` stop at empty lines as if they were words.
+///
+/// Moving forward past the last word returns the end of the buffer, one past its last char.
+pub fn find(
+    buffer: &Buffer,
+    (x, y): (usize, usize),
+    motion: WordMotion,
+    big: bool,
+) -> (usize, usize) {
+    let len = buffer.len_chars();
+    let class_at = |i: usize| buffer.char(i).map_or(Class::Blank, |c| class(c, big));
+    let empty_line = |i: usize| {
+        let (x, y) = buffer.position(i);
+        x == 0 && buffer.line_len(y) == 0
+    };
+
+    let start = buffer.char_idx(x, y);
+    let mut i = start;
+    match motion {
+        WordMotion::NextStart => {
+            let word = class_at(i);
+            while word != Class::Blank && i < len && class_at(i) == word {
+                i += 1;
+            }
+            while i < len && class_at(i) == Class::Blank && !(i != start && empty_line(i)) {
+                i += 1;
+            }
+        }
+        WordMotion::NextEnd => {
+            i += 1;
+            while i < len && class_at(i) == Class::Blank {
+                i += 1;
+            }
+            let word = class_at(i);
+            while i + 1 < len && class_at(i + 1) == word {
+                i += 1;
+            }
+            i = i.min(len.saturating_sub(1));
+        }
+        WordMotion::PreviousStart => {
+            while i > 0 {
+                i -= 1;
+                if class_at(i) != Class::Blank || empty_line(i) {
+                    break;
+                }
+            }
+            let word = class_at(i);
+            while word != Class::Blank && i > 0 && class_at(i - 1) == word {
+                i -= 1;
+            }
+        }
+        WordMotion::PreviousEnd => {
+            let word = class_at(i);
+            while word != Class::Blank && i > 0 && class_at(i) == word {
+                i -= 1;
+            }
+            if word != Class::Blank && class_at(i) == word {
+                // still in the first word of the buffer
+                return (0, 0);
+            }
+            while i > 0 && class_at(i) == Class::Blank && !(i != start && empty_line(i)) {
+                i -= 1;
+            }
+        }
+    }
+
+    buffer.position(i)
+}
+
+/// The end of the word under `(x, y)`, which `cw` changes up to instead of moving to the next
+/// word like `w` would.
+pub fn current_end(buffer: &Buffer, (x, y): (usize, usize), big: bool) -> (usize, usize) {
+    let line: Vec<char> = buffer.line(y).unwrap_or_default().chars().collect();
+    let Some(&c) = line.get(x) else {
+        return (x, y);
+    };
+
+    let word = class(c, big);
+    let end = (x..line.len())
+        .take_while(|&i| class(line[i], big) == word)
+        .last()
+        .unwrap_or(x);
+    (end, y)
+}
+
+/// The range of the word object around `(x, y)`, with the end exclusive: the word, punctuation
+/// or blanks under the cursor. Around a word also takes the blanks after it, or the ones before
+/// it when there are none after, and around blanks takes the word after them.
+pub fn word_object(
+    buffer: &Buffer,
+    (x, y): (usize, usize),
+    big: bool,
+    around: bool,
+) -> Option<((usize, usize), (usize, usize))> {
+    let line: Vec<char> = buffer.line(y)?.chars().collect();
+    if line.is_empty() {
+        return None;
+    }
+
+    // the run of chars of the same class as the one at `x`
+    let run = |x: usize| {
+        let c = class(line[x], big);
+        let start = (0..x)
+            .rev()
+            .take_while(|&i| class(line[i], big) == c)
+            .last()
+            .unwrap_or(x);
+        let end = (x..line.len())
+            .take_while(|&i| class(line[i], big) == c)
+            .count();
+        (start, x + end)
+    };
+
+    let x = x.min(line.len() - 1);
+    let (mut start, mut end) = run(x);
+    if around {
+        let blank = |i: usize| class(line[i], big) == Class::Blank;
+        if end < line.len() && (blank(x) || blank(end)) {
+            end = run(end).1;
+        } else if start > 0 && blank(start - 1) {
+            start = run(start - 1).0;
+        }
+    }
+
+    Some(((start, y), (end, y)))
+}
+
+/// The identifier under column `x` of `line`, a word of letters, digits and underscores that
+/// doesn't start with a digit, as its start and exclusive end columns.
+pub fn identifier_at(line: &str, x: usize) -> Option<(usize, usize)> {
+    let line: Vec<char> = line.chars().collect();
+    if class(*line.get(x)?, false) != Class::Word {
+        return None;
+    }
+
+    let is_word = |i: &usize| class(line[*i], false) == Class::Word;
+    let start = (0..x).rev().take_while(is_word).last().unwrap_or(x);
+    let end = x + (x..line.len()).take_while(is_word).count();
+    (!line[start].is_ascii_digit()).then_some((start, end))
+}
+
+/// The columns where `word` starts in `line` as a whole word, not as part of a longer one.
+pub fn occurrences(line: &str, word: &str) -> Vec<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let word: Vec<char> = word.chars().collect();
+    let is_word = |i: usize| {
+        chars
+            .get(i)
+            .is_some_and(|&c| class(c, false) == Class::Word)
+    };
+
+    (0..chars.len())
+        .filter(|&x| {
+            chars[x..].starts_with(&word) && (x == 0 || !is_word(x - 1)) && !is_word(x + word.len())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_occurrences() {
+        let line = "let count = count_all(count) + 2count;";
+        assert_eq!(identifier_at(line, 6), Some((4, 9)));
+        assert_eq!(identifier_at(line, 4), Some((4, 9)));
+        assert_eq!(identifier_at(line, 3), None);
+        // numbers aren't identifiers, even with letters after them
+        assert_eq!(identifier_at(line, 31), None);
+        assert_eq!(occurrences(line, "count"), vec![4, 22]);
+        assert_eq!(occurrences("ça ça", "ça"), vec![0, 3]);
+    }
+
+    #[test]
+    fn test_next_start() {
+        let buffer = Buffer::from_contents("foo.bar(baz) qux\n  next\n\nlast");
+        let w = |pos| find(&buffer, pos, WordMotion::NextStart, false);
+        assert_eq!(w((0, 0)), (3, 0));
+        assert_eq!(w((3, 0)), (4, 0));
+        assert_eq!(w((11, 0)), (13, 0));
+        assert_eq!(w((13, 0)), (2, 1));
+        assert_eq!(w((2, 1)), (0, 2));
+        assert_eq!(w((0, 2)), (0, 3));
+        assert_eq!(w((0, 3)), (4, 3));
+
+        let big_w = |pos| find(&buffer, pos, WordMotion::NextStart, true);
+        assert_eq!(big_w((0, 0)), (13, 0));
+    }
+
+    #[test]
+    fn test_previous_start() {
+        let buffer = Buffer::from_contents("foo.bar(baz) qux\n  next\n\nlast");
+        let b = |pos| find(&buffer, pos, WordMotion::PreviousStart, false);
+        assert_eq!(b((6, 0)), (4, 0));
+        assert_eq!(b((4, 0)), (3, 0));
+        assert_eq!(b((2, 1)), (13, 0));
+        assert_eq!(b((0, 3)), (0, 2));
+        assert_eq!(b((0, 2)), (2, 1));
+        assert_eq!(b((0, 0)), (0, 0));
+
+        let big_b = |pos| find(&buffer, pos, WordMotion::PreviousStart, true);
+        assert_eq!(big_b((13, 0)), (0, 0));
+    }
+
+    #[test]
+    fn test_word_ends() {
+        let buffer = Buffer::from_contents("foo.bar(baz) qux\n  next\n\nlast");
+        let e = |pos| find(&buffer, pos, WordMotion::NextEnd, false);
+        assert_eq!(e((0, 0)), (2, 0));
+        assert_eq!(e((2, 0)), (3, 0));
+        assert_eq!(e((15, 0)), (5, 1));
+        assert_eq!(e((5, 1)), (3, 3));
+        assert_eq!(find(&buffer, (0, 0), WordMotion::NextEnd, true), (11, 0));
+
+        let ge = |pos| find(&buffer, pos, WordMotion::PreviousEnd, false);
+        assert_eq!(ge((5, 0)), (3, 0));
+        assert_eq!(ge((2, 1)), (15, 0));
+        assert_eq!(ge((2, 3)), (0, 2));
+        assert_eq!(ge((1, 0)), (0, 0));
+
+        assert_eq!(current_end(&buffer, (1, 0), false), (2, 0));
+        assert_eq!(current_end(&buffer, (2, 0), false), (2, 0));
+        assert_eq!(current_end(&buffer, (1, 0), true), (11, 0));
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    backtrace::Backtrace,
+    fmt::Write,
+    fs,
+    panic::PanicHookInfo,
+    path::PathBuf,
+    sync::Mutex,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The last panic as the hook saw it, since only the hook gets its message and backtrace.
+static PANIC: Mutex<Option<String>> = Mutex::new(None);
+
+/// Keeps the message, location and backtrace of a panic for the crash report.
+pub fn record_panic(info: &PanicHookInfo) {
+    let thread = thread::current();
+    let mut text = format!("thread '{}' {info}\n", thread.name().unwrap_or("<unnamed>"));
+    let _ = write!(text, "\nbacktrace:\n{}", Backtrace::force_capture());
+    if let Ok(mut panic) = PANIC.lock() {
+        *panic = Some(text);
+    }
+}
+
+/// What the editor was doing when it panicked.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub buffer: String,
+    /// One-based line and column of the cursor.
+    pub cursor: (usize, usize),
+    /// The latest actions, oldest first.
+    pub actions: Vec<String>,
+    /// Where the unsaved changes of each modified buffer went.
+    pub recovered: Vec<String>,
+}
+
+impl Report {
+    fn text(&self, panic: &str) -> String {
+        let mut text = format!("fed {} crashed\n\n{panic}\n", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(text, "buffer: {}", self.buffer);
+        let _ = writeln!(text, "cursor: {}:{}", self.cursor.0, self.cursor.1);
+        let _ = writeln!(text, "\nrecent actions:");
+        for action in &self.actions {
+            let _ = writeln!(text, "  {action}");
+        }
+        if !self.recovered.is_empty() {
+            let _ = writeln!(text, "\nunsaved changes:");
+            for recovered in &self.recovered {
+                let _ = writeln!(text, "  {recovered}");
+            }
+        }
+        text
+    }
+
+    /// Writes the report with the recorded panic to `crash-<timestamp>.txt` in `dir`, returning
+    /// its path.
+    pub fn write(&self, dir: PathBuf) -> anyhow::Result<PathBuf> {
+        let panic = PANIC
+            .lock()
+            .ok()
+            .and_then(|mut panic| panic.take())
+            .unwrap_or_else(|| "panicked without a message\n".to_string());
+
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("crash-{}.txt", timestamp()));
+        fs::write(&path, self.text(&panic))?;
+        Ok(path)
+    }
+}
+
+/// Where crash reports and the unsaved text of unnamed buffers go, `~/.local/share/fed`.
+pub fn dir() -> anyhow::Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("No home directory"))?;
+    Ok(home.join(".local").join("share").join("fed"))
+}
+
+/// Seconds since the epoch, which sort in the order the crashes happened.
+pub fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_text() {
+        let report = Report {
+            buffer: "src/main.rs".to_string(),
+            cursor: (12, 5),
+            actions: vec!["MoveDown".to_string(), "DeleteLine".to_string()],
+            recovered: vec!["src/main.rs: swap file, recover with `fed -r src/main.rs`".to_string()],
+        };
+        let text = report.text("thread 'main' panicked at src/main.rs:1:1:\nboom\n");
+        assert!(text.contains("\nboom\n"));
+        assert!(text.contains("cursor: 12:5\n"));
+        assert!(text.contains("\nrecent actions:\n  MoveDown\n  DeleteLine\n"));
+        assert!(text.ends_with("recover with `fed -r src/main.rs`\n"));
+    }
+}
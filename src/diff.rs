@@ -0,0 +1,479 @@
+//! Two buffers compared side by side, as `fed --diff a b` and `:diffsplit` open them.
+
+use std::ops::Range;
+
+use crossterm::style::{Color, Stylize};
+
+use crate::{
+    core::{
+        buffer::Buffer,
+        syntax::{Selection, Viewport},
+    },
+    gitdiff::{changes, Change},
+    tui::{
+        highlight::{self, Overlays, Styles},
+        renderer::Renderer,
+        theme::Theme,
+        utils::{blend, char_width, darken, display_width},
+    },
+};
+
+/// The colors the backgrounds of added and changed lines lean towards, and the filler across
+/// from lines a side doesn't have.
+pub const ADDED: Color = Color::Rgb {
+    r: 0x3f,
+    g: 0xb9,
+    b: 0x50,
+};
+pub const CHANGED: Color = Color::Rgb {
+    r: 0x38,
+    g: 0x8b,
+    b: 0xfd,
+};
+pub const REMOVED: Color = Color::Rgb {
+    r: 0xf8,
+    g: 0x51,
+    b: 0x49,
+};
+
+/// A row of the side by side view with the line each side shows on it, `None` for the filler
+/// across from lines only the other side has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Row {
+    pub lines: [Option<usize>; 2],
+    /// Whether the row is part of a change rather than lines both sides share.
+    pub changed: bool,
+}
+
+/// Where the side by side view is drawn and what it shows over the text.
+pub struct View<'a> {
+    /// The first row shown.
+    pub row: usize,
+    pub width: usize,
+    pub height: usize,
+    /// The screen row the view starts on, below the tabline.
+    pub top: usize,
+    /// The cursor line of each side, drawn on the line highlight color.
+    pub cursor: [Option<usize>; 2],
+    /// The visual selection and the side it's on.
+    pub selection: Option<(usize, &'a Selection)>,
+    pub invisibles: bool,
+    /// The styles of syntax under the theme the view is drawn with.
+    pub styles: &'a Styles,
+    pub gutter: Gutter<'a>,
+}
+
+/// The gutter before the text of each side, laid out like the editor's by the `number`,
+/// `number_width`, `gutter_padding` and `gutter_separator` options.
+#[derive(Debug, Clone, Copy)]
+pub struct Gutter<'a> {
+    /// The least digits of the line numbers, `None` when they're off.
+    pub digits: Option<usize>,
+    /// The spaces between the line numbers and the separator.
+    pub padding: usize,
+    pub separator: &'a str,
+}
+
+impl Gutter<'_> {
+    /// The digits of the line numbers of a side with `len` lines, none when they're off.
+    fn number_digits(&self, len: usize) -> usize {
+        self.digits
+            .map_or(0, |digits| digits.max(len.to_string().len()))
+    }
+
+    /// The columns the gutter of a side with `len` lines takes: a space, the line numbers with
+    /// their padding, and the separator.
+    pub fn width(&self, len: usize) -> usize {
+        let numbers = match self.number_digits(len) {
+            0 => 0,
+            digits => digits + self.padding,
+        };
+        1 + numbers + display_width(self.separator)
+    }
+}
+
+/// Two buffers compared side by side, with their lines lined up in rows so the ones they share
+/// are across from each other.
+#[derive(Debug, Clone)]
+pub struct Diff {
+    /// The buffers on the left and on the right, by their index in the editor's list.
+    pub buffers: [usize; 2],
+    /// The revision and length of both buffers the rows were lined up for.
+    compared: Option<[(u64, usize); 2]>,
+    /// The runs of lines that differ, with the left side as `old` and the right one as `new`.
+    pub changes: Vec<Change>,
+    pub rows: Vec<Row>,
+    /// The row each line of each side is on.
+    lines: [Vec<usize>; 2],
+}
+
+impl Diff {
+    pub fn new(left: usize, right: usize) -> Self {
+        Diff {
+            buffers: [left, right],
+            compared: None,
+            changes: vec![],
+            rows: vec![],
+            lines: [vec![], vec![]],
+        }
+    }
+
+    /// Compares the buffers again when either changed since the last time. Returns whether it
+    /// did.
+    pub fn update(&mut self, left: &Buffer, right: &Buffer) -> bool {
+        let compared = [
+            (left.revision(), left.len_chars()),
+            (right.revision(), right.len_chars()),
+        ];
+        if self.compared == Some(compared) {
+            return false;
+        }
+        self.compared = Some(compared);
+
+        let (left, right) = (lines(left), lines(right));
+        let left: Vec<_> = left.iter().map(String::as_str).collect();
+        let right: Vec<_> = right.iter().map(String::as_str).collect();
+        self.changes = changes(&left, &right);
+        self.rows = align(&self.changes, left.len(), right.len());
+        self.lines = [0, 1].map(|side| {
+            let mut lines = vec![];
+            for (row, line) in self.rows.iter().enumerate() {
+                if line.lines[side].is_some() {
+                    lines.push(row);
+                }
+            }
+            lines
+        });
+        true
+    }
+
+    /// The side buffer `index` is on, 0 for the left and 1 for the right.
+    pub fn side(&self, index: usize) -> Option<usize> {
+        self.buffers.iter().position(|&buffer| buffer == index)
+    }
+
+    /// The row line `y` of `side` is on.
+    pub fn row(&self, side: usize, y: usize) -> usize {
+        self.lines[side].get(y).copied().unwrap_or(self.rows.len())
+    }
+
+    /// The first row shown with line `top` of `side` at the top, which brings the filler above
+    /// it into view.
+    pub fn top_row(&self, side: usize, top: usize) -> usize {
+        match top {
+            0 => 0,
+            top => self.row(side, top - 1) + 1,
+        }
+    }
+
+    /// The line of `side` on `row`, or the first one after it when the side has filler there.
+    pub fn line_at(&self, side: usize, row: usize) -> usize {
+        self.rows[row.min(self.rows.len())..]
+            .iter()
+            .find_map(|row| row.lines[side])
+            .unwrap_or_else(|| self.lines[side].len().saturating_sub(1))
+    }
+
+    /// The line of `side` the next or previous change after line `y` starts on.
+    pub fn next_change(&self, side: usize, y: usize, forward: bool) -> Option<usize> {
+        let starts = self
+            .changes
+            .iter()
+            .map(|change| lines_of(change, side).start);
+        if forward {
+            starts.filter(|&start| start > y).min()
+        } else {
+            starts.filter(|&start| start < y).max()
+        }
+    }
+
+    /// The changes with lines in `start..=end` of `side`, or with lines only the other side has
+    /// right above or below them.
+    pub fn changes_at(&self, side: usize, (start, end): (usize, usize)) -> Vec<Change> {
+        self.changes
+            .iter()
+            .filter(|change| {
+                let lines = lines_of(change, side);
+                if lines.is_empty() {
+                    (start..=end + 1).contains(&lines.start)
+                } else {
+                    lines.start <= end && lines.end > start
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Draws both sides with the separator between them.
+    pub fn draw(
+        &self,
+        screen: &mut dyn Renderer,
+        theme: &Theme,
+        buffers: [&mut Buffer; 2],
+        view: &View,
+    ) -> anyhow::Result<()> {
+        let [left, right] = buffers;
+        let end = (view.row + view.height).min(self.rows.len());
+        // the chars that changed within each changed line, from the other side's line
+        let spans: Vec<[Vec<Range<usize>>; 2]> = self.rows[view.row.min(end)..end]
+            .iter()
+            .map(|row| match row.lines {
+                [Some(x), Some(y)] if row.changed => {
+                    let a = left.line(x).unwrap_or_default();
+                    let b = right.line(y).unwrap_or_default();
+                    changed_chars(&a, &b)
+                }
+                _ => [vec![], vec![]],
+            })
+            .collect();
+
+        self.draw_side(screen, theme, left, 0, view, &spans)?;
+        self.draw_side(screen, theme, right, 1, view, &spans)?;
+
+        let (fg, bg) = theme.default_colors();
+        let x = panes(view.width)[0].end;
+        for y in 0..view.height {
+            screen.move_to(x, y);
+            screen.print_styled("│".with(darken(fg, 0.5)?).on(bg));
+        }
+        Ok(())
+    }
+
+    fn draw_side(
+        &self,
+        screen: &mut dyn Renderer,
+        theme: &Theme,
+        buffer: &mut Buffer,
+        side: usize,
+        view: &View,
+        spans: &[[Vec<Range<usize>>; 2]],
+    ) -> anyhow::Result<()> {
+        let pane = panes(view.width)[side].clone();
+        let text = (pane.start + view.gutter.width(buffer.len())).min(pane.end)..pane.end;
+        let end = (view.row + view.height).min(self.rows.len());
+        let rows = &self.rows[view.row.min(end)..end];
+
+        let (fg, bg) = theme.default_colors();
+        let number_fg = darken(fg, 0.5)?;
+        let added_bg = blend(bg, ADDED, 0.2);
+        let changed_bg = blend(bg, CHANGED, 0.15);
+        let text_bg = blend(bg, CHANGED, 0.4);
+        let filler_bg = blend(bg, REMOVED, 0.1);
+
+        let mut i = 0;
+        while i < rows.len() {
+            let Some(first) = rows[i].lines[side] else {
+                screen.move_to(pane.start, i);
+                screen.print_styled(" ".repeat(text.start - pane.start).on(bg));
+                let filler = "-".repeat(text.len());
+                screen.print_styled(filler.with(darken(fg, 0.6)?).on(filler_bg));
+                i += 1;
+                continue;
+            };
+
+            // the lines that follow each other on consecutive rows are drawn together
+            let mut len = 1;
+            while rows
+                .get(i + len)
+                .is_some_and(|row| row.lines[side] == Some(first + len))
+            {
+                len += 1;
+            }
+
+            let mut backgrounds = vec![];
+            let mut changed = vec![];
+            for (k, row) in rows[i..i + len].iter().enumerate() {
+                let y = first + k;
+                if row.changed {
+                    let bg = match row.lines[1 - side] {
+                        Some(_) => changed_bg,
+                        None => added_bg,
+                    };
+                    backgrounds.push((y, bg));
+                }
+                for range in spans[i + k][side].iter().filter(|range| !range.is_empty()) {
+                    let selection = Selection::new((range.start, y), (range.end - 1, y));
+                    changed.push((selection, text_bg));
+                }
+            }
+            let selection = view
+                .selection
+                .filter(|(selected, _)| *selected == side)
+                .map(|(_, selection)| selection);
+            let overlays = Overlays {
+                selection,
+                current_line: view.cursor[side],
+                line_backgrounds: &backgrounds,
+                spans: &changed,
+                invisibles: view.invisibles,
+                ..Default::default()
+            };
+            let viewport = Viewport::new(first, text.start, text.len(), len, false);
+            screen.set_top(view.top + i);
+            highlight::draw(screen, buffer, theme, view.styles, &viewport, &overlays)?;
+            screen.set_top(view.top);
+
+            let gutter = text.start - pane.start;
+            let digits = view.gutter.number_digits(buffer.len());
+            let padding = " ".repeat(view.gutter.padding);
+            for k in 0..len {
+                let number = match digits {
+                    0 => String::new(),
+                    digits => format!("{:>digits$}{padding}", first + k + 1),
+                };
+                // cut short when the pane is narrower than the gutter
+                let mut columns = 0;
+                let number: String = format!(" {number}{}", view.gutter.separator)
+                    .chars()
+                    .take_while(|&c| {
+                        columns += char_width(c);
+                        columns <= gutter
+                    })
+                    .collect();
+                screen.move_to(pane.start, i + k);
+                screen.print_styled(number.with(number_fg).on(bg));
+            }
+            i += len;
+        }
+
+        for y in rows.len()..view.height {
+            screen.move_to(pane.start, y);
+            screen.print_styled(" ".repeat(pane.len()).with(fg).on(bg));
+        }
+        Ok(())
+    }
+}
+
+/// The columns of each side in a view `width` columns wide, with one between them for the
+/// separator.
+pub fn panes(width: usize) -> [Range<usize>; 2] {
+    let left = width.saturating_sub(1) / 2;
+    [0..left, (left + 1).min(width)..width]
+}
+
+/// The lines of the change on `side`, `old` on the left and `new` on the right.
+pub fn lines_of(change: &Change, side: usize) -> Range<usize> {
+    match side {
+        0 => change.old.clone(),
+        _ => change.new.clone(),
+    }
+}
+
+/// Makes the lines of `changes` in `to`, the buffer on `side`, the same as in `from`, the
+/// buffer on the other side. Goes from the last change so the earlier ones keep their lines.
+pub fn copy_changes(changes: &[Change], from: &Buffer, to: &mut Buffer, side: usize) {
+    for change in changes.iter().rev() {
+        let lines: Vec<String> = lines_of(change, 1 - side)
+            .filter_map(|y| from.line(y))
+            .collect();
+        let lines: Vec<_> = lines.iter().map(String::as_str).collect();
+        to.replace_lines(lines_of(change, side), &lines);
+    }
+}
+
+fn lines(buffer: &Buffer) -> Vec<String> {
+    (0..buffer.len()).filter_map(|y| buffer.line(y)).collect()
+}
+
+/// Lines up the lines of both sides in rows, `left` and `right` lines long, pairing the lines
+/// of each change and filling in for the side with fewer.
+fn align(changes: &[Change], left: usize, right: usize) -> Vec<Row> {
+    let mut rows = vec![];
+    let (mut x, mut y) = (0, 0);
+    let end = Change {
+        old: left..left,
+        new: right..right,
+    };
+    for change in changes.iter().chain([&end]) {
+        while x < change.old.start {
+            rows.push(Row {
+                lines: [Some(x), Some(y)],
+                changed: false,
+            });
+            x += 1;
+            y += 1;
+        }
+        for i in 0..change.old.len().max(change.new.len()) {
+            let line = |lines: &Range<usize>| (i < lines.len()).then_some(lines.start + i);
+            rows.push(Row {
+                lines: [line(&change.old), line(&change.new)],
+                changed: true,
+            });
+        }
+        (x, y) = (change.old.end, change.new.end);
+    }
+    rows
+}
+
+/// The columns of `a` and of `b` that differ between the two lines.
+pub fn changed_chars(a: &str, b: &str) -> [Vec<Range<usize>>; 2] {
+    let a: Vec<_> = a.chars().collect();
+    let b: Vec<_> = b.chars().collect();
+    let changes = changes(&a, &b);
+    [
+        changes.iter().map(|change| change.old.clone()).collect(),
+        changes.iter().map(|change| change.new.clone()).collect(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(left: Option<usize>, right: Option<usize>, changed: bool) -> Row {
+        Row {
+            lines: [left, right],
+            changed,
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        let left = Buffer::from_contents("a\nb\nc\nd");
+        let right = Buffer::from_contents("a\nB\nx\nc");
+        let mut diff = Diff::new(0, 1);
+        assert!(diff.update(&left, &right));
+        assert!(!diff.update(&left, &right));
+        assert_eq!(
+            diff.rows,
+            vec![
+                row(Some(0), Some(0), false),
+                row(Some(1), Some(1), true),
+                row(None, Some(2), true),
+                row(Some(2), Some(3), false),
+                row(Some(3), None, true),
+            ]
+        );
+        assert_eq!(diff.row(0, 2), 3);
+        assert_eq!(diff.top_row(0, 2), 2);
+        assert_eq!(diff.line_at(0, 2), 2);
+        assert_eq!(diff.line_at(1, 4), 3);
+
+        assert_eq!(diff.next_change(1, 0, true), Some(1));
+        assert_eq!(diff.next_change(1, 1, true), Some(4));
+        assert_eq!(diff.next_change(0, 3, false), Some(1));
+        assert_eq!(diff.changes_at(0, (0, 0)), vec![]);
+        assert_eq!(diff.changes_at(1, (3, 3)).len(), 1);
+    }
+
+    #[test]
+    fn test_copy_changes() {
+        let left = Buffer::from_contents("a\nb\nc\nd");
+        let mut right = Buffer::from_contents("a\nB\nx\nc");
+        let mut diff = Diff::new(0, 1);
+        diff.update(&left, &right);
+
+        let changes = diff.changes.clone();
+        copy_changes(&changes, &left, &mut right, 1);
+        assert_eq!(right.to_string(), "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn test_changed_chars() {
+        assert_eq!(
+            changed_chars("let a = 1;", "let ab = 2;"),
+            [vec![5..5, 8..9], vec![5..6, 9..10]]
+        );
+        assert!(changed_chars("same", "same").iter().all(Vec::is_empty));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::shell;
+
+/// Pipes `text` through the formatter `command`, returning the formatted text. `{file}` in the
+/// command is replaced with the quoted path of the file, for formatters like
+/// `prettier --stdin-filepath {file}` that pick their rules from it. A formatter that prints
+/// nothing for text that isn't blank failed, even when it exits successfully.
+pub fn format(command: &str, file: Option<&str>, text: String) -> anyhow::Result<String> {
+    let command = match file {
+        Some(file) => command.replace("{file}", &quote(file)),
+        None => command.to_string(),
+    };
+
+    let blank = text.trim().is_empty();
+    let output = shell::run(&command, Some(text))?;
+    if let Some(error) = output.error() {
+        match output.stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => anyhow::bail!("Formatter failed, {error}: {line}"),
+            None => anyhow::bail!("Formatter failed, {error}"),
+        }
+    }
+    if output.stdout.is_empty() && !blank {
+        anyhow::bail!("Formatter failed, it printed nothing");
+    }
+    Ok(output.stdout)
+}
+
+/// Quotes `text` as a single word for the shell.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// The column of `new`, the formatted version of the line `old`, with the char that was at
+/// column `x`. Only the chars other than whitespace are counted, since those are what
+/// formatters move around.
+pub fn moved_column(old: &str, new: &str, x: usize) -> usize {
+    let before = old.chars().take(x).filter(|c| !c.is_whitespace()).count();
+
+    let mut seen = 0;
+    for (i, c) in new.chars().enumerate() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if seen == before {
+            return i;
+        }
+        seen += 1;
+    }
+    new.chars().count().saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moved_column() {
+        assert_eq!(moved_column("let  x=1;", "let x = 1;", 5), 4);
+        assert_eq!(moved_column("let  x=1;", "let x = 1;", 7), 8);
+        assert_eq!(moved_column("  foo( a )", "    foo(a)", 6), 8);
+        assert_eq!(moved_column("a b", "ab", 2), 1);
+        assert_eq!(moved_column("abc", "", 2), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format() {
+        let text = format("tr a-z A-Z", None, "fn main\n".to_string()).unwrap();
+        assert_eq!(text, "FN MAIN\n");
+        let text = format("echo {file}", Some("it's.rs"), String::new()).unwrap();
+        assert_eq!(text, "it's.rs\n");
+        assert!(format("exit 1", None, String::new()).is_err());
+        assert!(format("cat > /dev/null", None, "fn main\n".to_string()).is_err());
+        assert_eq!(
+            format("cat > /dev/null", None, "\n".to_string()).unwrap(),
+            ""
+        );
+    }
+}
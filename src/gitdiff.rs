@@ -0,0 +1,388 @@
+use std::{
+    ops::Range,
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::core::signs::{Sign, SignKind};
+
+/// How often the version of a file at `HEAD` is read again, to notice commits and checkouts.
+const HEAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Diffs needing more edits than this are shown as a single change, which keeps large rewrites
+/// from taking long to compare.
+const MAX_EDITS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+/// A run of lines that differ from `HEAD`, at zero-based lines of the buffer. Removed lines have
+/// no lines left in the buffer, so they start at the line that followed them and are empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hunk {
+    pub kind: HunkKind,
+    pub start: usize,
+    pub len: usize,
+}
+
+impl Hunk {
+    /// The line the hunk's sign goes on, which for removed lines is the one above them.
+    pub fn line(&self) -> usize {
+        match self.kind {
+            HunkKind::Removed => self.start.saturating_sub(1),
+            _ => self.start,
+        }
+    }
+
+    /// The signs for the lines of the hunk: `+` for added lines, `~` for changed ones and `-`
+    /// above removed ones.
+    pub fn signs(&self) -> impl Iterator<Item = (usize, Sign)> {
+        let (text, kind) = match self.kind {
+            HunkKind::Added => ('+', SignKind::Added),
+            HunkKind::Changed => ('~', SignKind::Changed),
+            HunkKind::Removed => ('-', SignKind::Removed),
+        };
+        let sign = Sign {
+            text,
+            kind,
+            priority: 1,
+        };
+        let lines = self.line()..self.line() + self.len.max(1);
+        lines.map(move |y| (y, sign))
+    }
+}
+
+/// How a buffer differs from the version of its file at `HEAD`.
+#[derive(Debug, Clone, Default)]
+pub struct GitDiff {
+    /// The lines of the file at `HEAD`, `None` when it isn't tracked by git.
+    head: Option<Vec<String>>,
+    /// When `head` was read, `None` to read it again on the next update.
+    loaded: Option<Instant>,
+    /// The buffer revision the hunks were found for.
+    revision: Option<u64>,
+    pub hunks: Vec<Hunk>,
+    /// The branch checked out in the file's repository, or the abbreviated commit when detached.
+    pub branch: Option<String>,
+}
+
+impl GitDiff {
+    /// Reads the file at `HEAD` again on the next update, as after writing it.
+    pub fn expire(&mut self) {
+        self.loaded = None;
+    }
+
+    /// Compares `text`, the buffer at `revision`, to the file at `HEAD` when either changed.
+    /// Returns whether the hunks or the branch changed.
+    pub fn update(&mut self, path: &Path, revision: u64, text: impl FnOnce() -> String) -> bool {
+        let stale = self
+            .loaded
+            .is_none_or(|loaded| loaded.elapsed() >= HEAD_INTERVAL);
+        let mut branched = false;
+        if stale {
+            let head = head_lines(path);
+            let branch = branch(path);
+            self.loaded = Some(Instant::now());
+            branched = branch != self.branch;
+            self.branch = branch;
+            if head == self.head && self.revision == Some(revision) {
+                return branched;
+            }
+            self.head = head;
+        } else if self.revision == Some(revision) {
+            return false;
+        }
+        self.revision = Some(revision);
+
+        let hunks = match &self.head {
+            Some(head) => {
+                let head: Vec<_> = head.iter().map(String::as_str).collect();
+                let text = text();
+                // the buffer keeps no line break after its last line, which files end with
+                let lines: Vec<_> = match text.as_str() {
+                    "" => vec![],
+                    text => text.split('\n').collect(),
+                };
+                diff(&head, &lines)
+            }
+            None => vec![],
+        };
+        let changed = hunks != self.hunks;
+        self.hunks = hunks;
+        changed || branched
+    }
+}
+
+/// The lines of `path` as committed at `HEAD`, or `None` when it isn't in a git repository or
+/// isn't committed.
+fn head_lines(path: &Path) -> Option<Vec<String>> {
+    let name = path.file_name()?.to_string_lossy();
+    let text = git(path, &["show", &format!("HEAD:./{name}")])?.replace("\r\n", "\n");
+    Some(text.lines().map(str::to_string).collect())
+}
+
+/// The branch checked out in the repository of `path`, or the abbreviated commit at `HEAD` when
+/// none is.
+fn branch(path: &Path) -> Option<String> {
+    let branch = git(path, &["branch", "--show-current"])
+        .filter(|branch| !branch.trim().is_empty())
+        .or_else(|| git(path, &["rev-parse", "--short", "HEAD"]))?;
+    Some(branch.trim().to_string())
+}
+
+/// Runs git with `args` in the directory of `path`, returning its output when it succeeds.
+fn git(path: &Path, args: &[&str]) -> Option<String> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let output = Command::new("git")
+        .current_dir(dir.unwrap_or(Path::new(".")))
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A run of lines of the old text, replaced by a run of lines of the new one. Either may be
+/// empty, for lines that were only added or only removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Finds the hunks that turn the `old` lines into the `new` ones. A run of removed lines
+/// followed by added ones counts as changed lines.
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    changes(old, new)
+        .into_iter()
+        .map(|change| Hunk {
+            kind: match (change.old.is_empty(), change.new.is_empty()) {
+                (true, _) => HunkKind::Added,
+                (_, true) => HunkKind::Removed,
+                _ => HunkKind::Changed,
+            },
+            start: change.new.start,
+            len: change.new.len(),
+        })
+        .collect()
+}
+
+/// Finds the runs of lines that differ between `old` and `new`, in order. Works the same on the
+/// chars of a line.
+pub fn changes<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    let edits = myers(old, new).unwrap_or_else(|| {
+        // too different to compare line by line, so it all changed
+        let mut edits = vec![Edit::Delete; old.len()];
+        edits.extend(vec![Edit::Insert; new.len()]);
+        edits
+    });
+
+    let mut changes = vec![];
+    let (mut x, mut y) = (prefix, prefix);
+    let mut change = Change {
+        old: x..x,
+        new: y..y,
+    };
+    for edit in edits {
+        match edit {
+            Edit::Equal => {
+                if !change.old.is_empty() || !change.new.is_empty() {
+                    changes.push(change);
+                }
+                x += 1;
+                y += 1;
+                change = Change {
+                    old: x..x,
+                    new: y..y,
+                };
+            }
+            Edit::Insert => {
+                y += 1;
+                change.new.end = y;
+            }
+            Edit::Delete => {
+                x += 1;
+                change.old.end = x;
+            }
+        }
+    }
+    if !change.old.is_empty() || !change.new.is_empty() {
+        changes.push(change);
+    }
+    changes
+}
+
+/// The line of the new text that line `y` of the old one became, the first line of its
+/// replacement when it changed.
+pub fn moved_line(changes: &[Change], y: usize) -> usize {
+    let mut shift = 0isize;
+    for change in changes {
+        if y < change.old.start {
+            break;
+        }
+        if y < change.old.end {
+            let offset = (y - change.old.start).min(change.new.len().saturating_sub(1));
+            return change.new.start + offset;
+        }
+        shift += change.new.len() as isize - change.old.len() as isize;
+    }
+    y.saturating_add_signed(shift)
+}
+
+/// The shortest edit script from `a` to `b` with Myers' algorithm, or `None` when it takes more
+/// than `MAX_EDITS` edits.
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Option<Vec<Edit>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDITS) as isize;
+    let offset = max + 1;
+    // the furthest x reached on each diagonal k = x - y, and a copy of it before each step
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace = vec![];
+    let index = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m, index));
+            }
+        }
+    }
+    None
+}
+
+fn backtrack(
+    trace: &[Vec<isize>],
+    n: isize,
+    m: isize,
+    index: impl Fn(isize) -> usize,
+) -> Vec<Edit> {
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let previous = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let (px, py) = if d == 0 {
+            (0, 0)
+        } else {
+            (v[index(previous)], v[index(previous)] - previous)
+        };
+
+        while x > px && y > py {
+            edits.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == px { Edit::Insert } else { Edit::Delete });
+        }
+        (x, y) = (px, py);
+    }
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(kind: HunkKind, start: usize, len: usize) -> Hunk {
+        Hunk { kind, start, len }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = ["a", "b", "c", "d", "e"];
+        assert_eq!(diff(&old, &old), vec![]);
+        assert_eq!(
+            diff(&old, &["a", "x", "b", "c", "d", "e"]),
+            vec![hunk(HunkKind::Added, 1, 1)]
+        );
+        assert_eq!(
+            diff(&old, &["a", "b", "e"]),
+            vec![hunk(HunkKind::Removed, 2, 0)]
+        );
+        assert_eq!(
+            diff(&old, &["a", "B", "c", "d", "e", "f", "g"]),
+            vec![hunk(HunkKind::Changed, 1, 1), hunk(HunkKind::Added, 5, 2)]
+        );
+        assert_eq!(
+            diff(&old, &["x", "c", "y", "e"]),
+            vec![hunk(HunkKind::Changed, 0, 1), hunk(HunkKind::Changed, 2, 1)]
+        );
+        assert_eq!(diff(&[], &["a"]), vec![hunk(HunkKind::Added, 0, 1)]);
+        assert_eq!(diff(&["a"], &[]), vec![hunk(HunkKind::Removed, 0, 0)]);
+
+        let changes = changes(&old, &["a", "B", "c", "e", "f"]);
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    old: 1..2,
+                    new: 1..2
+                },
+                Change {
+                    old: 3..4,
+                    new: 3..3
+                },
+                Change {
+                    old: 5..5,
+                    new: 4..5
+                },
+            ]
+        );
+        let moved: Vec<_> = (0..5).map(|y| moved_line(&changes, y)).collect();
+        assert_eq!(moved, [0, 1, 2, 3, 3]);
+
+        let removed = hunk(HunkKind::Removed, 2, 0);
+        assert_eq!(removed.signs().map(|(y, _)| y).collect::<Vec<_>>(), [1]);
+        let changed = hunk(HunkKind::Changed, 2, 3);
+        assert_eq!(
+            changed.signs().map(|(y, _)| y).collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
+    }
+}
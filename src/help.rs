@@ -0,0 +1,250 @@
+use crate::{
+    command::command_names,
+    core::{
+        action::Action,
+        operator::{Operator, Pending, WINDOW_PREFIX},
+    },
+    tui::keymap::Keymap,
+};
+
+/// The key sequences started by a prefix key, which run their action on the key after it rather
+/// than through the keymap.
+const SEQUENCES: &[(&str, Action)] = &[
+    ("gg", Action::StartOfBuffer),
+    ("gd", Action::GoToDefinition),
+    ("ge", Action::PreviousEndOfWord),
+    ("gE", Action::PreviousEndOfBigWord),
+    ("gj", Action::DisplayLineDown),
+    ("gk", Action::DisplayLineUp),
+    ("gt", Action::NextBuffer),
+    ("gT", Action::PreviousBuffer),
+    ("gu", Action::Lowercase),
+    ("gU", Action::Uppercase),
+    ("gc", Action::Comment),
+    ("zz", Action::CenterLine),
+    ("zt", Action::LineToTop),
+    ("zb", Action::LineToBottom),
+    ("z=", Action::SuggestSpelling),
+    ("]c", Action::NextHunk),
+    ("[c", Action::PreviousHunk),
+    ("]m", Action::NextFunction),
+    ("[m", Action::PreviousFunction),
+    ("]n", Action::EndOfNode),
+    ("[n", Action::StartOfNode),
+    ("]s", Action::NextMisspelling),
+    ("[s", Action::PreviousMisspelling),
+    ("]x", Action::NextConflict),
+    ("[x", Action::PreviousConflict),
+    ("<Space>f", Action::OpenPicker),
+    ("<C-w>w", Action::NextWindow),
+    ("<C-w>h", Action::LeftWindow),
+    ("<C-w>l", Action::RightWindow),
+];
+
+/// The text objects that can follow `i` or `a`, by key.
+const TEXT_OBJECTS: &[(&str, &str)] = &[
+    ("w", "word"),
+    ("W", "WORD"),
+    ("p", "paragraph"),
+    ("b", "parentheses"),
+    ("[", "brackets"),
+    ("B", "braces"),
+    ("<", "angle brackets"),
+    ("\"", "double quotes"),
+    ("'", "single quotes"),
+    ("`", "backticks"),
+];
+
+/// The rows of the hint before its entries continue in another column.
+const HINT_ROWS: usize = 12;
+
+/// The keys that can follow the `pending` key sequence with what each does, for the hint shown
+/// while it waits. Empty when any key goes, as after `f` or `m`.
+pub fn continuations(keymap: &Keymap, pending: Pending) -> Vec<(String, String)> {
+    let mut hints: Vec<(String, String)> = match pending {
+        Pending::Operator(operator) => {
+            let [(_, normal), ..] = keymap.modes();
+            // named keys like `<Left>` mostly repeat the letter motions, which keeps the hint short
+            let mut hints: Vec<_> = normal
+                .into_iter()
+                .filter(|(key, _)| key.chars().count() == 1)
+                .filter_map(|(key, action)| {
+                    if Operator::from_action(&action) == Some(operator) {
+                        Some((key, "lines".to_string()))
+                    } else {
+                        is_operator_motion(&action).then(|| (key, action.to_string()))
+                    }
+                })
+                .collect();
+            if let Some(key) = operator.line_key() {
+                hints.push((key.to_string(), "lines".to_string()));
+            }
+            hints.push(("i".to_string(), "inner object".to_string()));
+            hints.push(("a".to_string(), "around object".to_string()));
+            hints
+        }
+        Pending::Prefix(operator, prefix) => {
+            let prefix = match prefix {
+                ' ' => "<Space>".to_string(),
+                WINDOW_PREFIX => "<C-w>".to_string(),
+                prefix => prefix.to_string(),
+            };
+            SEQUENCES
+                .iter()
+                .filter(|(_, action)| operator.is_none() || is_operator_motion(action))
+                .filter_map(|(keys, action)| {
+                    let key = keys.strip_prefix(prefix.as_str())?;
+                    Some((key.to_string(), action.to_string()))
+                })
+                .collect()
+        }
+        Pending::TextObject(..) => TEXT_OBJECTS
+            .iter()
+            .map(|(key, object)| (key.to_string(), object.to_string()))
+            .collect(),
+        _ => vec![],
+    };
+    hints.sort();
+    hints
+}
+
+/// Lays out `hints` in columns of at most [`HINT_ROWS`] rows, going down each column first.
+pub fn hint_text(hints: &[(String, String)]) -> String {
+    let entries: Vec<_> = hints
+        .iter()
+        .map(|(key, description)| format!("{key:<3} {description}"))
+        .collect();
+    let width = entries.iter().map(|entry| entry.chars().count()).max();
+    let width = width.unwrap_or_default() + 2;
+    let rows = entries.len().min(HINT_ROWS);
+
+    (0..rows)
+        .map(|row| {
+            let line: String = entries
+                .iter()
+                .skip(row)
+                .step_by(rows)
+                .map(|entry| format!("{entry:<width$}"))
+                .collect();
+            line.trim_end().to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether `action` works as the motion of an operator, as in `dw`, `ct)` or `yG`.
+fn is_operator_motion(action: &Action) -> bool {
+    action.word_motion().is_some()
+        || action.char_search().is_some()
+        || matches!(
+            action,
+            Action::MoveLeft
+                | Action::MoveDown
+                | Action::MoveUp
+                | Action::MoveRight
+                | Action::StartOfLine
+                | Action::FirstNonBlank
+                | Action::EndOfLine
+                | Action::MatchingBracket
+                | Action::NextOccurrence
+                | Action::PreviousOccurrence
+                | Action::StartOfBuffer
+                | Action::EndOfBuffer
+                | Action::RepeatFind
+                | Action::RepeatFindBackward
+                | Action::GoToMark
+                | Action::GoToMarkLine
+                | Action::GotoPrefix
+        )
+}
+
+/// The text of the `:help` buffer, listing the bindings of `keymap` by mode, the key sequences
+/// and the commands.
+pub fn text(keymap: &Keymap) -> String {
+    let mut lines = vec![
+        "fed help".to_string(),
+        String::new(),
+        "The keys below include the ones bound in the [keys] tables of the config file."
+            .to_string(),
+        "Go back to the other buffers with :bn, :bp or gt. Quitting never asks to write this buffer."
+            .to_string(),
+    ];
+    let mut section = |title: String, entries: Vec<(String, String)>| {
+        lines.push(String::new());
+        lines.push(title);
+        lines.push(String::new());
+        for (key, description) in entries {
+            lines.push(format!("  {key:<12}{description}").trim_end().to_string());
+        }
+    };
+
+    for (mode, bindings) in keymap.modes() {
+        let entries = bindings
+            .into_iter()
+            .map(|(key, action)| (key, action.to_string()))
+            .collect();
+        section(format!("{mode} mode"), entries);
+    }
+    let sequences = SEQUENCES
+        .iter()
+        .map(|(keys, action)| (keys.to_string(), action.to_string()))
+        .collect();
+    section("Key sequences".to_string(), sequences);
+    let commands = command_names()
+        .into_iter()
+        .map(|name| (name, String::new()))
+        .collect();
+    section("Commands".to_string(), commands);
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        let text = text(&Keymap::default());
+        let lines: Vec<_> = text.lines().collect();
+        assert!(lines.contains(&"  <C-f>       page_down"));
+        assert!(lines.contains(&"  zt          line_to_top"));
+        assert!(lines.contains(&"  :q[uit]"));
+        assert!(lines.contains(&"  :h[elp]"));
+
+        let normal = lines
+            .iter()
+            .position(|&line| line == "Normal mode")
+            .unwrap();
+        let insert = lines
+            .iter()
+            .position(|&line| line == "Insert mode")
+            .unwrap();
+        assert!(normal < insert);
+    }
+
+    #[test]
+    fn test_continuations() {
+        let keymap = Keymap::default();
+        let hints = continuations(&keymap, Pending::Operator(Operator::Delete));
+        let keys: Vec<_> = hints.iter().map(|(key, _)| key.as_str()).collect();
+        for key in ["d", "w", "$", "f", "g", "i", "a"] {
+            assert!(keys.contains(&key), "{key}");
+        }
+        assert!(!keys.contains(&"p"));
+        assert!(!keys.contains(&"<C-f>"));
+        assert!(!keys.contains(&"<Left>"));
+
+        let hints = continuations(&keymap, Pending::Prefix(Some(Operator::Delete), 'g'));
+        let keys: Vec<_> = hints.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["E", "e", "g"]);
+        let hints = continuations(&keymap, Pending::Prefix(None, ' '));
+        assert_eq!(hints, [("f".to_string(), "open_picker".to_string())]);
+        assert!(continuations(&keymap, Pending::SetMark).is_empty());
+
+        let hints: Vec<_> = (0..14).map(|i| (i.to_string(), "x".to_string())).collect();
+        let text = hint_text(&hints);
+        assert_eq!(text.lines().count(), HINT_ROWS);
+        assert_eq!(text.lines().next(), Some("0   x  12  x"));
+    }
+}
@@ -0,0 +1,112 @@
+//! Messages framed like the language server protocol, a `Content-Length` header and a JSON
+//! body, spoken to language servers and plugins over their stdin and stdout.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+use serde_jsonrc::Value;
+
+use crate::warn;
+
+/// Reads the messages from `reader` on a thread of its own, handing them over through the
+/// returned channel until the stream ends. `what` names the other end in the log.
+pub fn read_messages(reader: impl Read + Send + 'static, what: &'static str) -> Receiver<Value> {
+    let mut reader = BufReader::new(reader);
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || loop {
+        match read_message(&mut reader) {
+            Ok(Some(message)) => {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(err) => {
+                warn!("error reading from {}: {}", what, err);
+                break;
+            }
+        }
+    });
+    messages
+}
+
+/// Writes the messages sent through the returned channel to `writer` on a thread of its own, so
+/// a slow reader on the other end doesn't hold up the editor. The thread stops when writing
+/// fails, after which sending fails too. `what` names the other end in the log.
+pub fn write_messages(
+    mut writer: impl Write + Send + 'static,
+    what: &'static str,
+) -> Sender<Value> {
+    let (sender, messages) = mpsc::channel::<Value>();
+    thread::spawn(move || {
+        for message in messages {
+            if let Err(err) = write_message(&mut writer, &message) {
+                warn!("error writing to {}: {}", what, err);
+                break;
+            }
+        }
+    });
+    sender
+}
+
+/// Writes `message` framed by a `Content-Length` header, as language servers and plugins read it.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+/// Reads one message framed by its `Content-Length` header, or `None` at the end of the
+/// stream.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_jsonrc::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_jsonrc::json;
+
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let message = json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} });
+        let mut bytes = vec![];
+        write_message(&mut bytes, &message).unwrap();
+        write_message(&mut bytes, &json!({ "id": 1 })).unwrap();
+        assert!(bytes.starts_with(b"Content-Length: 52\r\n\r\n{"));
+
+        let mut reader = &bytes[..];
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(json!({ "id": 1 })));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+}
@@ -1,29 +1,243 @@
 use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
     fs::{File, OpenOptions},
     io::Write,
     sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+/// Where the log goes unless `--log-file` or the config says otherwise.
+pub const DEFAULT_FILE: &str = "/tmp/fed.log";
+
+/// How many of the latest lines `:messages` can show.
+const HISTORY: usize = 500;
+
+/// How much goes to the log file, each level including the ones before it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+/// The `[log]` table: the level, the file, and levels for single modules, like
+/// `modules = { syntax = "warn" }`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LogConfig {
+    pub level: Option<String>,
+    pub file: Option<String>,
+    #[serde(default)]
+    pub modules: HashMap<String, String>,
+}
+
+/// What to log and where, from the config and the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogSettings {
+    pub level: LogLevel,
+    pub file: String,
+    /// Levels of modules that log more or less than `level`, by the name of their file, with
+    /// `main` for the editor itself.
+    pub modules: HashMap<String, LogLevel>,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::default(),
+            file: DEFAULT_FILE.to_string(),
+            modules: HashMap::new(),
+        }
+    }
+}
+
+impl TryFrom<LogConfig> for LogSettings {
+    type Error = anyhow::Error;
+
+    fn try_from(config: LogConfig) -> anyhow::Result<Self> {
+        let level = |level: &str| {
+            level
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid log level: {level}"))
+        };
+        Ok(Self {
+            level: match config.level {
+                Some(name) => level(&name)?,
+                None => LogLevel::default(),
+            },
+            file: config.file.unwrap_or_else(|| DEFAULT_FILE.to_string()),
+            modules: config
+                .modules
+                .into_iter()
+                .map(|(module, name)| Ok((module, level(&name)?)))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+impl LogSettings {
+    /// The level for the module at `path`, as `module_path!()` gives it, looked up by its last
+    /// segment so `fed::core::syntax` goes by `syntax`.
+    fn level(&self, path: &str) -> LogLevel {
+        let module = match path.rsplit_once("::") {
+            Some((_, module)) => module,
+            None => "main",
+        };
+        self.modules.get(module).copied().unwrap_or(self.level)
+    }
+}
+
 #[derive(Debug)]
 pub struct Logger {
     file: Mutex<File>,
+    settings: LogSettings,
+    /// The latest lines at `info` and above, kept whatever the settings so `:messages` can show
+    /// what went wrong even with logging off.
+    history: Mutex<VecDeque<String>>,
 }
 
 impl Logger {
-    pub fn new(file_path: &str) -> Self {
+    pub fn new(settings: LogSettings) -> anyhow::Result<Self> {
         let file = OpenOptions::new()
             .create(true)
             .append(true)
-            .open(file_path)
-            .expect("Unable to open log file");
+            .open(&settings.file)
+            .map_err(|e| anyhow::anyhow!("error opening log file {}: {}", settings.file, e))?;
 
-        Logger {
+        Ok(Logger {
             file: Mutex::new(file),
+            settings,
+            history: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    /// Whether messages of `level` from the module at `path` go to the log.
+    pub fn enabled(&self, level: LogLevel, path: &str) -> bool {
+        level != LogLevel::Off && level <= self.settings.level(path)
+    }
+
+    /// Writes `message` with the time, its level and module when `level` is enabled for it.
+    pub fn log(&self, level: LogLevel, path: &str, message: fmt::Arguments) {
+        let enabled = self.enabled(level, path);
+        let kept = level != LogLevel::Off && level <= LogLevel::Info;
+        if !enabled && !kept {
+            return;
+        }
+
+        let name = level.to_string().to_uppercase();
+        let line = format!(
+            "{} {name:<5} {path}: {message}",
+            timestamp(SystemTime::now())
+        );
+        if kept {
+            let mut history = self.history.lock().unwrap();
+            if history.len() == HISTORY {
+                history.pop_front();
+            }
+            history.push_back(line.clone());
+        }
+        if enabled {
+            let mut file = self.file.lock().unwrap();
+            // a full disk shouldn't take the editor down with it
+            let _ = writeln!(file, "{line}");
         }
     }
 
-    pub fn log(&self, message: &str) {
-        let mut file = self.file.lock().unwrap();
-        writeln!(file, "{}", message).expect("Unable to write to log file");
+    /// The latest lines at `info` and above, oldest first.
+    pub fn history(&self) -> Vec<String> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// `time` in UTC as `2024-01-31T12:00:00.000Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // the civil date of a day count, from Howard Hinnant's `civil_from_days`
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        since.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_208_000_250);
+        assert_eq!(timestamp(time), "2024-02-29T12:00:00.250Z");
+        let time = UNIX_EPOCH + Duration::from_secs(951_868_799);
+        assert_eq!(timestamp(time), "2000-02-29T23:59:59.000Z");
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = LogSettings::try_from(LogConfig {
+            level: Some("debug".to_string()),
+            file: None,
+            modules: HashMap::from([
+                ("syntax".to_string(), "warn".to_string()),
+                ("main".to_string(), "trace".to_string()),
+            ]),
+        })
+        .unwrap();
+        assert_eq!(settings.file, DEFAULT_FILE);
+        assert_eq!(settings.level("fed::core::buffer"), LogLevel::Debug);
+        assert_eq!(settings.level("fed::core::syntax"), LogLevel::Warn);
+        assert_eq!(settings.level("fed"), LogLevel::Trace);
+
+        let config = LogConfig {
+            level: Some("loud".to_string()),
+            ..Default::default()
+        };
+        assert!(LogSettings::try_from(config).is_err());
+    }
+
+    #[test]
+    fn test_history() {
+        let file = std::env::temp_dir().join(format!("fed-test-{}.log", std::process::id()));
+        let logger = Logger::new(LogSettings {
+            level: LogLevel::Off,
+            file: file.to_string_lossy().to_string(),
+            modules: HashMap::new(),
+        })
+        .unwrap();
+        logger.log(LogLevel::Error, "fed", format_args!("E37: No write"));
+        logger.log(LogLevel::Debug, "fed::buffer", format_args!("noise"));
+
+        let history = logger.history();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].ends_with(" ERROR fed: E37: No write"));
+        // the file only gets what the settings let through
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+        std::fs::remove_file(file).unwrap();
     }
 }
@@ -0,0 +1,465 @@
+use std::{
+    collections::HashMap,
+    path::{self, Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::Receiver,
+};
+
+use serde_jsonrc::{json, Value};
+
+use crate::{
+    core::signs::{Sign, SignKind},
+    jsonrpc,
+};
+
+/// How serious a diagnostic is, numbered like the protocol does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+impl Severity {
+    /// The sign marking a line with a diagnostic of this severity, where more serious ones take
+    /// precedence.
+    pub fn sign(self) -> Sign {
+        let kind = match self {
+            Severity::Error => SignKind::Error,
+            Severity::Warning => SignKind::Warning,
+            Severity::Information | Severity::Hint => SignKind::Information,
+        };
+        Sign {
+            text: '●',
+            kind,
+            priority: 10 - self as u8,
+        }
+    }
+}
+
+/// A problem a language server reported in a document. Positions are zero-based lines and
+/// UTF-16 columns as the server sends them, with an exclusive end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn parse(value: &Value) -> Option<Self> {
+        let position = |value: &Value| {
+            Some((
+                value["character"].as_u64()? as usize,
+                value["line"].as_u64()? as usize,
+            ))
+        };
+        let severity = match value["severity"].as_u64() {
+            Some(2) => Severity::Warning,
+            Some(3) => Severity::Information,
+            Some(4) => Severity::Hint,
+            _ => Severity::Error,
+        };
+
+        Some(Diagnostic {
+            start: position(&value["range"]["start"])?,
+            end: position(&value["range"]["end"])?,
+            severity,
+            message: value["message"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// A position in a document, with a UTF-16 column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub uri: String,
+    pub position: (usize, usize),
+}
+
+impl Location {
+    /// Parses a `Location`, or a `LocationLink` as servers may send instead.
+    fn parse(value: &Value) -> Option<Self> {
+        let uri = value["uri"].as_str().or(value["targetUri"].as_str())?;
+        let range = match &value["targetSelectionRange"] {
+            Value::Null => &value["range"],
+            range => range,
+        };
+        Some(Location {
+            uri: uri.to_string(),
+            position: (
+                range["start"]["character"].as_u64()? as usize,
+                range["start"]["line"].as_u64()? as usize,
+            ),
+        })
+    }
+}
+
+/// What a language server sent that the editor cares about.
+#[derive(Debug, PartialEq)]
+pub enum Incoming {
+    /// The full set of diagnostics for the document at a uri, replacing earlier ones.
+    Diagnostics(String, Vec<Diagnostic>),
+    /// Where the symbol asked about with [`Client::definition`] is defined, if anywhere.
+    Definition(Option<Location>),
+    /// The text describing the symbol asked about with [`Client::hover`], if any.
+    Hover(Option<String>),
+}
+
+/// The requests waiting for a response, to tell what the response is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    Initialize,
+    Definition,
+    Hover,
+}
+
+/// A language server running as a child process, spoken to over its stdin and stdout.
+///
+/// Its output is read on a separate thread and handed over through a channel, so the editor
+/// picks up what arrived with [`Client::poll`] without ever blocking on the server.
+#[derive(Debug)]
+pub struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    next_id: u64,
+    requests: HashMap<u64, Request>,
+    /// Messages held back until the server answers `initialize`, which must come first.
+    queued: Option<Vec<Value>>,
+}
+
+impl Client {
+    /// Starts the server with `command`, a program followed by its arguments, for the project
+    /// at `root`.
+    pub fn start(command: &str, root: &Path) -> anyhow::Result<Self> {
+        let mut args = command.split_whitespace();
+        let program = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Empty language server command"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| anyhow::anyhow!("Can't start language server {program}: {err}"))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let messages = jsonrpc::read_messages(stdout, "language server");
+
+        let mut client = Client {
+            child,
+            stdin,
+            messages,
+            next_id: 0,
+            requests: HashMap::new(),
+            queued: None,
+        };
+        client.request(
+            Request::Initialize,
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": uri(root),
+                "capabilities": {
+                    "textDocument": {
+                        "synchronization": { "dynamicRegistration": false },
+                        "publishDiagnostics": { "relatedInformation": false },
+                        "definition": { "linkSupport": true },
+                        "hover": { "contentFormat": ["plaintext", "markdown"] },
+                    },
+                },
+            }),
+        )?;
+        client.queued = Some(vec![]);
+
+        Ok(client)
+    }
+
+    fn send(&mut self, message: Value) -> anyhow::Result<()> {
+        match &mut self.queued {
+            Some(queued) => queued.push(message),
+            None => jsonrpc::write_message(&mut self.stdin, &message)?,
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, request: Request, method: &str, params: Value) -> anyhow::Result<()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.requests.insert(id, request);
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+    }
+
+    /// Whether responses are still expected, so the editor should check back soon.
+    pub fn is_waiting(&self) -> bool {
+        self.requests
+            .values()
+            .any(|&request| request != Request::Initialize)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> anyhow::Result<()> {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    pub fn did_open(
+        &mut self,
+        uri: &str,
+        language: &str,
+        version: u64,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": language,
+                    "version": version,
+                    "text": text,
+                },
+            }),
+        )
+    }
+
+    /// Sends the whole new text of the document, which every server accepts.
+    pub fn did_change(&mut self, uri: &str, version: u64, text: &str) -> anyhow::Result<()> {
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": version },
+                "contentChanges": [{ "text": text }],
+            }),
+        )
+    }
+
+    /// Asks where the symbol at `position`, with a UTF-16 column, is defined. The answer comes
+    /// back from [`Client::poll`] as [`Incoming::Definition`].
+    pub fn definition(&mut self, uri: &str, position: (usize, usize)) -> anyhow::Result<()> {
+        let params = position_params(uri, position);
+        self.request(Request::Definition, "textDocument/definition", params)
+    }
+
+    /// Asks for a description of the symbol at `position`, which comes back from
+    /// [`Client::poll`] as [`Incoming::Hover`].
+    pub fn hover(&mut self, uri: &str, position: (usize, usize)) -> anyhow::Result<()> {
+        let params = position_params(uri, position);
+        self.request(Request::Hover, "textDocument/hover", params)
+    }
+
+    /// Handles the messages that arrived since the last call, returning the ones for the
+    /// editor. Requests from the server get an empty reply, since the editor has nothing to
+    /// offer them.
+    pub fn poll(&mut self) -> anyhow::Result<Vec<Incoming>> {
+        let mut incoming = vec![];
+
+        while let Ok(message) = self.messages.try_recv() {
+            let method = message["method"].as_str();
+            match (method, message.get("id")) {
+                (None, Some(id)) => {
+                    let request = id.as_u64().and_then(|id| self.requests.remove(&id));
+                    let result = &message["result"];
+                    match request {
+                        Some(Request::Initialize) => {
+                            // initialized, so the messages held back can go out
+                            let queued = self.queued.take().unwrap_or_default();
+                            self.notify("initialized", json!({}))?;
+                            for message in queued {
+                                self.send(message)?;
+                            }
+                        }
+                        Some(Request::Definition) => {
+                            let location = match result {
+                                Value::Array(locations) => locations.first(),
+                                location => Some(location),
+                            };
+                            incoming.push(Incoming::Definition(location.and_then(Location::parse)));
+                        }
+                        Some(Request::Hover) => {
+                            let text = hover_text(&result["contents"]);
+                            incoming.push(Incoming::Hover(Some(text).filter(|t| !t.is_empty())));
+                        }
+                        None => {}
+                    }
+                }
+                (Some("textDocument/publishDiagnostics"), _) => {
+                    let params = &message["params"];
+                    let Some(uri) = params["uri"].as_str() else {
+                        continue;
+                    };
+                    let diagnostics = params["diagnostics"]
+                        .as_array()
+                        .map(|diagnostics| diagnostics.iter().filter_map(Diagnostic::parse))
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                    incoming.push(Incoming::Diagnostics(uri.to_string(), diagnostics));
+                }
+                (Some(_), Some(id)) => {
+                    let reply = json!({ "jsonrpc": "2.0", "id": id, "result": null });
+                    self.send(reply)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(incoming)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.notify("exit", Value::Null);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The `file://` uri of `path`, escaping the chars that can't appear in one as is.
+pub fn uri(path: &Path) -> String {
+    let path = path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = "file://".to_string();
+    for b in path.to_string_lossy().bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{b:02X}")),
+        }
+    }
+    uri
+}
+
+/// The path of a `file://` uri, undoing its escapes.
+pub fn path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let escaped = (b == b'%')
+            .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(b) => {
+                bytes.push(b);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8_lossy(&bytes).to_string()))
+}
+
+/// The plain text of hover contents, which may be markup, a marked string or a list of them.
+/// Markdown code fences are left out, keeping the code in them.
+fn hover_text(contents: &Value) -> String {
+    let text = match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(hover_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        contents => contents["value"].as_str().unwrap_or_default().to_string(),
+    };
+
+    text.lines()
+        .filter(|line| !line.starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+fn position_params(uri: &str, (x, y): (usize, usize)) -> Value {
+    json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": y, "character": x },
+    })
+}
+
+/// Converts a char index in `line` to the UTF-16 column language servers count in.
+pub fn utf16_column(line: &str, x: usize) -> usize {
+    line.chars().take(x).map(char::len_utf16).sum()
+}
+
+/// Converts a UTF-16 column, which language servers count in, to a char index in `line`.
+pub fn char_column(line: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.chars().enumerate() {
+        if units >= utf16 {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic() {
+        let diagnostic = Diagnostic::parse(&json!({
+            "range": {
+                "start": { "line": 2, "character": 4 },
+                "end": { "line": 2, "character": 9 },
+            },
+            "severity": 2,
+            "message": "unused variable",
+        }));
+        assert_eq!(
+            diagnostic,
+            Some(Diagnostic {
+                start: (4, 2),
+                end: (9, 2),
+                severity: Severity::Warning,
+                message: "unused variable".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_responses() {
+        let link = json!([{
+            "targetUri": "file:///src/lib.rs",
+            "targetRange": { "start": { "line": 1, "character": 0 } },
+            "targetSelectionRange": { "start": { "line": 3, "character": 7 } },
+        }]);
+        assert_eq!(
+            Location::parse(&link[0]),
+            Some(Location {
+                uri: "file:///src/lib.rs".to_string(),
+                position: (7, 3),
+            })
+        );
+
+        let markup = json!({ "kind": "markdown", "value": "```rust\nfn main()\n```\n\nDocs" });
+        assert_eq!(hover_text(&markup), "fn main()\n\nDocs");
+        let marked = json!([{ "language": "rust", "value": "u8" }, "Byte"]);
+        assert_eq!(hover_text(&marked), "u8\n\nByte");
+    }
+
+    #[test]
+    fn test_columns() {
+        assert_eq!(uri(Path::new("/tmp/a b.rs")), "file:///tmp/a%20b.rs");
+        assert_eq!(
+            path("file:///tmp/a%20b.rs"),
+            Some(PathBuf::from("/tmp/a b.rs"))
+        );
+        assert_eq!(utf16_column("a😀b", 2), 3);
+        assert_eq!(char_column("a😀b", 3), 2);
+        assert_eq!(char_column("héllo", 2), 2);
+        assert_eq!(char_column("ab", 9), 2);
+    }
+}
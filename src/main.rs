@@ -1,82 +1,207 @@
 use std::{
-    io::{stdout, Write},
-    panic,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    io::{self, stdout, IsTerminal, Write},
+    iter, panic,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
 };
 
-use command::get_command;
 use crossterm::{
-    cursor::{self, position, SetCursorStyle},
-    event::{
-        self, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, MouseButton,
-        MouseEvent, MouseEventKind,
-    },
-    style::{Color, PrintStyledContent, Stylize},
-    terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand, QueueableCommand,
+    cursor,
+    event::{self, poll, read, Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind},
+    style::{Color, Stylize},
+    terminal::{self, ClearType},
+    QueueableCommand,
 };
 use log::Logger;
 use once_cell::sync::OnceCell;
-use theme::Theme;
-use utils::{darken, hex_to_crossterm_color};
+use regex::Regex;
+use serde_jsonrc::json;
+use tui::theme::Theme;
+use tui::utils::{blend, darken, display_width, hex_to_crossterm_color};
 
 use crate::{
-    command::clear_commandline,
-    config::Config,
-    syntax::{highlight, Viewport},
+    args::Args,
+    command::{
+        clear_commandline, draw_commandline, draw_message, split_commands, Command, CommandLine,
+    },
+    config::{Config, LanguageConfig},
+    core::{
+        abbrev,
+        action::Action,
+        brackets::{self, AutoPair},
+        buffer::Buffer,
+        comment,
+        conflict::{Region, Resolution},
+        encoding::Encoding,
+        find::CharSearch,
+        increment,
+        indent::{self, Indentation},
+        mode::Mode,
+        operator::{text_object, MotionKind, Operator, Pending, WINDOW_PREFIX},
+        register::Register,
+        snippet::{self, Snippet},
+        spell::{self, Dictionary},
+        structure,
+        substitute::Substitute,
+        syntax::{update_highlights, ChunkType, Language, Selection, Viewport},
+        word::{self, WordMotion},
+    },
+    diff::Diff,
+    gitdiff::Hunk,
+    lsp::{Incoming, Severity},
+    make::{Make, Problem},
+    plugin::Plugin,
+    quickfix::{grep, Quickfix},
+    swap::SwapDir,
+    tui::{
+        background::{self, Background, LateAnswer},
+        colors::ColorSupport,
+        highlight::{self, Overlays, Styles},
+        keymap::Key,
+        pager::Pager,
+        picker::{list_files, Picker},
+        popup::Popup,
+        renderer::{Grid, Renderer},
+        screen::Screen,
+        scrollbar::{self, Scroll},
+        statusline,
+        tabline::{self, Tab},
+        tty::{self, Terminal},
+    },
+    watcher::Watcher,
 };
 
+mod args;
+mod blame;
+mod clipboard;
 mod command;
 mod config;
+mod core;
+mod crash;
+mod diff;
 mod error;
+mod format;
+mod gitdiff;
+mod help;
+mod jsonrpc;
 mod log;
-mod syntax;
-mod theme;
-mod utils;
+mod lsp;
+mod make;
+mod plugin;
+mod quickfix;
+mod shell;
+mod swap;
+mod tui;
+mod watcher;
+mod workdir;
 
 static LOGGER: OnceCell<Logger> = OnceCell::new();
 
+/// Replaying a macro stops queueing events past this many, which a macro that replays itself
+/// reaches quickly.
+const MAX_QUEUED_EVENTS: usize = 100_000;
+
+/// How long to wait for input before checking whether the config or theme changed.
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait for input while a language server owes an answer, like to `gd` or `K`.
+const LSP_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long to wait for input while plugins run, so what they send is handled soon after.
+const PLUGIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait for input while `:make` runs, so its result shows soon after the build ends.
+const MAKE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long typing has to pause before plugins are sent the changed buffer, so they don't get
+/// the whole text after every key.
+const PLUGIN_BUFFER_DELAY: Duration = Duration::from_millis(200);
+
+/// How long a key sequence waits for its next key before the keys that can follow it are shown.
+const HINT_DELAY: Duration = Duration::from_millis(500);
+
+/// How many of the latest actions a crash report lists.
+const RECENT_ACTIONS: usize = 20;
+
+/// The name of the `:help` buffer.
+const HELP: &str = "[Help]";
+
+/// Writes to the log at `level` when it's enabled for the calling module, formatting the
+/// message only then. Does nothing before the logger is set up, as in tests.
 #[macro_export]
-macro_rules! log {
-    ($($arg:tt)*) => {
-        {
-            let log_message = format!($($arg)*);
-            $crate::LOGGER.get().expect("Logger not initialized").log(&log_message);
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)*) => {
+        if let Some(logger) = $crate::LOGGER.get() {
+            logger.log($level, module_path!(), format_args!($($arg)*))
         }
     };
 }
 
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log_at!($crate::log::LogLevel::Error, $($arg)*)
+    };
+}
+
 #[macro_export]
 macro_rules! warn {
     ($($arg:tt)*) => {
-        {
-            let log_message = format!($($arg)*);
-            $crate::LOGGER.get().expect("Logger not initialized").log(&log_message);
-        }
+        $crate::log_at!($crate::log::LogLevel::Warn, $($arg)*)
     };
 }
 
-#[derive(Default, Debug)]
-enum Mode {
-    #[default]
-    Normal,
-    Insert,
-    Command,
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log_at!($crate::log::LogLevel::Info, $($arg)*)
+    };
 }
 
-impl Mode {
-    fn is_command(&self) -> bool {
-        matches!(self, Mode::Command)
-    }
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log_at!($crate::log::LogLevel::Debug, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::log_at!($crate::log::LogLevel::Trace, $($arg)*)
+    };
+}
+
+#[derive(Debug)]
+enum Message {
+    Info(String),
+    Error(String),
 }
 
 #[allow(unused)]
 #[derive(Default)]
 struct Editor {
     theme: Theme,
+    /// The styles of syntax under `theme`, resolved whenever it changes.
+    styles: Styles,
+    /// Whether the terminal's background is light or dark, which picks between the light and
+    /// the dark theme.
+    background: Background,
+    /// Asks the terminal for its background once it's set up, to pick the theme by it.
+    query_background: bool,
+    /// The answer to the background query that may still come in after it gave up waiting.
+    late_answer: LateAnswer,
     config: Config,
+    /// The config file, read again whenever it changes.
+    config_path: PathBuf,
+    screen: Box<dyn Renderer>,
     mode: Mode,
-    buffer: Vec<String>,
-    name: String,
+    buffer: Buffer,
+    buffers: Vec<Buffer>,
+    current: usize,
     width: usize,
     height: usize,
     cx: usize,
@@ -85,60 +210,203 @@ struct Editor {
     vtop: usize,
     vwidth: usize,
     vheight: usize,
-    waiting_key: Option<char>,
+    /// The first column shown when lines don't wrap, scrolled to keep the cursor in view.
+    hscroll: usize,
+    pending: Pending,
+    /// The snippet being filled in, until its last tab stop or leaving insert mode.
+    snippet: Option<snippet::Session>,
+    /// Whether `Ctrl-V` was pressed in insert mode, so the next key goes in as it is.
+    literal: bool,
+    /// The chars typed over in replace mode, to put back on backspace, `None` where typing
+    /// went past the end of the line.
+    replaced: Vec<Option<char>>,
+    /// The key sequence that is waiting and when it started to, to show its hint and time it out.
+    pending_since: Option<(Pending, Instant)>,
+    anchor: Option<(usize, usize)>,
+    register: Register,
+    /// The register picked with `"` for the next yank, delete or put.
+    register_name: Option<char>,
+    /// What `"+y` copied last, which `"+p` puts when the system clipboard can't be read.
+    clipboard: Option<Register>,
+    message: Option<Message>,
+    /// The command line typed in command mode.
+    command_line: CommandLine,
+    picker: Option<Picker>,
+    quickfix: Quickfix,
+    /// The `:make` running in the background.
+    make: Option<Make>,
+    /// The errors and warnings of the last `:make`, marked in the gutter of their files.
+    problems: Vec<Problem>,
+    /// The directory of the editor, kept while the current buffer has one of its own from `:lcd`.
+    global_dir: Option<PathBuf>,
+    /// The directory before the last `:cd` or `:lcd`, for `:cd -`.
+    previous_dir: Option<PathBuf>,
+    /// Hover information from the language server, shown over the buffer until the next key.
+    popup: Option<Popup>,
+    /// The dictionary `spell` checks with, loaded the first time it's on.
+    dictionary: Option<Dictionary>,
+    /// The words `z=` offered in its popup, by their number less one.
+    suggestions: Vec<String>,
+    /// Whether `:blame` annotations are shown after the lines.
+    blame: bool,
+    /// The two buffers shown side by side by `--diff` or `:diffsplit`.
+    diff: Option<Diff>,
+    /// The output of the last `:!cmd` or `:messages`, until it is closed.
+    pager: Option<Pager>,
+    /// The text of the message last written to the log, so each one is logged once for
+    /// `:messages`.
+    logged_message: Option<String>,
+    /// The latest actions run, for the crash report.
+    recent_actions: VecDeque<String>,
+    /// Language servers by language, `None` for ones that failed to start so they aren't tried
+    /// again.
+    lsp: HashMap<Language, Option<lsp::Client>>,
+    plugins: Vec<Plugin>,
+    /// The abbreviations added with `:iabbrev`, to add again after the config is reloaded.
+    abbreviations: HashMap<String, String>,
+    /// What each plugin set to show on the statusline, by plugin name.
+    plugin_status: BTreeMap<String, String>,
+    /// Events waiting to be handled before reading the terminal, queued by macro replays.
+    input: VecDeque<Event>,
+    /// The register being recorded into and the keys typed so far.
+    recording: Option<(char, Vec<Event>)>,
+    macros: HashMap<char, Vec<Event>>,
+    /// The keys of the normal mode command being typed, with the buffer and revision it started
+    /// on, which become the last change if the command edits that buffer.
+    change: Option<(usize, u64, Vec<Event>)>,
+    /// The keys of the last command that changed the buffer, an insert counting as one command
+    /// until it leaves insert mode, which `.` repeats.
+    last_change: Vec<Event>,
+    last_macro: Option<char>,
+    /// The last `f`, `t`, `F` or `T` search and its char, repeated by `;` and `,`.
+    last_find: Option<(CharSearch, char)>,
+    /// The identifier `*` or `#` last searched for, whose matches the statusline counts.
+    last_search: Option<String>,
+    /// The count typed before a command, like the `3` in `3j`.
+    count: Option<usize>,
+    /// Watches the config and theme files to apply their changes live.
+    watcher: Watcher,
+    swap: SwapDir,
+    /// When the last key or mouse event came in, to tell how long the editor has been idle.
+    last_input: Option<Instant>,
+    /// Set when buffers were autosaved since the last input, to show it on the statusline.
+    autosaved: bool,
     pending_redraw: bool,
     quit: bool,
 }
 
 impl Editor {
-    pub fn new(file: Option<String>) -> anyhow::Result<Self> {
-        let (width, height) = terminal::size()?;
-
-        log!("terminal size = {}x{}", width, height);
-
-        let (buffer, name) = match file {
-            Some(file) => {
-                log!("opening file: {}", file);
-                let buffer = std::fs::read_to_string(&file)?;
-                (buffer.lines().map(|s| s.to_string()).collect(), file)
+    pub fn new(args: &Args, config_path: PathBuf, config: Config) -> anyhow::Result<Self> {
+        // batch mode draws nothing, so any size does
+        let (width, height) = match args.batch {
+            Some(_) => (80, 24),
+            None => terminal::size()?,
+        };
+        let (width, height) = (width as usize, height as usize);
+        let screen: Box<dyn Renderer> = match args.batch {
+            Some(_) => Box::new(Grid::new(width, height)),
+            None => {
+                let colors = config.colors.unwrap_or_else(ColorSupport::detect);
+                debug!("terminal colors = {:?}", colors);
+                Box::new(Screen::new(width, height).with_colors(colors))
             }
-            None => (vec![String::new()], "No Name".to_string()),
         };
 
-        let vleft = 8;
-        let config = Config::read()?;
-
-        log!("config = {:#?}", config);
+        debug!("terminal size = {}x{}", width, height);
 
-        let theme = match &config.theme {
-            Some(theme) => {
-                if theme.ends_with(".tmTheme") {
-                    Theme::load_tm(theme).unwrap()
-                } else {
-                    Theme::load_vscode(theme).unwrap()
+        let vheight = height - 2;
+        let swap = SwapDir::new(SwapDir::default_dir()?);
+        let mut swap_message = None;
+        let mut buffers = args
+            .files()
+            .into_iter()
+            .map(|(file, position)| {
+                let (mut buffer, message) = open_buffer(&swap, &file, args.recover)?;
+                swap_message = swap_message.take().or(message);
+                buffer.readonly = args.readonly;
+                // starts with the requested line in the middle of the screen
+                if let Some((line, column)) = position {
+                    let line = line.min(buffer.len() - 1);
+                    let column = column.min(buffer.line_len(line).saturating_sub(1));
+                    let vtop = line.saturating_sub(vheight / 2);
+                    buffer.view = (column, line - vtop, vtop);
                 }
+                Ok(buffer)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // piped text, like `git diff | fed`, opens in a scratch buffer ahead of the files while
+        // crossterm reads keys from /dev/tty instead
+        let stdin = io::stdin();
+        if !stdin.is_terminal() && (args.batch.is_none() || buffers.is_empty()) {
+            let buffer = Buffer::from_reader(stdin.lock())?;
+            if buffer.len_chars() > 0 {
+                buffers.insert(0, buffer);
             }
-            None => Theme::default(),
+        }
+        if buffers.is_empty() {
+            buffers.push(Buffer::new());
+        }
+        // `--diff` compares the two files, after the piped text if there is any
+        let diff = (args.diff && buffers.len() >= 2)
+            .then(|| Diff::new(buffers.len() - 2, buffers.len() - 1));
+        let current = diff.as_ref().map_or(0, |diff| diff.buffers[0]);
+        // the active buffer lives in `buffer`, leaving a placeholder in its slot
+        let buffer = std::mem::take(&mut buffers[current]);
+
+        debug!("config = {:#?}", config);
+
+        let background = config
+            .background
+            .or_else(background::from_env)
+            .unwrap_or_default();
+        // the terminal can only be asked once it's set up, and only needs to be when the theme
+        // depends on the answer
+        let query_background = args.batch.is_none()
+            && args.theme.is_none()
+            && config.background.is_none()
+            && (config.light_theme.is_some() || config.dark_theme.is_some());
+
+        // a broken theme setting shouldn't keep the editor from starting
+        let theme = args.theme.as_ref().or(config.theme_for(background));
+        let (theme, message) = match theme.map(|name| Theme::load(name)) {
+            Some(Ok(theme)) => (theme, None),
+            Some(Err(err)) => (Theme::default(), Some(Message::Error(err.to_string()))),
+            None => (Theme::default(), swap_message),
         };
 
-        Ok(Self {
+        let (cx, cy, vtop) = buffer.view;
+        let mut editor = Self {
             mode: Mode::Normal,
+            styles: Styles::new(&theme),
             theme,
+            background,
+            query_background,
             buffer,
-            name,
-            width: width as usize,
-            height: height as usize,
-            cx: 0, // cursor x position on the viewport
-            cy: 0, // cursor y position on the viewport
-            vleft,
-            vtop: 0,
-            vwidth: width as usize - vleft,
-            vheight: height as usize - 2,
+            buffers,
+            current,
+            diff,
+            screen,
+            width,
+            height,
+            cx, // cursor x position on the viewport
+            cy, // cursor y position on the viewport
+            vtop,
+            vheight,
             config,
+            config_path,
+            message,
+            swap,
             ..Default::default()
-        })
+        };
+        editor.update_height();
+        editor.update_gutter();
+        editor.watch_files();
+        editor.cd_to_root();
+
+        Ok(editor)
     }
 
+    #[allow(unused)]
     pub fn line_number(&self) -> usize {
         self.vtop + self.cy + 1
     }
@@ -147,27 +415,92 @@ impl Editor {
         self.height - 1
     }
 
-    pub fn clear(&self) -> anyhow::Result<()> {
+    pub fn clear(&mut self) -> anyhow::Result<()> {
         stdout().queue(terminal::Clear(ClearType::All))?;
         stdout().queue(cursor::MoveTo(0, 0))?;
+        self.screen.invalidate();
         Ok(())
     }
 
     pub fn run(&mut self) -> anyhow::Result<()> {
-        stdout().execute(EnterAlternateScreen)?;
-        terminal::enable_raw_mode()?;
+        let _terminal = Terminal::enter()?;
+        if self.query_background {
+            if let Some(background) = background::query(&mut self.input, &mut self.late_answer)? {
+                if let Err(err) = self.set_background(background) {
+                    self.message = Some(Message::Error(err.to_string()));
+                }
+            }
+        }
+        self.start_plugins();
         self.clear()?;
         self.draw(true)?;
 
-        stdout().execute(EnableMouseCapture)?;
+        self.last_input = Some(Instant::now());
         loop {
-            let ev = read()?;
+            // checks for config and theme changes while waiting for input, and sooner for the
+            // answers of language servers, for key sequences to time out and for builds to end
+            let waiting = self
+                .lsp
+                .values()
+                .flatten()
+                .any(|client| client.is_waiting())
+                || self.pending != Pending::None;
+            let interval = if waiting {
+                LSP_INTERVAL
+            } else if !self.plugins.is_empty() {
+                PLUGIN_INTERVAL
+            } else if self.make.is_some() {
+                MAKE_INTERVAL
+            } else {
+                RELOAD_INTERVAL
+            };
+            if self.input.is_empty() && !poll(interval)? {
+                let saved = self.autosave();
+                self.update_swap_files();
+                let diagnostics = self.update_lsp();
+                let git = self.update_git() | self.update_blame();
+                let pending = self.update_pending();
+                let plugins = self.update_plugins();
+                let make = self.update_make();
+                let files = self.check_files();
+                if self.reload_changed()
+                    || files
+                    || saved
+                    || diagnostics
+                    || git
+                    || pending
+                    || plugins
+                    || make
+                {
+                    self.draw(true)?;
+                }
+                continue;
+            }
+
+            let ev = self.next_event()?;
+            if self.late_answer.swallow(&ev) {
+                continue;
+            }
+            let mode = self.mode.name();
+            if let Event::Key(event) = &ev {
+                let key = Key::from_event(event).to_string();
+                self.notify_plugins(
+                    plugin::Event::KeyPressed,
+                    json!({ "key": key, "mode": mode }),
+                );
+            }
             match self.handle_input(ev.clone()) {
                 Ok(redraw) => {
+                    if self.mode.name() != mode {
+                        let mode = self.mode.name();
+                        self.notify_plugins(plugin::Event::ModeChanged, json!({ "mode": mode }));
+                    }
+                    let redraw = self.show_diagnostic() || self.update_pending() || redraw;
+                    let redraw = self.update_plugins() || redraw;
                     self.draw(redraw)?;
                 }
                 Err(err) => {
-                    log!("Error: {}", err);
+                    error!("error handling {:?}: {}", ev, err);
                     break;
                 }
             }
@@ -176,30 +509,102 @@ impl Editor {
                 break;
             }
         }
-        stdout().execute(DisableMouseCapture)?;
+        self.remove_swap_files();
+        Ok(())
+    }
+
+    /// Runs the commands of `script` one after the other without the screen, printing their
+    /// messages and output, until one fails or quits. Changes left unwritten are dropped.
+    pub fn run_batch(&mut self, script: &str) -> anyhow::Result<()> {
+        // a leftover swap file is worth a warning, but the buffer is there to work on
+        if let Some(Message::Info(text) | Message::Error(text)) = self.message.take() {
+            eprintln!("{text}");
+        }
 
-        terminal::disable_raw_mode()?;
-        stdout().execute(LeaveAlternateScreen)?;
+        for command in split_commands(script) {
+            self.run_command(&command)
+                .map_err(|err| anyhow::anyhow!("{command}: {err}"))?;
+            if let Some(pager) = self.pager.take() {
+                for line in pager.lines() {
+                    println!("{line}");
+                }
+            }
+            match self.message.take() {
+                Some(Message::Info(text)) => println!("{text}"),
+                Some(Message::Error(text)) => anyhow::bail!("{command}: {text}"),
+                None => {}
+            }
+            if self.quit {
+                break;
+            }
+        }
         Ok(())
     }
 
     pub fn draw(&mut self, redraw: bool) -> anyhow::Result<()> {
+        // opening a second buffer brings up the tabline, taking a row from the buffer
+        let redraw = self.update_height() || redraw;
+        let redraw = self.update_diff() || redraw;
+        // moves that don't redraw on their own may still need to scroll for the margin
+        let redraw = self.scroll_to_margin() || redraw;
+        let redraw = self.scroll_to_column() || redraw;
+        self.log_message();
         if redraw || self.pending_redraw {
             self.pending_redraw = false;
 
             // log!("draw");
-            // TODO: add diff detection for all changes
+            self.update_gutter();
             self.adjust_cursor();
 
-            self.draw_buffer()?;
+            // the buffer and the windows over it are drawn below the tabline
+            self.screen.set_top(self.tabline_rows());
+            if self.diff_side().is_some() {
+                self.draw_diff()?;
+            } else {
+                self.draw_buffer()?;
+                self.draw_blame()?;
+                self.draw_gutter()?;
+                self.draw_scrollbar();
+            }
+
+            if let Some(picker) = &self.picker {
+                picker.draw(&mut *self.screen, &self.theme, self.width, self.vheight)?;
+            }
+            if self.quickfix.open {
+                self.quickfix
+                    .draw(&mut *self.screen, &self.theme, self.width, self.vheight)?;
+            }
+            if let Some(pager) = &self.pager {
+                pager.draw(&mut *self.screen, &self.theme, self.width, self.vheight)?;
+            }
+            if let Some(popup) = &self.popup {
+                let (x, y) = self.cursor_position();
+                popup.draw(
+                    &mut *self.screen,
+                    &self.theme,
+                    (self.vleft + x, y),
+                    self.width,
+                    self.vheight,
+                )?;
+            }
+            self.screen.set_top(0);
+            self.draw_tabline()?;
             self.draw_statusline()?;
-            self.draw_gutter()?;
 
             if self.mode.is_command() {
-                self.handle_command()?;
+                draw_commandline(self)?;
             } else {
-                clear_commandline(&self)?;
+                clear_commandline(self)?;
+                draw_message(self)?;
             }
+
+            let transparent = if self.config.transparent_background {
+                Some(hex_to_crossterm_color(&self.theme.background)?)
+            } else {
+                None
+            };
+            self.screen.set_transparent(transparent);
+            self.screen.flush()?;
         }
 
         self.draw_cursor()?;
@@ -208,57 +613,211 @@ impl Editor {
         Ok(())
     }
 
+    /// Draws the open buffers on the first row while the tabline is shown.
+    fn draw_tabline(&mut self) -> anyhow::Result<()> {
+        if self.tabline_rows() == 0 {
+            return Ok(());
+        }
+        let tabs = self.tabs();
+        tabline::draw(
+            &mut *self.screen,
+            &self.theme,
+            &tabs,
+            self.current,
+            self.width,
+        )
+    }
+
     pub fn draw_statusline(&mut self) -> anyhow::Result<()> {
-        let y = self.height as u16 - 2;
-        let line = " ".repeat(self.width);
-        let mode = format!(" {:?} ", self.mode).to_uppercase();
-        let pos = format!(" {}:{} ", self.by(), self.cx);
-        let filename = format!(" {} ", self.name);
-
-        let bar_bg = Color::Rgb {
-            r: 68,
-            g: 70,
-            b: 88,
-        };
-        stdout().queue(cursor::MoveTo(0, y))?;
-        stdout().queue(PrintStyledContent(line.on(bar_bg)))?;
-
-        // mode
-        let mode_fg = Color::Rgb { r: 0, g: 0, b: 0 };
-        let mode_bg = Color::Rgb {
-            r: 178,
-            g: 145,
-            b: 236,
-        };
-        stdout().queue(cursor::MoveTo(0, y))?;
-        stdout().queue(PrintStyledContent(mode.bold().with(mode_fg).on(mode_bg)))?;
-        stdout().queue(PrintStyledContent("".with(mode_bg).on(bar_bg)))?;
-
-        // filename
-        let name_fg = Color::White;
-        stdout().queue(PrintStyledContent(filename.with(name_fg).on(bar_bg)))?;
-
-        // position
-        let pos_fg = Color::Rgb { r: 0, g: 0, b: 0 };
-        let pos_bg = Color::Rgb {
-            r: 178,
-            g: 145,
-            b: 236,
-        };
-        stdout().queue(cursor::MoveTo(self.width as u16 - pos.len() as u16 - 1, y))?;
-        stdout().queue(PrintStyledContent("".with(pos_bg).on(bar_bg)))?;
-        stdout().queue(PrintStyledContent(pos.bold().with(pos_fg).on(pos_bg)))?;
+        let values = self.statusline_values();
+        let value = |name: &str| values.get(name).cloned().unwrap_or_default();
+        self.config
+            .statusline
+            .draw(&mut *self.screen, self.height - 2, self.width, value)
+    }
+
+    /// The values the statusline segments show, by field name. Only the fields the segments use
+    /// are filled in, since some, like `search`, go through the whole buffer.
+    fn statusline_values(&mut self) -> HashMap<&'static str, String> {
+        let fields = self.config.statusline.fields();
+        let search = if fields.contains(&"search") {
+            self.search_count()
+        } else {
+            None
+        };
+        let buffer = &self.buffer;
+        let mode = format!("{:?}", self.mode).to_uppercase();
+        let (line, column) = (self.by() + 1, self.bx() + 1);
+        let (autosaved, recording) = (self.autosaved, self.recording.as_ref());
+        let plugin_status: Vec<_> = self.plugin_status.values().cloned().collect();
+        let flag = |on: bool, text: &str| if on { text.to_string() } else { String::new() };
+        let value = |name: &str| match name {
+            "mode" => mode.clone(),
+            "file" => buffer.name.clone(),
+            "dirty" => flag(buffer.unsaved(), "[+]"),
+            "readonly" => flag(buffer.readonly, "[RO]"),
+            "noeol" => flag(buffer.missing_newline, "[noeol]"),
+            "autosaved" => flag(autosaved, "[autosaved]"),
+            "recording" => recording.map_or_else(String::new, |(register, _)| {
+                format!("recording @{register}")
+            }),
+            "plugins" => plugin_status.join(" "),
+            "language" => buffer
+                .language
+                .map_or_else(String::new, |language| language.id().to_string()),
+            // only shows the encoding when it isn't plain utf-8
+            "encoding" => match buffer.encoding {
+                Encoding::Utf8 => String::new(),
+                encoding => encoding.to_string(),
+            },
+            "line_ending" => buffer.line_ending.to_string(),
+            "branch" => buffer.git.branch.clone().unwrap_or_default(),
+            "line" => line.to_string(),
+            "column" => column.to_string(),
+            "percent" => format!("{}%", line * 100 / buffer.len().max(1)),
+            "lines" => buffer.len().to_string(),
+            "search" => search.map_or_else(String::new, |(n, total)| format!("match {n}/{total}")),
+            _ => String::new(),
+        };
+
+        statusline::FIELDS
+            .iter()
+            .filter(|name| fields.contains(name))
+            .map(|&name| (name, value(name)))
+            .collect()
+    }
+
+    /// Draws the scrollbar after the text when it's on, marking the lines with matches of the last
+    /// `*` or `#` search, and those with diagnostics over them.
+    fn draw_scrollbar(&mut self) {
+        if !self.config.scrollbar || self.vleft + self.vwidth >= self.width {
+            return;
+        }
+
+        let viewport = self.viewport();
+        let mut rows = 0;
+        let visible = (self.vtop..self.buffer.len())
+            .take_while(|&y| {
+                rows += viewport.rows(self.buffer.line_len(y));
+                rows <= self.vheight
+            })
+            .count();
+        let scroll = Scroll {
+            top: self.vtop,
+            visible: visible.max(1),
+            total: self.buffer.len(),
+        };
+
+        let mut marks = vec![];
+        if self.config.scrollbar_marks {
+            if let Some(word) = self.last_search.as_ref().filter(|_| !self.buffer.large) {
+                let (fg, _) = self.theme.default_colors();
+                let positions = self.buffer.word_positions(word);
+                marks.extend(positions.iter().map(|&(y, _)| (y, fg)));
+            }
+            let mut signs: Vec<_> = self
+                .buffer
+                .diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.start.1, diagnostic.severity.sign()))
+                .collect();
+            // the most severe goes last, to be drawn over the others on its row
+            signs.sort_by_key(|(_, sign)| sign.priority);
+            marks.extend(
+                signs
+                    .into_iter()
+                    .map(|(y, sign)| (y, self.theme.sign_color(sign.kind))),
+            );
+        }
+
+        scrollbar::draw(
+            &mut *self.screen,
+            &self.theme,
+            self.width - 1,
+            self.vheight,
+            scroll,
+            &marks,
+        );
+    }
+
+    /// Draws who last changed each visible line after its text while `:blame` is on, leaving out
+    /// the ones without room.
+    fn draw_blame(&mut self) -> anyhow::Result<()> {
+        let Some((_, blame)) = self.buffer.blame.as_ref().filter(|_| self.blame) else {
+            return Ok(());
+        };
+
+        let (fg, bg) = self.theme.default_colors();
+        let fg = darken(fg, 0.5)?;
+        let line_highlight = match &self.theme.line_highlight {
+            Some(color) => hex_to_crossterm_color(color)?,
+            None => bg,
+        };
+        let viewport = self.viewport();
+        let current = self.by();
+
+        let mut row = 0;
+        for y in self.vtop..self.buffer.len() {
+            let len = self.buffer.line_len(y);
+            let rows = viewport.rows(len);
+            row += rows;
+            if row > self.vheight {
+                break;
+            }
+
+            // the annotation goes after the text, on the last row of a wrapped line
+            let end = len - (rows - 1) * self.vwidth;
+            let Some(x) = (end + 3).checked_sub(self.hscroll) else {
+                continue;
+            };
+            let Some(line) = blame.get(y).filter(|_| x < self.vwidth) else {
+                continue;
+            };
+            let text: String = line.annotation().chars().take(self.vwidth - x).collect();
+            let bg = if y == current { line_highlight } else { bg };
+            self.screen.move_to(self.vleft + x, row - 1);
+            self.screen.print_styled(text.with(fg).on(bg));
+        }
 
         Ok(())
     }
 
+    /// Draws both buffers of the diff side by side, in place of the buffer and its gutter.
+    fn draw_diff(&mut self) -> anyhow::Result<()> {
+        let (Some(diff), Some(side)) = (&self.diff, self.diff_side().map(|(_, side)| side)) else {
+            return Ok(());
+        };
+
+        let selection = self.selection();
+        let mut cursor = [None, None];
+        cursor[side] = Some(self.by());
+        let view = diff::View {
+            row: diff.top_row(side, self.vtop),
+            width: self.width,
+            height: self.vheight,
+            top: self.tabline_rows(),
+            cursor,
+            selection: selection.as_ref().map(|selection| (side, selection)),
+            invisibles: self.config.list,
+            styles: &self.styles,
+            gutter: diff_gutter(&self.config),
+        };
+        let other = &mut self.buffers[diff.buffers[1 - side]];
+        let buffers = match side {
+            0 => [&mut self.buffer, other],
+            _ => [other, &mut self.buffer],
+        };
+        diff.draw(&mut *self.screen, &self.theme, buffers, &view)
+    }
+
+    #[allow(unused)]
     pub fn draw_commandline(&mut self) -> anyhow::Result<()> {
         let bg = hex_to_crossterm_color(&self.theme.background)?;
 
-        let y = self.height as u16 - 1;
+        let y = self.height - 1;
         let line = " ".repeat(self.width);
-        stdout().queue(cursor::MoveTo(0, y))?;
-        stdout().queue(PrintStyledContent(line.on(bg)))?;
+        self.screen.move_to(0, y);
+        self.screen.print_styled(line.on(bg));
         Ok(())
     }
 
@@ -284,24 +843,65 @@ impl Editor {
                 .clone()
                 .unwrap_or(self.theme.background.clone()),
         )?;
+        let bgh = match self
+            .theme
+            .gutter_background_highlight
+            .as_ref()
+            .or(self.theme.line_highlight.as_ref())
+        {
+            Some(color) => hex_to_crossterm_color(color)?,
+            None => bg,
+        };
+
+        let fg = if self.config.faded_line_numbers {
+            darken(fg, 0.5)?
+        } else {
+            fg
+        };
+        // the sign column, then the line number with its padding and the separator
+        let width = self.number_digits();
+        let padding = self.number_columns() - width;
+        let separator = &self.config.gutter_separator;
+        let viewport = self.viewport();
+        let signs = self.buffer.signs();
 
-        let width = self.vleft - 2;
+        // the line drawn on each row, wrapped continuation rows only get the separator
+        let mut line = self.vtop;
+        let mut continuation = 0;
         for y in 0..self.vheight {
-            let fg = if self.config.faded_line_numbers {
-                darken(fg, 0.5)?
+            let (color, bg) = if line == self.by() {
+                (fgh, bgh)
             } else {
-                fg
+                (fg, bg)
             };
-            let color = if y == self.cy { fgh } else { fg };
-            stdout().queue(cursor::MoveTo(0, y as u16))?;
-            if self.vtop + y >= self.buffer.len() {
-                stdout().queue(PrintStyledContent(
-                    " ".repeat(self.vleft).with(color).on(bg),
-                ))?;
+            self.screen.move_to(0, y);
+            if line >= self.buffer.len() {
+                self.screen
+                    .print_styled(" ".repeat(self.vleft).with(color).on(bg));
             } else {
-                let line_number = format!("{:>width$}", y + self.vtop + 1);
-                stdout().queue(PrintStyledContent(line_number.with(color).on(bg)))?;
-                stdout().queue(PrintStyledContent(" ▎".to_string().with(fg).on(bg)))?;
+                match signs.get(&line).filter(|_| continuation == 0) {
+                    Some(sign) => self.screen.print_styled(
+                        sign.text
+                            .to_string()
+                            .with(self.theme.sign_color(sign.kind))
+                            .on(bg),
+                    ),
+                    None => self.screen.print_styled(" ".with(fg).on(bg)),
+                }
+
+                let line_number = if continuation == 0 && width > 0 {
+                    format!("{:>width$}{}", line + 1, " ".repeat(padding))
+                } else {
+                    " ".repeat(width + padding)
+                };
+                self.screen.print_styled(line_number.with(color).on(bg));
+                self.screen.print_styled(separator.clone().with(fg).on(bg));
+            }
+
+            continuation += 1;
+            if continuation >= viewport.rows(self.buffer.line_len(line)) {
+                line += 1;
+                continuation = 0;
             }
         }
 
@@ -309,7 +909,7 @@ impl Editor {
     }
 
     pub fn draw_buffer(&mut self) -> anyhow::Result<()> {
-        log!("draw_buffer");
+        trace!("draw_buffer");
         // log!(
         //     "draw_buffer left={} width={} total={}",
         //     self.vleft,
@@ -317,18 +917,110 @@ impl Editor {
         //     self.width
         // );
 
-        let viewport = Viewport::new(self.vtop, self.vleft, self.vwidth, self.vheight);
-        highlight(&self.buffer, &self.theme, &viewport)?;
+        let viewport = self.viewport();
+        let selection = self.selection();
+
+        // marks the bracket under the cursor along with its match, except in large buffers where
+        // finding it could scan the whole file on every redraw
+        update_highlights(&mut self.buffer, &viewport)?;
+        let cursor = (self.bx(), self.by());
+        let matching = if self.buffer.large {
+            None
+        } else {
+            brackets::matching_bracket(&self.buffer, cursor)
+        };
+        let brackets = match matching {
+            Some(matching) => vec![cursor, matching],
+            None => vec![],
+        };
+
+        let mut underlines: Vec<_> = self
+            .buffer
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                // the end is exclusive, and empty ranges still mark the char they point at
+                let end = match diagnostic.end {
+                    end if end == diagnostic.start => end,
+                    (0, y) => (self.buffer.line_len(y - 1), y - 1),
+                    (x, y) => (x - 1, y),
+                };
+                Selection::new(diagnostic.start, end)
+            })
+            .collect();
+        if self.spell_checking() {
+            if let Some(dictionary) = &self.dictionary {
+                let end = (viewport.top + viewport.height).min(self.buffer.len());
+                for y in viewport.top..end {
+                    let words = spell::misspellings(dictionary, &self.buffer, y);
+                    underlines.extend(
+                        words
+                            .into_iter()
+                            .map(|(start, end)| Selection::new((start, y), (end - 1, y))),
+                    );
+                }
+            }
+        }
+
+        let occurrences = self.occurrences();
+        let backgrounds = self.conflict_backgrounds(&viewport);
+
+        let overlays = Overlays {
+            selection: selection.as_ref(),
+            brackets: &brackets,
+            underlines: &underlines,
+            occurrences: &occurrences,
+            current_line: Some(cursor.1),
+            line_backgrounds: &backgrounds,
+            invisibles: self.config.list,
+            ..Default::default()
+        };
+        highlight::draw(
+            &mut *self.screen,
+            &mut self.buffer,
+            &self.theme,
+            &self.styles,
+            &viewport,
+            &overlays,
+        )?;
 
         let (fg, bg) = self.theme.default_colors();
-        for y in position()?.1..self.vheight as u16 {
-            stdout().queue(cursor::MoveTo(self.vleft as u16, y))?;
-            stdout().queue(PrintStyledContent(" ".repeat(self.vwidth).with(fg).on(bg)))?;
+        for y in self.screen.position().1..self.vheight {
+            self.screen.move_to(self.vleft, y);
+            self.screen
+                .print_styled(" ".repeat(self.vwidth).with(fg).on(bg));
         }
 
         Ok(())
     }
 
+    /// The backgrounds of the lines of merge conflicts in `viewport`, tinted by the side they
+    /// are on with the markers standing out. Large buffers go without, as finding conflicts
+    /// reads every line.
+    fn conflict_backgrounds(&mut self, viewport: &Viewport) -> Vec<(usize, Color)> {
+        if self.buffer.large {
+            return vec![];
+        }
+        let (_, bg) = self.theme.default_colors();
+        let end = viewport.top + viewport.height;
+        self.buffer
+            .conflicts()
+            .into_iter()
+            .filter(|conflict| conflict.end >= viewport.top && conflict.start < end)
+            .flat_map(|conflict| {
+                (conflict.start..=conflict.end).filter_map(move |y| {
+                    let color = match conflict.region(y)? {
+                        Region::Marker => blend(bg, diff::CHANGED, 0.4),
+                        Region::Ours => blend(bg, diff::ADDED, 0.15),
+                        Region::Base => blend(bg, diff::REMOVED, 0.1),
+                        Region::Theirs => blend(bg, diff::CHANGED, 0.15),
+                    };
+                    Some((y, color))
+                })
+            })
+            .collect()
+    }
+
     pub fn adjust_cursor(&mut self) {
         if !self.affects_buffer() {
             return;
@@ -346,110 +1038,317 @@ impl Editor {
         // );
         if self.cx >= max_x {
             match self.mode {
-                Mode::Normal => self.cx = if max_x > 0 { max_x - 1 } else { 0 },
-                Mode::Insert => self.cx = max_x,
+                Mode::Normal | Mode::Visual => self.cx = if max_x > 0 { max_x - 1 } else { 0 },
+                Mode::Insert | Mode::Replace => self.cx = max_x,
                 Mode::Command => {}
             }
         }
+
+        // wrapped lines above the cursor can push it below the viewport
+        while self.wrapping() && self.cy > 0 && self.cursor_position().1 >= self.vheight {
+            self.vtop += 1;
+            self.cy -= 1;
+        }
+        // and moving it back onto a shorter line can take it left of the view
+        self.scroll_to_column();
     }
 
-    pub fn draw_cursor(&mut self) -> anyhow::Result<()> {
-        if !self.affects_buffer() {
-            return Ok(());
+    /// The lines kept in view above and below the cursor, at most half the viewport.
+    fn scroll_off(&self) -> usize {
+        (self.config.scroll_off as usize).min(self.vheight.saturating_sub(1) / 2)
+    }
+
+    /// Scrolls so `scrolloff` lines stay in view above and below the cursor, where the buffer
+    /// has them. Returns whether it scrolled.
+    fn scroll_to_margin(&mut self) -> bool {
+        if !self.affects_buffer() || self.vheight == 0 {
+            return false;
         }
 
-        // log!("draw_cursor cx={} cy={}", self.cx, self.cy);
-        match self.mode {
-            Mode::Normal => {
-                stdout().queue(SetCursorStyle::SteadyBlock)?;
+        let margin = self.scroll_off();
+        let (vtop, y) = (self.vtop, self.by());
+        self.vtop = self.vtop.min(y.saturating_sub(margin));
+
+        let bottom = (y + margin).min(self.buffer.len().saturating_sub(1));
+        if self.wrapping() {
+            let viewport = self.viewport();
+            let rows = |top: usize| -> usize {
+                (top..=bottom)
+                    .map(|line| viewport.rows(self.buffer.line_len(line)))
+                    .sum()
+            };
+            while self.vtop < y && rows(self.vtop) > self.vheight {
+                self.vtop += 1;
             }
-            Mode::Insert => {
-                stdout().queue(SetCursorStyle::SteadyBar)?;
+        } else {
+            if bottom >= self.vtop + self.vheight {
+                self.vtop = bottom + 1 - self.vheight;
+            }
+            // the filler across from lines only the other side of a diff has takes rows too
+            if let Some((diff, side)) = self.diff_side() {
+                let last = diff.row(side, bottom);
+                let mut top = self.vtop;
+                while top < y && last + 1 - diff.top_row(side, top) > self.vheight {
+                    top += 1;
+                }
+                self.vtop = top;
             }
-            Mode::Command => {}
         }
 
-        stdout().queue(cursor::MoveTo(
-            (self.vleft + self.cx).try_into()?,
-            self.cy.try_into()?,
-        ))?;
-        Ok(())
+        self.cy = y - self.vtop;
+        self.vtop != vtop
     }
 
-    fn move_to_next_char(&mut self, ch: char) -> bool {
-        let mut x = self.bx();
-        let mut y = self.by();
-
-        while y < self.buffer.len() {
-            // finds the next char in the buffer
-            let line = self.get_line(y).cloned();
-            log!("searching in line: {y} - {:?}", line);
-            if let Some(line) = line {
-                let mut nx = line.chars().skip(x + 1).position(|c| c == ch);
-                if nx.is_none() {
-                    nx = line
-                        .chars()
-                        .skip(x + 1)
-                        .position(|c| c == ch.to_ascii_uppercase());
-                }
-                if nx.is_none() {
-                    nx = line
-                        .chars()
-                        .skip(x + 1)
-                        .position(|c| c == ch.to_ascii_lowercase());
-                }
-                match nx {
-                    Some(x) => {
-                        self.cy = y;
-                        self.cx += x;
-                        return true;
-                    }
-                    None => {}
+    /// Scrolls long lines that don't wrap sideways to keep the cursor column in view. Returns
+    /// whether it scrolled.
+    fn scroll_to_column(&mut self) -> bool {
+        let hscroll = self.hscroll;
+        if self.wrapping() || self.diff_side().is_some() || self.vwidth == 0 {
+            self.hscroll = 0;
+        } else if self.cx < self.hscroll {
+            self.hscroll = self.cx;
+        } else if self.cx >= self.hscroll + self.vwidth {
+            self.hscroll = self.cx + 1 - self.vwidth;
+        }
+        self.hscroll != hscroll
+    }
+
+    /// Moves the cursor out of the `scrolloff` margins after the viewport scrolled under it, so
+    /// the margin doesn't scroll it back.
+    fn move_into_margin(&mut self) {
+        let margin = self.scroll_off();
+        let top = if self.vtop > 0 { margin } else { 0 };
+        let bottom = if self.vtop + self.vheight < self.buffer.len() {
+            self.vheight.saturating_sub(margin + 1)
+        } else {
+            self.vheight.saturating_sub(1)
+        };
+        let last = self.buffer.len().saturating_sub(self.vtop + 1);
+        self.cy = self.cy.clamp(top, bottom.max(top)).min(last);
+    }
+
+    /// The rows the tabline takes at the top, one when it is on and more than one buffer is open.
+    fn tabline_rows(&self) -> usize {
+        usize::from(self.config.tabline && self.buffers.len() > 1)
+    }
+
+    /// The open buffers as the tabline shows them.
+    fn tabs(&self) -> Vec<Tab> {
+        (0..self.buffers.len())
+            .map(|index| {
+                let buffer = self.buffer_at(index);
+                Tab {
+                    name: buffer.name.clone(),
+                    dirty: buffer.unsaved(),
                 }
-            }
-            y += 1;
-            x = 0;
+            })
+            .collect()
+    }
+
+    /// Gives the buffer the rows left by the tabline, the statusline and the command line.
+    /// Returns whether its height changed.
+    fn update_height(&mut self) -> bool {
+        let vheight = self.height.saturating_sub(2 + self.tabline_rows());
+        let changed = vheight != self.vheight;
+        self.vheight = vheight;
+        changed
+    }
+
+    /// Sizes the gutter for the sign column, the line numbers of the buffer with the padding
+    /// after them, and the separator, giving the text the rest of the width but the scrollbar's.
+    fn update_gutter(&mut self) {
+        if let Some((_, side)) = self.diff_side() {
+            let pane = diff::panes(self.width)[side].clone();
+            self.vleft =
+                (pane.start + diff_gutter(&self.config).width(self.buffer.len())).min(pane.end);
+            self.vwidth = pane.end - self.vleft;
+            return;
         }
+        let separator = display_width(&self.config.gutter_separator);
+        self.vleft = (1 + self.number_columns() + separator).min(self.width.saturating_sub(1));
+        self.vwidth = (self.width - self.vleft).saturating_sub(usize::from(self.config.scrollbar));
+    }
 
-        false
+    /// The digits of the line numbers, as many as the last one has but at least `number_width`,
+    /// none when they're off.
+    fn number_digits(&self) -> usize {
+        if !self.config.number {
+            return 0;
+        }
+        let digits = self.buffer.len().to_string().len();
+        digits.max(self.config.number_width as usize)
     }
 
-    fn move_line_to_center(&mut self) -> bool {
-        let y = self.cy;
-        let center_y = self.vheight / 2;
+    /// The columns of the gutter the line numbers and the padding after them take.
+    fn number_columns(&self) -> usize {
+        match self.number_digits() {
+            0 => 0,
+            digits => digits + self.config.gutter_padding as usize,
+        }
+    }
+
+    fn viewport(&self) -> Viewport {
+        Viewport::new(
+            self.vtop,
+            self.vleft,
+            self.vwidth,
+            self.vheight,
+            self.wrapping(),
+        )
+        .with_scroll(self.hscroll)
+    }
 
-        log!("move_line_to_center y: {} center_y: {}", y, center_y);
+    /// Whether long lines wrap, which they don't side by side in a diff.
+    fn wrapping(&self) -> bool {
+        self.config.wrap && self.diff_side().is_none()
+    }
+
+    /// The diff the current buffer is a side of, and which side it is.
+    fn diff_side(&self) -> Option<(&Diff, usize)> {
+        let diff = self.diff.as_ref()?;
+        Some((diff, diff.side(self.current)?))
+    }
 
-        if y == center_y {
+    /// Compares the buffers of the diff again when either changed. Returns whether it did.
+    fn update_diff(&mut self) -> bool {
+        let Some(mut diff) = self.diff.take() else {
             return false;
+        };
+        let [left, right] = diff.buffers;
+        let updated = diff.update(self.buffer_at(left), self.buffer_at(right));
+        self.diff = Some(diff);
+        updated
+    }
+
+    /// Position of the cursor on the screen relative to the viewport, which differs from
+    /// `(cx, cy)` when lines wrap or a diff has filler above the cursor.
+    fn cursor_position(&self) -> (usize, usize) {
+        if let Some((diff, side)) = self.diff_side() {
+            let row = diff.row(side, self.by());
+            return (self.cx, row.saturating_sub(diff.top_row(side, self.vtop)));
+        }
+        if !self.wrapping() {
+            return (self.cx.saturating_sub(self.hscroll), self.cy);
         }
 
-        if y > center_y {
-            // it's after the center
-            let dist = y - center_y;
-            log!("after the center (y > center), adding {} to top", dist);
-            self.vtop += dist;
-            self.cy -= dist;
-            log!("vtop = {} cy = {}", self.vtop, self.cy);
-        } else {
-            // it's before the center, so we need to scroll up by dist
-            let dist = center_y - y;
-            log!(
-                "before the center (y < center), subtracting {} from top",
-                dist
-            );
-            if let Some(vtop) = self.vtop.checked_sub(dist) {
-                self.vtop = vtop;
-                self.cy += dist;
-            } else {
-                let dist = self.vtop;
-                self.vtop = 0;
-                self.cy += dist;
+        let viewport = self.viewport();
+        let rows: usize = (self.vtop..self.by())
+            .map(|y| viewport.rows(self.buffer.line_len(y)))
+            .sum();
+        (self.cx % self.vwidth, rows + self.cx / self.vwidth)
+    }
+
+    pub fn draw_cursor(&mut self) -> anyhow::Result<()> {
+        if let Some(picker) = &self.picker {
+            let (x, y) = picker.cursor(self.width, self.vheight);
+            let y = self.tabline_rows() + y;
+            stdout().queue(self.config.cursor.command.command())?;
+            stdout().queue(cursor::MoveTo(x.try_into()?, y.try_into()?))?;
+            return Ok(());
+        }
+        if self.quickfix.open {
+            let (x, y) = self.quickfix.cursor(self.vheight);
+            let y = self.tabline_rows() + y;
+            stdout().queue(cursor::MoveTo(x.try_into()?, y.try_into()?))?;
+            return Ok(());
+        }
+        if let Some(pager) = &self.pager {
+            let (x, y) = pager.cursor(self.vheight);
+            let y = self.tabline_rows() + y;
+            stdout().queue(cursor::MoveTo(x.try_into()?, y.try_into()?))?;
+            return Ok(());
+        }
+
+        if self.mode.is_command() {
+            let (_, x) = self.command_line.view(self.width);
+            stdout().queue(self.config.cursor.command.command())?;
+            stdout().queue(cursor::MoveTo(x.try_into()?, self.command_y().try_into()?))?;
+            return Ok(());
+        }
+
+        if !self.affects_buffer() {
+            return Ok(());
+        }
+
+        // log!("draw_cursor cx={} cy={}", self.cx, self.cy);
+        let styles = &self.config.cursor;
+        let style = match self.mode {
+            Mode::Normal => styles.normal,
+            Mode::Visual => styles.visual,
+            Mode::Insert => styles.insert,
+            Mode::Replace => styles.replace,
+            Mode::Command => styles.command,
+        };
+        stdout().queue(style.command())?;
+
+        let (x, y) = self.cursor_position();
+        let y = self.tabline_rows() + y;
+        stdout().queue(cursor::MoveTo((self.vleft + x).try_into()?, y.try_into()?))?;
+        Ok(())
+    }
+
+    /// Moves to where `search` finds `c` in the cursor line, remembering the search for `;`
+    /// and `,`. Returns true if it moved.
+    fn find_char(&mut self, search: CharSearch, c: char, repeat: bool) -> bool {
+        if !repeat {
+            self.last_find = Some((search, c));
+        }
+
+        let line = self.line().unwrap_or_default();
+        match search.find(&line, self.bx(), c, repeat) {
+            Some(x) => {
+                self.cx = x;
+                true
             }
-            log!("vtop = {} cy = {}", self.vtop, self.cy);
+            None => false,
         }
+    }
 
-        true
+    /// Repeats the last char search, or runs it the other way when `reverse`.
+    fn repeat_find(&mut self, reverse: bool) -> bool {
+        let Some((search, c)) = self.last_find else {
+            return false;
+        };
+
+        let search = if reverse { search.reversed() } else { search };
+        self.find_char(search, c, true)
+    }
+
+    /// Scrolls so the cursor line is `row` rows from the top of the viewport, or as close as the
+    /// start of the buffer allows, keeping it out of the `scrolloff` margins. Returns whether it
+    /// scrolled.
+    fn move_line_to_row(&mut self, row: usize) -> bool {
+        let margin = self.scroll_off();
+        let row = row.clamp(margin, self.vheight.saturating_sub(margin + 1).max(margin));
+        let (vtop, y) = (self.vtop, self.by());
+        self.vtop = y.saturating_sub(row);
+        self.cy = y - self.vtop;
+        debug!(
+            "move_line_to_row row: {} vtop: {} cy: {}",
+            row, self.vtop, self.cy
+        );
+        self.vtop != vtop
+    }
+
+    /// Scrolls half a viewport down, or up, moving the cursor by as many lines so it stays on
+    /// the same row, for `<C-d>` and `<C-u>`.
+    fn scroll_half_page(&mut self, down: bool) {
+        let half = (self.vheight / 2).max(1);
+        let y = self.by();
+        let (vtop, y) = if down {
+            let max_top = self.buffer.len().saturating_sub(self.vheight);
+            let vtop = (self.vtop + half).min(max_top.max(self.vtop));
+            (vtop, (y + half).min(self.buffer.len().saturating_sub(1)))
+        } else {
+            (self.vtop.saturating_sub(half), y.saturating_sub(half))
+        };
+
+        self.vtop = vtop.min(y);
+        if y >= self.vtop + self.vheight {
+            self.vtop = y + 1 - self.vheight;
+        }
+        self.cy = y - self.vtop;
+        self.move_into_margin();
     }
 
     fn move_down(&mut self) -> bool {
@@ -477,42 +1376,67 @@ impl Editor {
         true
     }
 
-    fn move_up(&mut self) -> anyhow::Result<bool> {
-        // if we are inside the viewport
-        if self.cy > 0 {
-            self.cy -= 1;
-            return Ok(true);
-        } else {
-            // if we are at the top of the viewport
-            if self.vtop > 0 {
-                self.vtop -= 1;
-                return Ok(true);
-            }
+    /// Moves down one screen row, staying within a wrapped line while it continues below.
+    fn move_down_display_line(&mut self) -> bool {
+        if !self.wrapping() {
+            return self.move_down();
+        }
+
+        let column = self.cx % self.vwidth;
+        if self.cx - column + self.vwidth < self.current_line_len() {
+            self.cx += self.vwidth;
+            return true;
+        }
+
+        if self.move_down() {
+            self.cx = column;
+        }
+        true
+    }
+
+    /// Moves up one screen row, staying within a wrapped line while it continues above.
+    fn move_up_display_line(&mut self) -> anyhow::Result<bool> {
+        if !self.wrapping() {
+            return self.move_up();
+        }
+
+        if self.cx >= self.vwidth {
+            self.cx -= self.vwidth;
+            return Ok(true);
+        }
+
+        let column = self.cx;
+        if self.move_up()? {
+            let rows = self.viewport().rows(self.current_line_len());
+            self.cx = (rows - 1) * self.vwidth + column;
+        }
+        Ok(true)
+    }
+
+    fn move_up(&mut self) -> anyhow::Result<bool> {
+        // if we are inside the viewport
+        if self.cy > 0 {
+            self.cy -= 1;
+            return Ok(true);
+        } else {
+            // if we are at the top of the viewport
+            if self.vtop > 0 {
+                self.vtop -= 1;
+                return Ok(true);
+            }
         }
         Ok(false)
     }
 
     fn current_line_len(&self) -> usize {
-        self.line().map(|s| s.len()).unwrap_or(0)
+        self.buffer.line_len(self.by())
     }
 
     fn move_right(&mut self) -> anyhow::Result<bool> {
-        let mut redraw = false;
-
-        // if we're inside the viewport
-        if self.cx < self.vwidth - 1 {
-            if self.bx() < self.current_line_len() {
-                self.cx += 1;
-            }
-        } else {
-            // if we're at the right edge of the viewport
-            if self.vleft < self.line().map(|s| s.len() - 1).unwrap_or(0) {
-                self.vleft += 1;
-                self.cx += 1;
-                redraw = true;
-            }
+        if self.bx() < self.current_line_len() {
+            self.cx += 1;
         }
-        Ok(redraw)
+        Ok(false)
     }
 
     fn move_left(&mut self) -> anyhow::Result<bool> {
@@ -524,15 +1448,42 @@ impl Editor {
     }
 
     fn move_end_of_line(&mut self) -> anyhow::Result<bool> {
-        self.cx = self.current_line_len() - 1;
+        self.cx = self.current_line_len().saturating_sub(1);
         Ok(false)
     }
 
+    /// Jumps to the bracket matching the one under the cursor, or the next one on the line.
+    fn move_to_matching_bracket(&mut self) -> anyhow::Result<bool> {
+        let viewport = self.viewport();
+        update_highlights(&mut self.buffer, &viewport)?;
+        match brackets::jump_target(&self.buffer, (self.bx(), self.by())) {
+            Some(position) => {
+                self.move_to_position(position);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Moves to column 0, or with `smart_home` to the first non-blank char unless already there.
     fn move_start_of_line(&mut self) -> anyhow::Result<bool> {
-        self.cx = 0;
+        let first = self.first_non_blank();
+        self.cx = if self.config.smart_home && self.cx != first {
+            first
+        } else {
+            0
+        };
         Ok(false)
     }
 
+    /// The column of the first non-blank char of the cursor line, or of its last char when it's
+    /// all blanks, as `^` moves to.
+    fn first_non_blank(&self) -> usize {
+        let line = self.line().unwrap_or_default();
+        let indent = indent::leading_whitespace(&line).chars().count();
+        indent.min(line.chars().count().saturating_sub(1))
+    }
+
     fn bx(&self) -> usize {
         self.cx
     }
@@ -541,39 +1492,253 @@ impl Editor {
         self.vtop + self.cy
     }
 
+    /// Returns the next input event, replaying queued macro events before reading the terminal.
+    /// Keys read from the terminal are added to the macro being recorded.
+    pub fn next_event(&mut self) -> anyhow::Result<Event> {
+        if let Some(ev) = self.input.pop_front() {
+            return Ok(ev);
+        }
+
+        let ev = read()?;
+        self.last_input = Some(Instant::now());
+        self.autosaved = false;
+        if let (Some((_, events)), Event::Key(_) | Event::Paste(_)) = (&mut self.recording, &ev) {
+            events.push(ev.clone());
+        }
+        Ok(ev)
+    }
+
+    /// Times the key sequence waiting for its next key, showing the keys that can follow it
+    /// after [`HINT_DELAY`] and cancelling it after `timeoutlen`. Returns whether that changed
+    /// what is shown.
+    fn update_pending(&mut self) -> bool {
+        if self.pending == Pending::None {
+            self.pending_since = None;
+            return false;
+        }
+        let since = match self.pending_since {
+            Some((pending, since)) if pending == self.pending => since,
+            _ => {
+                let now = Instant::now();
+                self.pending_since = Some((self.pending, now));
+                now
+            }
+        };
+
+        // the suggestions of `z=` wait for their number as long as it takes
+        let timeout = match self.pending {
+            Pending::Suggestion(..) => 0,
+            _ => self.config.sequence_timeout_ms,
+        };
+        if timeout > 0 && since.elapsed() >= Duration::from_millis(timeout) {
+            self.pending = Pending::None;
+            self.pending_since = None;
+            self.count = None;
+            self.register_name = None;
+            return self.popup.take().is_some();
+        }
+        if !self.config.key_hints || since.elapsed() < HINT_DELAY || self.popup.is_some() {
+            return false;
+        }
+        let hints = help::continuations(&self.config.keymap, self.pending);
+        if hints.is_empty() {
+            return false;
+        }
+        self.popup = Some(Popup::new(&help::hint_text(&hints)));
+        true
+    }
+
     fn handle_input(&mut self, ev: Event) -> anyhow::Result<bool> {
         // log!("Event: {:?}", ev);
+        if let Event::Key(_) = ev {
+            self.message = None;
+            if self.popup.take().is_some() {
+                self.pending_redraw = true;
+            }
+        }
+
         if self.handle_events(&ev)? {
             return Ok(true);
         }
 
+        if self.picker.is_some() {
+            return self.handle_picker_input(ev);
+        }
+        if self.quickfix.open {
+            return self.handle_quickfix_input(ev);
+        }
+        if self.pager.is_some() {
+            return self.handle_pager_input(ev);
+        }
+        if let Event::Mouse(event) = ev {
+            if let Some(redraw) = self.handle_chrome_mouse(&event)? {
+                return Ok(redraw);
+            }
+        }
+
+        self.start_change(&ev);
+        let redraw = match (&ev, &self.mode) {
+            (Event::Paste(text), _) => Ok(self.paste(text)),
+            (_, Mode::Normal) => self.handle_normal_input(ev),
+            (_, Mode::Insert) => self.handle_insert_input(ev),
+            (_, Mode::Replace) => self.handle_replace_input(ev),
+            (_, Mode::Visual) => self.handle_visual_input(ev),
+            (_, Mode::Command) => self.handle_command_input(ev),
+        };
+        self.end_change();
+        redraw
+    }
+
+    /// Handles the mouse on the tabline and statusline in any mode: clicking a tab or the wheel
+    /// over the tabline switches buffers, clicking the mode cycles modes and clicking the cursor
+    /// position opens the command line to go to a line. Returns `None` for events over the
+    /// buffer.
+    fn handle_chrome_mouse(&mut self, event: &MouseEvent) -> anyhow::Result<Option<bool>> {
+        let (x, y) = (event.column as usize, event.row as usize);
+        let on_tabline = y < self.tabline_rows();
+        let on_statusline = y == self.height - 2;
+
+        let redraw = match event.kind {
+            MouseEventKind::Down(MouseButton::Left) if on_tabline => {
+                let tab = tabline::tab_at(&self.tabs(), self.current, self.width, x);
+                if let Some(index) = tab {
+                    self.switch_buffer(index);
+                }
+                tab.is_some()
+            }
+            MouseEventKind::ScrollDown if on_tabline => self.run_action(Action::NextBuffer)?,
+            MouseEventKind::ScrollUp if on_tabline => self.run_action(Action::PreviousBuffer)?,
+            MouseEventKind::Down(MouseButton::Left) if on_statusline => {
+                let values = self.statusline_values();
+                let value = |name: &str| values.get(name).cloned().unwrap_or_default();
+                let fields = self.config.statusline.fields_at(self.width, x, value)?;
+                if fields.iter().any(|field| field == "mode") {
+                    self.cycle_mode()?
+                } else if fields
+                    .iter()
+                    .any(|field| field == "line" || field == "column")
+                {
+                    if matches!(self.mode, Mode::Visual) {
+                        self.exit_visual();
+                    }
+                    self.run_action(Action::CommandMode)?
+                } else {
+                    false
+                }
+            }
+            MouseEventKind::Down(_) | MouseEventKind::ScrollDown | MouseEventKind::ScrollUp
+                if on_tabline || on_statusline =>
+            {
+                false
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(redraw))
+    }
+
+    /// Goes from normal to insert to visual mode and back to normal, as clicking the mode on the
+    /// statusline does. Replace mode goes back to normal.
+    fn cycle_mode(&mut self) -> anyhow::Result<bool> {
         match self.mode {
-            Mode::Normal => self.handle_normal_input(ev),
-            Mode::Insert => self.handle_insert_input(ev),
-            Mode::Command => Ok(true),
+            Mode::Normal => self.run_action(Action::Insert),
+            Mode::Insert => {
+                self.run_action(Action::NormalMode)?;
+                self.adjust_cursor();
+                self.run_action(Action::VisualMode)
+            }
+            Mode::Visual => {
+                self.exit_visual();
+                Ok(true)
+            }
+            Mode::Replace => self.run_action(Action::NormalMode),
+            Mode::Command => Ok(false),
+        }
+    }
+
+    /// Whether the editor is back in normal mode with no command partly typed.
+    fn is_idle(&self) -> bool {
+        matches!(self.mode, Mode::Normal)
+            && self.pending == Pending::None
+            && self.count.is_none()
+            && self.register_name.is_none()
+    }
+
+    /// Adds `ev` to the keys of the command being typed, starting a new one when idle.
+    fn start_change(&mut self, ev: &Event) {
+        if !matches!(ev, Event::Key(_) | Event::Paste(_)) {
+            return;
+        }
+        if self.change.is_none() && self.is_idle() {
+            self.change = Some((self.current, self.buffer.revision(), vec![]));
+        }
+        if let Some((_, _, events)) = &mut self.change {
+            events.push(ev.clone());
+        }
+    }
+
+    /// Keeps the keys of the command that just ended as the last change when it edited the
+    /// buffer it started on. Commands typed on the command line, like `:s`, aren't repeated.
+    fn end_change(&mut self) {
+        if self.mode.is_command() {
+            self.change = None;
+        }
+        if !self.is_idle() {
+            return;
+        }
+        if let Some((current, revision, events)) = self.change.take() {
+            if current == self.current && revision != self.buffer.revision() {
+                self.last_change = events;
+            }
         }
     }
 
-    fn line(&self) -> Option<&String> {
-        self.buffer.get(self.by())
+    /// Queues the keys of the last change to be handled again, ahead of anything already
+    /// queued, with `count` in place of the count it was typed with.
+    fn repeat_change(&mut self, count: Option<usize>) {
+        let is_digit = |ev: &Event| {
+            matches!(
+                ev,
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('0'..='9'),
+                    modifiers: event::KeyModifiers::NONE,
+                    ..
+                })
+            )
+        };
+        let typed = match count {
+            Some(_) => self
+                .last_change
+                .iter()
+                .take_while(|ev| is_digit(ev))
+                .count(),
+            None => 0,
+        };
+        let digits = count.map(|count| count.to_string()).unwrap_or_default();
+        let count = digits
+            .chars()
+            .map(|c| Event::Key(KeyEvent::new(KeyCode::Char(c), event::KeyModifiers::NONE)));
+        let events: Vec<_> = count
+            .chain(self.last_change[typed..].iter().cloned())
+            .collect();
+        for ev in events.into_iter().rev() {
+            self.input.push_front(ev);
+        }
     }
 
-    fn get_line(&self, y: usize) -> Option<&String> {
-        self.buffer.get(y)
+    fn line(&self) -> Option<String> {
+        self.buffer.line(self.by())
     }
 
     fn handle_events(&mut self, ev: &Event) -> anyhow::Result<bool> {
-        match ev {
-            Event::Resize(width, height) => {
-                log!("resize: {}x{}", width, height);
-                self.width = *width as usize;
-                self.height = *height as usize;
-                self.vwidth = *width as usize - self.vleft;
-                self.vheight = *height as usize - 2;
-                self.draw(true)?;
-                return Ok(true);
-            }
-            _ => {}
+        if let Event::Resize(width, height) = ev {
+            debug!("resize: {}x{}", width, height);
+            self.width = *width as usize;
+            self.height = *height as usize;
+            self.screen.resize(self.width, self.height);
+            self.update_height();
+            self.update_gutter();
+            self.draw(true)?;
+            return Ok(true);
         }
 
         Ok(false)
@@ -586,272 +1751,1147 @@ impl Editor {
     /// This function will return an error if there is an error on the underlying
     /// command execution.
     fn handle_normal_input(&mut self, ev: Event) -> anyhow::Result<bool> {
-        let mut redraw = false;
-
-        match self.waiting_key {
-            Some('t') => match ev {
-                Event::Key(KeyEvent {
-                    code: key,
-                    modifiers: _mods,
-                    ..
-                }) => match key {
-                    KeyCode::Char(c) => {
-                        redraw = self.move_to_next_char(c);
-                        self.waiting_key = None;
-                    }
-                    _ => {}
-                },
-                _ => {}
-            },
-            _ => {
-                redraw = self.handle_normal_event(ev)?;
+        if self.pending == Pending::None {
+            // digits start a count, except a leading 0 which moves to the start of the line
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Char(c @ '0'..='9'),
+                modifiers: event::KeyModifiers::NONE,
+                ..
+            }) = ev
+            {
+                if c != '0' || self.count.is_some() {
+                    let digit = c.to_digit(10).unwrap_or_default() as usize;
+                    let count = self.count.unwrap_or_default();
+                    self.count = Some(count.saturating_mul(10).saturating_add(digit));
+                    return Ok(false);
+                }
             }
+
+            return self.handle_normal_event(ev);
         }
 
-        Ok(redraw)
-    }
+        let Event::Key(event) = ev else {
+            return Ok(false);
+        };
+        if let Pending::Operator(operator) = self.pending {
+            self.pending = Pending::None;
+            return self.handle_operator_motion(operator, &event);
+        }
 
-    fn handle_normal_event(&mut self, ev: Event) -> anyhow::Result<bool> {
-        let mut redraw = false;
-        match ev {
-            Event::Mouse(MouseEvent {
-                kind,
-                column,
-                row,
-                modifiers: _modifiers,
-            }) => match kind {
-                MouseEventKind::Down(MouseButton::Left) => {
-                    log!("mouse up: {}, {}", column, row);
-                    redraw = self.move_to(column as usize, row as usize);
-                }
-                MouseEventKind::Drag(MouseButton::Left) => {
-                    log!("mouse drag: {}, {}", column, row);
-                }
-                MouseEventKind::ScrollUp => {
-                    redraw = self.scroll_up();
+        let c = match event.code {
+            KeyCode::Char(c) => c,
+            // any other key, like Esc, cancels the pending command
+            _ => {
+                self.pending = Pending::None;
+                return Ok(false);
+            }
+        };
+
+        let pending = std::mem::take(&mut self.pending);
+        // a count typed before a prefix only carries on to the operators it starts, as in `3gcc`
+        let count = match pending {
+            Pending::Prefix(None, _) => self.count.take(),
+            _ => None,
+        };
+        match pending {
+            // operators are handled above, they take any key as their motion
+            Pending::None | Pending::Operator(_) => Ok(false),
+            Pending::Prefix(None, 'g') if c == 'g' => self.run_action(Action::StartOfBuffer),
+            Pending::Prefix(None, 'z') if c == 'z' => self.run_action(Action::CenterLine),
+            Pending::Prefix(None, 'z') if c == 't' => self.run_action(Action::LineToTop),
+            Pending::Prefix(None, 'z') if c == 'b' => self.run_action(Action::LineToBottom),
+            Pending::Prefix(None, 'z') if c == '=' => self.run_action(Action::SuggestSpelling),
+            Pending::Prefix(None, ']') if c == 's' => self.run_action(Action::NextMisspelling),
+            Pending::Prefix(None, '[') if c == 's' => self.run_action(Action::PreviousMisspelling),
+            Pending::Prefix(None, ' ') if c == 'f' => self.run_action(Action::OpenPicker),
+            Pending::Prefix(None, 'g') if c == 'd' => self.run_action(Action::GoToDefinition),
+            Pending::Prefix(None, ']') if c == 'c' => self.run_action(Action::NextHunk),
+            Pending::Prefix(None, '[') if c == 'c' => self.run_action(Action::PreviousHunk),
+            Pending::Prefix(None, ']') if c == 'm' => self.run_action(Action::NextFunction),
+            Pending::Prefix(None, '[') if c == 'm' => self.run_action(Action::PreviousFunction),
+            Pending::Prefix(None, ']') if c == 'n' => self.run_action(Action::EndOfNode),
+            Pending::Prefix(None, '[') if c == 'n' => self.run_action(Action::StartOfNode),
+            Pending::Prefix(None, ']') if c == 'x' => self.run_action(Action::NextConflict),
+            Pending::Prefix(None, '[') if c == 'x' => self.run_action(Action::PreviousConflict),
+            Pending::Prefix(None, WINDOW_PREFIX) if c == 'w' => self.run_action(Action::NextWindow),
+            Pending::Prefix(None, WINDOW_PREFIX) if c == 'h' => self.run_action(Action::LeftWindow),
+            Pending::Prefix(None, WINDOW_PREFIX) if c == 'l' => {
+                self.run_action(Action::RightWindow)
+            }
+            Pending::Prefix(None, 'g') if c == 't' => self.run_action(Action::NextBuffer),
+            Pending::Prefix(None, 'g') if c == 'T' => self.run_action(Action::PreviousBuffer),
+            Pending::Prefix(None, 'g') if c == 'j' => self.run_action(Action::DisplayLineDown),
+            Pending::Prefix(None, 'g') if c == 'k' => self.run_action(Action::DisplayLineUp),
+            Pending::Prefix(None, 'g') if c == 'e' => self.run_action(Action::PreviousEndOfWord),
+            Pending::Prefix(None, 'g') if c == 'E' => self.run_action(Action::PreviousEndOfBigWord),
+            Pending::Prefix(None, 'g') if c == 'c' && self.selection().is_some() => {
+                if let Some(selection) = self.selection() {
+                    self.toggle_comments(selection.start.1, selection.end.1);
                 }
-                MouseEventKind::ScrollDown => {
-                    self.scroll_down();
-                    redraw = true;
+                self.exit_visual();
+                Ok(true)
+            }
+            Pending::Prefix(None, 'g') if matches!(c, 'u' | 'U' | 'c') => {
+                self.count = count;
+                self.run_action(match c {
+                    'u' => Action::Lowercase,
+                    'U' => Action::Uppercase,
+                    _ => Action::Comment,
+                })
+            }
+            Pending::Prefix(Some(operator), 'g') if c == 'e' || c == 'E' => {
+                self.operate_word(operator, WordMotion::PreviousEnd, c == 'E', 1)
+            }
+            // `gugu` as well as `guu`
+            Pending::Prefix(Some(operator), 'g') if operator.line_key() == Some(c) => {
+                self.operate(operator, MotionKind::Linewise, |_| {})
+            }
+            Pending::Prefix(Some(operator), 'g') if c == 'g' => {
+                self.operate(operator, MotionKind::Linewise, |e| {
+                    e.move_to_start_of_buffer()
+                })
+            }
+            Pending::Prefix(..) => Ok(false),
+            Pending::FindChar(None, search) => Ok(self.find_char(search, c, false)),
+            Pending::FindChar(Some(operator), search) => {
+                self.operate_if_moved(operator, search.kind(), |e| e.find_char(search, c, false))
+            }
+            Pending::SetMark => {
+                if c.is_ascii_lowercase() || c == '\'' || c == '`' {
+                    self.buffer.set_mark(mark_name(c), (self.bx(), self.by()));
                 }
-                _ => {}
-            },
-            Event::Key(KeyEvent {
-                code: key,
-                modifiers: mods,
-                ..
-            }) => match key {
-                KeyCode::Char(c) => match c {
-                    'M' => {
-                        redraw = self.move_to_middle_of_viewport();
-                    }
-                    'G' => {
-                        self.move_to_end_of_buffer();
-                        redraw = true;
-                    }
-                    'g' => match self.waiting_key {
-                        Some('g') => {
-                            self.move_to_start_of_buffer();
-                            self.waiting_key = None;
-                            redraw = true;
-                        }
-                        _ => {
-                            self.waiting_key = Some('g');
-                        }
-                    },
-                    'f' => {
-                        if mods.contains(event::KeyModifiers::CONTROL) {
-                            self.move_to_next_page();
-                            redraw = true;
-                        }
-                    }
-                    't' => {
-                        self.waiting_key = Some('t');
-                    }
-                    'b' => {
-                        if mods.contains(event::KeyModifiers::CONTROL) {
-                            self.move_to_previous_page()?;
-                        } else {
-                            self.move_to_previous_word();
-                        }
-                        redraw = true;
-                    }
-                    'q' => return Ok(false),
-                    'i' => {
-                        self.mode = Mode::Insert;
-                    }
-                    'a' => {
-                        self.move_right()?;
-                        self.mode = Mode::Insert;
-                    }
-                    ':' | ';' => {
-                        self.mode = Mode::Command;
-                        redraw = true;
-                    }
-                    'o' => {
-                        self.move_down();
-                        self.insert_line()?;
-                        self.mode = Mode::Insert;
-                        redraw = true;
-                    }
-                    'O' => {
-                        self.insert_line()?;
-                        self.mode = Mode::Insert;
-                        redraw = true;
-                    }
-                    'x' => {
-                        let x = self.bx();
-                        let y = self.by();
-                        if let Some(line) = self.line() {
-                            if x < line.len() {
-                                let line = self.buffer.get_mut(y).expect("line out of bounds");
-                                line.remove(x);
-                            }
-                            redraw = true;
-                        } else {
-                            warn!("line out of bounds: x: {}, y: {}", x, y);
-                        }
-                    }
-                    'd' => match self.waiting_key {
-                        Some('d') => {
-                            self.buffer.remove(self.by());
-                            self.waiting_key = None;
-                            redraw = true;
-                        }
-                        _ => {
-                            self.waiting_key = Some('d');
-                        }
-                    },
-                    'z' => match self.waiting_key {
-                        Some('z') => {
-                            self.waiting_key = None;
-                            redraw = self.move_line_to_center();
-                        }
-                        _ => {
-                            self.waiting_key = Some('z');
-                        }
-                    },
-                    'J' => {
-                        if let Some(line) = self.line() {
-                            let empty = String::new();
-                            let next_line = self.buffer.get(self.by() + 1).unwrap_or(&empty);
-                            let new_line = format!("{} {}", line, next_line);
-                            let y = self.by();
-                            self.buffer[y] = new_line;
-                            self.buffer.remove(self.by() + 1);
-                            redraw = true;
-                        } else {
-                            warn!("line out of bounds: x: {}, y: {}", self.bx(), self.by());
-                        }
-                    }
-                    'j' => {
-                        redraw = self.move_down();
-                    }
-                    'k' => {
-                        redraw = self.move_up()?;
-                    }
-                    'h' => {
-                        redraw = self.move_left()?;
-                    }
-                    'l' => {
-                        redraw = self.move_right()?;
-                    }
-                    '$' => {
-                        redraw = self.move_end_of_line()?;
-                    }
-                    '0' => {
-                        redraw = self.move_start_of_line()?;
-                    }
-                    'w' => {
-                        redraw = self.move_to_next_word()?;
+                Ok(false)
+            }
+            Pending::GoToMark(None, linewise) => Ok(self.go_to_mark(c, linewise)),
+            Pending::GoToMark(Some(operator), linewise) => {
+                let kind = if linewise {
+                    MotionKind::Linewise
+                } else {
+                    MotionKind::Exclusive
+                };
+                match self.mark_position(c, linewise) {
+                    Some(position) => {
+                        self.operate(operator, kind, |e| e.move_to_position(position))
                     }
-                    _ => {}
-                },
-                KeyCode::Down => {
-                    redraw = self.move_down();
+                    None => Ok(true),
                 }
-                KeyCode::Up => {
-                    self.move_up()?;
-                }
-                KeyCode::Left => {
-                    self.move_left()?;
-                }
-                KeyCode::Right => {
-                    self.move_right()?;
+            }
+            Pending::Register => {
+                match c {
+                    '"' | '+' | '*' => self.register_name = Some(c),
+                    _ => {
+                        self.message = Some(Message::Error(format!(
+                            "E354: Invalid register name: '{c}'"
+                        )))
+                    }
                 }
-                KeyCode::Esc => {
-                    self.mode = Mode::Normal;
+                Ok(false)
+            }
+            Pending::Record => {
+                if c.is_ascii_alphanumeric() {
+                    self.recording = Some((c, vec![]));
                 }
-                _ => {}
-            },
+                Ok(true)
+            }
+            Pending::Play(count) => {
+                self.play_macro(if c == '@' { self.last_macro } else { Some(c) }, count);
+                Ok(false)
+            }
+            Pending::Replace(count) => Ok(self.replace_chars(c, count)),
+            Pending::Suggestion(start, end) => {
+                let suggestions = std::mem::take(&mut self.suggestions);
+                let chosen = c
+                    .to_digit(10)
+                    .and_then(|n| (n as usize).checked_sub(1))
+                    .and_then(|i| suggestions.get(i));
+                let Some(word) = chosen else {
+                    return Ok(false);
+                };
+                self.buffer.remove(start, (end, start.1));
+                self.buffer.insert(start, word);
+                self.buffer.dirty = true;
+                self.move_to_position(start);
+                Ok(true)
+            }
+            Pending::TextObject(operator, inner) => {
+                let viewport = self.viewport();
+                update_highlights(&mut self.buffer, &viewport)?;
+                let Some((start, end)) =
+                    text_object(&self.buffer, (self.bx(), self.by()), c, inner)
+                else {
+                    return Ok(false);
+                };
 
-            _ => {}
+                match operator {
+                    Some(operator) => self.apply_operator(operator, start, end),
+                    None => self.select_range(start, end),
+                }
+                Ok(true)
+            }
         }
-
-        Ok(redraw)
     }
 
-    fn scroll_down(&mut self) {
-        let desired_vtop = self.vtop + self.config.mouse_scroll_lines as usize;
-        if desired_vtop < self.buffer.len() {
-            self.vtop = desired_vtop;
-            if let Some(cy) = self.cy.checked_sub(self.config.mouse_scroll_lines as usize) {
-                self.cy = cy;
-            } else {
-                self.cy = 0;
-            }
-        } else {
-            self.vtop = self.buffer.len() - self.vheight;
+    /// Handles the key after an operator: a motion, a text object, or the operator key again to
+    /// act on the whole line, or on as many lines as the count typed before the operator.
+    fn handle_operator_motion(
+        &mut self,
+        operator: Operator,
+        event: &KeyEvent,
+    ) -> anyhow::Result<bool> {
+        let count = self.count.take().unwrap_or(1);
+        if let KeyCode::Char(c @ ('i' | 'a')) = event.code {
+            self.pending = Pending::TextObject(Some(operator), c == 'i');
+            return Ok(false);
+        }
+        // `guu`, `gUU` and `gcc`, their second keys having another use on their own
+        if operator.line_key().map(KeyCode::Char) == Some(event.code) {
+            return self.operate(operator, MotionKind::Linewise, |e| {
+                for _ in 1..count {
+                    e.move_down();
+                }
+            });
         }
-    }
 
-    fn scroll_up(&mut self) -> bool {
-        if self.vtop == 0 {
-            return false;
+        let Some(action) = self.config.keymap.normal(event) else {
+            return Ok(false);
+        };
+        if let Some((motion, big)) = action.word_motion() {
+            return self.operate_word(operator, motion, big, count);
+        }
+        if let Some(search) = action.char_search() {
+            self.pending = Pending::FindChar(Some(operator), search);
+            return Ok(false);
         }
 
-        if let Some(desired_vtop) = self
-            .vtop
-            .checked_sub(self.config.mouse_scroll_lines as usize)
-        {
-            self.vtop = desired_vtop;
-            let desired_cy = self.cy + self.config.mouse_scroll_lines as usize;
-            if desired_cy < self.vheight {
-                self.cy = desired_cy;
-            } else {
-                self.cy = self.vheight - 1;
+        match action {
+            _ if Operator::from_action(&action) == Some(operator) => {
+                self.operate(operator, MotionKind::Linewise, |e| {
+                    for _ in 1..count {
+                        e.move_down();
+                    }
+                })
             }
-        } else {
-            self.vtop = 0;
+            Action::RepeatFind | Action::RepeatFindBackward => {
+                let Some((search, _)) = self.last_find else {
+                    return Ok(false);
+                };
+                let reverse = action == Action::RepeatFindBackward;
+                let kind = if reverse {
+                    search.reversed().kind()
+                } else {
+                    search.kind()
+                };
+                self.operate_if_moved(operator, kind, |e| e.repeat_find(reverse))
+            }
+            Action::GotoPrefix => {
+                self.pending = Pending::Prefix(Some(operator), 'g');
+                Ok(false)
+            }
+            Action::GoToMark | Action::GoToMarkLine => {
+                self.pending = Pending::GoToMark(Some(operator), action == Action::GoToMarkLine);
+                Ok(false)
+            }
+            Action::MoveDown => self.operate(operator, MotionKind::Linewise, |e| {
+                e.move_down();
+            }),
+            Action::MoveUp => self.operate(operator, MotionKind::Linewise, |e| {
+                let _ = e.move_up();
+            }),
+            Action::EndOfBuffer => self.operate(operator, MotionKind::Linewise, |e| {
+                e.move_to_end_of_buffer()
+            }),
+            Action::MoveLeft => self.operate(operator, MotionKind::Exclusive, |e| {
+                let _ = e.move_left();
+            }),
+            Action::MoveRight => self.operate(operator, MotionKind::Exclusive, |e| e.cx += 1),
+            Action::StartOfLine => self.operate(operator, MotionKind::Exclusive, |e| {
+                let _ = e.move_start_of_line();
+            }),
+            Action::FirstNonBlank => self.operate(operator, MotionKind::Exclusive, |e| {
+                e.cx = e.first_non_blank();
+            }),
+            Action::EndOfLine => self.operate(operator, MotionKind::Inclusive, |e| {
+                let _ = e.move_end_of_line();
+            }),
+            Action::MatchingBracket => self.operate(operator, MotionKind::Inclusive, |e| {
+                let _ = e.move_to_matching_bracket();
+            }),
+            Action::NextOccurrence | Action::PreviousOccurrence => {
+                self.operate(operator, MotionKind::Exclusive, |e| {
+                    e.move_to_occurrence(action == Action::NextOccurrence);
+                })
+            }
+            _ => Ok(false),
         }
-
-        true
     }
 
-    fn move_to(&mut self, x: usize, y: usize) -> bool {
-        if y > self.vheight - 1 {
-            return false;
-        }
+    /// Applies `operator` over `count` word motions. Like vim, `cw` on a word changes up to its
+    /// end as `ce` would, and `dw` on the last word of a line stops at the end of the line.
+    fn operate_word(
+        &mut self,
+        operator: Operator,
+        motion: WordMotion,
+        big: bool,
+        count: usize,
+    ) -> anyhow::Result<bool> {
+        let from = (self.bx(), self.by());
+        let on_word = self
+            .line()
+            .and_then(|line| line.chars().nth(from.0))
+            .is_some_and(|c| !c.is_whitespace());
+        let change_word =
+            operator == Operator::Change && motion == WordMotion::NextStart && on_word;
+        let kind = if change_word {
+            MotionKind::Inclusive
+        } else {
+            motion.kind()
+        };
 
-        self.cx = x - self.vleft;
-        self.cy = y;
-        if self.cx > self.current_line_len() {
-            self.cx = self.current_line_len() - 1;
-        }
+        self.operate(operator, kind, |e| {
+            let mut to = from;
+            for i in 0..count {
+                to = match (change_word, i) {
+                    (true, 0) => word::current_end(&e.buffer, to, big),
+                    (true, _) => word::find(&e.buffer, to, WordMotion::NextEnd, big),
+                    (false, _) => word::find(&e.buffer, to, motion, big),
+                };
+            }
+            if motion == WordMotion::NextStart && !change_word && to.1 > from.1 {
+                let y = to.1 - 1;
+                to = (e.buffer.line_len(y), y);
+            }
+            e.move_to_position(to);
+        })
+    }
 
-        return true;
+    /// Runs `motion` from the cursor and applies `operator` to the text it moved over, leaving
+    /// the cursor where the operator puts it rather than where the motion ended.
+    fn operate(
+        &mut self,
+        operator: Operator,
+        kind: MotionKind,
+        motion: impl FnOnce(&mut Self),
+    ) -> anyhow::Result<bool> {
+        self.operate_if_moved(operator, kind, |e| {
+            motion(e);
+            true
+        })
     }
 
-    fn move_to_next_page(&mut self) {
-        if self.buffer.len() > self.vtop + self.vheight {
-            self.vtop += self.vheight;
+    /// Like [`Editor::operate`], for motions that can fail, like `fz` on a line without a `z`,
+    /// which cancel the operator when they return false.
+    fn operate_if_moved(
+        &mut self,
+        operator: Operator,
+        kind: MotionKind,
+        motion: impl FnOnce(&mut Self) -> bool,
+    ) -> anyhow::Result<bool> {
+        let view = (self.cx, self.cy, self.vtop);
+        let from = (self.bx(), self.by());
+        let moved = motion(self);
+        let to = (self.bx(), self.by());
+        (self.cx, self.cy, self.vtop) = view;
+        if !moved {
+            return Ok(false);
+        }
+
+        // positions compare by line first
+        let (start, end) = if (from.1, from.0) <= (to.1, to.0) {
+            (from, to)
         } else {
-            self.vtop = self.buffer.len() - self.vheight;
+            (to, from)
+        };
+
+        match kind {
+            MotionKind::Linewise => self.apply_operator_lines(operator, start.1, end.1),
+            MotionKind::Inclusive => self.apply_operator(operator, start, (end.0 + 1, end.1)),
+            MotionKind::Exclusive => self.apply_operator(operator, start, end),
         }
+
+        Ok(true)
     }
 
-    fn move_to_line(&mut self, line: usize) {
-        self.vtop = line;
+    /// Applies `operator` to the text between `start` (inclusive) and `end` (exclusive).
+    fn apply_operator(&mut self, operator: Operator, start: (usize, usize), end: (usize, usize)) {
+        let text = match operator {
+            Operator::Yank => self.buffer.slice(start, end),
+            Operator::Delete | Operator::Change => {
+                self.buffer.dirty = true;
+                self.buffer.remove(start, end)
+            }
+            Operator::Indent | Operator::Dedent | Operator::Reindent => {
+                // shifting always acts on whole lines, leaving out the line of a bracket the
+                // range starts after or ends before, as in `>i{`
+                let mut first = start.1;
+                if start.0 >= self.buffer.line_len(first) && first < end.1 {
+                    first += 1;
+                }
+                let mut last = end.1;
+                let line = self.buffer.line(last).unwrap_or_default();
+                if end.0 <= indent::leading_whitespace(&line).chars().count() && last > first {
+                    last -= 1;
+                }
+                self.indent_lines(operator, first, last);
+                return;
+            }
+            Operator::Comment => {
+                // comments always go on whole lines, as in `gcip` or `gcj`
+                self.toggle_comments(start.1, end.1);
+                return;
+            }
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                // the case changes in place, leaving the register alone
+                let text = self.buffer.slice(start, end);
+                if let Some(changed) = operator.change_case(&text).filter(|c| *c != text) {
+                    self.buffer.remove(start, end);
+                    self.buffer.insert(start, &changed);
+                    self.buffer.dirty = true;
+                }
+                self.move_to_position(start);
+                return;
+            }
+        };
+        self.set_register(Register::charwise(text));
+
+        self.move_to_position(start);
+        if operator == Operator::Change {
+            self.mode = Mode::Insert;
+        }
+    }
+
+    /// Applies `operator` to lines `start..=end`. Changing lines leaves a single line with the
+    /// indentation of the first to insert into.
+    fn apply_operator_lines(&mut self, operator: Operator, start: usize, end: usize) {
+        let end = end.min(self.buffer.len() - 1);
+        if let Operator::Indent | Operator::Dedent | Operator::Reindent = operator {
+            self.indent_lines(operator, start, end);
+            return;
+        }
+        if operator == Operator::Comment {
+            self.toggle_comments(start, end);
+            return;
+        }
+        if let Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase = operator {
+            let x = self.bx();
+            let len = self.buffer.line_len(end);
+            self.apply_operator(operator, (0, start), (len, end));
+            self.move_to_position((x, start));
+            return;
+        }
+
+        let mut register = String::new();
+        for y in start..=end {
+            register.push_str(&self.buffer.line(y).unwrap_or_default());
+            register.push('\n');
+        }
+        self.set_register(Register::linewise(register));
+
+        match operator {
+            Operator::Yank => {
+                self.move_to_position((self.bx(), start));
+            }
+            Operator::Delete => {
+                for _ in start..=end {
+                    self.buffer.remove_line(start);
+                }
+                self.buffer.dirty = true;
+                self.move_to_position((0, start.min(self.buffer.len() - 1)));
+            }
+            Operator::Indent
+            | Operator::Dedent
+            | Operator::Reindent
+            | Operator::Lowercase
+            | Operator::Uppercase
+            | Operator::ToggleCase
+            | Operator::Comment => {}
+            Operator::Change => {
+                for _ in start..end {
+                    self.buffer.remove_line(start + 1);
+                }
+                // the line keeps its indentation, as a line opened with `o` would get
+                let line = self.buffer.line(start).unwrap_or_default();
+                let indent = indent::leading_whitespace(&line).chars().count();
+                let len = self.buffer.line_len(start);
+                self.buffer.remove((indent, start), (len, start));
+                self.buffer.dirty = true;
+                self.move_to_position((indent, start));
+                self.mode = Mode::Insert;
+            }
+        }
+    }
+
+    /// Adds `delta` to the number under or after the cursor, leaving the cursor on its last
+    /// char. Returns false when there's no number to change.
+    fn increment(&mut self, delta: i64) -> bool {
+        let y = self.by();
+        let line = self.line().unwrap_or_default();
+        let Some((start, end, text)) = increment::increment(&line, self.bx(), delta) else {
+            return false;
+        };
+
+        self.buffer.remove((start, y), (end, y));
+        self.buffer.insert((start, y), &text);
+        self.buffer.dirty = true;
+        self.move_to_position((start + text.chars().count() - 1, y));
+        true
+    }
+
+    /// Applies `operator` from the cursor up to `end` on the same or a later line, as `D`, `C`
+    /// and `s` do. Changing with nothing to cover, as on an empty line, still starts insert mode.
+    fn operate_until(&mut self, operator: Operator, end: (usize, usize)) {
+        let y = self.by();
+        let start = (self.bx().min(self.buffer.line_len(y)), y);
+        if start != end {
+            self.apply_operator(operator, start, end);
+        } else if operator == Operator::Change {
+            self.mode = Mode::Insert;
+        }
+    }
+
+    /// Toggles line comments on lines `start..=end` and moves to the first non-blank char of the
+    /// first one.
+    fn toggle_comments(&mut self, start: usize, end: usize) {
+        // a `comment` in the language's config goes over the built-in marker
+        let configured = self
+            .language_config()
+            .and_then(|config| config.comment.clone());
+        let language = self.buffer.language.as_ref();
+        let builtin = language.and_then(|language| language.line_comment());
+        let Some(marker) = configured.or(builtin.map(str::to_string)) else {
+            self.message = Some(Message::Error(
+                "No line comments for this buffer".to_string(),
+            ));
+            return;
+        };
+
+        let end = end.min(self.buffer.len() - 1);
+        let lines: Vec<_> = (start..=end)
+            .map(|y| self.buffer.line(y).unwrap_or_default())
+            .collect();
+        for (y, (line, toggled)) in
+            (start..).zip(lines.iter().zip(comment::toggle(&lines, &marker)))
+        {
+            if *line != toggled {
+                self.buffer.set_line(y, &toggled);
+                self.buffer.dirty = true;
+            }
+        }
+
+        let line = self.buffer.line(start).unwrap_or_default();
+        let x = indent::leading_whitespace(&line).chars().count();
+        self.move_to_position((x, start));
+    }
+
+    /// Applies `>`, `<` or `=` to lines `start..=end`.
+    fn indent_lines(&mut self, operator: Operator, start: usize, end: usize) {
+        match operator {
+            Operator::Reindent => self.reindent_lines(start, end),
+            _ => self.shift_lines(operator == Operator::Indent, start, end),
+        }
+    }
+
+    /// Indents lines `start..=end` by the syntax tree around them and moves to the first
+    /// non-blank char of the first line. Blank lines are emptied, and lines the tree can't tell,
+    /// like ones in a string, are left alone.
+    fn reindent_lines(&mut self, start: usize, end: usize) {
+        let end = end.min(self.buffer.len() - 1);
+        if let (Some(language), Some(tree)) = (self.buffer.language, self.buffer.syntax_tree()) {
+            let unit = self.indent_unit();
+            let text = self.buffer.to_string();
+            let levels = indent::levels(&language, &tree, &text, start..=end);
+            for (y, level) in (start..=end).zip(levels) {
+                let (Some(line), Some(level)) = (self.buffer.line(y), level) else {
+                    continue;
+                };
+                let reindented = match line.trim_start() {
+                    "" => String::new(),
+                    text => format!("{}{text}", unit.repeat(level)),
+                };
+                if reindented != line {
+                    self.buffer.set_line(y, &reindented);
+                    self.buffer.dirty = true;
+                }
+            }
+        }
+
+        let line = self.buffer.line(start).unwrap_or_default();
+        let x = indent::leading_whitespace(&line).chars().count();
+        self.move_to_position((x, start));
+    }
+
+    /// Indents or dedents lines `start..=end` by a level and moves to the first non-blank char
+    /// of the first line.
+    fn shift_lines(&mut self, indent: bool, start: usize, end: usize) {
+        let unit = self.indent_unit();
+        for y in start..=end.min(self.buffer.len() - 1) {
+            let Some(line) = self.buffer.line(y) else {
+                continue;
+            };
+            let shifted = if indent {
+                indent::indent_line(&line, &unit)
+            } else {
+                indent::dedent_line(&line, self.tab_size())
+            };
+            if shifted != line {
+                self.buffer.set_line(y, &shifted);
+                self.buffer.dirty = true;
+            }
+        }
+
+        let line = self.buffer.line(start).unwrap_or_default();
+        let x = indent::leading_whitespace(&line).chars().count();
+        self.move_to_position((x, start));
+    }
+
+    fn handle_normal_event(&mut self, ev: Event) -> anyhow::Result<bool> {
+        let mut redraw = false;
+        match ev {
+            Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: _modifiers,
+            }) => match kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    debug!("mouse up: {}, {}", column, row);
+                    let (x, y) = (column as usize, row as usize);
+                    let y = y.saturating_sub(self.tabline_rows());
+                    redraw = self.move_to(x, y);
+                    // clicking the line numbers selects the line
+                    if redraw && x < self.vleft {
+                        self.select_line();
+                    }
+                }
+                MouseEventKind::Drag(MouseButton::Left) => {
+                    debug!("mouse drag: {}, {}", column, row);
+                }
+                MouseEventKind::ScrollUp => {
+                    redraw = self.run_action(Action::ScrollUp)?;
+                }
+                MouseEventKind::ScrollDown => {
+                    redraw = self.run_action(Action::ScrollDown)?;
+                }
+                _ => {}
+            },
+            Event::Key(event) => {
+                if let Some(action) = self.config.keymap.normal(&event) {
+                    // motions and `x` repeat with a count, `@` takes it as the number of replays
+                    // and `g` passes it on to the key after it
+                    let times = match action {
+                        Action::GotoPrefix => 1,
+                        _ if action.is_motion() || action == Action::DeleteChar => {
+                            self.count.take().unwrap_or(1)
+                        }
+                        _ => 1,
+                    };
+                    if let Action::PutAfter | Action::PutBefore = action {
+                        if self.refuse_edit() {
+                            return Ok(true);
+                        }
+                        let count = self.count.take().unwrap_or(1);
+                        return Ok(self.put(action == Action::PutBefore, count));
+                    }
+                    for _ in 0..times {
+                        redraw |= self.run_action(action.clone())?;
+                    }
+                    // operators take the count when their motion is typed, as in `3>>`, or after
+                    // the prefix that starts them, as in `3gcc`, and a register is picked for the
+                    // command after it, as in `"+yy`
+                    if Operator::from_action(&action).is_some()
+                        || action == Action::SelectRegister
+                        || action == Action::GotoPrefix
+                    {
+                        return Ok(redraw);
+                    }
+                }
+                self.count = None;
+                self.register_name = None;
+            }
+            _ => {}
+        }
+
+        Ok(redraw)
+    }
+
+    /// Runs a key bound action. Returns true if a redraw is needed.
+    fn run_action(&mut self, action: Action) -> anyhow::Result<bool> {
+        if self.recent_actions.len() == RECENT_ACTIONS {
+            self.recent_actions.pop_front();
+        }
+        self.recent_actions.push_back(format!("{action:?}"));
+
+        if action.is_edit() && self.refuse_edit() {
+            return Ok(true);
+        }
+        let mut redraw = true;
+        if action.is_jump() {
+            self.record_jump();
+        }
+        match action {
+            Action::Nop => redraw = false,
+            Action::MoveLeft => redraw = self.move_left()?,
+            Action::MoveDown => redraw = self.move_down(),
+            Action::MoveUp => redraw = self.move_up()?,
+            Action::MoveRight => redraw = self.move_right()?,
+            Action::DisplayLineDown => redraw = self.move_down_display_line(),
+            Action::DisplayLineUp => redraw = self.move_up_display_line()?,
+            Action::NextWord
+            | Action::PreviousWord
+            | Action::EndOfWord
+            | Action::PreviousEndOfWord
+            | Action::NextBigWord
+            | Action::PreviousBigWord
+            | Action::EndOfBigWord
+            | Action::PreviousEndOfBigWord => {
+                if let Some((motion, big)) = action.word_motion() {
+                    self.move_by_word(motion, big);
+                }
+            }
+            Action::StartOfLine => redraw = self.move_start_of_line()?,
+            Action::FirstNonBlank => self.cx = self.first_non_blank(),
+            Action::EndOfLine => redraw = self.move_end_of_line()?,
+            Action::MatchingBracket => redraw = self.move_to_matching_bracket()?,
+            Action::StartOfBuffer => self.move_to_start_of_buffer(),
+            Action::EndOfBuffer => self.move_to_end_of_buffer(),
+            Action::MiddleOfViewport => redraw = self.move_to_middle_of_viewport(),
+            Action::PageDown => {
+                self.move_to_next_page();
+                self.move_into_margin();
+            }
+            Action::PageUp => {
+                self.move_to_previous_page()?;
+                self.move_into_margin();
+            }
+            Action::ScrollDown => {
+                redraw = self.scroll_by(self.config.mouse_scroll_lines as isize);
+                self.move_into_margin();
+            }
+            Action::ScrollUp => {
+                redraw = self.scroll_by(-(self.config.mouse_scroll_lines as isize));
+                self.move_into_margin();
+            }
+            Action::HalfPageDown => self.scroll_half_page(true),
+            Action::HalfPageUp => self.scroll_half_page(false),
+            Action::CenterLine => redraw = self.move_line_to_row(self.vheight / 2),
+            Action::LineToTop => redraw = self.move_line_to_row(0),
+            Action::LineToBottom => redraw = self.move_line_to_row(self.vheight.saturating_sub(1)),
+            Action::GoToLine(line) => {
+                if line == 0 {
+                    self.move_to_start_of_buffer();
+                } else if line >= self.buffer.len() - 1 {
+                    self.move_to_end_of_buffer();
+                } else {
+                    self.move_to_line(line);
+                }
+            }
+            Action::FindChar | Action::FindCharBackward | Action::Till | Action::TillBackward => {
+                if let Some(search) = action.char_search() {
+                    self.pending = Pending::FindChar(None, search);
+                }
+                redraw = false;
+            }
+            Action::JumpBack => {
+                let jump = self.buffer.jumps.back((self.bx(), self.by()));
+                redraw = self.jump_to(jump);
+            }
+            Action::JumpForward => {
+                let jump = self.buffer.jumps.forward();
+                redraw = self.jump_to(jump);
+            }
+            Action::ListJumps => self.list_jumps(),
+            Action::ShowMessages => self.show_messages(),
+            Action::SetMark => {
+                self.pending = Pending::SetMark;
+                redraw = false;
+            }
+            Action::GoToMark | Action::GoToMarkLine => {
+                self.pending = Pending::GoToMark(None, action == Action::GoToMarkLine);
+                redraw = false;
+            }
+            Action::GoToDefinition => self.lsp_request(lsp::Client::definition),
+            Action::Hover => self.lsp_request(lsp::Client::hover),
+            Action::RepeatFind => redraw = self.repeat_find(false),
+            Action::RepeatFindBackward => redraw = self.repeat_find(true),
+            Action::NextMisspelling => self.go_to_misspelling(true),
+            Action::PreviousMisspelling => self.go_to_misspelling(false),
+            Action::SuggestSpelling => {
+                self.suggest_spelling();
+                redraw = false;
+            }
+            Action::NextWindow => self.go_to_window(None),
+            Action::LeftWindow => self.go_to_window(Some(0)),
+            Action::RightWindow => self.go_to_window(Some(1)),
+            Action::NextConflict => self.go_to_conflict(true),
+            Action::PreviousConflict => self.go_to_conflict(false),
+            Action::NextHunk => self.go_to_hunk(true),
+            Action::PreviousHunk => self.go_to_hunk(false),
+            Action::NextFunction => self.go_to_function(true),
+            Action::PreviousFunction => self.go_to_function(false),
+            Action::StartOfNode => self.go_to_node_edge(false),
+            Action::EndOfNode => self.go_to_node_edge(true),
+            Action::ExpandSelection => self.expand_selection(),
+            Action::GotoPrefix
+            | Action::ScrollPrefix
+            | Action::NextPrefix
+            | Action::PreviousPrefix
+            | Action::Leader
+            | Action::WindowPrefix => {
+                let prefix = match action {
+                    Action::GotoPrefix => 'g',
+                    Action::ScrollPrefix => 'z',
+                    Action::NextPrefix => ']',
+                    Action::PreviousPrefix => '[',
+                    Action::WindowPrefix => WINDOW_PREFIX,
+                    _ => ' ',
+                };
+                self.pending = Pending::Prefix(None, prefix);
+                redraw = false;
+            }
+            Action::Delete
+            | Action::Change
+            | Action::Yank
+            | Action::Indent
+            | Action::Dedent
+            | Action::Reindent
+            | Action::Lowercase
+            | Action::Uppercase
+            | Action::Comment => {
+                if let Some(operator) = Operator::from_action(&action) {
+                    self.pending = Pending::Operator(operator);
+                }
+                redraw = false;
+            }
+            Action::Insert => self.mode = Mode::Insert,
+            Action::Append => {
+                self.move_right()?;
+                self.mode = Mode::Insert;
+            }
+            Action::OpenBelow => {
+                self.open_line_below();
+                self.mode = Mode::Insert;
+            }
+            Action::OpenAbove => {
+                self.open_line_above();
+                self.mode = Mode::Insert;
+            }
+            Action::ReplaceChar => {
+                self.pending = Pending::Replace(self.count.take().unwrap_or(1));
+                redraw = false;
+            }
+            Action::ReplaceMode => {
+                self.replaced.clear();
+                self.mode = Mode::Replace;
+            }
+            Action::DeleteChar => {
+                let x = self.bx();
+                let y = self.by();
+                if y < self.buffer.len() {
+                    if self.buffer.remove_char(x, y).is_some() {
+                        self.buffer.dirty = true;
+                    }
+                } else {
+                    warn!("line out of bounds: x: {}, y: {}", x, y);
+                }
+            }
+            Action::DeleteToEndOfLine | Action::ChangeToEndOfLine => {
+                let count = self.count.take().unwrap_or(1);
+                let y = (self.by() + count - 1).min(self.buffer.len().saturating_sub(1));
+                let end = (self.buffer.line_len(y), y);
+                let operator = match action {
+                    Action::DeleteToEndOfLine => Operator::Delete,
+                    _ => Operator::Change,
+                };
+                self.operate_until(operator, end);
+            }
+            Action::ToggleCase => {
+                let count = self.count.take().unwrap_or(1);
+                let (x, y) = (self.bx(), self.by());
+                let end = ((x + count).min(self.buffer.line_len(y)), y);
+                if (x, y) != end {
+                    self.apply_operator(Operator::ToggleCase, (x, y), end);
+                    self.move_to_position((end.0.min(self.buffer.line_len(y) - 1), y));
+                }
+            }
+            Action::Increment | Action::Decrement => {
+                let count = self.count.take().unwrap_or(1) as i64;
+                let delta = if action == Action::Increment {
+                    count
+                } else {
+                    -count
+                };
+                redraw = self.increment(delta);
+            }
+            Action::NextOccurrence | Action::PreviousOccurrence => {
+                redraw = self.move_to_occurrence(action == Action::NextOccurrence);
+            }
+            Action::SubstituteChar => {
+                let count = self.count.take().unwrap_or(1);
+                let y = self.by();
+                let end = ((self.bx() + count).min(self.buffer.line_len(y)), y);
+                self.operate_until(Operator::Change, end);
+            }
+            Action::SubstituteLine => {
+                let count = self.count.take().unwrap_or(1);
+                let y = self.by();
+                self.apply_operator_lines(Operator::Change, y, y + count - 1);
+            }
+            Action::DeleteLines(range) => {
+                let (start, end) = range.unwrap_or((self.by(), self.by()));
+                self.apply_operator_lines(Operator::Delete, start, end);
+            }
+            Action::Substitute(args, range) => {
+                let (start, end) = range.unwrap_or((self.by(), self.by()));
+                self.substitute(&args, start, end);
+            }
+            Action::JoinLines => {
+                if self.by() + 1 < self.buffer.len() {
+                    self.buffer.join_lines(self.by(), " ");
+                    self.buffer.dirty = true;
+                } else if self.line().is_none() {
+                    warn!("line out of bounds: x: {}, y: {}", self.bx(), self.by());
+                }
+            }
+            Action::CommandMode => self.mode = Mode::Command,
+            Action::VisualMode => {
+                self.anchor = Some((self.bx(), self.by()));
+                self.mode = Mode::Visual;
+            }
+            Action::NormalMode => self.mode = Mode::Normal,
+            Action::Backspace => {
+                self.remove_char()?;
+                self.move_left()?;
+            }
+            Action::Newline => self.split_line_at_cursor()?,
+            Action::Tab => {
+                let snippet = matches!(self.mode, Mode::Insert)
+                    && (self.expand_snippet() || self.next_tab_stop());
+                if !snippet {
+                    for c in self.indent_unit().chars() {
+                        self.insert_char(c)?;
+                        self.move_right()?;
+                    }
+                }
+            }
+            Action::InsertLiteral => {
+                self.literal = true;
+                redraw = false;
+            }
+            Action::PreviousTabStop => {
+                if let Some(session) = &mut self.snippet {
+                    if session.previous() {
+                        let start = session.range().start;
+                        self.move_to_idx(start);
+                    }
+                }
+            }
+            Action::Write(path, force) => {
+                self.write(path.as_deref(), force);
+            }
+            Action::WriteQuit(path, force) => {
+                if self.write(path.as_deref(), force) {
+                    self.quit = true;
+                }
+            }
+            Action::Exit(path, force) => {
+                if !self.buffer.unsaved() || self.write(path.as_deref(), force) {
+                    self.quit = true;
+                }
+            }
+            Action::Quit(force) => {
+                if force {
+                    self.quit = true;
+                } else if self.buffer.unsaved() {
+                    self.message = Some(Message::Error(
+                        "E37: No write since last change (add ! to override)".to_string(),
+                    ));
+                } else if let Some(i) =
+                    (0..self.buffers.len()).find(|&i| self.buffer_at(i).unsaved())
+                {
+                    self.message = Some(Message::Error(format!(
+                        "E162: No write since last change for buffer \"{}\"",
+                        self.buffer_at(i).name
+                    )));
+                } else {
+                    self.quit = true;
+                }
+            }
+            Action::Edit(path, force) => self.edit(path.as_deref(), force),
+            Action::NewBuffer => self.open_scratch(Buffer::scratch("[Scratch]", "")),
+            Action::Help => self.open_help(),
+            Action::NextBuffer => self.switch_buffer((self.current + 1) % self.buffers.len()),
+            Action::PreviousBuffer => {
+                let len = self.buffers.len();
+                self.switch_buffer((self.current + len - 1) % len);
+            }
+            Action::SwitchBuffer(n) => {
+                if n >= 1 && n <= self.buffers.len() {
+                    self.switch_buffer(n - 1);
+                } else {
+                    self.message = Some(Message::Error(format!("E86: Buffer {n} does not exist")));
+                }
+            }
+            Action::ListBuffers => self.list_buffers(),
+            Action::OpenPicker => self.open_picker(),
+            Action::Grep(pattern) => self.grep(&pattern),
+            Action::Make(args) => self.make(args.as_deref()),
+            Action::ChangeDir(dir, local) => self.change_dir(dir.as_deref(), local),
+            Action::PrintDir => match std::env::current_dir() {
+                Ok(dir) => self.message = Some(Message::Info(dir.display().to_string())),
+                Err(err) => self.message = Some(Message::Error(err.to_string())),
+            },
+            Action::Shell(command) => self.shell(&command),
+            Action::Plugin(name, key) => self.plugin_keybinding(&name, &key),
+            Action::Filter(command, range) => self.filter(&command, range),
+            Action::NextQuickfix => {
+                if self.quickfix.select_next() {
+                    self.jump_to_quickfix();
+                } else {
+                    self.quickfix_error();
+                }
+            }
+            Action::PreviousQuickfix => {
+                if self.quickfix.select_previous() {
+                    self.jump_to_quickfix();
+                } else {
+                    self.quickfix_error();
+                }
+            }
+            Action::OpenQuickfix => {
+                if self.quickfix.is_empty() {
+                    self.quickfix_error();
+                } else {
+                    self.quickfix.open = true;
+                }
+            }
+            Action::Format => {
+                if let Err(err) = self.format() {
+                    self.message = Some(Message::Error(err.to_string()));
+                }
+            }
+            Action::ToggleBlame => self.toggle_blame(),
+            Action::InspectHighlight => self.inspect_highlight()?,
+            Action::AcceptOurs => self.resolve_conflict(Resolution::Ours),
+            Action::AcceptTheirs => self.resolve_conflict(Resolution::Theirs),
+            Action::AcceptBoth => self.resolve_conflict(Resolution::Both),
+            Action::DiffSplit(path) => self.diff_split(&path),
+            Action::DiffOff => self.diff = None,
+            Action::DiffReload => self.diff_reload(),
+            Action::DiffGet(range) => self.diff_copy(range, false),
+            Action::DiffPut(range) => self.diff_copy(range, true),
+            Action::Theme(name) => self.set_theme(name.as_deref()),
+            Action::Set(option) => self.set_option(&option),
+            Action::Abbreviate(args) => self.abbreviate(args.as_deref()),
+            Action::Unabbreviate(abbreviation) => {
+                self.abbreviations.remove(&abbreviation);
+                if self.config.abbreviations.remove(&abbreviation).is_none() {
+                    self.message = Some(Message::Error("E24: No such abbreviation".to_string()));
+                }
+            }
+            Action::PutAfter => redraw = self.put(false, 1),
+            Action::PutBefore => redraw = self.put(true, 1),
+            Action::SelectRegister => {
+                self.pending = Pending::Register;
+                redraw = false;
+            }
+            Action::RecordMacro => {
+                if let Some((register, mut events)) = self.recording.take() {
+                    // drops the `q` that stopped the recording
+                    events.pop();
+                    self.macros.insert(register, events);
+                } else {
+                    self.pending = Pending::Record;
+                }
+            }
+            Action::PlayMacro => {
+                self.pending = Pending::Play(self.count.take().unwrap_or(1));
+                redraw = false;
+            }
+            Action::RepeatChange => {
+                let count = self.count.take();
+                self.repeat_change(count);
+                redraw = false;
+            }
+        }
+
+        Ok(redraw)
+    }
+
+    /// Scrolls the viewport `lines` down, or up when negative, leaving the cursor on its line
+    /// unless that goes off screen, where it moves to the nearest line still shown. Returns
+    /// whether it scrolled.
+    fn scroll_by(&mut self, lines: isize) -> bool {
+        let (vtop, y) = (self.vtop, self.by());
+        let last = self.buffer.len().saturating_sub(1);
+        self.vtop = if lines > 0 {
+            let max_top = self.buffer.len().saturating_sub(self.vheight);
+            (self.vtop + lines as usize).min(max_top.max(self.vtop))
+        } else {
+            self.vtop.saturating_sub(lines.unsigned_abs())
+        };
+        if self.vtop == vtop {
+            return false;
+        }
+
+        let bottom = (self.vtop + self.vheight.saturating_sub(1)).min(last);
+        self.cy = y.clamp(self.vtop, bottom.max(self.vtop)) - self.vtop;
+        if self.wrapping() {
+            // long lines take more than a row, so fewer of them fit below the top
+            let viewport = self.viewport();
+            let rows = |cy: usize| -> usize {
+                (self.vtop..=self.vtop + cy)
+                    .map(|line| viewport.rows(self.buffer.line_len(line)))
+                    .sum()
+            };
+            while self.cy > 0 && rows(self.cy) > self.vheight {
+                self.cy -= 1;
+            }
+        }
+        true
+    }
+
+    fn move_to(&mut self, x: usize, y: usize) -> bool {
+        if y >= self.vheight {
+            return false;
+        }
+
+        if self.wrapping() {
+            // finds the line drawn on row `y` and the offset of that row within it
+            let viewport = self.viewport();
+            let mut row = 0;
+            for line in self.vtop..self.buffer.len() {
+                let rows = viewport.rows(self.buffer.line_len(line));
+                if y < row + rows {
+                    self.cy = line - self.vtop;
+                    self.cx = (y - row) * self.vwidth + x.saturating_sub(self.vleft);
+                    return true;
+                }
+                row += rows;
+            }
+            return false;
+        }
+
+        if self.vtop + y >= self.buffer.len() {
+            return false;
+        }
+        self.cx = x.saturating_sub(self.vleft) + self.hscroll;
+        self.cy = y;
+        if self.cx > self.current_line_len() {
+            self.cx = self.current_line_len().saturating_sub(1);
+        }
+
+        true
+    }
+
+    fn move_to_next_page(&mut self) {
+        if self.buffer.len() > self.vtop + self.vheight {
+            self.vtop += self.vheight;
+        } else {
+            self.vtop = self.buffer.len().saturating_sub(self.vheight);
+        }
+    }
+
+    fn move_to_line(&mut self, line: usize) {
+        self.vtop = line;
         self.cy = 0;
         self.move_to_start_of_line();
     }
@@ -867,234 +2907,3323 @@ impl Editor {
     }
 
     fn move_to_end_of_buffer(&mut self) {
-        self.vtop = self.buffer.len() - self.vheight;
+        self.vtop = self.buffer.len().saturating_sub(self.vheight);
         self.move_to_end_of_viewport();
     }
 
-    fn move_to_middle_of_viewport(&mut self) -> bool {
-        let mid_y = self.vheight / 2;
-        if self.cy != mid_y {
-            if self.vtop + mid_y <= self.buffer.len() - 1 {
-                self.cy = mid_y;
-            } else {
-                let max_y = self.buffer.len() - self.vtop;
-                self.cy = max_y - 1;
-            }
-            return true;
+    fn move_to_middle_of_viewport(&mut self) -> bool {
+        let mid_y = self.vheight / 2;
+        if self.cy != mid_y {
+            if self.vtop + mid_y < self.buffer.len() {
+                self.cy = mid_y;
+            } else {
+                self.cy = self.buffer.len().saturating_sub(self.vtop + 1);
+            }
+            return true;
+        }
+        false
+    }
+    fn move_to_end_of_viewport(&mut self) {
+        if self.buffer.len() > self.vheight {
+            self.cy = self.vheight.saturating_sub(1);
+        } else {
+            self.cy = self.buffer.len().saturating_sub(1);
+        }
+    }
+
+    fn move_to_previous_page(&mut self) -> anyhow::Result<()> {
+        if self.vtop > self.vheight {
+            self.vtop -= self.vheight;
+        } else {
+            self.vtop = 0;
+        }
+        Ok(())
+    }
+
+    /// Moves by words, or by WORDs when `big`, as `w`, `b`, `e` and `ge` do.
+    fn move_by_word(&mut self, motion: WordMotion, big: bool) {
+        let (x, y) = word::find(&self.buffer, (self.bx(), self.by()), motion, big);
+        self.move_to_position((x.min(self.buffer.line_len(y).saturating_sub(1)), y));
+    }
+
+    /// Handles visual mode input. Motions are forwarded to normal mode so they extend the
+    /// selection, while operators act on the selected range and return to normal mode.
+    fn handle_visual_input(&mut self, ev: Event) -> anyhow::Result<bool> {
+        if self.pending != Pending::None {
+            self.handle_normal_input(ev)?;
+            return Ok(true);
+        }
+
+        if let Event::Key(event) = &ev {
+            // `i` and `a` select text objects here, while `u` and `U`, unbound in normal mode,
+            // change case
+            let edits = match self.config.keymap.normal(event) {
+                Some(Action::Insert | Action::Append) => false,
+                Some(action) => action.is_edit(),
+                None => matches!(event.code, KeyCode::Char('u' | 'U')),
+            };
+            if edits && self.refuse_edit() {
+                self.exit_visual();
+                return Ok(true);
+            }
+        }
+
+        match ev {
+            Event::Key(event) => match self.config.keymap.normal(&event) {
+                Some(Action::Delete | Action::DeleteChar) => {
+                    if let Some(selection) = self.selection() {
+                        let text = self.delete_range(&selection);
+                        self.set_register(Register::charwise(text));
+                        self.move_to_position(selection.start);
+                    }
+                    self.exit_visual();
+                }
+                Some(Action::Yank) => {
+                    if let Some(selection) = self.selection() {
+                        let text = self.range_text(&selection);
+                        self.set_register(Register::charwise(text));
+                        self.move_to_position(selection.start);
+                    }
+                    self.exit_visual();
+                }
+                // `u` and `U` have no use in normal mode but change the selection's case here
+                action @ (None | Some(Action::ToggleCase))
+                    if action.is_some() || matches!(event.code, KeyCode::Char('u' | 'U')) =>
+                {
+                    let operator = match event.code {
+                        KeyCode::Char('u') => Operator::Lowercase,
+                        KeyCode::Char('U') => Operator::Uppercase,
+                        _ => Operator::ToggleCase,
+                    };
+                    if let Some(selection) = self.selection() {
+                        let (ex, ey) = selection.end;
+                        self.apply_operator(operator, selection.start, (ex + 1, ey));
+                    }
+                    self.exit_visual();
+                }
+                Some(action @ (Action::Indent | Action::Dedent | Action::Reindent)) => {
+                    if let (Some(selection), Some(operator)) =
+                        (self.selection(), Operator::from_action(&action))
+                    {
+                        let (start, end) = (selection.start.1, selection.end.1);
+                        self.indent_lines(operator, start, end);
+                    }
+                    self.exit_visual();
+                }
+                Some(Action::VisualMode | Action::NormalMode) => self.exit_visual(),
+                Some(Action::ExpandSelection) => self.expand_selection(),
+                Some(Action::CommandMode) => {
+                    // the command applies to the selected lines, through the `'<` and `'>` marks
+                    if let Some(selection) = self.selection() {
+                        self.buffer.set_mark('<', selection.start);
+                        self.buffer.set_mark('>', selection.end);
+                    }
+                    self.exit_visual();
+                    self.mode = Mode::Command;
+                    "'<,'>".chars().for_each(|c| self.command_line.insert(c));
+                }
+                Some(action @ (Action::Insert | Action::Append)) => {
+                    self.pending = Pending::TextObject(None, action == Action::Insert);
+                }
+                Some(action) if action.is_motion() || action == Action::SelectRegister => {
+                    self.handle_normal_input(ev)?;
+                }
+                _ => {}
+            },
+            Event::Mouse(_) => {
+                self.handle_normal_input(ev)?;
+            }
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    /// Selects from `start` up to the exclusive `end`, as `viw` does. The selection is charwise,
+    /// so a range of whole lines stops at the last char of its last line.
+    fn select_range(&mut self, start: (usize, usize), (x, y): (usize, usize)) {
+        let end = match (x, y) {
+            (0, 0) => (0, 0),
+            (0, y) => (self.buffer.line_len(y - 1).saturating_sub(1), y - 1),
+            (x, y) => (x - 1, y),
+        };
+        self.anchor = Some(start);
+        self.move_to_position(end);
+    }
+
+    /// Selects the cursor line in visual mode, from its first char to its last.
+    fn select_line(&mut self) {
+        let y = self.by();
+        self.mode = Mode::Visual;
+        self.anchor = Some((0, y));
+        self.move_to_position((self.buffer.line_len(y).saturating_sub(1), y));
+    }
+
+    fn exit_visual(&mut self) {
+        self.anchor = None;
+        self.mode = Mode::Normal;
+    }
+
+    fn selection(&self) -> Option<Selection> {
+        if !matches!(self.mode, Mode::Visual) {
+            return None;
+        }
+
+        self.anchor
+            .map(|anchor| Selection::new(anchor, (self.bx(), self.by())))
+    }
+
+    /// Moves the cursor to the buffer position `(x, y)`, scrolling the viewport if needed.
+    fn move_to_position(&mut self, (x, y): (usize, usize)) {
+        if y < self.vtop || y >= self.vtop + self.vheight {
+            self.vtop = y;
+            self.cy = 0;
+        } else {
+            self.cy = y - self.vtop;
+        }
+        self.cx = x;
+    }
+
+    /// Keeps yanked or deleted text for `p`, also copying it to the system clipboard when `"+`
+    /// picked it.
+    fn set_register(&mut self, register: Register) {
+        if let Some('+' | '*') = self.register_name.take() {
+            if let Err(err) = clipboard::copy(&register.text) {
+                self.message = Some(Message::Error(err.to_string()));
+            }
+            self.clipboard = Some(register.clone());
+        }
+        self.register = register;
+    }
+
+    /// Puts the register `count` times after the cursor, or before it, leaving the cursor on the
+    /// last char put. Whole lines go below or above the cursor line instead, with the cursor on
+    /// the first of them. `"+p` puts the system clipboard.
+    fn put(&mut self, before: bool, count: usize) -> bool {
+        let register = match self.register_name.take() {
+            Some('+' | '*') => match clipboard::paste() {
+                Ok(text) => Register::from_clipboard(text),
+                Err(err) => match &self.clipboard {
+                    Some(register) => register.clone(),
+                    None => {
+                        self.message = Some(Message::Error(err.to_string()));
+                        return true;
+                    }
+                },
+            },
+            _ => self.register.clone(),
+        };
+        self.put_register(&register, before, count)
+    }
+
+    fn put_register(&mut self, register: &Register, before: bool, count: usize) -> bool {
+        if register.text.is_empty() {
+            return false;
+        }
+
+        let (x, y) = (self.bx(), self.by());
+        if register.linewise {
+            let first = if before { y } else { y + 1 };
+            let lines: Vec<_> = register.lines().collect();
+            for (i, line) in iter::repeat_n(&lines, count).flatten().enumerate() {
+                self.buffer.insert_line(first + i, line);
+            }
+            let line = self.buffer.line(first).unwrap_or_default();
+            let x = indent::leading_whitespace(&line).chars().count();
+            self.move_to_position((x, first));
+        } else {
+            let x = if before || self.buffer.line_len(y) == 0 {
+                x
+            } else {
+                x + 1
+            };
+            let end = self.buffer.insert((x, y), &register.text.repeat(count));
+            self.move_to_position(end);
+        }
+        self.buffer.dirty = true;
+        true
+    }
+
+    /// Returns the text covered by `selection`, with lines joined by `\n`.
+    fn range_text(&self, selection: &Selection) -> String {
+        let (ex, ey) = selection.end;
+        self.buffer.slice(selection.start, (ex + 1, ey))
+    }
+
+    /// Removes the text covered by `selection` from the buffer, returning it.
+    fn delete_range(&mut self, selection: &Selection) -> String {
+        let (ex, ey) = selection.end;
+        let text = self.buffer.remove(selection.start, (ex + 1, ey));
+        self.buffer.dirty = true;
+
+        text
+    }
+
+    fn handle_insert_input(&mut self, ev: Event) -> anyhow::Result<bool> {
+        let Event::Key(event) = ev else {
+            return Ok(true);
+        };
+
+        if std::mem::take(&mut self.literal) {
+            let c = match event.code {
+                KeyCode::Char(c) => Some(c),
+                KeyCode::Tab => Some('\t'),
+                _ => None,
+            };
+            if let Some(c) = c {
+                self.take_placeholder(&None, KeyCode::Char(c));
+                self.insert_char(c)?;
+                self.move_right()?;
+                self.sync_snippet();
+                return Ok(true);
+            }
+        }
+
+        let action = self.config.keymap.insert(&event);
+        // deleting the placeholder is all backspace and delete do to it
+        if self.take_placeholder(&action, event.code) && action.is_some() {
+            self.sync_snippet();
+            return Ok(true);
+        }
+
+        let boundary = match (&action, event.code) {
+            (Some(action), _) => matches!(action, Action::Newline | Action::NormalMode),
+            (None, KeyCode::Char(c)) => !word::is_word_char(c),
+            _ => false,
+        };
+        if boundary {
+            self.expand_abbreviation();
+        }
+
+        let autopairs = self.config.autopairs;
+        let line = self.line().unwrap_or_default();
+        let x = self.bx();
+        match (action, event.code) {
+            (Some(Action::Backspace), _) if autopairs && brackets::in_empty_pair(&line, x) => {
+                self.buffer.remove_char(x, self.by());
+                self.run_action(Action::Backspace)?;
+            }
+            (Some(action), _) => {
+                self.run_action(action)?;
+            }
+            (None, KeyCode::Char(c)) => {
+                let pair = if autopairs {
+                    brackets::auto_pair(&line, x, c)
+                } else {
+                    AutoPair::Insert
+                };
+                match pair {
+                    AutoPair::Insert => self.insert_char(c)?,
+                    AutoPair::Pair(close) => {
+                        self.insert_char(close)?;
+                        self.insert_char(c)?;
+                    }
+                    AutoPair::Skip => {}
+                }
+                self.move_right()?;
+            }
+            _ => {}
+        }
+        self.sync_snippet();
+
+        Ok(true)
+    }
+
+    /// Replaces the abbreviation that ends at the cursor with what it stands for.
+    fn expand_abbreviation(&mut self) {
+        let line = self.line().unwrap_or_default();
+        let (x, y) = (self.bx(), self.by());
+        let Some((start, expansion)) = abbrev::find(&self.config.abbreviations, &line, x) else {
+            return;
+        };
+        let expansion = expansion.to_string();
+        self.buffer.remove((start, y), (x, y));
+        self.move_to_position((start, y));
+        self.insert_text(&expansion);
+    }
+
+    /// Adds the abbreviation `:iabbrev` was given, or lists them all without arguments.
+    fn abbreviate(&mut self, args: Option<&str>) {
+        let Some(args) = args else {
+            let mut list: Vec<_> = self
+                .config
+                .abbreviations
+                .iter()
+                .map(|(abbreviation, expansion)| format!("{abbreviation} {expansion}"))
+                .collect();
+            list.sort();
+            let list = if list.is_empty() {
+                "No abbreviation found".to_string()
+            } else {
+                list.join(" | ")
+            };
+            self.message = Some(Message::Info(list));
+            return;
+        };
+
+        match abbrev::parse(args) {
+            Ok((abbreviation, expansion)) => {
+                self.abbreviations
+                    .insert(abbreviation.clone(), expansion.clone());
+                self.config.abbreviations.insert(abbreviation, expansion);
+            }
+            Err(err) => self.message = Some(Message::Error(err.to_string())),
+        }
+    }
+
+    /// Expands the snippet whose trigger is the word before the cursor, moving to its first tab
+    /// stop. Returns false when there's no such snippet.
+    fn expand_snippet(&mut self) -> bool {
+        let Some(dir) = snippet::snippets_dir() else {
+            return false;
+        };
+        let line = self.line().unwrap_or_default();
+        let (x, y) = (self.bx(), self.by());
+        let before: Vec<char> = line.chars().take(x).collect();
+        let start = before
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1);
+        let trigger: String = before[start..].iter().collect();
+        if trigger.is_empty() {
+            return false;
+        }
+
+        let language = self.buffer.language.map(|language| language.id());
+        let body = match snippet::find(&dir, language, &trigger) {
+            Ok(Some(body)) => body,
+            Ok(None) => return false,
+            Err(err) => {
+                self.message = Some(Message::Error(format!("Error reading snippets: {err}")));
+                return true;
+            }
+        };
+
+        // the lines after the first keep the indentation of the one the trigger is on
+        let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+        let body = body
+            .replace('\t', &self.indent_unit())
+            .replace('\n', &format!("\n{indent}"));
+        let snippet = Snippet::parse(&body);
+
+        self.buffer.remove((start, y), (x, y));
+        let idx = self.buffer.char_idx(start, y);
+        self.buffer.insert((start, y), &snippet.text);
+        self.buffer.dirty = true;
+        let session = snippet::Session::new(&snippet, idx, &self.buffer);
+        self.move_to_idx(session.range().start);
+        self.snippet = Some(session).filter(|session| !session.is_done());
+        true
+    }
+
+    /// Moves to the next tab stop of the snippet being filled in, which is done at the last one.
+    /// Returns false when there's no snippet.
+    fn next_tab_stop(&mut self) -> bool {
+        let Some(session) = &mut self.snippet else {
+            return false;
+        };
+        session.next();
+        let start = session.range().start;
+        if session.is_done() {
+            self.snippet = None;
+        }
+        self.move_to_idx(start);
+        true
+    }
+
+    /// Removes the placeholder of the tab stop the cursor was just moved to when the key types
+    /// or deletes, as typing replaces it. Returns whether it did.
+    fn take_placeholder(&mut self, action: &Option<Action>, code: KeyCode) -> bool {
+        let typing = matches!(
+            (action, code),
+            (None, KeyCode::Char(_)) | (Some(Action::Backspace | Action::DeleteChar), _)
+        );
+        let cursor = self.buffer.char_idx(self.bx(), self.by());
+        let Some(range) = self
+            .snippet
+            .as_mut()
+            .filter(|_| typing)
+            .and_then(|session| session.take_placeholder(cursor))
+        else {
+            return false;
+        };
+        let (start, end) = (
+            self.buffer.position(range.start),
+            self.buffer.position(range.end),
+        );
+        self.buffer.remove(start, end);
+        self.buffer.dirty = true;
+        true
+    }
+
+    /// Keeps the snippet being filled in up with the edit just made, ending it when the edit was
+    /// outside its current tab stop or insert mode was left.
+    fn sync_snippet(&mut self) {
+        let cursor = self.buffer.char_idx(self.bx(), self.by());
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+        let insert = matches!(self.mode, Mode::Insert);
+        match session.sync(&mut self.buffer, cursor).filter(|_| insert) {
+            Some(idx) if idx != cursor => self.move_to_idx(idx),
+            Some(_) => {}
+            None => self.snippet = None,
+        }
+    }
+
+    fn move_to_idx(&mut self, idx: usize) {
+        let position = self.buffer.position(idx);
+        self.move_to_position(position);
+    }
+
+    /// Handles replace mode input, which types over the chars after the cursor and otherwise
+    /// works like insert mode. Backspace puts back what was typed over.
+    fn handle_replace_input(&mut self, ev: Event) -> anyhow::Result<bool> {
+        let Event::Key(event) = ev else {
+            return Ok(true);
+        };
+
+        match (self.config.keymap.insert(&event), event.code) {
+            (Some(Action::Backspace), _) => self.restore_replaced_char()?,
+            (Some(action), _) => {
+                // the chars typed over are only put back in the same run of typing
+                self.replaced.clear();
+                self.run_action(action)?;
+            }
+            (None, KeyCode::Char(c)) => {
+                let (x, y) = (self.bx(), self.by());
+                let replaced = self.buffer.remove_char(x, y);
+                self.replaced.push(replaced);
+                self.insert_char(c)?;
+                self.move_right()?;
+            }
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    /// Moves back over the last char typed in replace mode, putting back the char it typed over.
+    fn restore_replaced_char(&mut self) -> anyhow::Result<()> {
+        if self.bx() > 0 {
+            self.move_left()?;
+        }
+        let Some(replaced) = self.replaced.pop() else {
+            return Ok(());
+        };
+        let (x, y) = (self.bx(), self.by());
+        self.buffer.remove_char(x, y);
+        if let Some(c) = replaced {
+            self.buffer.insert_char(x, y, c);
+        }
+        Ok(())
+    }
+
+    /// Replaces `count` chars from the cursor with `c`, as `r` does, leaving the cursor on the
+    /// last one. Does nothing when the line has fewer chars left. Returns true if it replaced.
+    fn replace_chars(&mut self, c: char, count: usize) -> bool {
+        let (x, y) = (self.bx(), self.by());
+        if x + count > self.buffer.line_len(y) {
+            return false;
+        }
+        for x in x..x + count {
+            self.buffer.remove_char(x, y);
+            self.buffer.insert_char(x, y, c);
+        }
+        self.buffer.dirty = true;
+        self.cx = x + count - 1;
+        true
+    }
+
+    fn indent_unit(&self) -> String {
+        indent::unit(self.tab_size(), self.tab_to_spaces())
+    }
+
+    /// The `[languages]` settings for the language of the current buffer.
+    fn language_config(&self) -> Option<&LanguageConfig> {
+        let language = self.buffer.language?;
+        self.config.language(language.id())
+    }
+
+    /// The indentation detected in the file of the current buffer, with `detect_indent`.
+    fn detected_indentation(&self) -> Option<Indentation> {
+        self.buffer
+            .indentation
+            .filter(|_| self.config.detect_indent)
+    }
+
+    /// The tab size of the current buffer: the one `:set` gave it, the one its file uses, its
+    /// language's or else the config's.
+    fn tab_size(&self) -> u8 {
+        let detected = match self.detected_indentation() {
+            Some(Indentation::Spaces(size)) => Some(size),
+            _ => None,
+        };
+        let language = self
+            .language_config()
+            .and_then(|language| language.tab_size);
+        let size = self.buffer.tab_size.or(detected).or(language);
+        size.unwrap_or(self.config.tab_size)
+    }
+
+    /// Whether tabs are typed as spaces in the current buffer, going by the same settings as
+    /// [`Editor::tab_size`].
+    fn tab_to_spaces(&self) -> bool {
+        let detected = self
+            .detected_indentation()
+            .map(|indentation| indentation != Indentation::Tabs);
+        let language = self
+            .language_config()
+            .and_then(|language| language.tab_to_spaces);
+        let spaces = self.buffer.tab_to_spaces.or(detected).or(language);
+        spaces.unwrap_or(self.config.tab_to_spaces)
+    }
+
+    /// Splits the line at the cursor, indenting the new line to follow the one it came from.
+    fn split_line_at_cursor(&mut self) -> anyhow::Result<()> {
+        let x = self.bx();
+        let y = self.by();
+        let Some(line) = self.line() else {
+            warn!("line out of bounds: x: {}, y: {}", x, y);
+            return Ok(());
+        };
+
+        let head: String = line.chars().take(x).collect();
+        let tail: String = line.chars().skip(x).collect();
+        self.buffer.split_line(x, y);
+        let indent = self.syntax_indent(y + 1).unwrap_or_else(|| {
+            indent::next_line_indent(&head, self.buffer.language.as_ref(), &self.indent_unit())
+        });
+        self.buffer
+            .set_line(y + 1, &format!("{indent}{}", tail.trim_start()));
+        self.buffer.dirty = true;
+        self.move_down();
+        self.cx = indent.chars().count();
+        Ok(())
+    }
+
+    /// Opens an indented line below the cursor and moves to its end.
+    fn open_line_below(&mut self) {
+        let y = self.by();
+        let line = self.line().unwrap_or_default();
+
+        self.buffer.insert_line(y + 1, "");
+        let indent = self.syntax_indent(y + 1).unwrap_or_else(|| {
+            indent::next_line_indent(&line, self.buffer.language.as_ref(), &self.indent_unit())
+        });
+        self.buffer.set_line(y + 1, &indent);
+        self.buffer.dirty = true;
+        self.move_down();
+        self.cx = indent.chars().count();
+    }
+
+    /// Opens an indented line above the cursor, by default with the same indentation as the
+    /// cursor line.
+    fn open_line_above(&mut self) {
+        let y = self.by();
+        let line = self.line().unwrap_or_default();
+
+        self.buffer.insert_line(y, "");
+        let indent = self
+            .syntax_indent(y)
+            .unwrap_or_else(|| indent::leading_whitespace(&line).to_string());
+        self.buffer.set_line(y, &indent);
+        self.buffer.dirty = true;
+        self.cx = indent.chars().count();
+    }
+
+    /// The indentation line `y` gets from the syntax tree of the buffer, unless the buffer has no
+    /// language or the tree can't tell, as while a block is still missing its closing bracket.
+    fn syntax_indent(&mut self, y: usize) -> Option<String> {
+        let tree = self.buffer.syntax_tree()?;
+        let language = self.buffer.language?;
+        let level = indent::levels(&language, &tree, &self.buffer.to_string(), y..=y)[0]?;
+        Some(self.indent_unit().repeat(level))
+    }
+
+    /// Handles text pasted into the terminal, which arrives whole with bracketed paste. Insert
+    /// and replace mode insert it as a single edit, without the auto-indent it would get if it
+    /// were typed, normal mode puts it after the cursor like `p`, and the command line takes its
+    /// first line.
+    fn paste(&mut self, text: &str) -> bool {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        match self.mode {
+            Mode::Insert | Mode::Replace => {
+                self.insert_text(&text);
+                true
+            }
+            Mode::Normal => {
+                self.refuse_edit() || self.put_register(&Register::from_clipboard(text), false, 1)
+            }
+            Mode::Command => {
+                let line = text.lines().next().unwrap_or_default();
+                line.chars().for_each(|c| self.command_line.insert(c));
+                true
+            }
+            Mode::Visual => false,
+        }
+    }
+
+    /// Shows that the buffer can't be changed when it's locked, like the `:help` buffer is, and
+    /// returns whether it is.
+    fn refuse_edit(&mut self) -> bool {
+        if self.buffer.locked {
+            self.message = Some(Message::Error(
+                "E21: Cannot make changes, the buffer can't be modified".to_string(),
+            ));
+        }
+        self.buffer.locked
+    }
+
+    /// Inserts `text` at the cursor as a single edit, leaving the cursor after it.
+    fn insert_text(&mut self, text: &str) {
+        let (x, y) = (self.bx(), self.by());
+        let start = self.buffer.char_idx(x, y);
+        self.buffer.insert((x, y), text);
+        self.buffer.dirty = true;
+        let end = self.buffer.position(start + text.chars().count());
+        self.move_to_position(end);
+    }
+
+    fn insert_char(&mut self, c: char) -> anyhow::Result<()> {
+        let x = self.bx();
+        let y = self.by();
+
+        self.buffer.insert_char(x, y, c);
+        self.buffer.dirty = true;
+        Ok(())
+    }
+
+    fn remove_char(&mut self) -> anyhow::Result<()> {
+        let x = self.bx();
+        let y = self.by();
+        if x > 0 && self.buffer.remove_char(x - 1, y).is_some() {
+            self.buffer.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn affects_buffer(&self) -> bool {
+        matches!(
+            self.mode,
+            Mode::Normal | Mode::Insert | Mode::Visual | Mode::Replace
+        )
+    }
+
+    /// Writes the buffer to `path`, or to the current file when no path is given. Returns true
+    /// when the write succeeded, otherwise the error is left on the command line.
+    fn write(&mut self, path: Option<&str>, force: bool) -> bool {
+        let Some(path) = path
+            .map(|p| p.to_string())
+            .or_else(|| self.buffer.file.clone())
+        else {
+            self.message = Some(Message::Error("E32: No file name".to_string()));
+            return false;
+        };
+
+        if self.buffer.readonly && !force && self.buffer.file.as_deref() == Some(path.as_str()) {
+            self.message = Some(Message::Error(
+                "E45: 'readonly' option is set (add ! to override)".to_string(),
+            ));
+            return false;
+        }
+
+        // formats the buffer before writing it to its own file, which still gets written when
+        // formatting fails
+        let own_file = self.buffer.file.as_deref() == Some(path.as_str());
+        if own_file && !force && self.buffer.changed_on_disk() {
+            let error = "File changed on disk since reading it, :diffreload to compare";
+            self.message = Some(Message::Error(format!("{error} (add ! to override)")));
+            return false;
+        }
+        if own_file && self.config.ensure_final_newline {
+            self.buffer.missing_newline = false;
+        }
+        if own_file && self.config.trim_trailing_whitespace {
+            self.trim_trailing_whitespace();
+        }
+        let formatted = if own_file && self.config.format_on_save && self.formatter().is_some() {
+            self.format()
+        } else {
+            Ok(())
+        };
+
+        match self.buffer.write_to(&path) {
+            Ok(len) => {
+                log!("wrote {} bytes to {}", len, path);
+                // scratch buffers write a copy of their text, staying unnamed
+                if self.buffer.file.is_none() && !self.buffer.scratch {
+                    self.buffer.file = Some(path.clone());
+                    self.buffer.name = path.clone();
+                }
+                if self.buffer.file.as_deref() == Some(path.as_str()) {
+                    self.buffer.written();
+                }
+                self.message = Some(match formatted {
+                    Ok(()) => Message::Info(format!(
+                        "\"{}\" {}L, {}B written",
+                        path,
+                        self.buffer.len(),
+                        len
+                    )),
+                    Err(err) => Message::Error(format!("{err}, wrote it unformatted")),
+                });
+                true
+            }
+            Err(err) => {
+                error!("error writing {}: {}", path, err);
+                self.message = Some(Message::Error(err.to_string()));
+                false
+            }
+        }
+    }
+
+    /// Removes whitespace at the end of lines, keeping the cursor within its line.
+    fn trim_trailing_whitespace(&mut self) {
+        if self.buffer.trim_trailing_whitespace() {
+            self.buffer.dirty = true;
+            let x = self
+                .bx()
+                .min(self.buffer.line_len(self.by()).saturating_sub(1));
+            self.move_to_position((x, self.by()));
+        }
+    }
+
+    /// Writes every modified buffer back to its file once the editor has been idle for the
+    /// configured time. Returns true when anything was written. A buffer that fails to write,
+    /// like one on a full disk, isn't tried again until it's edited.
+    fn autosave(&mut self) -> bool {
+        let Some(secs) = self.config.autosave_interval_secs else {
+            return false;
+        };
+        if self
+            .last_input
+            .is_some_and(|last| last.elapsed() < Duration::from_secs(secs))
+        {
+            return false;
+        }
+
+        let mut saved = false;
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            let Some(file) = buffer
+                .file
+                .as_deref()
+                .filter(|_| buffer.dirty && !buffer.readonly && !buffer.changed_on_disk())
+                .filter(|_| buffer.autosave_failed != Some(buffer.revision()))
+            else {
+                continue;
+            };
+
+            match buffer.write_to(file) {
+                Ok(len) => {
+                    log!("autosaved {} bytes to {}", len, file);
+                    buffer.written();
+                    saved = true;
+                }
+                Err(err) => {
+                    error!("error autosaving {}: {}", file, err);
+                    buffer.autosave_failed = Some(buffer.revision());
+                    self.message = Some(Message::Error(err.to_string()));
+                }
+            }
+        }
+        self.autosaved |= saved;
+        saved
+    }
+
+    fn buffer_at(&self, index: usize) -> &Buffer {
+        if index == self.current {
+            &self.buffer
+        } else {
+            &self.buffers[index]
+        }
+    }
+
+    /// Makes the buffer at `index` the active one, saving the cursor and scroll position of the
+    /// current buffer so they are restored when switching back.
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.current || index >= self.buffers.len() {
+            return;
+        }
+
+        self.buffer.view = (self.cx, self.cy, self.vtop);
+        std::mem::swap(&mut self.buffer, &mut self.buffers[self.current]);
+        std::mem::swap(&mut self.buffer, &mut self.buffers[index]);
+        self.current = index;
+        (self.cx, self.cy, self.vtop) = self.buffer.view;
+        // the file may have been reloaded with fewer lines while in the background
+        if self.by() >= self.buffer.len() {
+            self.move_to_position((self.bx(), self.buffer.len() - 1));
+        }
+        self.anchor = None;
+        self.follow_dir();
+    }
+
+    /// Remembers the cursor position before a jump, for the jump list and the `'` mark.
+    fn record_jump(&mut self) {
+        let position = (self.bx(), self.by());
+        self.buffer.jumps.push(position);
+        self.buffer.set_mark('\'', position);
+    }
+
+    /// The position of `mark`, or of the first non-blank of its line when `linewise`, showing an
+    /// error when it isn't set.
+    fn mark_position(&mut self, mark: char, linewise: bool) -> Option<(usize, usize)> {
+        let Some((x, y)) = self.buffer.mark(mark_name(mark)) else {
+            self.message = Some(Message::Error("E20: Mark not set".to_string()));
+            return None;
+        };
+
+        if !linewise {
+            return Some((x.min(self.buffer.line_len(y).saturating_sub(1)), y));
+        }
+        let line = self.buffer.line(y).unwrap_or_default();
+        Some((line.chars().take_while(|c| c.is_whitespace()).count(), y))
+    }
+
+    fn go_to_mark(&mut self, mark: char, linewise: bool) -> bool {
+        let Some(position) = self.mark_position(mark, linewise) else {
+            return true;
+        };
+
+        self.record_jump();
+        self.move_to_position(position);
+        true
+    }
+
+    /// Loads the dictionary the first time it's needed, showing why when it can't be.
+    fn load_dictionary(&mut self) -> bool {
+        if self.dictionary.is_some() {
+            return true;
+        }
+        match Dictionary::load(self.config.spell_dictionary.as_deref()) {
+            Ok(dictionary) => {
+                self.dictionary = Some(dictionary);
+                true
+            }
+            Err(err) => {
+                self.message = Some(Message::Error(err.to_string()));
+                false
+            }
+        }
+    }
+
+    /// Whether misspelled words are checked for in the buffer, turning `spell` off when there's
+    /// no dictionary. Large buffers aren't checked.
+    fn spell_checking(&mut self) -> bool {
+        if !self.config.spell || self.buffer.large {
+            return false;
+        }
+        if !self.load_dictionary() {
+            self.config.spell = false;
+            return false;
+        }
+        true
+    }
+
+    /// Moves to the start of the next misspelled word after the cursor, or the previous one
+    /// before it.
+    fn go_to_misspelling(&mut self, forward: bool) {
+        if !self.spell_checking() {
+            if self.message.is_none() {
+                let message = "E756: Spell checking is not enabled".to_string();
+                self.message = Some(Message::Error(message));
+            }
+            return;
+        }
+        let viewport = self.viewport();
+        if let Err(err) = update_highlights(&mut self.buffer, &viewport) {
+            warn!("error highlighting the buffer: {}", err);
+        }
+        let Some(dictionary) = &self.dictionary else {
+            return;
+        };
+
+        let (x, y) = (self.bx(), self.by());
+        let starts = |line| {
+            spell::misspellings(dictionary, &self.buffer, line)
+                .into_iter()
+                .map(move |(start, _)| (start, line))
+        };
+        let found = if forward {
+            (y..self.buffer.len())
+                .flat_map(starts)
+                .find(|&(start, line)| line > y || start > x)
+        } else {
+            (0..=y)
+                .rev()
+                .flat_map(|line| starts(line).rev())
+                .find(|&(start, line)| line < y || start < x)
+        };
+
+        match found {
+            Some(position) => self.move_to_position(position),
+            None => {
+                let message = "No more misspelled words".to_string();
+                self.message = Some(Message::Info(message));
+            }
+        }
+    }
+
+    /// Shows the words the one under the cursor may have meant to be, numbered for
+    /// [`Pending::Suggestion`] to pick one.
+    fn suggest_spelling(&mut self) {
+        // suggestions only need a dictionary, not `spell` on
+        if !self.load_dictionary() {
+            return;
+        }
+        let Some(dictionary) = &self.dictionary else {
+            return;
+        };
+
+        let line = self.line().unwrap_or_default();
+        let (x, y) = (self.bx(), self.by());
+        let Some((start, end)) = spell::words(&line)
+            .into_iter()
+            .find(|&(start, end)| (start..end).contains(&x))
+        else {
+            self.message = Some(Message::Error("No word under the cursor".to_string()));
+            return;
+        };
+
+        let word: String = line.chars().skip(start).take(end - start).collect();
+        let suggestions = dictionary.suggest(&word);
+        if suggestions.is_empty() {
+            self.message = Some(Message::Info("Sorry, no suggestions".to_string()));
+            return;
+        }
+
+        let text = iter::once(format!("Change \"{word}\" to:"))
+            .chain(
+                suggestions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, suggestion)| format!("{} {suggestion}", i + 1)),
+            )
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.popup = Some(Popup::new(&text));
+        self.suggestions = suggestions;
+        self.pending = Pending::Suggestion((start, y), end);
+    }
+
+    /// Moves to the first line of the next hunk of changes from git's `HEAD` below the cursor, or
+    /// the previous one above it.
+    fn go_to_hunk(&mut self, forward: bool) {
+        let y = self.by();
+        if let Some((diff, side)) = self.diff_side() {
+            match diff.next_change(side, y, forward) {
+                Some(line) => self.move_to_position((0, line.min(self.buffer.len() - 1))),
+                None => self.message = Some(Message::Info("No more changes".to_string())),
+            }
+            return;
+        }
+        let lines = self.buffer.git.hunks.iter().map(Hunk::line);
+        let line = if forward {
+            lines.filter(|&line| line > y).min()
+        } else {
+            lines.filter(|&line| line < y).max()
+        };
+
+        match line {
+            Some(line) => self.move_to_position((0, line.min(self.buffer.len() - 1))),
+            None => self.message = Some(Message::Info("No more hunks".to_string())),
+        }
+    }
+
+    /// Moves to the start marker of the next or previous merge conflict.
+    fn go_to_conflict(&mut self, forward: bool) {
+        let y = self.by();
+        let starts = self.buffer.conflicts().into_iter().map(|c| c.start);
+        let line = if forward {
+            starts.filter(|&start| start > y).min()
+        } else {
+            starts.filter(|&start| start < y).max()
+        };
+
+        match line {
+            Some(line) => self.move_to_position((0, line)),
+            None => self.message = Some(Message::Info("No more conflicts".to_string())),
+        }
+    }
+
+    /// Replaces the merge conflict under the cursor with the side kept, leaving the cursor on
+    /// its first line.
+    fn resolve_conflict(&mut self, resolution: Resolution) {
+        let y = self.by();
+        let Some(conflict) = self
+            .buffer
+            .conflicts()
+            .into_iter()
+            .find(|conflict| conflict.region(y).is_some())
+        else {
+            self.message = Some(Message::Error("No conflict under the cursor".to_string()));
+            return;
+        };
+
+        conflict.resolve(&mut self.buffer, resolution);
+        self.buffer.dirty = true;
+        let y = conflict.start.min(self.buffer.len() - 1);
+        self.move_to_position((0, y));
+    }
+
+    /// Moves to a side of the diff, or the other one, keeping the cursor and the top of the view
+    /// on the same rows.
+    fn go_to_window(&mut self, target: Option<usize>) {
+        let Some((diff, side)) = self.diff_side() else {
+            self.message = Some(Message::Error("E441: There is no other window".to_string()));
+            return;
+        };
+        let target = target.unwrap_or(1 - side);
+        if target == side {
+            return;
+        }
+
+        let line = diff.line_at(target, diff.row(side, self.by()));
+        let vtop = diff
+            .line_at(target, diff.top_row(side, self.vtop))
+            .min(line);
+        let (index, cx) = (diff.buffers[target], self.cx);
+        self.switch_buffer(index);
+        (self.cx, self.cy, self.vtop) = (cx, line - vtop, vtop);
+    }
+
+    /// Moves to the start of the next or previous function or class in the syntax tree.
+    fn go_to_function(&mut self, forward: bool) {
+        let (Some(language), Some(tree)) = (self.buffer.language, self.buffer.syntax_tree()) else {
+            self.message = Some(Message::Error("No syntax tree for this buffer".to_string()));
+            return;
+        };
+
+        let position = (self.bx(), self.by());
+        match structure::definition(&self.buffer, &language, &tree, position, forward) {
+            Some(position) => self.move_to_position(position),
+            None => self.message = Some(Message::Info("No more functions".to_string())),
+        }
+    }
+
+    /// Moves to the start or the end of the syntax node around the cursor.
+    fn go_to_node_edge(&mut self, end: bool) {
+        let Some(tree) = self.buffer.syntax_tree() else {
+            self.message = Some(Message::Error("No syntax tree for this buffer".to_string()));
+            return;
+        };
+
+        let position = (self.bx(), self.by());
+        let edge = if end {
+            structure::node_end(&self.buffer, &tree, position)
+        } else {
+            structure::node_start(&self.buffer, &tree, position)
+        };
+        if let Some(position) = edge {
+            self.move_to_position(position);
+        }
+    }
+
+    /// Selects the syntax node under the cursor, or grows the visual selection to the node
+    /// around it.
+    fn expand_selection(&mut self) {
+        let Some(tree) = self.buffer.syntax_tree() else {
+            self.message = Some(Message::Error("No syntax tree for this buffer".to_string()));
+            return;
+        };
+
+        let cursor = (self.bx(), self.by());
+        let selection = self
+            .selection()
+            .unwrap_or_else(|| Selection::new(cursor, cursor));
+        if let Some((start, end)) =
+            structure::expand(&self.buffer, &tree, selection.start, selection.end)
+        {
+            self.mode = Mode::Visual;
+            self.select_range(start, end);
+        }
+    }
+
+    /// The identifier under the cursor.
+    fn identifier_under_cursor(&self) -> Option<String> {
+        let line = self.line()?;
+        let (start, end) = word::identifier_at(&line, self.bx())?;
+        Some(line.chars().skip(start).take(end - start).collect())
+    }
+
+    /// The other occurrences of the identifier under the cursor on the visible lines. When the
+    /// highlights can tell, identifiers in strings and comments are left out, and so are the
+    /// occurrences of one outside them. Only in normal mode, and never in large buffers.
+    fn occurrences(&self) -> Vec<Selection> {
+        if !matches!(self.mode, Mode::Normal) || self.buffer.large {
+            return vec![];
+        }
+        let Some(word) = self.identifier_under_cursor() else {
+            return vec![];
+        };
+
+        let highlights = &self.buffer.highlights;
+        let current = highlights.is_current(&self.buffer);
+        let cursor = (self.bx(), self.by());
+        if current && highlights.is_literal(cursor) {
+            return vec![];
+        }
+        let len = word.chars().count();
+        let mut occurrences = vec![];
+        for y in self.vtop..(self.vtop + self.vheight).min(self.buffer.len()) {
+            let line = self.buffer.line(y).unwrap_or_default();
+            for x in word::occurrences(&line, &word) {
+                let under_cursor = y == cursor.1 && (x..x + len).contains(&cursor.0);
+                if !(under_cursor || current && highlights.is_literal((x, y))) {
+                    occurrences.push(Selection::new((x, y), (x + len - 1, y)));
+                }
+            }
+        }
+        occurrences
+    }
+
+    /// Which match of the last `*` or `#` search the cursor is on and how many there are, while
+    /// it's on one. Large buffers go without, as counting reads every line.
+    fn search_count(&mut self) -> Option<(usize, usize)> {
+        let word = self.last_search.as_ref().filter(|_| !self.buffer.large)?;
+        let cursor = (self.by(), self.bx());
+        let positions = self.buffer.word_positions(word);
+        let index = positions.iter().position(|&position| position == cursor)?;
+        Some((index + 1, positions.len()))
+    }
+
+    /// Moves to the next occurrence of the identifier under the cursor as a whole word, or to
+    /// the previous one, going around the end of the buffer, as `*` and `#` do.
+    fn move_to_occurrence(&mut self, forward: bool) -> bool {
+        let Some(word) = self.identifier_under_cursor() else {
+            self.message = Some(Message::Error("E348: No string under cursor".to_string()));
+            return false;
+        };
+
+        let (cx, cy) = (self.bx(), self.by());
+        let (start, _) = self
+            .line()
+            .and_then(|line| word::identifier_at(&line, cx))
+            .unwrap_or((cx, cy));
+        let cursor = (cy, start);
+        let positions = self.buffer.word_positions(&word).to_vec();
+        self.last_search = Some(word);
+
+        let (found, wrapped) = if forward {
+            match positions.iter().find(|&&position| position > cursor) {
+                Some(&position) => (position, false),
+                None => (positions[0], true),
+            }
+        } else {
+            match positions.iter().rev().find(|&&position| position < cursor) {
+                Some(&position) => (position, false),
+                None => (positions[positions.len() - 1], true),
+            }
+        };
+
+        if wrapped {
+            let message = if forward {
+                "search hit BOTTOM, continuing at TOP"
+            } else {
+                "search hit TOP, continuing at BOTTOM"
+            };
+            self.message = Some(Message::Info(message.to_string()));
+        }
+        let (y, x) = found;
+        self.record_jump();
+        self.move_to_position((x, y));
+        true
+    }
+
+    /// Moves to a position from the jump list, which edits may have left past the end of its
+    /// line or of the buffer.
+    fn jump_to(&mut self, jump: Option<(usize, usize)>) -> bool {
+        let Some((x, y)) = jump else {
+            return false;
+        };
+
+        let y = y.min(self.buffer.len() - 1);
+        let x = x.min(self.buffer.line_len(y).saturating_sub(1));
+        self.move_to_position((x, y));
+        true
+    }
+
+    fn list_jumps(&mut self) {
+        let list = self
+            .buffer
+            .jumps
+            .iter()
+            .map(|((x, y), current)| {
+                format!("{}{}:{}", if current { "> " } else { "" }, y + 1, x + 1)
+            })
+            .collect::<Vec<_>>();
+        let list = if list.is_empty() {
+            "No jumps".to_string()
+        } else {
+            list.join(" | ")
+        };
+        self.message = Some(Message::Info(list));
+    }
+
+    /// Logs the message on the command line the first time it's drawn.
+    fn log_message(&mut self) {
+        let text = match &self.message {
+            Some(Message::Info(text) | Message::Error(text)) => Some(text),
+            None => None,
+        };
+        if self.logged_message.as_ref() == text {
+            return;
+        }
+        match &self.message {
+            Some(Message::Info(text)) => log!("{}", text),
+            Some(Message::Error(text)) => error!("{}", text),
+            None => {}
+        }
+        self.logged_message = text.cloned();
+    }
+
+    /// Opens the latest messages and log lines in the pager, scrolled to the newest.
+    fn show_messages(&mut self) {
+        let history = LOGGER.get().map(Logger::history).unwrap_or_default();
+        if history.is_empty() {
+            self.message = Some(Message::Info("No messages".to_string()));
+            return;
+        }
+        let mut pager = Pager::new(":messages".to_string(), &history.join("\n"));
+        pager.scroll(isize::MAX, self.vheight);
+        self.pager = Some(pager);
+    }
+
+    fn list_buffers(&mut self) {
+        let list = (0..self.buffers.len())
+            .map(|i| {
+                let buffer = self.buffer_at(i);
+                let line = if i == self.current {
+                    self.by()
+                } else {
+                    buffer.view.1 + buffer.view.2
+                };
+                format!(
+                    "{}{} \"{}\"{} line {}",
+                    i + 1,
+                    if i == self.current { " %a" } else { "" },
+                    buffer.name,
+                    if buffer.unsaved() { " +" } else { "" },
+                    line + 1
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+        self.message = Some(Message::Info(list));
+    }
+
+    /// Queues the keys recorded in `register` to be handled `count` times, ahead of anything
+    /// already queued so macros can replay other macros.
+    fn play_macro(&mut self, register: Option<char>, count: usize) {
+        let Some((register, events)) =
+            register.and_then(|register| Some((register, self.macros.get(&register)?)))
+        else {
+            return;
+        };
+
+        if self.input.len() + events.len().saturating_mul(count) > MAX_QUEUED_EVENTS {
+            self.input.clear();
+            self.message = Some(Message::Error(format!("Macro @{register} is too long")));
+            return;
+        }
+
+        for _ in 0..count {
+            for ev in events.iter().rev() {
+                self.input.push_front(ev.clone());
+            }
+        }
+        self.last_macro = Some(register);
+    }
+
+    fn open_picker(&mut self) {
+        match std::env::current_dir() {
+            Ok(dir) => self.picker = Some(Picker::new(list_files(&dir))),
+            Err(err) => self.message = Some(Message::Error(err.to_string())),
+        }
+    }
+
+    /// Runs `:set`. `fileformat` belongs to the buffer and converts its line endings on the next
+    /// write, `background` switches to the theme for it, while the other options change the
+    /// config until its file is reloaded.
+    fn set_option(&mut self, option: &str) {
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+
+        let message = match (name, value) {
+            ("ff" | "fileformat" | "ff?" | "fileformat?", None) => {
+                Message::Info(format!("fileformat={}", self.buffer.line_ending))
+            }
+            ("bg" | "background" | "bg?" | "background?", None) => {
+                Message::Info(format!("background={}", self.background))
+            }
+            ("bg" | "background", Some(value)) => match value.parse() {
+                Ok(background) => match self.set_background(background) {
+                    Ok(()) => return,
+                    Err(err) => Message::Error(err.to_string()),
+                },
+                Err(_) => Message::Error(format!("E474: Invalid argument: {option}")),
+            },
+            ("ff" | "fileformat", Some(value)) => match value.parse() {
+                Ok(line_ending) => {
+                    if self.buffer.line_ending != line_ending {
+                        self.buffer.line_ending = line_ending;
+                        self.buffer.dirty = true;
+                    }
+                    return;
+                }
+                Err(_) => Message::Error(format!("E474: Invalid argument: {option}")),
+            },
+            ("", None) => Message::Error("E471: Argument required".to_string()),
+            _ => match self.config.set(option) {
+                // the tab settings show the buffer's, which may come from its file or language
+                Ok(Some(value)) if value.starts_with("tabsize=") => {
+                    Message::Info(format!("tabsize={}", self.tab_size()))
+                }
+                Ok(Some(value)) if value.ends_with("tabtospaces") => Message::Info(format!(
+                    "{}tabtospaces",
+                    if self.tab_to_spaces() { "" } else { "no" }
+                )),
+                Ok(Some(value)) => Message::Info(value),
+                // and setting them sets the buffer's too, like vim's `:set` does
+                Ok(None) => {
+                    match name.strip_prefix("no").unwrap_or(name) {
+                        "tabsize" | "ts" => self.buffer.tab_size = Some(self.config.tab_size),
+                        "tabtospaces" | "tts" => {
+                            self.buffer.tab_to_spaces = Some(self.config.tab_to_spaces)
+                        }
+                        _ => {}
+                    }
+                    return;
+                }
+                Err(err) => Message::Error(err.to_string()),
+            },
+        };
+        self.message = Some(message);
+    }
+
+    /// Switches to the theme called `name`, or shows the current theme's name.
+    fn set_theme(&mut self, name: Option<&str>) {
+        let Some(name) = name else {
+            self.message = Some(Message::Info(self.theme.name.clone()));
+            return;
+        };
+
+        match Theme::load(name) {
+            Ok(theme) => {
+                self.use_theme(theme);
+                self.watch_files();
+            }
+            Err(err) => self.message = Some(Message::Error(err.to_string())),
+        }
+    }
+
+    /// Switches to the theme the config has for a terminal with a `background` of that kind.
+    fn set_background(&mut self, background: Background) -> anyhow::Result<()> {
+        let before = self.config.theme_for(self.background).cloned();
+        self.background = background;
+        let theme = self.config.theme_for(background).cloned();
+        if theme != before {
+            self.use_theme(match theme {
+                Some(name) => Theme::load(&name)?,
+                None => Theme::default(),
+            });
+            self.watch_files();
+        }
+        Ok(())
+    }
+
+    fn use_theme(&mut self, theme: Theme) {
+        self.styles = Styles::new(&theme);
+        self.theme = theme;
+    }
+
+    /// Starts watching the files whose changes are applied while the editor runs: the config file
+    /// and the file the theme was loaded from.
+    fn watch_files(&mut self) {
+        let files = [Some(self.config_path.clone()), self.theme.path.clone()];
+        self.watcher = Watcher::new(files.into_iter().flatten());
+    }
+
+    /// Saves the unsaved changes of every buffer to its swap file, and removes the swap files of
+    /// buffers that were written since.
+    fn update_swap_files(&mut self) {
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            let Some(file) = buffer.file.as_deref().filter(|_| buffer.swappable) else {
+                continue;
+            };
+
+            let result = if buffer.dirty && buffer.swapped != Some(buffer.revision()) {
+                let text = buffer.to_string();
+                self.swap
+                    .write(file, &text)
+                    .map(|()| Some(buffer.revision()))
+            } else if !buffer.dirty && buffer.swapped.is_some() {
+                self.swap.remove(file).map(|()| None)
+            } else {
+                continue;
+            };
+
+            match result {
+                Ok(swapped) => buffer.swapped = swapped,
+                Err(err) => warn!("error updating the swap file for {}: {}", file, err),
+            }
+        }
+    }
+
+    /// Follows the files of the buffers that changed on disk: buffers without unsaved changes are
+    /// reloaded with `autoread`, the others get a warning once, as writing them would throw
+    /// away what changed. Returns whether anything did.
+    fn check_files(&mut self) -> bool {
+        let mut messages = vec![];
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            let Some(file) = buffer.file.clone() else {
+                continue;
+            };
+            let modified = watcher::modified(Path::new(&file));
+            if modified == buffer.modified || buffer.conflict {
+                continue;
+            }
+
+            if modified.is_none() {
+                buffer.conflict = true;
+                let error = format!("E211: File \"{file}\" no longer available");
+                messages.push(Message::Error(error));
+            } else if self.config.autoread && !buffer.dirty {
+                messages.push(match buffer.reload() {
+                    Ok(()) => Message::Info(format!("\"{file}\" reloaded, it changed on disk")),
+                    Err(err) => Message::Error(format!("Error reloading {file}: {err}")),
+                });
+            } else {
+                buffer.conflict = true;
+                let error = format!(
+                    "W12: \"{file}\" changed on disk, :e! to load it or :diffreload to compare"
+                );
+                messages.push(Message::Error(error));
+            }
+        }
+        if messages.is_empty() {
+            return false;
+        }
+
+        let y = self.by().min(self.buffer.len() - 1);
+        self.move_to_position((self.bx(), y));
+        // errors matter more than reloads when several files changed at once
+        messages.sort_by_key(|message| matches!(message, Message::Error(_)));
+        self.message = messages.pop();
+        true
+    }
+
+    /// Marks the lines of the buffers that differ from their files at git's `HEAD`. Returns
+    /// whether any marks changed.
+    fn update_git(&mut self) -> bool {
+        let mut updated = false;
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            updated |= buffer.update_git();
+        }
+        updated
+    }
+
+    /// The formatter command configured for the language of the buffer.
+    fn formatter(&self) -> Option<&String> {
+        let language = self.buffer.language?;
+        self.config.formatter(language.id())
+    }
+
+    /// Pipes the buffer through its formatter and applies the lines that changed, keeping the
+    /// cursor on the same text. The buffer is left alone when there is no formatter or it fails.
+    fn format(&mut self) -> anyhow::Result<()> {
+        let Some(command) = self.formatter() else {
+            anyhow::bail!("No formatter for this buffer");
+        };
+        let text = self.buffer.to_string();
+        let formatted = format::format(command, self.buffer.file.as_deref(), format!("{text}\n"))?;
+
+        let old: Vec<_> = text.split('\n').collect();
+        let new: Vec<_> = formatted.lines().collect();
+        let changes = gitdiff::changes(&old, &new);
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let (x, y) = (self.bx(), self.by());
+        for change in changes.iter().rev() {
+            self.buffer
+                .replace_lines(change.old.clone(), &new[change.new.clone()]);
+        }
+        self.buffer.dirty = true;
+
+        // keeps the cursor on the same char and screen row
+        let moved = gitdiff::moved_line(&changes, y).min(self.buffer.len() - 1);
+        let line = self.buffer.line(moved).unwrap_or_default();
+        self.vtop = moved.saturating_sub(self.cy);
+        self.cy = moved - self.vtop;
+        self.cx = format::moved_column(old[y], &line, x);
+        Ok(())
+    }
+
+    /// Opens `path` on the right of the current buffer, showing how the two differ.
+    fn diff_split(&mut self, path: &str) {
+        let current = self.current;
+        self.open_file(path);
+        if self.current == current {
+            if self.message.is_none() {
+                self.message = Some(Message::Error(
+                    "Can't diff a buffer with itself".to_string(),
+                ));
+            }
+            return;
+        }
+        self.diff = Some(Diff::new(current, self.current));
+    }
+
+    /// Opens what the file of the buffer holds on disk now side by side with it, for
+    /// `:diffreload` after it changed under unsaved changes.
+    fn diff_reload(&mut self) {
+        let Some(file) = self.buffer.file.clone() else {
+            self.message = Some(Message::Error("E32: No file name".to_string()));
+            return;
+        };
+        let mut disk = match Buffer::open(&file) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                self.message = Some(Message::Error(format!(
+                    "E484: Can't open file {file}: {err}"
+                )));
+                return;
+            }
+        };
+        disk.file = None;
+        disk.name = format!("{file} (on disk)");
+        disk.scratch = true;
+        disk.readonly = true;
+
+        let current = self.current;
+        self.open_scratch(disk);
+        self.diff = Some(Diff::new(current, self.current));
+    }
+
+    /// Makes the changes in `range`, or at the cursor line, the same on both sides of the diff,
+    /// taking the other side's lines or putting this side's there.
+    fn diff_copy(&mut self, range: Option<(usize, usize)>, put: bool) {
+        let Some((diff, side)) = self.diff_side() else {
+            self.message = Some(Message::Error(
+                "E99: Current buffer is not in diff mode".to_string(),
+            ));
+            return;
+        };
+        let changes = diff.changes_at(side, range.unwrap_or((self.by(), self.by())));
+        if changes.is_empty() {
+            self.message = Some(Message::Info("No changes here".to_string()));
+            return;
+        }
+
+        let other = diff.buffers[1 - side];
+        if put {
+            let buffer = &mut self.buffers[other];
+            diff::copy_changes(&changes, &self.buffer, buffer, 1 - side);
+            buffer.dirty = true;
+        } else {
+            diff::copy_changes(&changes, &self.buffers[other], &mut self.buffer, side);
+            self.buffer.dirty = true;
+            let y = self.by().min(self.buffer.len() - 1);
+            self.move_to_position((self.bx(), y));
+        }
+    }
+
+    /// Shows how the text under the cursor is highlighted: its tree-sitter capture and syntax
+    /// node, the theme scope the capture maps to, and the colors and style that come of it.
+    fn inspect_highlight(&mut self) -> anyhow::Result<()> {
+        let viewport = self.viewport();
+        update_highlights(&mut self.buffer, &viewport)?;
+        let position = (self.bx(), self.by());
+        let capture = self
+            .buffer
+            .highlights
+            .chunk_type(position)
+            .unwrap_or(ChunkType::None);
+        let node = self
+            .buffer
+            .syntax_tree()
+            .and_then(|tree| structure::node_kind(&self.buffer, &tree, position));
+        let resolved = highlight::resolve(&self.theme, &capture);
+
+        let mut parts = vec![format!("capture {capture}")];
+        if let Some(node) = node {
+            parts.push(format!("node {node}"));
+        }
+        parts.push(match (resolved.scope, resolved.selector) {
+            (Some(scope), Some(selector)) if selector == scope => format!("scope {scope}"),
+            (Some(scope), Some(selector)) => format!("scope {scope} (from {selector})"),
+            (Some(scope), None) => format!("scope {scope} (not in theme {})", self.theme.name),
+            (None, _) => "no scope".to_string(),
+        });
+        parts.push(format!("fg {}", resolved.fg));
+        parts.push(format!(
+            "bg {}",
+            resolved.bg.as_deref().unwrap_or(&self.theme.background)
+        ));
+        if let Some(font_style) = resolved.font_style {
+            parts.push(font_style.name().to_string());
+        }
+        self.message = Some(Message::Info(parts.join(", ")));
+        Ok(())
+    }
+
+    fn toggle_blame(&mut self) {
+        self.blame = !self.blame;
+        if !self.blame {
+            for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+                buffer.blame = None;
+            }
+            return;
+        }
+
+        if self.buffer.file.is_none() {
+            self.blame = false;
+            self.message = Some(Message::Error("E32: No file name".to_string()));
+            return;
+        }
+        self.update_blame();
+    }
+
+    /// Runs `git blame` on the current buffer while `:blame` is on, when its text changed since
+    /// the last time, turning it off if that fails. Returns whether there is a new blame to draw.
+    fn update_blame(&mut self) -> bool {
+        let buffer = &mut self.buffer;
+        let Some(file) = buffer.file.as_deref().filter(|_| self.blame) else {
+            return false;
+        };
+        if buffer
+            .blame
+            .as_ref()
+            .is_some_and(|(revision, _)| *revision == buffer.revision())
+        {
+            return false;
+        }
+
+        match blame::blame(Path::new(file), &buffer.file_contents()) {
+            Ok(lines) => buffer.blame = Some((buffer.revision(), lines)),
+            Err(err) => {
+                self.blame = false;
+                buffer.blame = None;
+                self.message = Some(Message::Error(err.to_string()));
+            }
+        }
+        true
+    }
+
+    /// Starts the language servers the open buffers need, sends them the buffers that changed
+    /// since the last call and handles what they sent back, like diagnostics or the answer to a
+    /// `gd` or `K`. Returns whether there is anything new to draw.
+    fn update_lsp(&mut self) -> bool {
+        self.start_language_servers();
+        self.sync_buffers();
+
+        let mut incoming = vec![];
+        for client in self.lsp.values_mut().flatten() {
+            match client.poll() {
+                Ok(messages) => incoming.extend(messages),
+                Err(err) => warn!("error talking to the language server: {}", err),
+            }
+        }
+
+        let mut updated = false;
+        for message in incoming {
+            match message {
+                Incoming::Diagnostics(uri, diagnostics) => {
+                    let buffer = iter::once(&mut self.buffer)
+                        .chain(self.buffers.iter_mut())
+                        .find(|buffer| {
+                            buffer
+                                .file
+                                .as_ref()
+                                .is_some_and(|file| lsp::uri(Path::new(file)) == uri)
+                        });
+                    if let Some(buffer) = buffer {
+                        buffer.set_diagnostics(diagnostics);
+                        let signs = buffer
+                            .diagnostics
+                            .iter()
+                            .map(|diagnostic| (diagnostic.start.1, diagnostic.severity.sign()))
+                            .collect::<Vec<_>>();
+                        buffer.set_signs("diagnostics", signs);
+                        updated = true;
+                    }
+                }
+                Incoming::Definition(Some(location)) => {
+                    self.go_to_location(location);
+                    updated = true;
+                }
+                Incoming::Definition(None) => {
+                    self.message = Some(Message::Error("No definition found".to_string()));
+                    updated = true;
+                }
+                Incoming::Hover(Some(text)) => {
+                    self.popup = Some(Popup::new(&text));
+                    updated = true;
+                }
+                Incoming::Hover(None) => {
+                    self.message = Some(Message::Info("No hover information".to_string()));
+                    updated = true;
+                }
+            }
+        }
+
+        if updated {
+            self.show_diagnostic();
+        }
+        updated
+    }
+
+    fn start_plugins(&mut self) {
+        let mut plugins: Vec<_> = self.config.plugins.iter().collect();
+        plugins.sort();
+        for (name, command) in plugins {
+            match Plugin::start(name, command) {
+                Ok(plugin) => self.plugins.push(plugin),
+                Err(err) => self.message = Some(Message::Error(err.to_string())),
+            }
+        }
+    }
+
+    /// Sends `event` to the plugins subscribed to it.
+    fn notify_plugins(&mut self, event: plugin::Event, params: serde_jsonrc::Value) {
+        for plugin in &mut self.plugins {
+            if let Err(err) = plugin.notify(event, params.clone()) {
+                warn!("error sending {} to plugin {}: {}", event, plugin.name, err);
+            }
+        }
+    }
+
+    /// Carries out what the plugins asked for since the last call, then sends the current
+    /// buffer to the ones that haven't seen it as it is, once typing pauses. Plugins that
+    /// stopped are dropped. Returns whether there is anything new to draw.
+    fn update_plugins(&mut self) -> bool {
+        let mut updated = false;
+        for i in 0..self.plugins.len() {
+            let incoming = match self.plugins[i].poll() {
+                Ok(incoming) => incoming,
+                Err(err) => {
+                    warn!("error talking to plugin {}: {}", self.plugins[i].name, err);
+                    continue;
+                }
+            };
+            for plugin::Incoming { id, request } in incoming {
+                let result = self.run_plugin_request(i, request);
+                if let Err(err) = self.plugins[i].respond(id, result) {
+                    warn!("error answering plugin {}: {}", self.plugins[i].name, err);
+                }
+                updated = true;
+            }
+        }
+
+        let typing = self
+            .last_input
+            .is_some_and(|last| last.elapsed() < PLUGIN_BUFFER_DELAY);
+        let seen = (self.buffer.name.clone(), self.buffer.revision());
+        let mut params = None;
+        for plugin in &mut self.plugins {
+            let subscribed = plugin.subscribed(plugin::Event::BufferChanged);
+            if !subscribed || typing || self.buffer.large || plugin.buffer.as_ref() == Some(&seen) {
+                continue;
+            }
+            let params = params.get_or_insert_with(|| {
+                json!({
+                    "name": self.buffer.name,
+                    "file": self.buffer.file,
+                    "revision": self.buffer.revision(),
+                    "text": self.buffer.to_string(),
+                })
+            });
+            if let Err(err) = plugin.notify(plugin::Event::BufferChanged, params.clone()) {
+                warn!(
+                    "error sending the buffer to plugin {}: {}",
+                    plugin.name, err
+                );
+            }
+            plugin.buffer = Some(seen.clone());
+        }
+        self.drop_stopped_plugins() || updated
+    }
+
+    /// Drops the plugins that exited or stopped reading what they're sent, along with their
+    /// statusline text. Returns whether there were any.
+    fn drop_stopped_plugins(&mut self) -> bool {
+        let mut stopped = vec![];
+        for mut plugin in std::mem::take(&mut self.plugins) {
+            if plugin.is_running() {
+                self.plugins.push(plugin);
+            } else {
+                stopped.push(plugin.name.clone());
+            }
+        }
+        if stopped.is_empty() {
+            return false;
+        }
+        for name in &stopped {
+            warn!("plugin {} stopped, dropping it", name);
+            self.plugin_status.remove(name);
+        }
+        let names = stopped.join(", ");
+        self.message = Some(Message::Error(format!("Plugin stopped: {names}")));
+        true
+    }
+
+    /// Carries out `request` from the plugin at index `i` of `plugins`.
+    fn run_plugin_request(&mut self, i: usize, request: plugin::Request) -> anyhow::Result<()> {
+        match request {
+            plugin::Request::InsertText(text) => {
+                if self.buffer.locked {
+                    anyhow::bail!("E21: Cannot make changes, the buffer can't be modified");
+                }
+                self.insert_text(&text);
+                self.pending_redraw = true;
+            }
+            plugin::Request::SetStatus(text) => {
+                let name = self.plugins[i].name.clone();
+                if text.is_empty() {
+                    self.plugin_status.remove(&name);
+                } else {
+                    self.plugin_status.insert(name, text);
+                }
+                self.pending_redraw = true;
+            }
+            plugin::Request::DefineKeybinding(mode, key, action) => {
+                self.config.keymap.bind(&mode, key, action.clone())?;
+                self.plugins[i].bindings.push((mode, key, action));
+            }
+        }
+        Ok(())
+    }
+
+    /// Tells the plugin `name` that the key it bound was pressed.
+    fn plugin_keybinding(&mut self, name: &str, key: &str) {
+        let mode = self.mode.name();
+        let Some(plugin) = self.plugins.iter_mut().find(|plugin| plugin.name == name) else {
+            self.message = Some(Message::Error(format!("No plugin named {name}")));
+            return;
+        };
+        if let Err(err) = plugin.keybinding(key, mode) {
+            warn!("error sending a key to plugin {}: {}", name, err);
+        }
+    }
+
+    fn start_language_servers(&mut self) {
+        let languages: Vec<_> = iter::once(&self.buffer)
+            .chain(self.buffers.iter())
+            .filter(|buffer| buffer.file.is_some())
+            .filter_map(|buffer| buffer.language)
+            .collect();
+        for language in languages {
+            let Some(command) = self.config.lsp_command(language.id()) else {
+                continue;
+            };
+            if self.lsp.contains_key(&language) {
+                continue;
+            }
+
+            let root = std::env::current_dir().unwrap_or_default();
+            let client = match lsp::Client::start(command, &root) {
+                Ok(client) => Some(client),
+                Err(err) => {
+                    self.message = Some(Message::Error(err.to_string()));
+                    None
+                }
+            };
+            self.lsp.insert(language, client);
+        }
+    }
+
+    /// Sends the language servers the text of the buffers that changed since they last saw them.
+    fn sync_buffers(&mut self) {
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            let (Some(file), Some(language)) = (&buffer.file, buffer.language) else {
+                continue;
+            };
+            let Some(Some(client)) = self.lsp.get_mut(&language) else {
+                continue;
+            };
+            if buffer.large || buffer.synced == Some(buffer.revision()) {
+                continue;
+            }
+
+            let uri = lsp::uri(Path::new(file));
+            let text = buffer.to_string();
+            let result = match buffer.synced {
+                Some(_) => client.did_change(&uri, buffer.revision(), &text),
+                None => client.did_open(&uri, language.id(), buffer.revision(), &text),
+            };
+            match result {
+                Ok(()) => buffer.synced = Some(buffer.revision()),
+                Err(err) => warn!("error sending {} to the language server: {}", file, err),
+            }
+        }
+    }
+
+    /// Asks the language server of the current buffer about the cursor position with `request`,
+    /// after sending it the latest text. The answer comes in later through `update_lsp`.
+    fn lsp_request(
+        &mut self,
+        request: fn(&mut lsp::Client, &str, (usize, usize)) -> anyhow::Result<()>,
+    ) {
+        self.start_language_servers();
+        self.sync_buffers();
+
+        let line = self.buffer.line(self.by()).unwrap_or_default();
+        let position = (lsp::utf16_column(&line, self.bx()), self.by());
+        let client = match (&self.buffer.file, self.buffer.language) {
+            (Some(file), Some(language)) if !self.buffer.large => self
+                .lsp
+                .get_mut(&language)
+                .and_then(Option::as_mut)
+                .map(|client| (client, lsp::uri(Path::new(file)))),
+            _ => None,
+        };
+        let Some((client, uri)) = client else {
+            self.message = Some(Message::Error(
+                "No language server for this buffer".to_string(),
+            ));
+            return;
+        };
+
+        if let Err(err) = request(client, &uri, position) {
+            self.message = Some(Message::Error(err.to_string()));
+        }
+    }
+
+    /// Jumps to the definition a language server answered `gd` with, opening its file when it
+    /// isn't the current buffer.
+    fn go_to_location(&mut self, location: lsp::Location) {
+        let Some(path) = lsp::path(&location.uri) else {
+            return;
+        };
+        // files in the project open by their relative path, like the picker opens them
+        let root = std::env::current_dir().unwrap_or_default();
+        let path = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+        let path = path.to_string_lossy().to_string();
+
+        self.record_jump();
+        if self.buffer.file.as_deref() != Some(path.as_str()) {
+            self.open_file(&path);
+            if self.buffer.file.as_deref() != Some(path.as_str()) {
+                return;
+            }
+        }
+
+        let (column, y) = location.position;
+        let line = self.buffer.line(y).unwrap_or_default();
+        self.jump_to(Some((lsp::char_column(&line, column), y)));
+    }
+
+    /// Shows the message of the diagnostic under the cursor, or of the first one on the cursor
+    /// line, when there is no other message. Returns whether it showed one.
+    fn show_diagnostic(&mut self) -> bool {
+        if self.message.is_some() || !matches!(self.mode, Mode::Normal) {
+            return false;
+        }
+
+        let (x, y) = (self.bx(), self.by());
+        let on_line = |diagnostic: &&lsp::Diagnostic| diagnostic.start.1 == y;
+        let diagnostics = &self.buffer.diagnostics;
+        let Some(diagnostic) = diagnostics
+            .iter()
+            .filter(on_line)
+            .find(|diagnostic| (diagnostic.start.0..=diagnostic.end.0).contains(&x))
+            .or_else(|| diagnostics.iter().find(on_line))
+        else {
+            return false;
+        };
+
+        let text = diagnostic
+            .message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        self.message = Some(match diagnostic.severity {
+            Severity::Error => Message::Error(text),
+            _ => Message::Info(text),
+        });
+        true
+    }
+
+    /// Writes a crash report after a panic, saving the unsaved changes of every buffer first: to
+    /// its swap file for `fed -r` when it can have one, and next to the report otherwise.
+    /// Returns the path of the report.
+    fn report_crash(&mut self) -> anyhow::Result<PathBuf> {
+        let dir = crash::dir()?;
+        let timestamp = crash::timestamp();
+        let mut recovered = vec![];
+        for (i, buffer) in iter::once(&self.buffer)
+            .chain(self.buffers.iter())
+            .enumerate()
+            .filter(|(_, buffer)| buffer.dirty)
+        {
+            let text = buffer.to_string();
+            let result = match buffer.file.as_deref() {
+                Some(file) if buffer.swappable => self
+                    .swap
+                    .write(file, &text)
+                    .map(|()| format!("{file}: recover with `fed -r {file}`")),
+                _ => {
+                    let path = dir.join(format!("crash-{timestamp}-{i}.txt"));
+                    fs::create_dir_all(&dir)
+                        .and_then(|()| fs::write(&path, &text))
+                        .map_err(anyhow::Error::from)
+                        .map(|()| format!("{}: saved to {}", buffer.name, path.display()))
+                }
+            };
+            recovered.push(result.unwrap_or_else(|err| format!("{}: {err}", buffer.name)));
+        }
+
+        let report = crash::Report {
+            buffer: self.buffer.name.clone(),
+            cursor: (self.by() + 1, self.bx() + 1),
+            actions: self.recent_actions.iter().cloned().collect(),
+            recovered,
+        };
+        report.write(dir)
+    }
+
+    /// Removes the swap files of all buffers when quitting.
+    fn remove_swap_files(&mut self) {
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            remove_swap_file(&self.swap, buffer);
+        }
+    }
+
+    /// Reloads the config and the theme when their files changed. Returns true when anything was
+    /// reloaded, leaving a notice or the error that kept it from reloading.
+    fn reload_changed(&mut self) -> bool {
+        let changed = self.watcher.changed();
+        if changed.is_empty() {
+            return false;
+        }
+
+        self.message = match self.reload(&changed) {
+            Ok(notice) => Some(Message::Info(notice.to_string())),
+            Err(err) => Some(Message::Error(format!("Error reloading: {err}"))),
+        };
+        self.watch_files();
+        true
+    }
+
+    fn reload(&mut self, changed: &[PathBuf]) -> anyhow::Result<&'static str> {
+        let theme_changed = self
+            .theme
+            .path
+            .as_ref()
+            .is_some_and(|path| changed.contains(path));
+
+        if changed.contains(&self.config_path) {
+            let config = Config::read_from_file(&self.config_path.to_string_lossy())?;
+            let background = config.background.unwrap_or(self.background);
+            let theme = config.theme_for(background);
+            if theme != self.config.theme_for(self.background) || theme_changed {
+                self.use_theme(match theme {
+                    Some(name) => Theme::load(name)?,
+                    None => Theme::default(),
+                });
+            }
+            if config.spell_dictionary != self.config.spell_dictionary {
+                self.dictionary = None;
+            }
+            self.background = background;
+            self.config = config;
+            // the keys plugins bound outlive the config they were bound in
+            for plugin in &self.plugins {
+                for (mode, key, action) in &plugin.bindings {
+                    self.config.keymap.bind(mode, *key, action.clone())?;
+                }
+            }
+            // and so do the abbreviations added with `:iabbrev`
+            self.config.abbreviations.extend(self.abbreviations.clone());
+            return Ok("Config reloaded");
+        }
+
+        if let Some(path) = self.theme.path.clone().filter(|_| theme_changed) {
+            self.use_theme(Theme::load_file(&path)?);
+        }
+        Ok("Theme reloaded")
+    }
+
+    fn handle_picker_input(&mut self, ev: Event) -> anyhow::Result<bool> {
+        let (
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }),
+            Some(picker),
+        ) = (ev, &mut self.picker)
+        else {
+            return Ok(false);
+        };
+
+        let ctrl = modifiers.contains(event::KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Esc => self.picker = None,
+            KeyCode::Enter => {
+                let file = picker.selected().map(str::to_string);
+                self.picker = None;
+                if let Some(file) = file {
+                    self.open_file(&file);
+                }
+            }
+            KeyCode::Up => picker.select_previous(),
+            KeyCode::Down => picker.select_next(),
+            KeyCode::Char('p') if ctrl => picker.select_previous(),
+            KeyCode::Char('n') if ctrl => picker.select_next(),
+            KeyCode::Backspace => picker.pop(),
+            KeyCode::Char(c) => picker.push(c),
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    /// Searches the files under the current directory for the regex `pattern`, listing the
+    /// matches in the quickfix window.
+    fn grep(&mut self, pattern: &str) {
+        if pattern.is_empty() {
+            self.message = Some(Message::Error("E471: Argument required".to_string()));
+            return;
+        }
+        let regex = match Regex::new(pattern) {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.message = Some(Message::Error(format!(
+                    "E383: Invalid search string: {err}"
+                )));
+                return;
+            }
+        };
+        let dir = match std::env::current_dir() {
+            Ok(dir) => dir,
+            Err(err) => {
+                self.message = Some(Message::Error(err.to_string()));
+                return;
+            }
+        };
+
+        self.quickfix = Quickfix::new(grep(&dir, &regex));
+        self.message = Some(if self.quickfix.is_empty() {
+            Message::Error(format!("E480: No match: {pattern}"))
+        } else {
+            Message::Info(self.quickfix.status())
+        });
+    }
+
+    /// Changes the directory of the editor for `:cd`, or of the window of the current buffer for
+    /// `:lcd`, to `arg`: home without it and the previous directory for `-`.
+    fn change_dir(&mut self, arg: Option<&str>, local: bool) {
+        let dir = match arg {
+            Some("-") => self.previous_dir.clone(),
+            arg => workdir::expand(arg),
+        };
+        let Some(dir) = dir else {
+            self.message = Some(Message::Error("E186: No previous directory".to_string()));
+            return;
+        };
+        let previous = std::env::current_dir().ok();
+        if let Err(err) = self.enter_dir(&dir) {
+            self.message = Some(Message::Error(format!(
+                "E344: Can't find directory \"{}\": {err}",
+                dir.display()
+            )));
+            return;
+        }
+
+        if local {
+            if self.buffer.dir.is_none() {
+                self.global_dir = previous.clone();
+            }
+            self.buffer.dir = std::env::current_dir().ok();
+        } else {
+            self.buffer.dir = None;
+            self.global_dir = None;
+        }
+        self.previous_dir = previous;
+        if let Ok(dir) = std::env::current_dir() {
+            self.message = Some(Message::Info(dir.display().to_string()));
+        }
+    }
+
+    /// Moves to the directory of the buffer that just became current: its own from `:lcd`, or
+    /// back to the editor's when it has none.
+    fn follow_dir(&mut self) {
+        let dir = match &self.buffer.dir {
+            Some(dir) => {
+                if self.global_dir.is_none() {
+                    self.global_dir = std::env::current_dir().ok();
+                }
+                dir.clone()
+            }
+            None => match self.global_dir.take() {
+                Some(dir) => dir,
+                None => return,
+            },
+        };
+        if std::env::current_dir().is_ok_and(|cwd| cwd == dir) {
+            return;
+        }
+        if let Err(err) = self.enter_dir(&dir) {
+            self.message = Some(Message::Error(err.to_string()));
+        }
+    }
+
+    /// Changes the directory of the editor to the root of the git repository the current file is
+    /// in, with `cd_to_root`. Buffers with their own directory from `:lcd` keep it.
+    fn cd_to_root(&mut self) {
+        if !self.config.cd_to_root || self.buffer.dir.is_some() {
+            return;
+        }
+        let file = self.buffer.file.as_deref().map(Path::new);
+        let Some(root) = file.and_then(workdir::project_root) else {
+            return;
+        };
+        if std::env::current_dir().is_ok_and(|cwd| cwd == root) {
+            return;
+        }
+        if let Err(err) = self.enter_dir(&root) {
+            self.message = Some(Message::Error(err.to_string()));
+        }
+    }
+
+    /// Makes `dir` the current directory, renaming the files of the buffers, the quickfix list and
+    /// the `:make` problems so their relative names still point at the same files.
+    fn enter_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let from = std::env::current_dir()?;
+        std::env::set_current_dir(dir)?;
+        let to = std::env::current_dir()?;
+
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            if let Some(file) = &buffer.file {
+                let file = workdir::relocate(file, &from, &to);
+                buffer.name = file.clone();
+                buffer.file = Some(file);
+            }
+        }
+        self.quickfix.relocate(&from, &to);
+        for problem in &mut self.problems {
+            problem.entry.file = workdir::relocate(&problem.entry.file, &from, &to);
+        }
+        Ok(())
+    }
+
+    /// Starts building the project around the current directory for `:make`, with `args` after
+    /// the configured or known build command.
+    fn make(&mut self, args: Option<&str>) {
+        if let Some(make) = &self.make {
+            let error = format!("Already running {}", make.command);
+            self.message = Some(Message::Error(error));
+            return;
+        }
+        let dir = match std::env::current_dir() {
+            Ok(dir) => dir,
+            Err(err) => {
+                self.message = Some(Message::Error(err.to_string()));
+                return;
+            }
+        };
+        let Some((mut command, project)) = make::command(&self.config.make, &dir) else {
+            let error = "No build command for this directory, set one under [make]";
+            self.message = Some(Message::Error(error.to_string()));
+            return;
+        };
+        if let Some(args) = args {
+            command = format!("{command} {args}");
+        }
+
+        self.message = Some(Message::Info(format!("Running {command}...")));
+        self.make = Some(Make::start(&command, &project));
+    }
+
+    /// Lists the errors of the `:make` that finished since the last call in the quickfix list,
+    /// marks them in the gutter and jumps to the first one. Returns whether it finished.
+    fn update_make(&mut self) -> bool {
+        let Some(output) = self.make.as_ref().and_then(Make::poll) else {
+            return false;
+        };
+        let make = self.make.take().expect("make is running");
+        let output = match output {
+            Ok(output) => output,
+            Err(err) => {
+                self.message = Some(Message::Error(err.to_string()));
+                return true;
+            }
+        };
+
+        let cwd = std::env::current_dir().unwrap_or_else(|_| make.dir.clone());
+        let text = format!("{}\n{}", output.stdout, output.stderr);
+        self.problems = make::parse(&text, &make.dir, &cwd);
+        let entries = self.problems.iter().map(|problem| problem.entry.clone());
+        self.quickfix = Quickfix::new(entries.collect());
+        self.quickfix.open = false;
+        self.mark_problems();
+
+        if self.quickfix.is_empty() {
+            self.message = Some(match output.error() {
+                Some(error) => Message::Error(format!("{}: {error}", make.command)),
+                None => Message::Info(format!("{}: no errors", make.command)),
+            });
+        } else {
+            self.jump_to_quickfix();
+        }
+        true
+    }
+
+    /// Marks the lines of the open buffers that the last `:make` found problems on.
+    fn mark_problems(&mut self) {
+        for buffer in iter::once(&mut self.buffer).chain(self.buffers.iter_mut()) {
+            let file = buffer.file.as_deref();
+            let signs: Vec<_> = self
+                .problems
+                .iter()
+                .filter(|problem| Some(problem.entry.file.as_str()) == file)
+                .map(|problem| (problem.entry.line, problem.severity.sign()))
+                .collect();
+            buffer.set_signs("make", signs);
         }
-        false
     }
-    fn move_to_end_of_viewport(&mut self) {
-        if self.buffer.len() > self.vheight {
-            self.cy = self.vheight - 1;
-        } else {
-            self.cy = self.buffer.len() - 1;
+
+    /// Opens the file of the selected quickfix entry and moves to the match.
+    fn jump_to_quickfix(&mut self) {
+        let Some(entry) = self.quickfix.selected().cloned() else {
+            return;
+        };
+
+        self.record_jump();
+        self.open_file(&entry.file);
+        self.mark_problems();
+        if self.buffer.file.as_deref() == Some(entry.file.as_str()) {
+            self.jump_to(Some((entry.column, entry.line)));
+            self.message = Some(Message::Info(self.quickfix.status()));
         }
     }
 
-    fn move_to_previous_page(&mut self) -> anyhow::Result<()> {
-        if self.vtop > self.vheight {
-            self.vtop -= self.vheight;
+    /// Reports why `:cnext`, `:cprevious` or `:copen` had nowhere to go.
+    fn quickfix_error(&mut self) {
+        let error = if self.quickfix.is_empty() {
+            "E42: No Errors"
         } else {
-            self.vtop = 0;
-        }
-        Ok(())
+            "E553: No more items"
+        };
+        self.message = Some(Message::Error(error.to_string()));
     }
 
-    fn move_to_next_word(&mut self) -> anyhow::Result<bool> {
-        if let Some(line) = self.line() {
-            let x = self.bx();
-            let mut nx = line.chars().skip(x).position(|c| c.is_whitespace());
-            if nx.is_none() {
-                nx = Some(line.len() - x);
+    /// Runs `command` through the shell for `:!cmd`, showing what it printed in the pager.
+    fn shell(&mut self, command: &str) {
+        let output = match shell::run(command, None) {
+            Ok(output) => output,
+            Err(err) => {
+                self.message = Some(Message::Error(err.to_string()));
+                return;
             }
-            match nx {
-                Some(x) => {
-                    self.cx += x + 1;
-                }
-                None => {
-                    self.cx = line.len() - 1;
-                }
+        };
+
+        let mut text = output.stdout.clone();
+        text.push_str(&output.stderr);
+        if let Some(error) = output.error() {
+            if text.is_empty() {
+                self.message = Some(Message::Error(error));
+                return;
             }
+            text.push_str(&format!("\n{error}"));
         }
-        Ok(false)
+        if text.trim().is_empty() {
+            self.message = Some(Message::Info(format!(":!{command}")));
+            return;
+        }
+        self.pager = Some(Pager::new(format!(":!{command}"), &text));
     }
 
-    fn move_to_previous_word(&mut self) {
-        if let Some(line) = self.line() {
-            let x = self.bx();
-            let mut px = line
-                .chars()
-                .rev()
-                .skip(line.len() - x + 1)
-                .position(|c| c.is_whitespace());
-            if px.is_none() {
-                px = Some(line.len() - x);
-            }
-            match px {
-                Some(x) => {
-                    self.cx -= x + 1;
-                }
-                None => {
-                    self.cx = 0;
-                }
+    /// Replaces lines `start..=end` with what `command` prints when given them, for
+    /// `:{range}!cmd`. The lines are left alone when the command fails.
+    fn filter(&mut self, command: &str, (start, end): (usize, usize)) {
+        let input: String = (start..=end)
+            .filter_map(|y| self.buffer.line(y))
+            .map(|line| line + "\n")
+            .collect();
+        let output = match shell::run(command, Some(input)) {
+            Ok(output) => output,
+            Err(err) => {
+                self.message = Some(Message::Error(err.to_string()));
+                return;
             }
+        };
+        if let Some(error) = output.error() {
+            let error = match output.stderr.lines().next() {
+                Some(line) => format!("{error}: {line}"),
+                None => error,
+            };
+            self.message = Some(Message::Error(error));
+            return;
         }
+
+        let lines: Vec<_> = output.stdout.lines().collect();
+        self.buffer.replace_lines(start..end + 1, &lines);
+        self.buffer.dirty = true;
+        self.move_to_position((0, start.min(self.buffer.len() - 1)));
+        let count = end - start + 1;
+        self.message = Some(Message::Info(format!(
+            "{count} line{} filtered",
+            if count == 1 { "" } else { "s" }
+        )));
     }
 
-    fn handle_insert_input(&mut self, ev: Event) -> anyhow::Result<bool> {
-        match ev {
-            Event::Key(KeyEvent {
-                code: KeyCode::Char(c),
-                ..
-            }) => {
-                self.insert_char(c)?;
-                self.move_right()?;
+    fn handle_pager_input(&mut self, ev: Event) -> anyhow::Result<bool> {
+        let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = ev
+        else {
+            return Ok(false);
+        };
+        let Some(pager) = &mut self.pager else {
+            return Ok(false);
+        };
+
+        let ctrl = modifiers.contains(event::KeyModifiers::CONTROL);
+        let height = self.vheight;
+        let page = pager.page(height) as isize;
+        match code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => self.pager = None,
+            KeyCode::Down | KeyCode::Char('j') => pager.scroll(1, height),
+            KeyCode::Up | KeyCode::Char('k') => pager.scroll(-1, height),
+            KeyCode::Char('d') if ctrl => pager.scroll(page / 2, height),
+            KeyCode::Char('u') if ctrl => pager.scroll(-page / 2, height),
+            KeyCode::Char('f') if ctrl => pager.scroll(page, height),
+            KeyCode::Char('b') if ctrl => pager.scroll(-page, height),
+            KeyCode::PageDown | KeyCode::Char(' ') => pager.scroll(page, height),
+            KeyCode::PageUp => pager.scroll(-page, height),
+            KeyCode::Char('g') | KeyCode::Home => pager.scroll(isize::MIN, height),
+            KeyCode::Char('G') | KeyCode::End => pager.scroll(isize::MAX, height),
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    fn handle_quickfix_input(&mut self, ev: Event) -> anyhow::Result<bool> {
+        let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = ev
+        else {
+            return Ok(false);
+        };
+
+        let ctrl = modifiers.contains(event::KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => self.quickfix.open = false,
+            KeyCode::Enter => {
+                self.quickfix.open = false;
+                self.jump_to_quickfix();
             }
-            Event::Key(KeyEvent {
-                code: kc,
-                modifiers: _m,
-                ..
-            }) => match kc {
-                KeyCode::Esc => {
-                    self.mode = Mode::Normal;
-                }
-                KeyCode::Left => {
-                    self.move_left()?;
-                }
-                KeyCode::Backspace => {
-                    self.remove_char()?;
-                    self.move_left()?;
-                }
-                KeyCode::Delete => {
-                    self.remove_char()?;
-                }
-                KeyCode::Enter => {
-                    self.split_line_at_cursor()?;
-                }
-                KeyCode::Tab => {
-                    for _ in 0..self.config.tab_size {
-                        self.insert_char(' ')?;
-                        self.move_right()?;
-                    }
-                }
-                _ => {}
-            },
+            KeyCode::Up | KeyCode::Char('k') => _ = self.quickfix.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => _ = self.quickfix.select_next(),
+            KeyCode::Char('p') if ctrl => _ = self.quickfix.select_previous(),
+            KeyCode::Char('n') if ctrl => _ = self.quickfix.select_next(),
             _ => {}
         }
 
         Ok(true)
     }
 
-    fn at_end_of_line(&self) -> bool {
-        self.bx() == self.line().map(|s| s.len()).unwrap_or(0)
+    /// Switches to the buffer for `path`, opening it in a new buffer if it isn't open yet.
+    fn open_file(&mut self, path: &str) {
+        if let Some(i) =
+            (0..self.buffers.len()).find(|&i| self.buffer_at(i).file.as_deref() == Some(path))
+        {
+            self.switch_buffer(i);
+            return;
+        }
+
+        match open_buffer(&self.swap, path, false) {
+            Ok((buffer, message)) => {
+                self.buffers.push(buffer);
+                self.switch_buffer(self.buffers.len() - 1);
+                self.message = message;
+                self.cd_to_root();
+            }
+            Err(err) => {
+                self.message = Some(Message::Error(format!(
+                    "E484: Can't open file {path}: {err}"
+                )));
+            }
+        }
+    }
+
+    /// Adds `buffer` after the open ones and switches to it.
+    fn open_scratch(&mut self, buffer: Buffer) {
+        self.buffers.push(buffer);
+        self.switch_buffer(self.buffers.len() - 1);
     }
 
-    fn split_line_at_cursor(&mut self) -> anyhow::Result<()> {
-        if self.at_end_of_line() {
-            self.move_down();
-            self.insert_line()?;
-            return Ok(());
+    /// Switches to the help buffer, opening it with the current key bindings when it isn't open.
+    fn open_help(&mut self) {
+        let open = (0..self.buffers.len()).find(|&i| {
+            let buffer = self.buffer_at(i);
+            buffer.scratch && buffer.name == HELP
+        });
+        match open {
+            Some(i) => self.switch_buffer(i),
+            None => {
+                let mut buffer = Buffer::scratch(HELP, &help::text(&self.config.keymap));
+                buffer.readonly = true;
+                buffer.locked = true;
+                self.open_scratch(buffer);
+            }
         }
+    }
 
-        let x = self.bx();
-        let y = self.by();
+    /// Loads `path` into the current buffer, or reloads the current file when no path is given.
+    fn edit(&mut self, path: Option<&str>, force: bool) {
+        if self.buffer.unsaved() && !force {
+            self.message = Some(Message::Error(
+                "E37: No write since last change (add ! to override)".to_string(),
+            ));
+            return;
+        }
+
+        let Some(path) = path.or(self.buffer.file.as_deref()).map(str::to_string) else {
+            self.message = Some(Message::Error("E32: No file name".to_string()));
+            return;
+        };
+
+        // the changes are being thrown away, so their swap file goes too
+        remove_swap_file(&self.swap, &mut self.buffer);
+        match open_buffer(&self.swap, &path, false) {
+            Ok((buffer, message)) => {
+                let dir = self.buffer.dir.take();
+                self.buffer = buffer;
+                self.buffer.dir = dir;
+                (self.cx, self.cy, self.vtop) = (0, 0, 0);
+                self.message = message
+                    .or_else(|| Some(Message::Info(format!("\"{path}\" {}L", self.buffer.len()))));
+                self.cd_to_root();
+            }
+            Err(err) => {
+                self.message = Some(Message::Error(format!(
+                    "E484: Can't open file {path}: {err}"
+                )));
+            }
+        }
+    }
+
+    /// Runs `:s` with `args` like `/foo/bar/g` over lines `start..=end`.
+    fn substitute(&mut self, args: &str, start: usize, end: usize) {
+        let substitute = match Substitute::parse(args) {
+            Ok(substitute) => substitute,
+            Err(err) => {
+                self.message = Some(Message::Error(err.to_string()));
+                return;
+            }
+        };
 
-        let line = self.line().cloned();
-        if let Some(line) = line {
-            let (left, right) = line.split_at(x).clone();
+        let (count, lines, last) = substitute.apply(&mut self.buffer, start, end);
+        let Some(last) = last else {
+            self.message = Some(Message::Error(format!(
+                "E486: Pattern not found: {}",
+                substitute.pattern()
+            )));
+            return;
+        };
+
+        self.buffer.dirty = true;
+        self.move_to_position((0, last));
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        self.message = Some(Message::Info(format!(
+            "{count} substitution{} on {lines} line{}",
+            plural(count),
+            plural(lines),
+        )));
+    }
 
-            let line = self.buffer.get_mut(y).expect("line out of bounds");
-            *line = left.to_string();
+    fn handle_command_input(&mut self, ev: Event) -> anyhow::Result<bool> {
+        let Event::Key(event) = ev else {
+            return Ok(false);
+        };
 
-            self.buffer.insert(y + 1, right.to_string());
-            self.move_down();
-            self.move_start_of_line()?;
+        let line = &mut self.command_line;
+        match (self.config.keymap.command(&event), event.code) {
+            (Some(Action::NormalMode), _) => {
+                line.clear();
+                self.mode = Mode::Normal;
+            }
+            (Some(Action::Newline), _) => {
+                let line = line.submit();
+                self.mode = Mode::Normal;
+                self.run_command(&line)?;
+            }
+            // backspacing over an empty line leaves command mode
+            (Some(Action::Backspace), _) => {
+                if line.backspace() {
+                    return Ok(true);
+                }
+                self.mode = Mode::Normal;
+            }
+            (Some(Action::DeleteChar), _) => line.delete(),
+            (Some(Action::MoveLeft), _) => line.move_left(),
+            (Some(Action::MoveRight), _) => line.move_right(),
+            (Some(Action::StartOfLine), _) => line.move_to_start(),
+            (Some(Action::EndOfLine), _) => line.move_to_end(),
+            (Some(Action::MoveUp), _) => line.previous(),
+            (Some(Action::MoveDown), _) => line.next(),
+            (Some(_), _) => {}
+            (None, KeyCode::Char(c)) => line.insert(c),
+            _ => {}
         }
-        Ok(())
+
+        Ok(true)
     }
 
-    fn insert_char(&mut self, c: char) -> anyhow::Result<()> {
-        let x = self.bx();
-        let y = self.by();
+    fn run_command(&mut self, line: &str) -> anyhow::Result<()> {
+        debug!("command: {}", line);
+        if line.trim().is_empty() {
+            return Ok(());
+        }
 
-        let line = self.buffer.get_mut(y).expect("line out of bounds");
-        line.insert(x as usize, c);
+        let mark = |name| self.buffer.mark(name).map(|(_, y)| y);
+        match Command::parse(line, self.by(), self.buffer.len() - 1, &mark)
+            .and_then(|command| command.action())
+        {
+            Ok(action) => {
+                self.run_action(action)?;
+            }
+            Err(err) => self.message = Some(Message::Error(err.to_string())),
+        }
         Ok(())
     }
+}
 
-    fn insert_line(&mut self) -> anyhow::Result<()> {
-        self.buffer.insert(self.by(), String::new());
-        Ok(())
+/// Opens `path` into a buffer that saves its changes to a swap file. When recovering, the text
+/// comes from the swap file left behind by an earlier session. Otherwise such a swap file is kept
+/// for later and the buffer doesn't get one of its own.
+fn open_buffer(
+    swap: &SwapDir,
+    path: &str,
+    recover: bool,
+) -> anyhow::Result<(Buffer, Option<Message>)> {
+    let mut buffer = Buffer::open(path)?;
+    if buffer.large {
+        return Ok((buffer, None));
     }
 
-    fn remove_char(&mut self) -> anyhow::Result<()> {
-        let x = self.bx();
-        let y = self.by();
-        if x > 0 {
-            let line = self.buffer.get_mut(y).expect("line out of bounds");
-            line.remove(x - 1);
+    let found = swap.exists(path);
+    // a swap file left behind is kept for `fed -r` rather than overwritten
+    buffer.swappable = !found || recover;
+    let message = match (found, recover) {
+        (true, true) => {
+            buffer.set_text(&swap.read(path)?);
+            buffer.dirty = true;
+            Some(Message::Info(format!(
+                "Recovered \"{path}\", write it to keep the changes"
+            )))
         }
-        Ok(())
+        (true, false) => Some(Message::Error(format!(
+            "E325: ATTENTION: Found a swap file for \"{path}\", run `fed -r {path}` to recover"
+        ))),
+        (false, true) => Some(Message::Error(format!(
+            "E305: No swap file found for {path}"
+        ))),
+        (false, false) => None,
+    };
+    Ok((buffer, message))
+}
+
+/// The gutter of the sides of a diff, laid out like the buffer's by `config`.
+fn diff_gutter(config: &Config) -> diff::Gutter<'_> {
+    diff::Gutter {
+        digits: config.number.then_some(config.number_width as usize),
+        padding: config.gutter_padding as usize,
+        separator: &config.gutter_separator,
     }
+}
 
-    fn affects_buffer(&self) -> bool {
-        matches!(self.mode, Mode::Normal | Mode::Insert)
+/// The name a mark is stored under, where `` ` `` is the same mark as `'`.
+fn mark_name(c: char) -> char {
+    if c == '`' {
+        '\''
+    } else {
+        c
     }
+}
 
-    fn handle_command(&mut self) -> anyhow::Result<()> {
-        if let Some(cmd) = get_command(&self)? {
-            log!("command: {}", cmd);
-            if cmd == "q" {
-                self.quit = true;
-            } else if cmd == "$" {
-                self.move_to_end_of_buffer();
-            } else if let Ok(line) = cmd.parse::<usize>() {
-                if line == 0 {
-                    self.move_to_start_of_buffer();
-                } else if line <= self.buffer.len() {
-                    self.move_to_line(line - 1);
-                }
-            }
+/// Removes the swap file of `buffer`, if it has one.
+fn remove_swap_file(swap: &SwapDir, buffer: &mut Buffer) {
+    if let (Some(file), Some(_)) = (&buffer.file, buffer.swapped.take()) {
+        if let Err(err) = swap.remove(file) {
+            warn!("error removing the swap file for {}: {}", file, err);
         }
+    }
+}
 
-        self.mode = Mode::Normal;
-        self.draw(true)?;
-        Ok(())
+/// The commands `--batch` runs: the contents of the script file it names, one command per line,
+/// or the argument itself.
+fn batch_script(arg: &str) -> anyhow::Result<String> {
+    if !Path::new(arg).is_file() {
+        return Ok(arg.to_string());
     }
+    fs::read_to_string(arg).map_err(|err| anyhow::anyhow!("error reading {arg}: {err}"))
+}
+
+/// Reads the config file from `--config` or its default path.
+fn read_config(args: &Args) -> anyhow::Result<(PathBuf, Config)> {
+    let path = match &args.config {
+        Some(path) => PathBuf::from(path),
+        None => Config::path()?,
+    };
+    let config = Config::read_from_file(&path.to_string_lossy())?;
+    Ok((path, config))
 }
 
-fn init_logger() {
-    LOGGER.set(Logger::new("/tmp/fed.log")).unwrap();
+/// Starts logging as the config says, with `--log-level` and `--log-file` taking precedence.
+fn init_logger(args: &Args, config: &Config) -> anyhow::Result<()> {
+    let mut settings = config.log.clone();
+    if let Some(level) = args.log_level {
+        settings.level = level;
+    }
+    if let Some(file) = &args.log_file {
+        settings.file = file.clone();
+    }
+    LOGGER.set(Logger::new(settings)?).unwrap();
+    Ok(())
 }
 
 fn setup_panic_hook() {
     let default_panic = panic::take_hook();
     panic::set_hook(Box::new(move |info| {
         // Clean up the terminal
-        let _ = terminal::disable_raw_mode();
-        let _ = stdout().execute(LeaveAlternateScreen);
+        tty::restore();
+        crash::record_panic(info);
 
         // Call the default panic hook
         default_panic(info);
     }));
 }
 
+/// Prints the files `fed -r` can recover.
+fn list_swap_files() {
+    let files = match SwapDir::default_dir() {
+        Ok(dir) => SwapDir::new(dir).files(),
+        Err(_) => vec![],
+    };
+    if files.is_empty() {
+        println!("No swap files found.");
+        return;
+    }
+
+    println!("Swap files found:");
+    for (i, file) in files.iter().enumerate() {
+        println!("{:>4}. {file}", i + 1);
+    }
+}
+
 fn main() {
-    setup_panic_hook();
-    init_logger();
+    let args: Args = argh::from_env();
+    if args.version {
+        println!("fed {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+
+    if args.diff && args.files().len() != 2 {
+        eprintln!("fed: --diff takes two files");
+        process::exit(2);
+    }
+
+    if args.recover && args.files().is_empty() {
+        list_swap_files();
+        return;
+    }
 
-    let file = std::env::args().nth(1);
-    // let theme = std::env::args()
-    //     .nth(2)
-    //     .unwrap_or("src/fixtures/GitHub.tmTheme".to_string());
+    setup_panic_hook();
+    let (config_path, config) = match read_config(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to read the config: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = init_logger(&args, &config) {
+        eprintln!("Failed to start logging: {}", e);
+        return;
+    }
 
-    let mut editor = match Editor::new(file) {
+    let mut editor = match Editor::new(&args, config_path, config) {
         Ok(e) => e,
         Err(e) => {
             eprintln!("Failed to initialize editor: {}", e);
             return;
         }
     };
-    editor.run().unwrap();
+    if let Some(script) = &args.batch {
+        let result = batch_script(script).and_then(|script| editor.run_batch(&script));
+        if let Err(err) = result {
+            eprintln!("fed: {err:#}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    match panic::catch_unwind(panic::AssertUnwindSafe(|| editor.run())) {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => {
+            eprintln!("fed: {err:#}");
+            process::exit(1);
+        }
+        Err(_) => {
+            match editor.report_crash() {
+                Ok(path) => eprintln!("fed crashed, see {}", path.display()),
+                Err(err) => eprintln!("fed crashed and couldn't write a report: {err:#}"),
+            }
+            process::exit(101);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::signs::{Sign, SignKind};
+
+    /// An editor on `text` that draws on a grid of `width` by `height` cells.
+    fn editor(text: &str, width: usize, height: usize) -> Editor {
+        let mut editor = Editor {
+            buffer: Buffer::from_contents(text),
+            screen: Box::new(Grid::new(width, height)),
+            width,
+            height,
+            ..Default::default()
+        };
+        editor.update_height();
+        editor.update_gutter();
+        editor
+    }
+
+    #[test]
+    fn test_draw_buffer_and_gutter() {
+        let mut editor = editor("one\ntwo", 12, 5);
+        editor.buffer.set_signs(
+            "lsp",
+            [(
+                1,
+                Sign {
+                    text: '●',
+                    kind: SignKind::Error,
+                    priority: 1,
+                },
+            )],
+        );
+        editor.draw_buffer().unwrap();
+        editor.draw_gutter().unwrap();
+
+        assert_eq!(editor.screen.row(0), "   1 ▎one   ");
+        assert_eq!(editor.screen.row(1), "●  2 ▎two   ");
+        assert_eq!(editor.screen.row(2), "            ");
+        let sign = editor.screen.cell(0, 1).unwrap();
+        assert_eq!(sign.fg, editor.theme.sign_color(SignKind::Error));
+    }
+
+    #[test]
+    fn test_gutter_appearance() {
+        let mut editor = editor("one\ntwo", 12, 5);
+        editor.config.gutter_separator = "│ ".to_string();
+        editor.config.gutter_padding = 2;
+        editor.update_gutter();
+        editor.draw_buffer().unwrap();
+        editor.draw_gutter().unwrap();
+        assert_eq!(editor.screen.row(0), "   1  │ one ");
+
+        editor.set_option("nonumber");
+        editor.update_gutter();
+        editor.draw_buffer().unwrap();
+        editor.draw_gutter().unwrap();
+        assert_eq!((editor.vleft, editor.vwidth), (3, 9));
+        assert_eq!(editor.screen.row(1), " │ two      ");
+
+        // wide separators take the columns they're shown in, and the text starts after them
+        editor.config.gutter_separator = "｜".to_string();
+        editor.update_gutter();
+        editor.draw_buffer().unwrap();
+        editor.draw_gutter().unwrap();
+        assert_eq!(editor.vleft, 3);
+        assert_eq!(editor.screen.row(0), " ｜one      ");
+        assert_eq!(editor.screen.cell(editor.vleft, 0).unwrap().ch, 'o');
+    }
+
+    #[test]
+    fn test_draw_statusline() {
+        let mut editor = editor("one\ntwo\nthree", 40, 5);
+        editor.buffer.name = "notes.txt".to_string();
+        editor.run_action(Action::MoveDown).unwrap();
+        editor.draw_statusline().unwrap();
+
+        let row = editor.screen.row(3);
+        assert!(row.contains(" NORMAL "), "{row}");
+        assert!(row.contains(" notes.txt "), "{row}");
+        assert!(row.trim_end().ends_with("2:1 66%"), "{row}");
+    }
+
+    #[test]
+    fn test_failed_find_cancels_operator() {
+        let keys = |editor: &mut Editor, keys: &str| {
+            for c in keys.chars() {
+                let event = KeyEvent::new(KeyCode::Char(c), event::KeyModifiers::NONE);
+                editor.handle_input(Event::Key(event)).unwrap();
+            }
+        };
+        let mut editor = editor("abc abc", 40, 5);
+        keys(&mut editor, "dfzd;dtz");
+        assert_eq!(editor.buffer.to_string(), "abc abc");
+        keys(&mut editor, "dfb");
+        assert_eq!(editor.buffer.to_string(), "c abc");
+    }
+
+    #[test]
+    fn test_horizontal_scroll() {
+        let mut editor = editor("0123456789abcdefghij\nshort", 12, 5);
+        editor.config.wrap = false;
+        editor.update_gutter();
+        editor.run_action(Action::EndOfLine).unwrap();
+        editor.draw(true).unwrap();
+
+        assert_eq!(editor.hscroll, 14);
+        assert_eq!(editor.cursor_position(), (5, 0));
+        assert_eq!(editor.screen.row(0), "   1 ▎efghij");
+
+        editor.run_action(Action::MoveDown).unwrap();
+        editor.draw(true).unwrap();
+        assert_eq!(editor.hscroll, 4);
+        assert_eq!(editor.screen.row(1), "   2 ▎t     ");
+    }
+
+    #[test]
+    fn test_help_is_locked() {
+        let keys = |editor: &mut Editor, keys: &str| {
+            for c in keys.chars() {
+                let event = KeyEvent::new(KeyCode::Char(c), event::KeyModifiers::NONE);
+                editor.handle_input(Event::Key(event)).unwrap();
+            }
+        };
+        let mut editor = editor("", 40, 5);
+        editor.buffers = vec![Buffer::new()];
+        editor.open_help();
+        assert_eq!(editor.current, 1);
+        let text = editor.buffer.to_string();
+
+        keys(&mut editor, "ixyz");
+        keys(&mut editor, "ddxJvjd");
+        assert!(matches!(editor.mode, Mode::Normal));
+        assert_eq!(editor.buffer.to_string(), text);
+        assert!(matches!(&editor.message, Some(Message::Error(m)) if m.starts_with("E21")));
+        editor.paste("text");
+        assert_eq!(editor.buffer.to_string(), text);
+    }
+
+    #[test]
+    fn test_autosave_failure() {
+        let mut editor = editor("text", 40, 5);
+        editor.config.autosave_interval_secs = Some(1);
+        editor.buffer.file = Some("/nonexistent/dir/file.txt".to_string());
+        editor.buffer.insert((0, 0), "more ");
+        editor.buffer.dirty = true;
+
+        assert!(!editor.autosave());
+        assert!(matches!(editor.message.take(), Some(Message::Error(_))));
+        assert!(!editor.autosave());
+        assert!(editor.message.is_none());
+
+        editor.buffer.insert((0, 0), "x");
+        assert!(!editor.autosave());
+        assert!(matches!(editor.message, Some(Message::Error(_))));
+    }
+
+    #[test]
+    fn test_switch_to_shorter_buffer() {
+        let mut editor = editor("one\ntwo", 40, 5);
+        editor.buffers = vec![Buffer::new(), Buffer::from_contents("a\nb\nc\nd\ne\nf")];
+        editor.buffers[1].view = (0, 2, 3);
+        editor.buffers[1].set_text("a\nb");
+
+        editor.switch_buffer(1);
+        assert_eq!(editor.by(), 1);
+        assert!(editor.vtop <= 1);
+    }
+
+    #[test]
+    fn test_reload_keeps_abbreviations() {
+        let path = std::env::temp_dir().join(format!("fed-abbrev-{}.toml", std::process::id()));
+        fs::write(&path, "[abbreviations]\nbtw = \"by the way\"\n").unwrap();
+        let mut editor = editor("", 40, 5);
+        editor.config_path = path.clone();
+        editor.abbreviate(Some("teh the"));
+
+        editor.reload(std::slice::from_ref(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(editor.config.abbreviations["teh"], "the");
+        assert_eq!(editor.config.abbreviations["btw"], "by the way");
+    }
+
+    #[test]
+    fn test_stopped_plugin_is_dropped() {
+        let mut editor = editor("text", 40, 5);
+        editor.plugins.push(Plugin::start("quits", "true").unwrap());
+        editor
+            .plugin_status
+            .insert("quits".to_string(), "on".to_string());
+
+        let started = Instant::now();
+        while editor.plugins[0].is_running() && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(editor.update_plugins());
+        assert!(editor.plugins.is_empty());
+        assert!(editor.plugin_status.is_empty());
+        assert!(matches!(
+            &editor.message,
+            Some(Message::Error(m)) if m == "Plugin stopped: quits"
+        ));
+    }
+
+    #[test]
+    fn test_substitute_message() {
+        let mut editor = editor("foo foo\nbar\nfoo", 40, 5);
+        editor.substitute("/foo/x/g", 0, 2);
+        assert_eq!(editor.buffer.to_string(), "x x\nbar\nx");
+        assert!(matches!(
+            &editor.message,
+            Some(Message::Info(m)) if m == "3 substitutions on 2 lines"
+        ));
+    }
+
+    #[test]
+    fn test_draw_scrollbar() {
+        let text = (1..=20)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut editor = editor(&text, 20, 7);
+        editor.config.scrollbar = true;
+        editor.buffer.diagnostics = vec![lsp::Diagnostic {
+            start: (0, 15),
+            end: (1, 15),
+            severity: lsp::Severity::Error,
+            message: "oops".to_string(),
+        }];
+        editor.draw(true).unwrap();
+
+        assert_eq!(editor.vleft + editor.vwidth, 19);
+        let column: String = (0..5)
+            .map(|y| editor.screen.cell(19, y).unwrap().ch)
+            .collect();
+        assert_eq!(column, "   ─ ");
+        assert_eq!(
+            editor.screen.cell(19, 3).unwrap().fg,
+            editor.theme.sign_color(SignKind::Error)
+        );
+    }
+
+    #[test]
+    fn test_search_count() {
+        let mut editor = editor(
+            "one two
+one
+two one",
+            40,
+            5,
+        );
+        assert_eq!(editor.search_count(), None);
+        editor.run_action(Action::NextOccurrence).unwrap();
+        assert_eq!(editor.search_count(), Some((2, 3)));
+        editor.run_action(Action::NextOccurrence).unwrap();
+        assert_eq!(editor.statusline_values()["search"], "match 3/3");
+        editor.run_action(Action::MoveUp).unwrap();
+        assert_eq!(editor.search_count(), None);
+    }
+
+    #[test]
+    fn test_draw_wrapped_lines() {
+        let mut editor = editor("abcdefghij\nk", 10, 6);
+        editor.config.wrap = true;
+        editor.draw_buffer().unwrap();
+        editor.draw_gutter().unwrap();
+
+        assert_eq!(editor.screen.row(0), "   1 ▎abcd");
+        assert_eq!(editor.screen.row(1), "     ▎efgh");
+        assert_eq!(editor.screen.row(2), "     ▎ij  ");
+        assert_eq!(editor.screen.row(3), "   2 ▎k   ");
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut editor = editor("a\nb\nc", 21, 6);
+        editor.buffers = vec![Buffer::new(), Buffer::from_contents("a\nB\nc\nd")];
+        editor.diff = Some(Diff::new(0, 1));
+        editor.update_diff();
+        editor.update_gutter();
+        editor.draw_diff().unwrap();
+
+        assert_eq!(editor.screen.row(0), "   1 ▎a   │   1 ▎a   ");
+        assert_eq!(editor.screen.row(1), "   2 ▎b   │   2 ▎B   ");
+        assert_eq!(editor.screen.row(3), "      ----│   4 ▎d   ");
+        assert_eq!((editor.vleft, editor.vwidth), (6, 4));
+
+        // the gutter follows the options like the buffer's does
+        editor.set_option("nonumber");
+        editor.update_gutter();
+        editor.draw_diff().unwrap();
+        assert_eq!(editor.screen.row(0), " ▎a       │ ▎a       ");
+        assert_eq!((editor.vleft, editor.vwidth), (2, 8));
+
+        editor.run_action(Action::NextWindow).unwrap();
+        assert_eq!(editor.current, 1);
+        editor.update_gutter();
+        assert_eq!(editor.vleft, 13);
+        editor.run_action(Action::NextHunk).unwrap();
+        assert_eq!(editor.by(), 1);
+
+        editor.run_action(Action::DiffGet(None)).unwrap();
+        assert_eq!(editor.buffer.to_string(), "a\nb\nc\nd");
+        editor.update_diff();
+        editor.run_action(Action::DiffPut(Some((3, 3)))).unwrap();
+        assert_eq!(editor.buffers[0].to_string(), "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn test_check_files() {
+        let path = std::env::temp_dir().join(format!("fed-changed-{}", std::process::id()));
+        let file = path.to_string_lossy().to_string();
+        let touch = |text: &str, secs| {
+            fs::write(&path, text).unwrap();
+            let time = std::time::SystemTime::now() + Duration::from_secs(secs);
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        };
+        touch("a\nb\nc\n", 0);
+
+        let mut editor = editor("", 20, 5);
+        editor.buffer = Buffer::open(&file).unwrap();
+        editor.move_to_position((0, 2));
+        assert!(!editor.check_files());
+
+        touch("x\n", 5);
+        assert!(editor.check_files());
+        assert_eq!(editor.buffer.to_string(), "x");
+        assert_eq!(editor.by(), 0);
+
+        editor.buffer.insert((0, 0), "y");
+        editor.buffer.dirty = true;
+        touch("z\n", 10);
+        assert!(editor.check_files());
+        assert!(!editor.check_files());
+        assert!(!editor.write(None, false));
+        assert!(editor.write(None, true));
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, "yx\n");
+    }
+
+    #[test]
+    fn test_final_newline() {
+        let path = std::env::temp_dir().join(format!("fed-eol-{}", std::process::id()));
+        let file = path.to_string_lossy().to_string();
+        fs::write(&path, "a  \nb").unwrap();
+
+        let mut editor = editor("", 20, 5);
+        editor.buffer = Buffer::open(&file).unwrap();
+        assert!(editor.buffer.missing_newline);
+        assert_eq!(editor.statusline_values()["noeol"], "[noeol]");
+
+        editor.config.ensure_final_newline = false;
+        editor.config.trim_trailing_whitespace = true;
+        assert!(editor.write(None, false));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb");
+
+        editor.config.ensure_final_newline = true;
+        assert!(editor.write(None, false));
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, "a\nb\n");
+        assert!(!editor.buffer.missing_newline);
+    }
+
+    #[test]
+    fn test_detected_indentation() {
+        let path = std::env::temp_dir().join(format!("fed-indent-{}", std::process::id()));
+        fs::write(&path, "a:\n  b:\n    c: 1\n").unwrap();
+        let buffer = Buffer::open(&path.to_string_lossy()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut editor = editor("", 20, 5);
+        editor.buffer = buffer;
+        assert_eq!(editor.indent_unit(), "  ");
+        editor.set_option("ts?");
+        assert!(matches!(&editor.message, Some(Message::Info(value)) if value == "tabsize=2"));
+
+        editor.set_option("tabsize=3");
+        assert_eq!(editor.indent_unit(), "   ");
+        editor.set_option("notts");
+        assert_eq!(editor.indent_unit(), "\t");
+    }
+
+    #[test]
+    fn test_language_config() {
+        let mut editor = editor("fn main() {}", 20, 5);
+        editor.buffer.language = Some(Language::Rust);
+        let rust = LanguageConfig {
+            tab_size: Some(2),
+            comment: Some("#".to_string()),
+            ..Default::default()
+        };
+        editor.config.languages.insert("rust".to_string(), rust);
+        assert_eq!(editor.indent_unit(), "  ");
+
+        editor.buffer.indentation = Some(Indentation::Spaces(3));
+        assert_eq!(editor.indent_unit(), "   ");
+        editor.set_option("ts=8");
+        assert_eq!(editor.indent_unit(), " ".repeat(8));
+
+        editor.toggle_comments(0, 0);
+        assert_eq!(editor.buffer.line(0).unwrap(), "# fn main() {}");
+    }
+
+    #[test]
+    fn test_inspect_highlight() {
+        let mut editor = editor("fn main() {}", 40, 5);
+        editor.buffer.language = Some(Language::Rust);
+        editor.inspect_highlight().unwrap();
+        let info =
+            "capture keyword, node function_item, scope keyword, fg #e78284, bg #303446, italic";
+        assert!(matches!(&editor.message, Some(Message::Info(message)) if message == info));
+
+        editor.cx = 3;
+        editor.inspect_highlight().unwrap();
+        let info = "capture function, node identifier, scope entity.name.function, fg #";
+        assert!(
+            matches!(&editor.message, Some(Message::Info(message)) if message.starts_with(info))
+        );
+    }
+
+    #[test]
+    fn test_set_background() {
+        let mut editor = editor("", 40, 5);
+        editor.config.light_theme = Some("src/fixtures/GitHub.tmTheme".to_string());
+        let dark = editor.theme.name.clone();
+
+        editor.set_option("bg=light");
+        assert_eq!(editor.background, Background::Light);
+        assert_ne!(editor.theme.name, dark);
+        editor.set_option("background?");
+        assert!(matches!(&editor.message, Some(Message::Info(m)) if m == "background=light"));
+
+        editor.set_option("bg=dark");
+        assert_eq!(editor.theme.name, dark);
+        editor.set_option("bg=grey");
+        assert!(matches!(&editor.message, Some(Message::Error(m)) if m.starts_with("E474")));
+    }
 }
@@ -0,0 +1,258 @@
+//! `:make`, which runs the build command of the project in the background and turns the errors
+//! and warnings the compiler prints into quickfix entries.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::{
+    lsp::Severity,
+    quickfix::Entry,
+    shell::{self, ShellOutput},
+};
+
+/// The build commands of the projects marked by these files, unless the `[make]` table of the
+/// config has its own.
+const DEFAULT_COMMANDS: &[(&str, &str)] = &[
+    ("Cargo.toml", "cargo build"),
+    ("go.mod", "go build ./..."),
+    ("package.json", "npm run build"),
+    ("Makefile", "make"),
+];
+
+/// The header of a rustc diagnostic, like `error[E0425]: cannot find value`.
+static RUST_HEADER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(error|warning)(\[\w+\])?: (.*)$").unwrap());
+
+/// Where a rustc diagnostic points, ` --> src/main.rs:2:13`.
+static RUST_LOCATION: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*--> (.+):(\d+):(\d+)$").unwrap());
+
+/// The `file:line:col: error: message` lines of gcc, clang, go and most other compilers, where
+/// the column and the severity may be left out.
+static LOCATION: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^([^\s:][^:]*):(\d+):(?:(\d+):)?\s*(?:(fatal error|error|warning|note):)?\s*(.*)$")
+        .unwrap()
+});
+
+/// An error or warning from the build, and where it points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub entry: Entry,
+    pub severity: Severity,
+}
+
+/// A build running in the background.
+#[derive(Debug)]
+pub struct Make {
+    pub command: String,
+    /// The project directory the command runs in, which the paths it prints are relative to.
+    pub dir: PathBuf,
+    output: Receiver<anyhow::Result<ShellOutput>>,
+}
+
+impl Make {
+    /// Starts `command` in `dir` on another thread.
+    pub fn start(command: &str, dir: &Path) -> Self {
+        let (sender, output) = mpsc::channel();
+        let (line, cwd) = (command.to_string(), dir.to_path_buf());
+        thread::spawn(move || {
+            let _ = sender.send(shell::run_in(&line, None, Some(&cwd)));
+        });
+        Make {
+            command: command.to_string(),
+            dir: dir.to_path_buf(),
+            output,
+        }
+    }
+
+    /// What the command printed and how it exited, once it's done.
+    pub fn poll(&self) -> Option<anyhow::Result<ShellOutput>> {
+        match self.output.try_recv() {
+            Ok(output) => Some(output),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Some(Err(anyhow::anyhow!("The build stopped without a result")))
+            }
+        }
+    }
+}
+
+/// The build command for the project around `dir` and the directory it runs in: the nearest
+/// directory up from `dir` with a file from `commands`, which maps project files to commands,
+/// or with one of the files known without it.
+pub fn command(commands: &HashMap<String, String>, dir: &Path) -> Option<(String, PathBuf)> {
+    let mut configured: Vec<_> = commands.iter().collect();
+    configured.sort();
+    let known = DEFAULT_COMMANDS
+        .iter()
+        .filter(|(file, _)| !commands.contains_key(*file));
+
+    for dir in dir.ancestors() {
+        let found = configured
+            .iter()
+            .map(|&(file, command)| (file.as_str(), command.as_str()))
+            .chain(known.clone().copied())
+            .find(|(file, _)| dir.join(file).exists());
+        if let Some((_, command)) = found {
+            return Some((command.to_string(), dir.to_path_buf()));
+        }
+    }
+    None
+}
+
+/// The errors and warnings in the `output` of a build run in `dir`, with their files relative
+/// to `cwd` when they are under it. Lines that look like locations but point at files that
+/// don't exist are left out.
+pub fn parse(output: &str, dir: &Path, cwd: &Path) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut header: Option<(Severity, String)> = None;
+
+    let path = |file: &str| -> Option<String> {
+        let path = dir.join(file);
+        if !path.is_file() {
+            return None;
+        }
+        let path = path.strip_prefix(cwd).unwrap_or(&path);
+        Some(path.to_string_lossy().to_string())
+    };
+    let number = |n: Option<regex::Match>| {
+        n.and_then(|n| n.as_str().parse::<usize>().ok())
+            .map_or(0, |n| n.saturating_sub(1))
+    };
+
+    for line in output.lines() {
+        if let Some(captures) = RUST_HEADER.captures(line) {
+            let severity = match &captures[1] {
+                "warning" => Severity::Warning,
+                _ => Severity::Error,
+            };
+            header = Some((severity, line.to_string()));
+            continue;
+        }
+        if let Some(captures) = RUST_LOCATION.captures(line) {
+            let (Some((severity, text)), Some(file)) = (header.take(), path(&captures[1])) else {
+                continue;
+            };
+            problems.push(Problem {
+                entry: Entry {
+                    file,
+                    line: number(captures.get(2)),
+                    column: number(captures.get(3)),
+                    text,
+                },
+                severity,
+            });
+            continue;
+        }
+        if let Some(captures) = LOCATION.captures(line) {
+            let Some(file) = path(&captures[1]) else {
+                continue;
+            };
+            let severity = match captures.get(4).map(|m| m.as_str()) {
+                Some("warning") => Severity::Warning,
+                Some("note") => Severity::Information,
+                _ => Severity::Error,
+            };
+            problems.push(Problem {
+                entry: Entry {
+                    file,
+                    line: number(captures.get(2)),
+                    column: number(captures.get(3)),
+                    text: captures[5].to_string(),
+                },
+                severity,
+            });
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let root = std::env::temp_dir().join(format!("fed-make-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+
+        let commands = HashMap::new();
+        assert_eq!(
+            command(&commands, &root.join("src")),
+            Some(("cargo build".to_string(), root.clone()))
+        );
+        let commands = HashMap::from([("Cargo.toml".to_string(), "cargo check".to_string())]);
+        assert_eq!(
+            command(&commands, &root),
+            Some(("cargo check".to_string(), root.clone()))
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_parse() {
+        let root = std::env::temp_dir().join(format!("fed-make-parse-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("a.c"), "").unwrap();
+
+        let output = "\
+   Compiling fed v0.1.0
+warning: unused variable: `x`
+ --> src/main.rs:2:9
+  |
+error[E0425]: cannot find value `y` in this scope
+  --> src/main.rs:3:5
+error: could not compile `fed`
+a.c:10:3: error: expected ';'
+a.c:12: note: declared here
+b.c:1:1: error: not a file here
+";
+        let problems = parse(output, &root, &root);
+        fs::remove_dir_all(&root).unwrap();
+
+        let found: Vec<_> = problems
+            .iter()
+            .map(|problem| {
+                let entry = &problem.entry;
+                (
+                    entry.file.as_str(),
+                    entry.line,
+                    entry.column,
+                    entry.text.as_str(),
+                    problem.severity,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "src/main.rs",
+                    1,
+                    8,
+                    "warning: unused variable: `x`",
+                    Severity::Warning
+                ),
+                (
+                    "src/main.rs",
+                    2,
+                    4,
+                    "error[E0425]: cannot find value `y` in this scope",
+                    Severity::Error
+                ),
+                ("a.c", 9, 2, "expected ';'", Severity::Error),
+                ("a.c", 11, 0, "declared here", Severity::Information),
+            ]
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Plugins are programs the editor starts from the `[plugins]` table of `fed.toml` and talks to
+//! over their stdin and stdout, with JSON-RPC messages framed like the language server
+//! protocol's.
+//!
+//! A plugin asks for the events it wants with `subscribe`, and acts on the editor with
+//! `insert_text`, `set_status` and `define_keybinding`. Requests with an `id` get an empty
+//! result, or an error when they can't be carried out.
+
+use std::{
+    collections::HashSet,
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    sync::mpsc::{Receiver, Sender},
+};
+
+use serde_jsonrc::{json, Value};
+use strum_macros::{Display, EnumString};
+
+use crate::{core::action::Action, jsonrpc, tui::keymap::Key};
+
+/// The JSON-RPC error for a method the editor doesn't have.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// The JSON-RPC error for a request whose params don't make sense.
+const INVALID_PARAMS: i64 = -32602;
+
+/// What plugins can subscribe to, each sent as a notification of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Event {
+    /// The text of the current buffer changed, or another buffer became the current one, with
+    /// its `name`, `file`, `revision` and `text`.
+    BufferChanged,
+    /// The editor switched to the `mode` given by its lowercase name.
+    ModeChanged,
+    /// A `key` was pressed in a `mode`, with the key written like in `[keys.normal]`.
+    KeyPressed,
+}
+
+/// What a plugin asked the editor to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Inserts `text` at the cursor, leaving the cursor after it.
+    InsertText(String),
+    /// Shows text in the `{plugins}` field of the statusline, or nothing when empty.
+    SetStatus(String),
+    /// Binds a key in a mode, given by name, to an action. Keys bound without an action name
+    /// send the plugin a `keybinding` notification with the key and mode when pressed.
+    DefineKeybinding(String, Key, Action),
+}
+
+/// A request from a plugin with the id to answer it by, if it wants an answer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incoming {
+    pub id: Option<Value>,
+    pub request: Request,
+}
+
+/// A plugin running as a child process.
+#[derive(Debug)]
+pub struct Plugin {
+    pub name: String,
+    child: Child,
+    /// The messages for the plugin, written to its stdin on a thread of its own.
+    outgoing: Sender<Value>,
+    /// Set once sending failed, when the plugin stopped reading its stdin.
+    stopped: bool,
+    messages: Receiver<Value>,
+    events: HashSet<Event>,
+    /// The name and revision of the buffer last sent with `buffer_changed`.
+    pub buffer: Option<(String, u64)>,
+    /// The keys the plugin bound, to bind again after the config is reloaded.
+    pub bindings: Vec<(String, Key, Action)>,
+}
+
+impl Plugin {
+    /// Starts the plugin `name` with `command`, a program followed by its arguments.
+    pub fn start(name: &str, command: &str) -> anyhow::Result<Self> {
+        let mut args = command.split_whitespace();
+        let program = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Empty command for plugin {name}"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| anyhow::anyhow!("Can't start plugin {name}: {err}"))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Plugin {
+            name: name.to_string(),
+            child,
+            outgoing: jsonrpc::write_messages(stdin, "plugin"),
+            stopped: false,
+            messages: jsonrpc::read_messages(stdout, "plugin"),
+            events: HashSet::new(),
+            buffer: None,
+            bindings: vec![],
+        })
+    }
+
+    pub fn subscribed(&self, event: Event) -> bool {
+        self.events.contains(&event)
+    }
+
+    /// Sends `event` with `params` when the plugin subscribed to it.
+    pub fn notify(&mut self, event: Event, params: Value) -> anyhow::Result<()> {
+        if !self.subscribed(event) {
+            return Ok(());
+        }
+        self.send(json!({ "jsonrpc": "2.0", "method": event.to_string(), "params": params }))
+    }
+
+    /// Tells the plugin a key it bound without an action was pressed.
+    pub fn keybinding(&mut self, key: &str, mode: &str) -> anyhow::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "keybinding",
+            "params": { "key": key, "mode": mode },
+        }))
+    }
+
+    /// Answers the request `id`, if it has one, with an empty result or the error it ran into.
+    pub fn respond(&mut self, id: Option<Value>, result: anyhow::Result<()>) -> anyhow::Result<()> {
+        let Some(id) = id else {
+            return Ok(());
+        };
+        match result {
+            Ok(()) => self.send(json!({ "jsonrpc": "2.0", "id": id, "result": null })),
+            Err(err) => self.error(id, INVALID_PARAMS, &err.to_string()),
+        }
+    }
+
+    fn error(&mut self, id: Value, code: i64, message: &str) -> anyhow::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }))
+    }
+
+    fn send(&mut self, message: Value) -> anyhow::Result<()> {
+        if self.outgoing.send(message).is_err() {
+            self.stopped = true;
+            anyhow::bail!("Plugin {} stopped reading", self.name);
+        }
+        Ok(())
+    }
+
+    /// Whether the plugin is still running and reading what it's sent.
+    pub fn is_running(&mut self) -> bool {
+        !self.stopped && matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Handles the messages that arrived since the last call, returning the requests for the
+    /// editor. Subscriptions are handled here, and requests that can't be parsed are answered
+    /// with an error.
+    pub fn poll(&mut self) -> anyhow::Result<Vec<Incoming>> {
+        let mut incoming = vec![];
+
+        while let Ok(message) = self.messages.try_recv() {
+            let Some(method) = message["method"].as_str() else {
+                // the editor asks nothing, so there are no responses to wait for
+                continue;
+            };
+            let id = message.get("id").cloned();
+            let params = &message["params"];
+
+            if method == "subscribe" {
+                let result = self.subscribe(params);
+                self.respond(id, result)?;
+                continue;
+            }
+            match parse_request(&self.name, method, params) {
+                Ok(Some(request)) => incoming.push(Incoming { id, request }),
+                Ok(None) => {
+                    if let Some(id) = id {
+                        self.error(id, METHOD_NOT_FOUND, &format!("Unknown method: {method}"))?;
+                    }
+                }
+                Err(err) => self.respond(id, Err(err))?,
+            }
+        }
+
+        Ok(incoming)
+    }
+
+    fn subscribe(&mut self, params: &Value) -> anyhow::Result<()> {
+        let names = params["events"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Missing events"))?;
+        for name in names {
+            let name = name.as_str().unwrap_or_default();
+            let event =
+                Event::from_str(name).map_err(|_| anyhow::anyhow!("Unknown event: {name}"))?;
+            self.events.insert(event);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The request for `method` with `params` from the plugin named `plugin`, or `None` when
+/// there's no such method.
+fn parse_request(plugin: &str, method: &str, params: &Value) -> anyhow::Result<Option<Request>> {
+    let string = |name: &str| {
+        params[name]
+            .as_str()
+            .map(|value| value.to_string())
+            .ok_or_else(|| anyhow::anyhow!("Missing {name}"))
+    };
+
+    let request = match method {
+        "insert_text" => Request::InsertText(string("text")?),
+        "set_status" => Request::SetStatus(string("text")?),
+        "define_keybinding" => {
+            let mode = string("mode")?;
+            let key: Key = string("key")?.parse()?;
+            let action = match params["action"].as_str() {
+                Some(name) => {
+                    Action::from_str(name).map_err(|_| anyhow::anyhow!("Unknown action: {name}"))?
+                }
+                None => Action::Plugin(plugin.to_string(), key.to_string()),
+            };
+            Request::DefineKeybinding(mode, key, action)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = |method, params| parse_request("words", method, &params);
+        assert_eq!(
+            request("insert_text", json!({ "text": "hi" })).unwrap(),
+            Some(Request::InsertText("hi".to_string()))
+        );
+        assert_eq!(
+            request(
+                "define_keybinding",
+                json!({ "mode": "normal", "key": "<C-t>", "action": "move_down" })
+            )
+            .unwrap(),
+            Some(Request::DefineKeybinding(
+                "normal".to_string(),
+                "<C-t>".parse().unwrap(),
+                Action::MoveDown
+            ))
+        );
+        assert_eq!(
+            request(
+                "define_keybinding",
+                json!({ "mode": "insert", "key": "<C-t>" })
+            )
+            .unwrap(),
+            Some(Request::DefineKeybinding(
+                "insert".to_string(),
+                "<C-t>".parse().unwrap(),
+                Action::Plugin("words".to_string(), "<C-t>".to_string())
+            ))
+        );
+        assert!(request("set_status", json!({})).is_err());
+        assert!(request(
+            "define_keybinding",
+            json!({ "mode": "normal", "key": "<Nope>" })
+        )
+        .is_err());
+        assert_eq!(request("shutdown", json!({})).unwrap(), None);
+    }
+}
@@ -0,0 +1,223 @@
+use std::{fs, path::Path};
+
+use crossterm::style::Stylize;
+use regex::Regex;
+
+use crate::{
+    tui::{
+        picker::list_files,
+        renderer::Renderer,
+        theme::Theme,
+        utils::{brigthen, hex_to_crossterm_color},
+    },
+    workdir,
+};
+
+/// Stops searching after this many matches.
+const MAX_ENTRIES: usize = 10_000;
+
+/// How many entries the quickfix window shows at once.
+const HEIGHT: usize = 10;
+
+/// A match found by `:grep`, at a zero-based line and char column of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+/// The results of the last `:grep`, which `:cnext` and `:cprevious` step through and `:copen`
+/// shows in a window over the bottom of the buffer.
+#[derive(Debug, Default)]
+pub struct Quickfix {
+    entries: Vec<Entry>,
+    selected: usize,
+    /// Whether the window is showing and taking keys.
+    pub open: bool,
+}
+
+impl Quickfix {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Quickfix {
+            open: !entries.is_empty(),
+            entries,
+            selected: 0,
+        }
+    }
+
+    /// Renames the files of the entries, relative to `from`, for the editor having moved to `to`.
+    pub fn relocate(&mut self, from: &Path, to: &Path) {
+        for entry in &mut self.entries {
+            entry.file = workdir::relocate(&entry.file, from, to);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn select_next(&mut self) -> bool {
+        if self.selected + 1 < self.entries.len() {
+            self.selected += 1;
+            return true;
+        }
+        false
+    }
+
+    pub fn select_previous(&mut self) -> bool {
+        if self.selected > 0 {
+            self.selected -= 1;
+            return true;
+        }
+        false
+    }
+
+    /// Describes the selected entry like vim does, `(2 of 7): fn main() {`.
+    pub fn status(&self) -> String {
+        let text = self.selected().map_or("", |entry| entry.text.trim());
+        format!("({} of {}): {text}", self.selected + 1, self.entries.len())
+    }
+
+    /// Where the terminal cursor goes while the window is open, on the selected entry.
+    pub fn cursor(&self, height: usize) -> (usize, usize) {
+        let (y, rows) = Self::area(height);
+        (0, y + 1 + self.selected - self.first(rows))
+    }
+
+    /// The rows the window takes at the bottom of a `height` rows tall area, as `(y, rows)`
+    /// where the first row is the title.
+    fn area(height: usize) -> (usize, usize) {
+        let rows = HEIGHT.min(height.saturating_sub(1));
+        (height.saturating_sub(rows + 1), rows)
+    }
+
+    /// The first entry shown, scrolled to keep the selection visible.
+    fn first(&self, rows: usize) -> usize {
+        (self.selected + 1).saturating_sub(rows)
+    }
+
+    pub fn draw(
+        &self,
+        screen: &mut dyn Renderer,
+        theme: &Theme,
+        width: usize,
+        height: usize,
+    ) -> anyhow::Result<()> {
+        let (y, rows) = Self::area(height);
+        if rows == 0 {
+            return Ok(());
+        }
+
+        let (fg, bg) = theme.default_colors();
+        let bg = brigthen(bg, 0.05)?;
+        let selection_bg = match &theme.selection {
+            Some(selection) => hex_to_crossterm_color(selection)?,
+            None => brigthen(bg, 0.2)?,
+        };
+        let fit = |text: &str| -> String {
+            let text: String = text.chars().take(width).collect();
+            format!("{text:<width$}")
+        };
+
+        screen.move_to(0, y);
+        screen.print_styled(
+            fit(&format!(" [Quickfix List] {} matches", self.entries.len()))
+                .with(fg)
+                .on(brigthen(bg, 0.1)?),
+        );
+
+        let first = self.first(rows);
+        for row in 0..rows {
+            let i = first + row;
+            let (text, bg) = match self.entries.get(i) {
+                Some(entry) => (
+                    format!(
+                        "{}|{} col {}| {}",
+                        entry.file,
+                        entry.line + 1,
+                        entry.column + 1,
+                        entry.text.trim()
+                    ),
+                    if i == self.selected { selection_bg } else { bg },
+                ),
+                None => (String::new(), bg),
+            };
+            screen.move_to(0, y + 1 + row);
+            screen.print_styled(fit(&text).with(fg).on(bg));
+        }
+
+        Ok(())
+    }
+}
+
+/// Searches the files under `root` for `pattern`, one entry for the first match on each line.
+/// Files that aren't UTF-8, like binaries, are skipped.
+pub fn grep(root: &Path, pattern: &Regex) -> Vec<Entry> {
+    let mut entries = vec![];
+
+    for file in list_files(root) {
+        let Ok(contents) = fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+
+        for (line, text) in contents.lines().enumerate() {
+            let Some(m) = pattern.find(text) else {
+                continue;
+            };
+
+            entries.push(Entry {
+                file: file.clone(),
+                line,
+                column: text[..m.start()].chars().count(),
+                text: text.to_string(),
+            });
+            if entries.len() >= MAX_ENTRIES {
+                return entries;
+            }
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grep() {
+        let root = std::env::temp_dir().join(format!("fed-grep-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/a.rs"),
+            "fn main() {\n    let café = foo();\n}\n",
+        )
+        .unwrap();
+        fs::write(root.join("b.txt"), "foo foo\n").unwrap();
+        fs::write(root.join("bin"), b"foo\xff").unwrap();
+
+        let entries = grep(&root, &Regex::new("fo+").unwrap());
+        fs::remove_dir_all(&root).unwrap();
+
+        let found: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.file.as_str(), entry.line, entry.column))
+            .collect();
+        assert_eq!(found, vec![("b.txt", 0, 0), ("src/a.rs", 1, 15)]);
+
+        let mut quickfix = Quickfix::new(entries);
+        assert!(quickfix.open);
+        assert_eq!(quickfix.status(), "(1 of 2): foo foo");
+        assert!(quickfix.select_next());
+        assert!(!quickfix.select_next());
+        assert_eq!(quickfix.status(), "(2 of 2): let café = foo();");
+        assert!(quickfix.select_previous());
+        assert!(!quickfix.select_previous());
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    env,
+    io::Write,
+    path::Path,
+    process::{Command, ExitStatus, Stdio},
+    thread,
+};
+
+/// What a command run through the shell printed and how it exited.
+#[derive(Debug)]
+pub struct ShellOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
+
+impl ShellOutput {
+    /// Describes a failed exit like vim, `shell returned 1`.
+    pub fn error(&self) -> Option<String> {
+        if self.status.success() {
+            return None;
+        }
+        Some(match self.status.code() {
+            Some(code) => format!("shell returned {code}"),
+            None => "shell was killed".to_string(),
+        })
+    }
+}
+
+/// Runs `command` through the user's shell, `$SHELL` or `sh`, with `input` on its stdin.
+pub fn run(command: &str, input: Option<String>) -> anyhow::Result<ShellOutput> {
+    run_in(command, input, None)
+}
+
+/// Like [`run`], but in `dir` when given instead of the current directory.
+pub fn run_in(
+    command: &str,
+    input: Option<String>,
+    dir: Option<&Path>,
+) -> anyhow::Result<ShellOutput> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_string()));
+        shell.arg("-c");
+        shell
+    };
+    if let Some(dir) = dir {
+        shell.current_dir(dir);
+    }
+
+    let mut child = shell
+        .arg(command)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow::anyhow!("E282: Can't run the shell: {err}"))?;
+
+    // writes from another thread so a command that prints while it reads doesn't block on a
+    // full pipe
+    let writer = child.stdin.take().zip(input).map(|(mut stdin, input)| {
+        thread::spawn(move || {
+            // a command that doesn't read all of its input closes the pipe early
+            let _ = stdin.write_all(input.as_bytes());
+        })
+    });
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+
+    Ok(ShellOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n"),
+        stderr: String::from_utf8_lossy(&output.stderr).replace("\r\n", "\n"),
+        status: output.status,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let output = run("sort", Some("b\nc\na\n".to_string())).unwrap();
+        assert_eq!(output.stdout, "a\nb\nc\n");
+        assert_eq!(output.error(), None);
+
+        let output = run("echo oops >&2; exit 3", None).unwrap();
+        assert_eq!(output.stderr, "oops\n");
+        assert_eq!(output.error().as_deref(), Some("shell returned 3"));
+    }
+}
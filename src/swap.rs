@@ -0,0 +1,102 @@
+use std::{
+    fs, io,
+    path::{self, PathBuf},
+};
+
+/// Where buffers keep their unsaved changes while being edited, so a crash doesn't lose them and
+/// `fed -r` can recover them. A swap file holds the absolute path of the file it belongs to on
+/// its first line, followed by the buffer's text.
+#[derive(Debug, Default)]
+pub struct SwapDir {
+    dir: PathBuf,
+}
+
+impl SwapDir {
+    pub fn new(dir: PathBuf) -> Self {
+        SwapDir { dir }
+    }
+
+    /// The default location, `~/.local/share/fed/swap`.
+    pub fn default_dir() -> anyhow::Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("No home directory"))?;
+        Ok(home.join(".local").join("share").join("fed").join("swap"))
+    }
+
+    /// The swap file for `file`, named after its absolute path with `%` for each separator like
+    /// vim does.
+    fn path(&self, file: &str) -> PathBuf {
+        let name = absolute(file).replace(path::MAIN_SEPARATOR, "%");
+        self.dir.join(format!("{name}.swp"))
+    }
+
+    pub fn exists(&self, file: &str) -> bool {
+        self.path(file).exists()
+    }
+
+    pub fn write(&self, file: &str, text: &str) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(file), format!("{}\n{text}", absolute(file)))?;
+        Ok(())
+    }
+
+    /// Returns the text saved for `file`.
+    pub fn read(&self, file: &str) -> anyhow::Result<String> {
+        let contents = fs::read_to_string(self.path(file))?;
+        let (_, text) = contents.split_once('\n').unwrap_or_default();
+        Ok(text.to_string())
+    }
+
+    pub fn remove(&self, file: &str) -> anyhow::Result<()> {
+        match fs::remove_file(self.path(file)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The files with a swap file waiting to be recovered.
+    pub fn files(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+
+        let mut files: Vec<_> = entries
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|contents| contents.lines().next().map(str::to_string))
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+fn absolute(file: &str) -> String {
+    path::absolute(file)
+        .unwrap_or_else(|_| PathBuf::from(file))
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_files() {
+        let dir = std::env::temp_dir().join(format!("fed-swap-{}", std::process::id()));
+        let swap = SwapDir::new(dir.clone());
+        let file = "/tmp/notes.txt";
+        assert!(!swap.exists(file));
+        assert!(swap.files().is_empty());
+
+        swap.write(file, "one\ntwo").unwrap();
+        assert!(swap.exists(file));
+        assert!(dir.join("%tmp%notes.txt.swp").exists());
+        assert_eq!(swap.read(file).unwrap(), "one\ntwo");
+        assert_eq!(swap.files(), vec![file.to_string()]);
+
+        swap.remove(file).unwrap();
+        assert!(!swap.exists(file));
+        swap.remove(file).unwrap();
+        fs::remove_dir(dir).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+//! Whether the terminal has a light or a dark background, to pick the theme that goes with it.
+
+use std::{
+    collections::VecDeque,
+    env,
+    io::{stdout, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers};
+use strum_macros::{Display, EnumString};
+
+/// How long to wait for the terminal to tell its background color, since terminals that don't
+/// support asking never answer.
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// How long after giving up on the answer it may still come in, over a slow connection.
+const LATE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, EnumString, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Background {
+    Light,
+    #[default]
+    Dark,
+}
+
+/// The background `COLORFGBG` gives, which some terminals set to the ANSI colors of their text
+/// and background, like `15;0`.
+pub fn from_env() -> Option<Background> {
+    from_colorfgbg(&env::var("COLORFGBG").ok()?)
+}
+
+fn from_colorfgbg(value: &str) -> Option<Background> {
+    match value.rsplit(';').next()?.parse::<u8>().ok()? {
+        7 | 9..=15 => Some(Background::Light),
+        _ => Some(Background::Dark),
+    }
+}
+
+/// What is left of an answer to [`query`] that didn't come in time, whose chars would otherwise
+/// be read as keys when it comes in later.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LateAnswer {
+    /// There's no answer left to come.
+    #[default]
+    None,
+    /// The answer may still start coming in until the given time.
+    Expected(Instant),
+    /// The answer started coming in and goes on until its terminator.
+    Reading,
+}
+
+impl LateAnswer {
+    /// Whether `event` is part of the late answer and should be dropped.
+    pub fn swallow(&mut self, event: &Event) -> bool {
+        let Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers,
+            ..
+        }) = *event
+        else {
+            return false;
+        };
+        match *self {
+            LateAnswer::Expected(until) if Instant::now() > until => {
+                *self = LateAnswer::None;
+                false
+            }
+            LateAnswer::Expected(_) if (c, modifiers) == (']', KeyModifiers::ALT) => {
+                *self = LateAnswer::Reading;
+                true
+            }
+            LateAnswer::Reading => {
+                if (c, modifiers) == ('g', KeyModifiers::CONTROL)
+                    || (c, modifiers) == ('\\', KeyModifiers::ALT)
+                {
+                    *self = LateAnswer::None;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Asks the terminal for its background color with OSC 11, which it answers like a key press
+/// would come in. Keys pressed while waiting for the answer are kept in `input`, and `late` is
+/// set when the answer may still come in after giving up on it.
+pub fn query(
+    input: &mut VecDeque<Event>,
+    late: &mut LateAnswer,
+) -> anyhow::Result<Option<Background>> {
+    let mut out = stdout();
+    out.write_all(b"\x1b]11;?\x07")?;
+    out.flush()?;
+
+    // the answer, `ESC ] 11;rgb:1e1e/1e1e/2e2e BEL`, comes in as Alt-] followed by its chars and
+    // Ctrl-G, or Alt-\ when it ends in `ESC \` instead
+    let deadline = Instant::now() + TIMEOUT;
+    let mut answer: Option<String> = None;
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        if !poll(left)? {
+            break;
+        }
+        let event = read()?;
+        let Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers,
+            ..
+        }) = event
+        else {
+            input.push_back(event);
+            continue;
+        };
+        match answer.as_mut() {
+            None if c == ']' && modifiers == KeyModifiers::ALT => answer = Some(String::new()),
+            Some(text) if (c, modifiers) == ('g', KeyModifiers::CONTROL) => {
+                return Ok(from_osc11(text));
+            }
+            Some(text) if (c, modifiers) == ('\\', KeyModifiers::ALT) => {
+                return Ok(from_osc11(text));
+            }
+            Some(text) => text.push(c),
+            None => input.push_back(event),
+        }
+    }
+    *late = match answer {
+        Some(_) => LateAnswer::Reading,
+        None => LateAnswer::Expected(Instant::now() + LATE_TIMEOUT),
+    };
+    Ok(None)
+}
+
+/// The background of the color in an OSC 11 answer, `11;rgb:1e1e/1e1e/2e2e`, whose channels
+/// can have from one to four hex digits.
+fn from_osc11(answer: &str) -> Option<Background> {
+    let rgb = answer.strip_prefix("11;")?;
+    let rgb = rgb
+        .strip_prefix("rgb:")
+        .or_else(|| rgb.strip_prefix("rgba:"))?;
+    let channels: Vec<f32> = rgb
+        .split('/')
+        .take(3)
+        .map(|channel| {
+            let max = 16u32.checked_pow(channel.len() as u32)? - 1;
+            let value = u32::from_str_radix(channel, 16).ok()?;
+            Some(value as f32 / max as f32)
+        })
+        .collect::<Option<_>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    Some(if luminance > 0.5 {
+        Background::Light
+    } else {
+        Background::Dark
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_osc11() {
+        assert_eq!(from_osc11("11;rgb:1e1e/1e1e/2e2e"), Some(Background::Dark));
+        assert_eq!(from_osc11("11;rgb:ff/ff/f0"), Some(Background::Light));
+        assert_eq!(
+            from_osc11("11;rgba:eeee/eeee/eeee/ffff"),
+            Some(Background::Light)
+        );
+        assert_eq!(from_osc11("11;rgb:zz/00/00"), None);
+        assert_eq!(from_osc11("10;rgb:ff/ff/ff"), None);
+    }
+
+    #[test]
+    fn test_late_answer() {
+        let key = |c, modifiers| Event::Key(KeyEvent::new(KeyCode::Char(c), modifiers));
+        let mut late = LateAnswer::Expected(Instant::now() + LATE_TIMEOUT);
+        assert!(!late.swallow(&key('j', KeyModifiers::NONE)));
+        assert!(late.swallow(&key(']', KeyModifiers::ALT)));
+        assert!(late.swallow(&key('1', KeyModifiers::NONE)));
+        assert!(late.swallow(&key('\\', KeyModifiers::ALT)));
+        assert_eq!(late, LateAnswer::None);
+        assert!(!late.swallow(&key(']', KeyModifiers::ALT)));
+
+        let mut late = LateAnswer::Reading;
+        assert!(late.swallow(&key('g', KeyModifiers::CONTROL)));
+        assert!(!late.swallow(&key('g', KeyModifiers::CONTROL)));
+    }
+
+    #[test]
+    fn test_from_colorfgbg() {
+        assert_eq!(from_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(from_colorfgbg("0;default;15"), Some(Background::Light));
+        assert_eq!(from_colorfgbg("0;7"), Some(Background::Light));
+        assert_eq!(from_colorfgbg("15;default"), None);
+        assert_eq!("light".parse(), Ok(Background::Light));
+    }
+}
@@ -0,0 +1,194 @@
+//! Fitting the RGB colors of themes to terminals that can't show them, by picking the nearest
+//! color they can.
+
+use std::{env, process::Command};
+
+use crossterm::style::Color;
+
+/// The 16 colors of the ANSI palette as xterm shows them, in crossterm's order.
+const ANSI: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::DarkRed, [205, 0, 0]),
+    (Color::DarkGreen, [0, 205, 0]),
+    (Color::DarkYellow, [205, 205, 0]),
+    (Color::DarkBlue, [0, 0, 238]),
+    (Color::DarkMagenta, [205, 0, 205]),
+    (Color::DarkCyan, [0, 205, 205]),
+    (Color::Grey, [229, 229, 229]),
+    (Color::DarkGrey, [127, 127, 127]),
+    (Color::Red, [255, 0, 0]),
+    (Color::Green, [0, 255, 0]),
+    (Color::Yellow, [255, 255, 0]),
+    (Color::Blue, [92, 92, 255]),
+    (Color::Magenta, [255, 0, 255]),
+    (Color::Cyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+/// The levels of each channel in the 6x6x6 color cube of the 256-color palette.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSupport {
+    #[default]
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorSupport {
+    /// Parses the `colors` config setting: `truecolor`, `256` or `16`.
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "truecolor" | "24bit" => Ok(ColorSupport::TrueColor),
+            "256" => Ok(ColorSupport::Ansi256),
+            "16" => Ok(ColorSupport::Ansi16),
+            _ => anyhow::bail!("Invalid colors {name}, expected truecolor, 256 or 16"),
+        }
+    }
+
+    /// What the terminal supports, from `COLORTERM` and the number of colors its terminfo entry
+    /// gives, falling back to guessing from `TERM`.
+    pub fn detect() -> Self {
+        let colors = Command::new("tput")
+            .arg("colors")
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .and_then(|colors| colors.trim().parse().ok());
+        Self::from_env(
+            env::var("COLORTERM").ok().as_deref(),
+            env::var("TERM").ok().as_deref(),
+            colors,
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>, colors: Option<u32>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColorSupport::TrueColor;
+        }
+        match colors {
+            Some(colors) if colors >= 1 << 24 => ColorSupport::TrueColor,
+            Some(colors) if colors >= 256 => ColorSupport::Ansi256,
+            Some(_) => ColorSupport::Ansi16,
+            // without terminfo, the name of the terminal is all there is to go by
+            None => match term {
+                Some(term) if term.ends_with("-direct") => ColorSupport::TrueColor,
+                Some(term) if term.contains("256") => ColorSupport::Ansi256,
+                Some("linux" | "vt100" | "vt220" | "xterm" | "screen" | "ansi") => {
+                    ColorSupport::Ansi16
+                }
+                _ => ColorSupport::TrueColor,
+            },
+        }
+    }
+
+    /// The nearest color to `color` the terminal can show. Named colors are left alone, as are
+    /// the palette colors the terminal has.
+    pub fn fit(self, color: Color) -> Color {
+        let rgb = match (self, color) {
+            (ColorSupport::TrueColor, _) => return color,
+            (_, Color::Rgb { r, g, b }) => [r, g, b],
+            (ColorSupport::Ansi16, Color::AnsiValue(value)) if value >= 16 => palette_rgb(value),
+            _ => return color,
+        };
+        match self {
+            ColorSupport::Ansi256 => Color::AnsiValue(nearest_256(rgb)),
+            _ => nearest_16(rgb),
+        }
+    }
+}
+
+/// The squared distance between two colors, weighted for how the eye sees each channel.
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    let [dr, dg, db] = [0, 1, 2].map(|i| (a[i] as i32 - b[i] as i32).pow(2) as u32);
+    2 * dr + 4 * dg + 3 * db
+}
+
+/// The index of the nearest color in the 256-color palette, out of its color cube and its grays.
+/// The first 16 are left out, since terminals change them.
+fn nearest_256(rgb: [u8; 3]) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let [r, g, b] = rgb.map(level);
+    let cube = (16 + 36 * r + 6 * g + b) as u8;
+
+    let average = rgb.iter().map(|&c| c as u32).sum::<u32>() / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(rgb, palette_rgb(gray)) < distance(rgb, palette_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// The nearest of the 16 ANSI colors.
+fn nearest_16(rgb: [u8; 3]) -> Color {
+    ANSI.iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map_or(Color::Reset, |&(color, _)| color)
+}
+
+/// The RGB of a color from the 256-color palette.
+fn palette_rgb(value: u8) -> [u8; 3] {
+    match value {
+        0..=15 => ANSI[value as usize].1,
+        16..=231 => {
+            let i = value as usize - 16;
+            [CUBE[i / 36], CUBE[i / 6 % 6], CUBE[i % 6]]
+        }
+        _ => [8 + 10 * (value - 232); 3],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_env() {
+        let detect = ColorSupport::from_env;
+        assert_eq!(
+            detect(Some("truecolor"), Some("xterm-256color"), Some(256)),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            detect(None, Some("xterm-256color"), Some(256)),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(detect(None, Some("xterm"), Some(8)), ColorSupport::Ansi16);
+        assert_eq!(
+            detect(None, Some("screen-256color"), None),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(detect(None, Some("linux"), None), ColorSupport::Ansi16);
+        assert_eq!(detect(None, None, None), ColorSupport::TrueColor);
+    }
+
+    #[test]
+    fn test_fit() {
+        let rgb = |r, g, b| Color::Rgb { r, g, b };
+        let ansi256 = ColorSupport::Ansi256;
+        assert_eq!(ansi256.fit(rgb(255, 0, 0)), Color::AnsiValue(196));
+        assert_eq!(ansi256.fit(rgb(40, 42, 54)), Color::AnsiValue(236));
+        assert_eq!(ansi256.fit(rgb(95, 135, 175)), Color::AnsiValue(67));
+        assert_eq!(ansi256.fit(Color::Red), Color::Red);
+
+        let ansi16 = ColorSupport::Ansi16;
+        assert_eq!(ansi16.fit(rgb(40, 42, 54)), Color::Black);
+        assert_eq!(ansi16.fit(rgb(250, 250, 240)), Color::White);
+        assert_eq!(ansi16.fit(rgb(200, 30, 20)), Color::DarkRed);
+        assert_eq!(ansi16.fit(Color::AnsiValue(196)), Color::Red);
+        assert_eq!(ansi16.fit(Color::Reset), Color::Reset);
+
+        assert_eq!(ColorSupport::TrueColor.fit(rgb(1, 2, 3)), rgb(1, 2, 3));
+        assert_eq!(ColorSupport::parse("256").unwrap(), ColorSupport::Ansi256);
+        assert!(ColorSupport::parse("88").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crossterm::cursor::SetCursorStyle;
+use serde::{Deserialize, Serialize};
+
+/// The `[cursor]` table, with a style for each mode like `"block"`, `"bar"` or `"underline"`,
+/// optionally prefixed with `blinking`, as in `insert = "blinking bar"`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct CursorConfig {
+    pub normal: Option<String>,
+    pub insert: Option<String>,
+    pub visual: Option<String>,
+    pub replace: Option<String>,
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Block,
+    Bar,
+    Underline,
+}
+
+/// The shape of the cursor and whether it blinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorStyle {
+    pub shape: Shape,
+    pub blink: bool,
+}
+
+impl CursorStyle {
+    const fn steady(shape: Shape) -> Self {
+        Self {
+            shape,
+            blink: false,
+        }
+    }
+
+    /// The crossterm command that sets this style.
+    pub fn command(self) -> SetCursorStyle {
+        match (self.shape, self.blink) {
+            (Shape::Block, false) => SetCursorStyle::SteadyBlock,
+            (Shape::Block, true) => SetCursorStyle::BlinkingBlock,
+            (Shape::Bar, false) => SetCursorStyle::SteadyBar,
+            (Shape::Bar, true) => SetCursorStyle::BlinkingBar,
+            (Shape::Underline, false) => SetCursorStyle::SteadyUnderScore,
+            (Shape::Underline, true) => SetCursorStyle::BlinkingUnderScore,
+        }
+    }
+}
+
+impl std::str::FromStr for CursorStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (blink, shape) = match s.trim().strip_prefix("blinking") {
+            Some(shape) => (true, shape.trim_start()),
+            None => (false, s.trim()),
+        };
+        let shape = match shape {
+            "block" => Shape::Block,
+            "bar" => Shape::Bar,
+            "underline" => Shape::Underline,
+            _ => anyhow::bail!("invalid cursor style: {s}"),
+        };
+        Ok(Self { shape, blink })
+    }
+}
+
+/// The cursor style of each mode. Pickers use the command line's style since they are typed
+/// into the same way.
+#[derive(Debug, Clone)]
+pub struct CursorStyles {
+    pub normal: CursorStyle,
+    pub insert: CursorStyle,
+    pub visual: CursorStyle,
+    pub replace: CursorStyle,
+    pub command: CursorStyle,
+}
+
+impl Default for CursorStyles {
+    fn default() -> Self {
+        Self {
+            normal: CursorStyle::steady(Shape::Block),
+            insert: CursorStyle::steady(Shape::Bar),
+            visual: CursorStyle::steady(Shape::Block),
+            replace: CursorStyle::steady(Shape::Underline),
+            command: CursorStyle::steady(Shape::Bar),
+        }
+    }
+}
+
+impl TryFrom<CursorConfig> for CursorStyles {
+    type Error = anyhow::Error;
+
+    fn try_from(config: CursorConfig) -> anyhow::Result<Self> {
+        let default = CursorStyles::default();
+        let style = |style: Option<String>, default: CursorStyle| match style {
+            Some(style) => style.parse(),
+            None => Ok(default),
+        };
+        Ok(Self {
+            normal: style(config.normal, default.normal)?,
+            insert: style(config.insert, default.insert)?,
+            visual: style(config.visual, default.visual)?,
+            replace: style(config.replace, default.replace)?,
+            command: style(config.command, default.command)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_styles() {
+        let styles = CursorStyles::try_from(CursorConfig {
+            normal: Some("underline".to_string()),
+            insert: Some("blinking bar".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(styles.normal, CursorStyle::steady(Shape::Underline));
+        assert_eq!(
+            styles.insert,
+            CursorStyle {
+                shape: Shape::Bar,
+                blink: true
+            }
+        );
+        assert_eq!(styles.replace, CursorStyles::default().replace);
+
+        assert!("blinking".parse::<CursorStyle>().is_err());
+        assert!("beam".parse::<CursorStyle>().is_err());
+    }
+}
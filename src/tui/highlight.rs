@@ -0,0 +1,717 @@
+use std::{borrow::Cow, cmp, collections::HashMap};
+
+use crossterm::style::{Attribute, Attributes, Color};
+use lazy_static::lazy_static;
+use strum::IntoEnumIterator;
+
+use crate::{
+    core::{
+        buffer::Buffer,
+        syntax::{update_highlights, ChunkType, Selection, Span, Viewport},
+    },
+    trace,
+    tui::{
+        renderer::Renderer,
+        theme::{FontStyle, SettingAttributes, Theme},
+        utils::{blend, brigthen, hex_to_crossterm_color},
+    },
+};
+
+lazy_static! {
+    /// The TextMate scope themes color each tree-sitter capture by.
+    pub static ref TS_TO_THEME: HashMap<String, String> = HashMap::from_iter(
+        vec![
+            ("attribute", "entity.other.attribute-name"),
+            ("boolean", "constant.language.boolean"),
+            ("carriage-return", "No direct equivalent"),
+            ("comment", "comment"),
+            ("comment.documentation", "comment.block.documentation"),
+            ("constant", "constant"),
+            ("constant.builtin", "constant.language"),
+            ("constructor", "entity.name.function.constructor"),
+            ("constructor.builtin", "No direct equivalent"),
+            ("embedded", "text.html"),
+            ("error", "invalid"),
+            ("escape", "constant.character.escape"),
+            ("function", "entity.name.function"),
+            ("function.builtin", "support.function"),
+            ("keyword", "keyword"),
+            ("markup", "markup"),
+            ("markup.bold", "markup.bold"),
+            ("markup.heading", "markup.heading"),
+            ("markup.italic", "markup.italic"),
+            ("markup.link", "markup.underline.link"),
+            ("markup.link.url", "markup.underline.link"),
+            ("markup.list", "markup.list"),
+            ("markup.list.checked", "No direct equivalent"),
+            ("markup.list.numbered", "markup.list.numbered"),
+            ("markup.list.unchecked", "No direct equivalent"),
+            ("markup.list.unnumbered", "markup.list.unnumbered"),
+            ("markup.quote", "markup.quote"),
+            ("markup.raw", "markup.raw"),
+            ("markup.raw.block", "markup.raw.block"),
+            ("markup.raw.inline", "markup.raw.inline"),
+            ("markup.strikethrough", "markup.strikethrough"),
+            ("module", "No direct equivalent"),
+            ("number", "constant.numeric"),
+            ("operator", "keyword.operator"),
+            ("property", "variable.other.property"),
+            ("property.builtin", "support.type.property-name"),
+            ("punctuation", "punctuation"),
+            ("punctuation.bracket", "punctuation.section"),
+            ("punctuation.delimiter", "punctuation.separator"),
+            ("punctuation.special", "No direct equivalent"),
+            ("string", "string"),
+            ("string.escape", "constant.character.escape"),
+            ("string.regexp", "string.regexp"),
+            ("string.special", "No direct equivalent"),
+            ("string.special.symbol", "No direct equivalent"),
+            ("tag", "entity.name.tag"),
+            ("type", "entity.name.type"),
+            ("type.builtin", "support.type"),
+            ("variable", "variable"),
+            ("variable.builtin", "variable.language"),
+            ("variable.member", "variable.other.member"),
+            ("variable.parameter", "variable.parameter"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+    );
+}
+
+/// What gets drawn over the syntax colors of the visible lines.
+#[derive(Debug, Default)]
+pub struct Overlays<'a> {
+    pub selection: Option<&'a Selection>,
+    /// The bracket under the cursor and its match.
+    pub brackets: &'a [(usize, usize)],
+    /// Ranges to underline, like the ones diagnostics point at.
+    pub underlines: &'a [Selection],
+    /// The other occurrences of the identifier under the cursor, drawn on a dim background.
+    pub occurrences: &'a [Selection],
+    /// The cursor line, drawn on the line highlight color.
+    pub current_line: Option<usize>,
+    /// Lines drawn on a background of their own across the whole width, like the changed lines
+    /// of a diff, over the cursor line's.
+    pub line_backgrounds: &'a [(usize, Color)],
+    /// Ranges drawn on a background of their own, like the chars that changed within a line.
+    pub spans: &'a [(Selection, Color)],
+    /// Shows tabs as `»`, spaces as `·` and line ends as `¬` in the theme's invisibles color,
+    /// with whitespace at the end of lines in red.
+    pub invisibles: bool,
+}
+
+/// How the theme draws a kind of syntax, and where that comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved<'a> {
+    /// The theme scope the tree-sitter capture maps to, `None` for captures without one.
+    pub scope: Option<&'a str>,
+    /// The selector of the most specific theme rule for the scope, `None` when no rule matches
+    /// it and the defaults are used.
+    pub selector: Option<&'a str>,
+    pub fg: String,
+    /// The background of the rules, `None` to draw on the line's.
+    pub bg: Option<String>,
+    pub font_style: Option<FontStyle>,
+}
+
+/// How `theme` draws text highlighted as `typ`.
+pub fn resolve<'a>(theme: &'a Theme, typ: &ChunkType) -> Resolved<'a> {
+    let scope = TS_TO_THEME
+        .get(&typ.to_string())
+        .map(String::as_str)
+        .filter(|&scope| scope != "No direct equivalent");
+    let (selector, settings) = match scope.and_then(|scope| theme.scope_settings(scope)) {
+        Some((selector, settings)) => (Some(selector), settings),
+        None => (None, SettingAttributes::default()),
+    };
+    Resolved {
+        scope,
+        selector,
+        fg: settings
+            .foreground
+            .unwrap_or_else(|| theme.foreground.clone()),
+        bg: settings.background,
+        font_style: settings.font_style,
+    }
+}
+
+/// The colors and attributes text of one kind of syntax is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Color,
+    /// `None` to draw on the background of the line.
+    pub bg: Option<Color>,
+    pub attrs: Attributes,
+}
+
+/// The style of every kind of syntax under a theme, resolved once when the theme is loaded so
+/// drawing looks it up by kind instead of going through the theme's rules for every chunk. It's
+/// the same for every language, since captures map to theme scopes the same way in all of them.
+#[derive(Debug, Clone)]
+pub struct Styles {
+    /// By the index of the kind in [`ChunkType`].
+    styles: Vec<Style>,
+}
+
+impl Styles {
+    /// Resolves the styles of `theme`. Colors that don't parse are left to the theme's foreground
+    /// and the line's background.
+    pub fn new(theme: &Theme) -> Self {
+        let color = |hex: &str| hex_to_crossterm_color(hex).ok();
+        let foreground = color(&theme.foreground).unwrap_or(Color::Reset);
+        let styles = ChunkType::iter()
+            .map(|typ| {
+                let resolved = resolve(theme, &typ);
+                Style {
+                    fg: color(&resolved.fg).unwrap_or(foreground),
+                    bg: resolved.bg.as_deref().and_then(color),
+                    attrs: resolved
+                        .font_style
+                        .map_or_else(Attributes::default, |style| style.attributes()),
+                }
+            })
+            .collect();
+        Styles { styles }
+    }
+
+    pub fn get(&self, typ: &ChunkType) -> Style {
+        self.styles[typ.clone() as usize]
+    }
+}
+
+impl Default for Styles {
+    fn default() -> Self {
+        Styles::new(&Theme::default())
+    }
+}
+
+fn clear_line(
+    screen: &mut dyn Renderer,
+    theme: &Theme,
+    viewport: &Viewport,
+    bg: Color,
+) -> anyhow::Result<()> {
+    let fg = hex_to_crossterm_color(&theme.foreground)?;
+
+    screen.set_fg(fg);
+    screen.set_bg(bg);
+    screen.set_attrs(Attributes::default());
+
+    screen.move_to_column(viewport.left);
+    screen.print(&" ".repeat(viewport.width));
+    screen.move_to_column(viewport.left);
+
+    Ok(())
+}
+
+/// Draws the lines of `buffer` in `viewport` in the theme's syntax colors, with `overlays` on
+/// top, recomputing the buffer's highlights first when it changed.
+pub fn draw(
+    screen: &mut dyn Renderer,
+    buffer: &mut Buffer,
+    theme: &Theme,
+    styles: &Styles,
+    viewport: &Viewport,
+    overlays: &Overlays,
+) -> anyhow::Result<()> {
+    update_highlights(buffer, viewport)?;
+    let Overlays {
+        selection,
+        brackets,
+        underlines,
+        occurrences,
+        current_line,
+        line_backgrounds,
+        spans,
+        invisibles,
+    } = *overlays;
+    let lines = buffer.highlights.visible(viewport);
+
+    let selection_bg = match &theme.selection {
+        Some(selection) => hex_to_crossterm_color(selection)?,
+        None => brigthen(hex_to_crossterm_color(&theme.background)?, 0.2)?,
+    };
+    let bracket_bg = match &theme.bracket_match {
+        Some(color) => hex_to_crossterm_color(color)?,
+        None => selection_bg,
+    };
+    let background = hex_to_crossterm_color(&theme.background)?;
+    let occurrence_bg = blend(background, selection_bg, 0.5);
+    let line_highlight = match &theme.line_highlight {
+        Some(color) => hex_to_crossterm_color(color)?,
+        None => background,
+    };
+    let invisibles_fg = match &theme.invisibles {
+        Some(color) => hex_to_crossterm_color(color)?,
+        None => brigthen(background, 0.3)?,
+    };
+
+    screen.move_to(viewport.left, 0);
+
+    for (i, full_line) in lines.iter().enumerate() {
+        let y = screen.position().1;
+        if y >= viewport.height {
+            break;
+        }
+
+        // the cursor line is drawn on the highlight color across the whole width
+        let line_bg = match line_backgrounds
+            .iter()
+            .find(|(y, _)| *y == viewport.top + i)
+        {
+            Some(&(_, bg)) => bg,
+            None if current_line == Some(viewport.top + i) => line_highlight,
+            None => background,
+        };
+
+        // clears every row the line wraps onto before drawing it
+        let len = full_line
+            .iter()
+            .map(|chunk| chunk.contents.chars().count())
+            .sum();
+        let (line, first) = shown(full_line, viewport);
+        for row in y..cmp::min(y + viewport.rows(len), viewport.height) {
+            screen.move_to(viewport.left, row);
+            clear_line(screen, theme, viewport, line_bg)?;
+        }
+        screen.move_to(viewport.left, y);
+
+        let y = viewport.top + i;
+        let selected = selection.and_then(|s| s.columns(y));
+        let occurred: Vec<_> = occurrences.iter().filter_map(|o| o.columns(y)).collect();
+        let spanned: Vec<_> = spans
+            .iter()
+            .filter_map(|(s, bg)| s.columns(y).map(|columns| (columns, *bg)))
+            .collect();
+        let marked = |column: usize| {
+            if selected.is_some_and(|(start, end)| (start..end).contains(&column)) {
+                Some(selection_bg)
+            } else if brackets.contains(&(column, y)) {
+                Some(bracket_bg)
+            } else if occurred
+                .iter()
+                .any(|&(start, end)| (start..end).contains(&column))
+            {
+                Some(occurrence_bg)
+            } else {
+                spanned
+                    .iter()
+                    .find(|((start, end), _)| (*start..*end).contains(&column))
+                    .map(|&(_, bg)| bg)
+            }
+        };
+        let underlined: Vec<_> = underlines.iter().filter_map(|u| u.columns(y)).collect();
+        let is_underlined = |column: usize| {
+            underlined
+                .iter()
+                .any(|&(start, end)| (start..end).contains(&column))
+        };
+        // whitespace from here to the end of the line is trailing
+        let trailing = if invisibles {
+            let text: String = full_line
+                .iter()
+                .map(|chunk| chunk.contents.as_str())
+                .collect();
+            text.trim_end_matches([' ', '\t']).chars().count()
+        } else {
+            len
+        };
+        let plain = selected.is_none()
+            && !brackets.iter().any(|&(_, by)| by == y)
+            && underlined.is_empty()
+            && occurred.is_empty()
+            && spanned.is_empty()
+            && !invisibles;
+        let mut x = first;
+
+        for chunk in line.iter() {
+            let Style { fg, bg, attrs } = styles.get(&chunk.typ);
+            let setting_fg = fg;
+            let setting_bg = bg.unwrap_or(line_bg);
+            screen.set_fg(setting_fg);
+            screen.set_attrs(attrs);
+
+            trace!("chunk {:?}: {:?} {fg:?}", chunk.typ, chunk.contents);
+            let chunk_start = x;
+            x += chunk.contents.chars().count();
+
+            if plain {
+                screen.set_bg(setting_bg);
+                print_wrapped(screen, viewport, &chunk.contents);
+                continue;
+            }
+
+            // splits the chunk into runs of chars that share a style, so the selection, matching
+            // brackets, occurrences, diagnostics and invisibles stand out
+            let style = |column: usize, c: char| {
+                let fg = match c {
+                    ' ' | '\t' if invisibles && column >= trailing => Color::Red,
+                    ' ' | '\t' if invisibles => invisibles_fg,
+                    _ => setting_fg,
+                };
+                let bg = marked(column).unwrap_or(setting_bg);
+                (fg, bg, is_underlined(column))
+            };
+            let print_run = |screen: &mut dyn Renderer, (fg, bg, underline), text: &str| {
+                screen.set_fg(fg);
+                screen.set_bg(bg);
+                screen.set_attrs(if underline {
+                    attrs | Attribute::Underlined
+                } else {
+                    attrs
+                });
+                if invisibles {
+                    let text = text.replace('\t', "»").replace(' ', "·");
+                    print_wrapped(screen, viewport, &text);
+                } else {
+                    print_wrapped(screen, viewport, text);
+                }
+            };
+            let mut start = 0;
+            let first = chunk.contents.chars().next().unwrap_or_default();
+            let mut run = style(chunk_start, first);
+            for (column, (offset, c)) in (chunk_start..).zip(chunk.contents.char_indices()) {
+                let char_style = style(column, c);
+                if char_style != run {
+                    print_run(screen, run, &chunk.contents[start..offset]);
+                    (start, run) = (offset, char_style);
+                }
+            }
+            print_run(screen, run, &chunk.contents[start..]);
+        }
+
+        screen.set_attrs(Attributes::default());
+
+        // the selection continues past the end of the line, so we mark the line break, unless
+        // the line is scrolled so its end is out of view
+        let past_end = selected.is_some_and(|(start, end)| end > x && start <= x);
+        let end_shown = viewport.wrap
+            || (x == len && (viewport.scroll..viewport.scroll + viewport.width).contains(&x));
+        if invisibles && end_shown {
+            screen.set_fg(invisibles_fg);
+            screen.set_bg(if past_end { selection_bg } else { line_bg });
+            print_wrapped(screen, viewport, "¬");
+        } else if past_end && end_shown {
+            screen.set_bg(selection_bg);
+            screen.print(" ");
+        }
+
+        screen.move_to_next_line();
+    }
+
+    Ok(())
+}
+
+/// The part of `line` the viewport shows and the column it starts at. Lines that wrap are shown
+/// whole, others from the viewport's `scroll` column for as many columns as it's wide.
+fn shown<'a>(line: &'a [Span], viewport: &Viewport) -> (Cow<'a, [Span]>, usize) {
+    if viewport.wrap {
+        return (Cow::Borrowed(line), 0);
+    }
+
+    let end = viewport.scroll + viewport.width;
+    let mut spans = vec![];
+    let mut x = 0;
+    for span in line {
+        let len = span.contents.chars().count();
+        let (start, stop) = (x.max(viewport.scroll), (x + len).min(end));
+        if start < stop {
+            spans.push(Span {
+                contents: span
+                    .contents
+                    .chars()
+                    .skip(start - x)
+                    .take(stop - start)
+                    .collect(),
+                typ: span.typ.clone(),
+            });
+        }
+        x += len;
+    }
+    (Cow::Owned(spans), viewport.scroll.min(x))
+}
+
+/// Prints `text`, continuing on the next row when it reaches the right edge of a wrapping
+/// viewport. Without wrapping the screen clips it instead.
+fn print_wrapped(screen: &mut dyn Renderer, viewport: &Viewport, text: &str) {
+    if !viewport.wrap {
+        screen.print(text);
+        return;
+    }
+
+    let right = viewport.left + viewport.width;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (x, y) = screen.position();
+        if x >= right {
+            screen.move_to(viewport.left, y + 1);
+            continue;
+        }
+
+        let end = byte_offset(rest, right - x);
+        screen.print(&rest[..end]);
+        rest = &rest[end..];
+    }
+}
+
+/// Converts a char offset within `s` into a byte offset.
+fn byte_offset(s: &str, chars: usize) -> usize {
+    s.char_indices()
+        .nth(chars)
+        .map(|(i, _)| i)
+        .unwrap_or(s.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::syntax::Language, tui::renderer::Grid};
+
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport {
+            top: 0,
+            left: 0,
+            width: 80,
+            height: 24,
+            wrap: false,
+            scroll: 0,
+        };
+
+        let mut buffer = Buffer::from_contents(
+            r#"
+        fn main() {
+            println!("Hello, world!");
+        }
+        "#,
+        );
+        buffer.language = Some(Language::Rust);
+
+        let mut screen = Grid::new(80, 24);
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &Overlays::default(),
+        )
+        .unwrap();
+        assert_eq!(screen.cell(8, 1).unwrap().ch, 'f');
+    }
+
+    #[test]
+    fn test_styles() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let styles = Styles::new(&theme);
+        for typ in ChunkType::iter() {
+            let resolved = resolve(&theme, &typ);
+            let style = styles.get(&typ);
+            assert_eq!(style.fg, hex_to_crossterm_color(&resolved.fg).unwrap());
+            assert_eq!(
+                style.bg,
+                resolved.bg.map(|bg| hex_to_crossterm_color(&bg).unwrap())
+            );
+        }
+        assert!(styles.get(&ChunkType::Comment).attrs.has(Attribute::Italic));
+        assert_eq!(styles.get(&ChunkType::None).bg, None);
+    }
+
+    #[test]
+    fn test_highlight_font_style() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport::new(0, 0, 40, 4, false);
+
+        let mut buffer = Buffer::from_contents("let a = 1; // note");
+        buffer.language = Some(Language::Rust);
+
+        let mut screen = Grid::new(40, 4);
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &Overlays::default(),
+        )
+        .unwrap();
+
+        // keywords are bold and comments italic in this theme
+        let attrs = |x| screen.cell(x, 0).unwrap().attrs;
+        assert!(attrs(0).has(crossterm::style::Attribute::Bold));
+        assert!(attrs(11).has(crossterm::style::Attribute::Italic));
+        assert_eq!(attrs(4), Attributes::default());
+        assert_eq!(attrs(30), Attributes::default());
+    }
+
+    #[test]
+    fn test_highlight_underlines() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport::new(0, 0, 20, 2, false);
+        let mut buffer = Buffer::from_contents("let a = b;\nc");
+
+        let mut screen = Grid::new(20, 2);
+        let overlays = Overlays {
+            underlines: &[Selection::new((8, 0), (9, 0))],
+            ..Default::default()
+        };
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &overlays,
+        )
+        .unwrap();
+
+        let underlined = |x, y| screen.cell(x, y).unwrap().attrs.has(Attribute::Underlined);
+        assert!(!underlined(7, 0));
+        assert!(underlined(8, 0));
+        assert!(underlined(9, 0));
+        assert!(!underlined(0, 1));
+    }
+
+    #[test]
+    fn test_highlight_occurrences() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport::new(0, 0, 20, 2, false);
+        let mut buffer = Buffer::from_contents("a = b + a;\nc");
+
+        let mut screen = Grid::new(20, 2);
+        let overlays = Overlays {
+            occurrences: &[Selection::new((8, 0), (8, 0))],
+            ..Default::default()
+        };
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &overlays,
+        )
+        .unwrap();
+
+        let bg = |x| screen.cell(x, 0).unwrap().bg;
+        assert_ne!(bg(8), bg(0));
+        assert_eq!(bg(7), bg(0));
+        assert_eq!(bg(9), bg(0));
+    }
+
+    #[test]
+    fn test_highlight_backgrounds() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport::new(0, 0, 20, 2, false);
+        let mut buffer = Buffer::from_contents("a = b;\nc");
+
+        let mut screen = Grid::new(20, 2);
+        let overlays = Overlays {
+            line_backgrounds: &[(0, Color::DarkGreen)],
+            spans: &[(Selection::new((4, 0), (4, 0)), Color::Green)],
+            current_line: Some(0),
+            ..Default::default()
+        };
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &overlays,
+        )
+        .unwrap();
+
+        let bg = |x, y| screen.cell(x, y).unwrap().bg;
+        assert_eq!(bg(0, 0), Color::DarkGreen);
+        assert_eq!(bg(4, 0), Color::Green);
+        assert_eq!(bg(15, 0), Color::DarkGreen);
+        assert_ne!(bg(0, 1), Color::DarkGreen);
+    }
+
+    #[test]
+    fn test_highlight_invisibles() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport::new(0, 0, 20, 2, false);
+        let mut buffer = Buffer::from_contents("\ta b \nc");
+
+        let mut screen = Grid::new(20, 2);
+        let overlays = Overlays {
+            invisibles: true,
+            ..Default::default()
+        };
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &overlays,
+        )
+        .unwrap();
+
+        let row: String = (0..7).map(|x| screen.cell(x, 0).unwrap().ch).collect();
+        assert_eq!(row, "»a·b·¬ ");
+        assert_eq!(screen.cell(4, 0).unwrap().fg, Color::Red);
+        assert_ne!(screen.cell(2, 0).unwrap().fg, Color::Red);
+        assert_eq!(screen.cell(1, 1).unwrap().ch, '¬');
+    }
+
+    #[test]
+    fn test_highlight_scrolled() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport::new(0, 1, 4, 3, false).with_scroll(3);
+        let mut buffer = Buffer::from_contents("abcdefghij\nxy\nabcdef");
+
+        let mut screen = Grid::new(6, 3);
+        let overlays = Overlays {
+            invisibles: true,
+            ..Default::default()
+        };
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &overlays,
+        )
+        .unwrap();
+
+        let row = |y| {
+            (0..6)
+                .map(|x| screen.cell(x, y).unwrap().ch)
+                .collect::<String>()
+        };
+        assert_eq!(row(0), " defg ");
+        assert_eq!(row(1), "      ");
+        assert_eq!(row(2), " def¬ ");
+    }
+
+    #[test]
+    fn test_highlight_wrap() {
+        let theme = Theme::load_tm("src/fixtures/GitHub.tmTheme").unwrap();
+        let viewport = Viewport::new(0, 2, 4, 5, true);
+        let mut buffer = Buffer::from_contents("abcdefghij\nxy");
+
+        let mut screen = Grid::new(6, 5);
+        draw(
+            &mut screen,
+            &mut buffer,
+            &theme,
+            &Styles::new(&theme),
+            &viewport,
+            &Overlays::default(),
+        )
+        .unwrap();
+        assert_eq!(viewport.rows(10), 3);
+        assert_eq!(screen.cell(5, 0).unwrap().ch, 'd');
+        assert_eq!(screen.cell(2, 1).unwrap().ch, 'e');
+        assert_eq!(screen.cell(3, 2).unwrap().ch, 'j');
+        assert_eq!(screen.cell(2, 3).unwrap().ch, 'x');
+        assert_eq!(screen.position().1, 4);
+    }
+}
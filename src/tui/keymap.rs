@@ -0,0 +1,398 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::core::action::Action;
+
+/// A key with its modifiers, written like vim: `x`, `G`, `<C-f>`, `<Esc>` or `<Space>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn from_event(event: &KeyEvent) -> Self {
+        let mut modifiers = event.modifiers;
+        let mut code = event.code;
+        match code {
+            // the case of the char already tells whether shift was held
+            KeyCode::Char(_) => modifiers.remove(KeyModifiers::SHIFT),
+            // terminals send shift-tab as a key of its own
+            KeyCode::BackTab => {
+                code = KeyCode::Tab;
+                modifiers.insert(KeyModifiers::SHIFT);
+            }
+            _ => {}
+        }
+
+        Key { code, modifiers }
+    }
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::anyhow!("invalid key: {s}");
+
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(Key {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+            });
+        }
+
+        let name = s
+            .strip_prefix('<')
+            .and_then(|s| s.strip_suffix('>'))
+            .ok_or_else(invalid)?;
+
+        let mut modifiers = KeyModifiers::NONE;
+        let mut name = name;
+        while let Some((modifier, rest)) = name.split_once('-').filter(|(_, rest)| !rest.is_empty())
+        {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "c" => KeyModifiers::CONTROL,
+                "a" | "m" => KeyModifiers::ALT,
+                "s" => KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            };
+            name = rest;
+        }
+
+        let code = match name.to_lowercase().as_str() {
+            "esc" => KeyCode::Esc,
+            "cr" | "enter" => KeyCode::Enter,
+            "bs" => KeyCode::Backspace,
+            "del" => KeyCode::Delete,
+            "tab" => KeyCode::Tab,
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    // the terminal reports ctrl chars in lowercase
+                    (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+                        KeyCode::Char(c.to_ascii_lowercase())
+                    }
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(invalid()),
+                }
+            }
+        };
+
+        if let KeyCode::Char(_) = code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Ok(Key { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    /// Writes the key the way it is parsed, so `<C-f>` comes back as `<C-f>`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char('<') => "lt".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Esc => "Esc".to_string(),
+            KeyCode::Enter => "CR".to_string(),
+            KeyCode::Backspace => "BS".to_string(),
+            KeyCode::Delete => "Del".to_string(),
+            KeyCode::Tab => "Tab".to_string(),
+            KeyCode::Up => "Up".to_string(),
+            KeyCode::Down => "Down".to_string(),
+            KeyCode::Left => "Left".to_string(),
+            KeyCode::Right => "Right".to_string(),
+            KeyCode::Home => "Home".to_string(),
+            KeyCode::End => "End".to_string(),
+            KeyCode::PageUp => "PageUp".to_string(),
+            KeyCode::PageDown => "PageDown".to_string(),
+            code => format!("{code:?}"),
+        };
+
+        let mut prefix = String::new();
+        for (modifier, letter) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                prefix.push_str(letter);
+            }
+        }
+
+        let plain = matches!(self.code, KeyCode::Char(c) if c != ' ' && c != '<');
+        if plain && prefix.is_empty() {
+            write!(f, "{name}")
+        } else {
+            write!(f, "<{prefix}{name}>")
+        }
+    }
+}
+
+const NORMAL: &[(&str, Action)] = &[
+    ("h", Action::MoveLeft),
+    ("j", Action::MoveDown),
+    ("k", Action::MoveUp),
+    ("l", Action::MoveRight),
+    ("<Left>", Action::MoveLeft),
+    ("<Down>", Action::MoveDown),
+    ("<Up>", Action::MoveUp),
+    ("<Right>", Action::MoveRight),
+    ("w", Action::NextWord),
+    ("b", Action::PreviousWord),
+    ("e", Action::EndOfWord),
+    ("W", Action::NextBigWord),
+    ("B", Action::PreviousBigWord),
+    ("E", Action::EndOfBigWord),
+    ("0", Action::StartOfLine),
+    ("<Home>", Action::StartOfLine),
+    ("^", Action::FirstNonBlank),
+    ("$", Action::EndOfLine),
+    ("<End>", Action::EndOfLine),
+    ("%", Action::MatchingBracket),
+    ("*", Action::NextOccurrence),
+    ("#", Action::PreviousOccurrence),
+    ("G", Action::EndOfBuffer),
+    ("M", Action::MiddleOfViewport),
+    ("<C-f>", Action::PageDown),
+    ("<C-b>", Action::PageUp),
+    ("<C-d>", Action::HalfPageDown),
+    ("<C-u>", Action::HalfPageUp),
+    ("<C-o>", Action::JumpBack),
+    // terminals send <C-i> as <Tab>
+    ("<Tab>", Action::JumpForward),
+    ("f", Action::FindChar),
+    ("F", Action::FindCharBackward),
+    ("t", Action::Till),
+    ("T", Action::TillBackward),
+    (";", Action::RepeatFind),
+    (",", Action::RepeatFindBackward),
+    ("m", Action::SetMark),
+    ("`", Action::GoToMark),
+    ("'", Action::GoToMarkLine),
+    ("K", Action::Hover),
+    ("g", Action::GotoPrefix),
+    ("z", Action::ScrollPrefix),
+    ("]", Action::NextPrefix),
+    ("[", Action::PreviousPrefix),
+    ("<Space>", Action::Leader),
+    ("<C-w>", Action::WindowPrefix),
+    ("d", Action::Delete),
+    ("c", Action::Change),
+    ("y", Action::Yank),
+    ("p", Action::PutAfter),
+    ("P", Action::PutBefore),
+    ("\"", Action::SelectRegister),
+    (">", Action::Indent),
+    ("<", Action::Dedent),
+    ("=", Action::Reindent),
+    ("i", Action::Insert),
+    ("a", Action::Append),
+    ("o", Action::OpenBelow),
+    ("O", Action::OpenAbove),
+    ("x", Action::DeleteChar),
+    ("~", Action::ToggleCase),
+    ("<C-a>", Action::Increment),
+    ("<C-x>", Action::Decrement),
+    ("D", Action::DeleteToEndOfLine),
+    ("C", Action::ChangeToEndOfLine),
+    ("s", Action::SubstituteChar),
+    ("S", Action::SubstituteLine),
+    ("r", Action::ReplaceChar),
+    ("R", Action::ReplaceMode),
+    ("J", Action::JoinLines),
+    (":", Action::CommandMode),
+    ("v", Action::VisualMode),
+    ("<A-o>", Action::ExpandSelection),
+    ("q", Action::RecordMacro),
+    ("@", Action::PlayMacro),
+    (".", Action::RepeatChange),
+    ("<Esc>", Action::NormalMode),
+];
+
+const INSERT: &[(&str, Action)] = &[
+    ("<Esc>", Action::NormalMode),
+    ("<Left>", Action::MoveLeft),
+    ("<BS>", Action::Backspace),
+    ("<Del>", Action::DeleteChar),
+    ("<CR>", Action::Newline),
+    ("<Tab>", Action::Tab),
+    ("<S-Tab>", Action::PreviousTabStop),
+    ("<C-v>", Action::InsertLiteral),
+];
+
+/// Editing the command line reuses the insert mode actions, with `move_up` and `move_down` going
+/// through the history.
+const COMMAND: &[(&str, Action)] = &[
+    ("<Esc>", Action::NormalMode),
+    ("<C-c>", Action::NormalMode),
+    ("<CR>", Action::Newline),
+    ("<BS>", Action::Backspace),
+    ("<Del>", Action::DeleteChar),
+    ("<Left>", Action::MoveLeft),
+    ("<Right>", Action::MoveRight),
+    ("<Home>", Action::StartOfLine),
+    ("<C-b>", Action::StartOfLine),
+    ("<End>", Action::EndOfLine),
+    ("<C-e>", Action::EndOfLine),
+    ("<Up>", Action::MoveUp),
+    ("<C-p>", Action::MoveUp),
+    ("<Down>", Action::MoveDown),
+    ("<C-n>", Action::MoveDown),
+];
+
+/// The key bindings for normal, insert and command mode. Chars without a binding in insert or
+/// command mode are inserted as typed.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    normal: HashMap<Key, Action>,
+    insert: HashMap<Key, Action>,
+    command: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = |defaults: &[(&str, Action)]| {
+            defaults
+                .iter()
+                .map(|(key, action)| (key.parse().expect("invalid default key"), action.clone()))
+                .collect()
+        };
+
+        Keymap {
+            normal: bindings(NORMAL),
+            insert: bindings(INSERT),
+            command: bindings(COMMAND),
+        }
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults with the user's bindings on top, where each binding
+    /// maps a key to an action name.
+    pub fn with_bindings(
+        normal: &HashMap<String, String>,
+        insert: &HashMap<String, String>,
+        command: &HashMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        let mut keymap = Keymap::default();
+
+        for (bindings, keys) in [
+            (normal, &mut keymap.normal),
+            (insert, &mut keymap.insert),
+            (command, &mut keymap.command),
+        ] {
+            for (key, action) in bindings {
+                let action = Action::from_str(action)
+                    .map_err(|_| anyhow::anyhow!("invalid action for {key}: {action}"))?;
+                keys.insert(key.parse()?, action);
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// Binds `key` to `action` in the mode named `mode`, `normal`, `insert` or `command`.
+    pub fn bind(&mut self, mode: &str, key: Key, action: Action) -> anyhow::Result<()> {
+        let keys = match mode {
+            "normal" => &mut self.normal,
+            "insert" => &mut self.insert,
+            "command" => &mut self.command,
+            _ => anyhow::bail!("invalid mode: {mode}"),
+        };
+        keys.insert(key, action);
+        Ok(())
+    }
+
+    /// The bindings of each mode by the mode's name, sorted by key, as `:help` lists them.
+    pub fn modes(&self) -> [(&'static str, Vec<(String, Action)>); 3] {
+        let sorted = |bindings: &HashMap<Key, Action>| {
+            let mut bindings: Vec<_> = bindings
+                .iter()
+                .map(|(key, action)| (key.to_string(), action.clone()))
+                .collect();
+            bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+            bindings
+        };
+        [
+            ("Normal", sorted(&self.normal)),
+            ("Insert", sorted(&self.insert)),
+            ("Command", sorted(&self.command)),
+        ]
+    }
+
+    pub fn normal(&self, event: &KeyEvent) -> Option<Action> {
+        self.normal.get(&Key::from_event(event)).cloned()
+    }
+
+    pub fn insert(&self, event: &KeyEvent) -> Option<Action> {
+        self.insert.get(&Key::from_event(event)).cloned()
+    }
+
+    pub fn command(&self, event: &KeyEvent) -> Option<Action> {
+        self.command.get(&Key::from_event(event)).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        let key = |s: &str| s.parse::<Key>().unwrap();
+        assert_eq!(key("x").code, KeyCode::Char('x'));
+        assert_eq!(key("<C-F>").code, KeyCode::Char('f'));
+        assert_eq!(key("<C-f>").modifiers, KeyModifiers::CONTROL);
+        assert_eq!(key("<esc>").code, KeyCode::Esc);
+        assert_eq!(key("<Space>").code, KeyCode::Char(' '));
+        assert_eq!(key("-").code, KeyCode::Char('-'));
+        assert_eq!(key("<C-->").code, KeyCode::Char('-'));
+        assert!("<Nope>".parse::<Key>().is_err());
+        assert!("ab".parse::<Key>().is_err());
+
+        for name in [
+            "x", "G", "<C-f>", "<A-o>", "<Esc>", "<Space>", "<lt>", "<C-->", "<S-Tab>",
+        ] {
+            assert_eq!(key(name).to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_bindings() {
+        let normal = HashMap::from([
+            ("L".to_string(), "move_right".to_string()),
+            ("x".to_string(), "nop".to_string()),
+        ]);
+        let keymap = Keymap::with_bindings(&normal, &HashMap::new(), &HashMap::new()).unwrap();
+
+        let event = KeyEvent::new(KeyCode::Char('L'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.normal(&event), Some(Action::MoveRight));
+        let event = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(keymap.normal(&event), Some(Action::Nop));
+        let event = KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.normal(&event), Some(Action::PageDown));
+
+        let normal = HashMap::from([("x".to_string(), "fly".to_string())]);
+        assert!(Keymap::with_bindings(&normal, &HashMap::new(), &HashMap::new()).is_err());
+
+        let mut keymap = Keymap::default();
+        let key = "<C-t>".parse().unwrap();
+        keymap.bind("insert", key, Action::EndOfLine).unwrap();
+        let event = KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.insert(&event), Some(Action::EndOfLine));
+        assert!(keymap.bind("visual", key, Action::EndOfLine).is_err());
+    }
+}
@@ -0,0 +1,19 @@
+//! The terminal frontend: the screen the editor draws on, the windows drawn over the buffer,
+//! themes and key bindings, and setting the terminal up and back.
+
+pub mod background;
+pub mod colors;
+pub mod cursor_style;
+pub mod highlight;
+pub mod keymap;
+pub mod pager;
+pub mod picker;
+pub mod popup;
+pub mod renderer;
+pub mod screen;
+pub mod scrollbar;
+pub mod statusline;
+pub mod tabline;
+pub mod theme;
+pub mod tty;
+pub mod utils;
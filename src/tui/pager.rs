@@ -0,0 +1,118 @@
+use crossterm::style::Stylize;
+
+use crate::tui::{renderer::Renderer, theme::Theme, utils::brigthen};
+
+/// Text too long for the command line, like the output of `:!cmd`, shown in a window over the
+/// bottom of the buffer that scrolls until it is closed.
+#[derive(Debug)]
+pub struct Pager {
+    title: String,
+    lines: Vec<String>,
+    /// The first line shown.
+    top: usize,
+}
+
+impl Pager {
+    pub fn new(title: String, text: &str) -> Self {
+        Pager {
+            title,
+            lines: text
+                .lines()
+                .map(|line| line.replace('\t', "    "))
+                .collect(),
+            top: 0,
+        }
+    }
+
+    /// Where the pager sits in a `height` rows tall area, as the row of its title and how many
+    /// lines of text fit below it. Short text takes only the rows it needs, long text the whole
+    /// area.
+    fn area(&self, height: usize) -> (usize, usize) {
+        let rows = self.lines.len().min(height.saturating_sub(1));
+        (height.saturating_sub(rows + 1), rows)
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Scrolls by `delta` lines, up when negative, keeping the last page in view.
+    pub fn scroll(&mut self, delta: isize, height: usize) {
+        let (_, rows) = self.area(height);
+        let max = self.lines.len().saturating_sub(rows);
+        self.top = self.top.saturating_add_signed(delta).min(max);
+    }
+
+    /// How many lines a page of the window is in a `height` rows tall area.
+    pub fn page(&self, height: usize) -> usize {
+        self.area(height).1.max(1)
+    }
+
+    /// Where the terminal cursor goes while the window is open, at the start of the title.
+    pub fn cursor(&self, height: usize) -> (usize, usize) {
+        (0, self.area(height).0)
+    }
+
+    pub fn draw(
+        &self,
+        screen: &mut dyn Renderer,
+        theme: &Theme,
+        width: usize,
+        height: usize,
+    ) -> anyhow::Result<()> {
+        let (y, rows) = self.area(height);
+
+        let (fg, bg) = theme.default_colors();
+        let bg = brigthen(bg, 0.05)?;
+        let fit = |text: &str| -> String {
+            let text: String = text.chars().take(width).collect();
+            format!("{text:<width$}")
+        };
+
+        let position = if self.lines.len() > rows {
+            format!(
+                " ({}-{} of {})",
+                self.top + 1,
+                self.top + rows,
+                self.lines.len()
+            )
+        } else {
+            String::new()
+        };
+        screen.move_to(0, y);
+        screen.print_styled(
+            fit(&format!(" {}{position}", self.title))
+                .with(fg)
+                .on(brigthen(bg, 0.1)?),
+        );
+
+        for (row, line) in self.lines.iter().skip(self.top).take(rows).enumerate() {
+            screen.move_to(0, y + 1 + row);
+            screen.print_styled(fit(line).with(fg).on(bg));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll() {
+        let text: Vec<_> = (1..=20).map(|n| n.to_string()).collect();
+        let mut pager = Pager::new(":!seq 20".to_string(), &text.join("\n"));
+        assert_eq!(pager.area(11), (0, 10));
+        assert_eq!(pager.area(30), (9, 20));
+
+        pager.scroll(5, 11);
+        assert_eq!(pager.top, 5);
+        pager.scroll(100, 11);
+        assert_eq!(pager.top, 10);
+        pager.scroll(-3, 11);
+        assert_eq!(pager.top, 7);
+        pager.scroll(-100, 11);
+        assert_eq!(pager.top, 0);
+    }
+}
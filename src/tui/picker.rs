@@ -0,0 +1,253 @@
+use std::{cmp::Reverse, fs, path::Path};
+
+use crossterm::style::Stylize;
+
+use crate::tui::{
+    renderer::Renderer,
+    theme::Theme,
+    utils::{brigthen, hex_to_crossterm_color},
+};
+
+/// Directories that are never worth opening files from.
+const IGNORED_DIRS: [&str; 2] = ["target", "node_modules"];
+
+/// Stops walking the directory tree after this many files.
+const MAX_FILES: usize = 20_000;
+
+/// A fuzzy file picker drawn as an overlay above the buffer.
+#[derive(Debug, Default)]
+pub struct Picker {
+    files: Vec<String>,
+    query: String,
+    /// Indexes into `files` that match the query, best match first.
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(files: Vec<String>) -> Self {
+        let mut picker = Picker {
+            files,
+            ..Default::default()
+        };
+        picker.filter();
+        picker
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.filter();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.filter();
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.matches
+            .get(self.selected)
+            .map(|&i| self.files[i].as_str())
+    }
+
+    fn filter(&mut self) {
+        let mut matches: Vec<(i64, usize)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(i, file)| fuzzy_score(&self.query, file).map(|score| (score, i)))
+            .collect();
+        matches.sort_by_key(|&(score, i)| (Reverse(score), self.files[i].len(), i));
+
+        self.matches = matches.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    /// The box the picker is drawn in, as `(x, y, width, height)`.
+    fn area(width: usize, height: usize) -> (usize, usize, usize, usize) {
+        let w = width.saturating_sub(4).min(80);
+        let h = height.saturating_sub(2).min(20);
+        ((width - w) / 2, (height - h) / 2, w, h)
+    }
+
+    /// Where the terminal cursor goes while the picker is open, at the end of the query.
+    pub fn cursor(&self, width: usize, height: usize) -> (usize, usize) {
+        let (x, y, w, _) = Self::area(width, height);
+        (
+            x + (3 + self.query.chars().count()).min(w.saturating_sub(1)),
+            y,
+        )
+    }
+
+    /// Draws the prompt followed by as many matches as fit, scrolled to keep the selection
+    /// visible.
+    pub fn draw(
+        &self,
+        screen: &mut dyn Renderer,
+        theme: &Theme,
+        width: usize,
+        height: usize,
+    ) -> anyhow::Result<()> {
+        let (x, y, w, h) = Self::area(width, height);
+        if w < 4 || h < 2 {
+            return Ok(());
+        }
+
+        let (fg, bg) = theme.default_colors();
+        let bg = brigthen(bg, 0.05)?;
+        let selection_bg = match &theme.selection {
+            Some(selection) => hex_to_crossterm_color(selection)?,
+            None => brigthen(bg, 0.2)?,
+        };
+        let fit = |text: &str| -> String {
+            let text: String = text.chars().take(w).collect();
+            format!("{text:<w$}")
+        };
+
+        let count = format!("{}/{} ", self.matches.len(), self.files.len());
+        let prompt = format!(" > {}", self.query);
+        let padding = w.saturating_sub(prompt.chars().count() + count.chars().count());
+        screen.move_to(x, y);
+        screen.print_styled(
+            fit(&format!("{prompt}{}{count}", " ".repeat(padding)))
+                .with(fg)
+                .on(bg),
+        );
+
+        let rows = h - 1;
+        let first = (self.selected + 1).saturating_sub(rows);
+        for row in 0..rows {
+            let i = first + row;
+            let (text, bg) = match self.matches.get(i) {
+                Some(&file) if i == self.selected => {
+                    (format!("  {}", self.files[file]), selection_bg)
+                }
+                Some(&file) => (format!("  {}", self.files[file]), bg),
+                None => (String::new(), bg),
+            };
+            screen.move_to(x, y + 1 + row);
+            screen.print_styled(fit(&text).with(fg).on(bg));
+        }
+
+        Ok(())
+    }
+}
+
+/// Scores how well `candidate` matches `query` when the query chars appear in it in order,
+/// ignoring case. Consecutive chars and chars at the start of a path segment or word score
+/// higher. Returns `None` when it doesn't match at all.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut previous: Option<char> = None;
+    let mut consecutive = false;
+
+    for c in candidate.chars() {
+        let Some(&q) = query.peek() else {
+            break;
+        };
+
+        if c.to_lowercase().eq(q.to_lowercase()) {
+            score += 1;
+            if consecutive {
+                score += 5;
+            }
+            if previous.is_none_or(|p| matches!(p, '/' | '_' | '-' | '.' | ' ')) {
+                score += 3;
+            }
+            consecutive = true;
+            query.next();
+        } else {
+            consecutive = false;
+        }
+        previous = Some(c);
+    }
+
+    query.peek().is_none().then_some(score)
+}
+
+/// Lists the files under `root` as paths relative to it, skipping hidden entries and build
+/// directories.
+pub fn list_files(root: &Path) -> Vec<String> {
+    let mut files = vec![];
+    let mut dirs = vec![root.to_path_buf()];
+
+    'walk: while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || IGNORED_DIRS.contains(&name.as_ref()) {
+                continue;
+            }
+
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(typ) if typ.is_dir() => dirs.push(path),
+                Ok(_) => {
+                    if let Ok(relative) = path.strip_prefix(root) {
+                        files.push(relative.to_string_lossy().to_string());
+                    }
+                    if files.len() >= MAX_FILES {
+                        break 'walk;
+                    }
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("smr", "src/main.rs").is_some());
+        assert!(fuzzy_score("SMR", "src/main.rs").is_some());
+        assert_eq!(fuzzy_score("nim", "src/main.rs"), None);
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+
+        // consecutive and word start matches rank higher
+        let main = fuzzy_score("main", "src/main.rs").unwrap();
+        let scattered = fuzzy_score("main", "src/my_animation.rs").unwrap();
+        assert!(main > scattered);
+    }
+
+    #[test]
+    fn test_picker_filter() {
+        let files = vec![
+            "README.md".to_string(),
+            "src/buffer.rs".to_string(),
+            "src/main.rs".to_string(),
+        ];
+        let mut picker = Picker::new(files);
+        assert_eq!(picker.matches.len(), 3);
+
+        picker.push('b');
+        picker.push('u');
+        assert_eq!(picker.selected(), Some("src/buffer.rs"));
+
+        picker.pop();
+        picker.pop();
+        picker.push('x');
+        assert_eq!(picker.selected(), None);
+    }
+}
@@ -0,0 +1,94 @@
+use crossterm::style::Stylize;
+
+use crate::tui::{renderer::Renderer, theme::Theme, utils::brigthen};
+
+/// At most this many lines of a popup are shown.
+const MAX_HEIGHT: usize = 15;
+
+/// At most this many columns of a popup are shown.
+const MAX_WIDTH: usize = 80;
+
+/// A box of text drawn over the buffer next to the cursor, like the hover information of a
+/// language server. It stays up until the next key.
+#[derive(Debug)]
+pub struct Popup {
+    lines: Vec<String>,
+}
+
+impl Popup {
+    pub fn new(text: &str) -> Self {
+        Popup {
+            lines: text
+                .lines()
+                .map(|line| line.replace('\t', "    "))
+                .collect(),
+        }
+    }
+
+    /// The size of the box, with a column of padding on each side of the text.
+    fn size(&self, width: usize, height: usize) -> (usize, usize) {
+        let longest = self.lines.iter().map(|line| line.chars().count()).max();
+        let w = (longest.unwrap_or_default() + 2).min(MAX_WIDTH).min(width);
+        let h = self.lines.len().min(MAX_HEIGHT).min(height);
+        (w, h)
+    }
+
+    /// Draws the popup above the screen position `(x, y)` of the cursor, or below it when there
+    /// is no room above, keeping it within a screen `width` columns wide and `height` rows tall.
+    pub fn draw(
+        &self,
+        screen: &mut dyn Renderer,
+        theme: &Theme,
+        (x, y): (usize, usize),
+        width: usize,
+        height: usize,
+    ) -> anyhow::Result<()> {
+        let (w, h) = self.size(width, height.saturating_sub(1));
+        if w < 3 || h == 0 {
+            return Ok(());
+        }
+
+        let top = if y >= h {
+            y - h
+        } else if y + 1 + h <= height {
+            y + 1
+        } else {
+            return Ok(());
+        };
+        let left = x.min(width - w);
+
+        let (fg, bg) = theme.default_colors();
+        let bg = brigthen(bg, 0.1)?;
+        for (row, line) in self.lines.iter().take(h).enumerate() {
+            let text: String = line.chars().take(w - 2).collect();
+            let inner = w - 2;
+            screen.move_to(left, top + row);
+            screen.print_styled(format!(" {text:<inner$} ").with(fg).on(bg));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tui::renderer::Grid;
+
+    #[test]
+    fn test_popup_placement() {
+        let theme = Theme::default();
+        let popup = Popup::new("fn main()\n\nRuns\tit");
+        assert_eq!(popup.size(80, 20), (12, 3));
+
+        // above the cursor when there is room, below it otherwise
+        let mut screen = Grid::new(20, 10);
+        popup.draw(&mut screen, &theme, (15, 5), 20, 10).unwrap();
+        assert_eq!(screen.cell(9, 2).unwrap().ch, 'f');
+        assert_eq!(screen.cell(9, 4).unwrap().ch, 'R');
+
+        let mut screen = Grid::new(20, 10);
+        popup.draw(&mut screen, &theme, (0, 1), 20, 10).unwrap();
+        assert_eq!(screen.cell(1, 2).unwrap().ch, 'f');
+    }
+}
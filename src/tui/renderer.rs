@@ -0,0 +1,285 @@
+use std::fmt::Display;
+
+use crossterm::style::{Attributes, Color, ContentStyle, StyledContent};
+
+use crate::tui::utils::char_width;
+
+/// The char of the cell right of a wide char, which the wide char covers on the terminal.
+pub const CONTINUATION: char = '\0';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub attrs: Attributes,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            ch: ' ',
+            fg: Color::Reset,
+            bg: Color::Reset,
+            attrs: Attributes::default(),
+        }
+    }
+}
+
+/// What the editor draws on: a grid of cells written to like crossterm commands are queued,
+/// shown all at once on `flush`.
+///
+/// [`Screen`](super::screen::Screen) puts the cells on the terminal, [`Grid`] only keeps them so
+/// tests can check what would be there.
+pub trait Renderer {
+    fn size(&self) -> (usize, usize);
+
+    /// Starts over with blank cells at the new size.
+    fn resize(&mut self, width: usize, height: usize);
+
+    /// Forgets what is shown, forcing the next flush to show every cell.
+    fn invalidate(&mut self) {}
+
+    /// Leaves cells with the background `bg` on the terminal's own background, so it shows
+    /// through them, or none with `None`.
+    fn set_transparent(&mut self, _bg: Option<Color>) {}
+
+    /// Makes `move_to` and `position` count rows from `top`, so the area below it is drawn like
+    /// a screen of its own.
+    fn set_top(&mut self, top: usize);
+
+    fn position(&self) -> (usize, usize);
+
+    fn move_to(&mut self, x: usize, y: usize);
+
+    fn move_to_column(&mut self, x: usize);
+
+    fn move_to_next_line(&mut self);
+
+    fn set_fg(&mut self, fg: Color);
+
+    fn set_bg(&mut self, bg: Color);
+
+    fn set_attrs(&mut self, attrs: Attributes);
+
+    /// Prints `text` with the current colors, clipping anything past the right edge.
+    fn print(&mut self, text: &str);
+
+    /// Prints `text` in `style`, falling back to the current colors for unset ones. The style
+    /// doesn't change the current colors, like crossterm's `PrintStyledContent`.
+    fn print_with_style(&mut self, text: &str, style: ContentStyle);
+
+    /// The cell at `(x, y)`, counting rows from the top of the grid whatever `set_top` says.
+    fn cell(&self, x: usize, y: usize) -> Option<Cell>;
+
+    /// Shows what was drawn since the last flush.
+    fn flush(&mut self) -> anyhow::Result<()>;
+}
+
+impl dyn Renderer + '_ {
+    /// Prints styled content like `"text".with(fg).on(bg)`.
+    pub fn print_styled<D: Display>(&mut self, content: StyledContent<D>) {
+        self.print_with_style(&content.content().to_string(), *content.style());
+    }
+
+    /// The chars on row `y`, wide chars once.
+    #[allow(unused)]
+    pub fn row(&self, y: usize) -> String {
+        let (width, _) = self.size();
+        (0..width)
+            .filter_map(|x| self.cell(x, y))
+            .map(|cell| cell.ch)
+            .filter(|&ch| ch != CONTINUATION)
+            .collect()
+    }
+}
+
+/// The editor gets its renderer once it knows the size of the terminal.
+impl Default for Box<dyn Renderer> {
+    fn default() -> Self {
+        Box::new(Grid::default())
+    }
+}
+
+/// The cells of a frame in memory, and where and how the next ones get printed.
+#[derive(Debug)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    x: usize,
+    y: usize,
+    /// The row that `move_to` counts rows from, leaving the ones above it to the tabline.
+    top: usize,
+    fg: Color,
+    bg: Color,
+    attrs: Attributes,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid::new(0, 0)
+    }
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![Cell::default(); width * height],
+            x: 0,
+            y: 0,
+            top: 0,
+            fg: Color::Reset,
+            bg: Color::Reset,
+            attrs: Attributes::default(),
+        }
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    /// Prints the chars of `text` in as many cells as they take on the terminal, a wide char
+    /// followed by a [`CONTINUATION`]. Zero width chars are left out, and a wide char that
+    /// doesn't fit before the right edge is a space.
+    fn print_cells(&mut self, text: &str, fg: Color, bg: Color, attrs: Attributes) {
+        for ch in text.chars() {
+            let ch = if ch.is_control() { ' ' } else { ch };
+            let (ch, width) = match char_width(ch) {
+                0 => continue,
+                2 if self.x + 1 >= self.width => (' ', 1),
+                width => (ch, width),
+            };
+            self.set(self.x, Cell { ch, fg, bg, attrs });
+            if width == 2 {
+                let ch = CONTINUATION;
+                self.set(self.x + 1, Cell { ch, fg, bg, attrs });
+            }
+            self.x += width;
+        }
+    }
+
+    /// Puts `cell` at column `x` of the current row, blanking what is left of a wide char it
+    /// overwrites half of.
+    fn set(&mut self, x: usize, cell: Cell) {
+        if x >= self.width || self.y >= self.height {
+            return;
+        }
+        let i = self.y * self.width + x;
+        if self.cells[i].ch == CONTINUATION && x > 0 {
+            self.cells[i - 1].ch = ' ';
+        }
+        if x + 1 < self.width && self.cells[i + 1].ch == CONTINUATION {
+            self.cells[i + 1].ch = ' ';
+        }
+        self.cells[i] = cell;
+    }
+}
+
+impl Renderer for Grid {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        *self = Grid::new(width, height);
+    }
+
+    fn set_top(&mut self, top: usize) {
+        self.top = top;
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.x, self.y.saturating_sub(self.top))
+    }
+
+    fn move_to(&mut self, x: usize, y: usize) {
+        self.x = x;
+        self.y = self.top + y;
+    }
+
+    fn move_to_column(&mut self, x: usize) {
+        self.x = x;
+    }
+
+    fn move_to_next_line(&mut self) {
+        self.x = 0;
+        self.y += 1;
+    }
+
+    fn set_fg(&mut self, fg: Color) {
+        self.fg = fg;
+    }
+
+    fn set_bg(&mut self, bg: Color) {
+        self.bg = bg;
+    }
+
+    fn set_attrs(&mut self, attrs: Attributes) {
+        self.attrs = attrs;
+    }
+
+    fn print(&mut self, text: &str) {
+        self.print_cells(text, self.fg, self.bg, self.attrs);
+    }
+
+    fn print_with_style(&mut self, text: &str, style: ContentStyle) {
+        let fg = style.foreground_color.unwrap_or(self.fg);
+        let bg = style.background_color.unwrap_or(self.bg);
+        self.print_cells(text, fg, bg, style.attributes);
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Option<Cell> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.cells.get(y * self.width + x).copied()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn test_print_clips_to_width() {
+        let mut grid = Grid::new(4, 2);
+        grid.move_to(2, 1);
+        grid.print("abc");
+        assert_eq!(grid.cell(2, 1).unwrap().ch, 'a');
+        assert_eq!(grid.cell(3, 1).unwrap().ch, 'b');
+        assert_eq!(grid.position(), (5, 1));
+    }
+
+    #[test]
+    fn test_top_offsets_rows() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_top(1);
+        grid.move_to(0, 0);
+        grid.print("a");
+        assert_eq!(grid.cell(0, 1).unwrap().ch, 'a');
+        assert_eq!(grid.position(), (1, 0));
+    }
+
+    #[test]
+    fn test_print_styled() {
+        let mut grid = Grid::new(6, 1);
+        let renderer: &mut dyn Renderer = &mut grid;
+        renderer.set_bg(Color::Blue);
+        renderer.print_styled("ab".with(Color::Red));
+        renderer.print("c");
+        assert_eq!(renderer.row(0), "abc   ");
+        let cell = renderer.cell(1, 0).unwrap();
+        assert_eq!((cell.fg, cell.bg), (Color::Red, Color::Blue));
+        // the styled colors are only for the styled text
+        assert_eq!(renderer.cell(2, 0).unwrap().fg, Color::Reset);
+    }
+}
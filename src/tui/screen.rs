@@ -0,0 +1,291 @@
+use std::io::{stdout, Write};
+
+use crossterm::{
+    cursor::MoveTo,
+    style::{
+        Attribute, Attributes, Color, ContentStyle, Print, SetAttribute, SetAttributes,
+        SetBackgroundColor, SetForegroundColor,
+    },
+    QueueableCommand,
+};
+
+use crate::tui::{
+    colors::ColorSupport,
+    renderer::{Cell, Grid, Renderer, CONTINUATION},
+    utils::char_width,
+};
+
+/// The renderer for the terminal.
+///
+/// Drawing code writes into a [`Grid`], and `flush` only emits the cells that changed since the
+/// last frame so unchanged parts of the screen are never repainted.
+#[derive(Debug, Default)]
+pub struct Screen {
+    grid: Grid,
+    /// The frame currently on the terminal, `None` when it is unknown and must be fully repainted.
+    previous: Option<Vec<Cell>>,
+    /// The colors the terminal can show, which the colors of cells are fit to as they are written.
+    colors: ColorSupport,
+    /// The background left to the terminal, see [`Renderer::set_transparent`].
+    transparent: Option<Color>,
+}
+
+impl Screen {
+    pub fn new(width: usize, height: usize) -> Self {
+        Screen {
+            grid: Grid::new(width, height),
+            previous: None,
+            colors: ColorSupport::default(),
+            transparent: None,
+        }
+    }
+
+    pub fn with_colors(mut self, colors: ColorSupport) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Writes the cells that differ from the previous frame to `out`.
+    pub fn write_to<W: Write>(&mut self, out: &mut W) -> anyhow::Result<()> {
+        // where the terminal cursor is and which style it is printing with
+        let mut at: Option<(usize, usize)> = None;
+        let mut style: Option<(Color, Color, Attributes)> = None;
+
+        let (width, height) = self.grid.size();
+        let cells = self.grid.cells();
+        for y in 0..height {
+            for x in 0..width {
+                let i = y * width + x;
+                let cell = cells[i];
+                // printing the wide char before it took care of this cell
+                if cell.ch == CONTINUATION {
+                    continue;
+                }
+                if let Some(previous) = &self.previous {
+                    if previous[i] == cell {
+                        continue;
+                    }
+                }
+
+                let fg = self.colors.fit(cell.fg);
+                let bg = match self.transparent {
+                    Some(bg) if bg == cell.bg => Color::Reset,
+                    _ => self.colors.fit(cell.bg),
+                };
+                if at != Some((x, y)) {
+                    out.queue(MoveTo(x as u16, y as u16))?;
+                }
+
+                let (current_fg, current_bg, attrs) =
+                    style.unwrap_or((Color::Reset, Color::Reset, cell.attrs));
+                if style.is_none() || attrs != cell.attrs {
+                    out.queue(SetAttribute(Attribute::Reset))?;
+                    out.queue(SetAttributes(cell.attrs))?;
+                    out.queue(SetForegroundColor(fg))?;
+                    out.queue(SetBackgroundColor(bg))?;
+                } else {
+                    if current_fg != fg {
+                        out.queue(SetForegroundColor(fg))?;
+                    }
+                    if current_bg != bg {
+                        out.queue(SetBackgroundColor(bg))?;
+                    }
+                }
+                style = Some((fg, bg, cell.attrs));
+
+                out.queue(Print(cell.ch))?;
+                at = Some((x + char_width(cell.ch), y));
+            }
+        }
+
+        if style.is_some() {
+            out.queue(SetAttribute(Attribute::Reset))?;
+        }
+        self.previous = Some(cells.to_vec());
+
+        Ok(())
+    }
+}
+
+impl Renderer for Screen {
+    fn size(&self) -> (usize, usize) {
+        self.grid.size()
+    }
+
+    fn resize(&mut self, width: usize, height: usize) {
+        *self = Screen {
+            transparent: self.transparent,
+            ..Screen::new(width, height).with_colors(self.colors)
+        };
+    }
+
+    fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    fn set_transparent(&mut self, bg: Option<Color>) {
+        if self.transparent != bg {
+            self.transparent = bg;
+            self.invalidate();
+        }
+    }
+
+    fn set_top(&mut self, top: usize) {
+        self.grid.set_top(top);
+    }
+
+    fn position(&self) -> (usize, usize) {
+        self.grid.position()
+    }
+
+    fn move_to(&mut self, x: usize, y: usize) {
+        self.grid.move_to(x, y);
+    }
+
+    fn move_to_column(&mut self, x: usize) {
+        self.grid.move_to_column(x);
+    }
+
+    fn move_to_next_line(&mut self) {
+        self.grid.move_to_next_line();
+    }
+
+    fn set_fg(&mut self, fg: Color) {
+        self.grid.set_fg(fg);
+    }
+
+    fn set_bg(&mut self, bg: Color) {
+        self.grid.set_bg(bg);
+    }
+
+    fn set_attrs(&mut self, attrs: Attributes) {
+        self.grid.set_attrs(attrs);
+    }
+
+    fn print(&mut self, text: &str) {
+        self.grid.print(text);
+    }
+
+    fn print_with_style(&mut self, text: &str, style: ContentStyle) {
+        self.grid.print_with_style(text, style);
+    }
+
+    fn cell(&self, x: usize, y: usize) -> Option<Cell> {
+        self.grid.cell(x, y)
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.write_to(&mut stdout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Stylize;
+
+    use super::*;
+
+    #[test]
+    fn test_flush_only_changed_cells() {
+        let mut screen = Screen::new(10, 3);
+        screen.print_with_style("hello", ContentStyle::new().red());
+
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        assert!(!out.is_empty());
+
+        // nothing changed, nothing to emit
+        let mut out = vec![];
+        screen.move_to(0, 0);
+        screen.print_with_style("hello", ContentStyle::new().red());
+        screen.write_to(&mut out).unwrap();
+        assert!(out.is_empty());
+
+        screen.move_to(1, 0);
+        screen.print_with_style("a", ContentStyle::new().red());
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains('a'));
+        assert!(!out.contains('h'));
+    }
+
+    #[test]
+    fn test_wide_chars() {
+        let mut screen = Screen::new(6, 1);
+        screen.print("日本x");
+        assert_eq!(
+            (screen.cell(1, 0).unwrap().ch, screen.position()),
+            (CONTINUATION, (5, 0))
+        );
+
+        // the terminal moves past both columns of a wide char, so `x` needs no move
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("日本x"), "{out:?}");
+        assert!(!out.contains('\0'));
+
+        // overwriting half of a wide char blanks the rest of it
+        screen.move_to(3, 0);
+        screen.print("a");
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[1;3H"), "{out:?}");
+        assert!(out.ends_with(" a\x1b[0m"), "{out:?}");
+        let screen: &dyn Renderer = &screen;
+        assert_eq!(screen.row(0), "日 ax ");
+    }
+
+    #[test]
+    fn test_fit_colors() {
+        let mut screen = Screen::new(1, 1).with_colors(ColorSupport::Ansi256);
+        screen.set_fg(Color::Rgb { r: 255, g: 0, b: 0 });
+        screen.print("x");
+
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\x1b[38;5;196m"));
+        assert!(!out.contains("38;2"));
+    }
+
+    #[test]
+    fn test_transparent() {
+        let bg = Color::Rgb {
+            r: 48,
+            g: 52,
+            b: 70,
+        };
+        let mut screen = Screen::new(2, 1);
+        screen.set_bg(bg);
+        screen.set_fg(Color::Rgb { r: 255, g: 0, b: 0 });
+        screen.print("ab");
+        screen.set_transparent(Some(bg));
+
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\x1b[49m"));
+        assert!(!out.contains("48;2"));
+        assert!(out.contains("38;2;255;0;0"));
+
+        // turning it off repaints the cells with the background
+        screen.set_transparent(None);
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("48;2;48;52;70"));
+    }
+
+    #[test]
+    fn test_invalidate_repaints_everything() {
+        let mut screen = Screen::new(3, 1);
+        screen.write_to(&mut vec![]).unwrap();
+        screen.invalidate();
+
+        let mut out = vec![];
+        screen.write_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().matches(' ').count(), 3);
+    }
+}
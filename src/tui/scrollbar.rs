@@ -0,0 +1,121 @@
+//! The scrollbar on the right edge of the buffer, showing where the lines in view are in the
+//! file, with marks on the lines that have search matches or diagnostics.
+
+use std::ops::Range;
+
+use crossterm::style::{Color, Stylize};
+
+use crate::tui::{renderer::Renderer, theme::Theme, utils::blend};
+
+/// The lines of the buffer a scrollbar stands for.
+#[derive(Debug, Clone, Copy)]
+pub struct Scroll {
+    /// The first line in view.
+    pub top: usize,
+    /// How many lines are in view.
+    pub visible: usize,
+    /// How many lines the buffer has.
+    pub total: usize,
+}
+
+impl Scroll {
+    /// The rows of a `height` tall scrollbar the thumb covers. It's never shorter than a row,
+    /// and reaches the last row only once the last line is in view.
+    fn thumb(&self, height: usize) -> Range<usize> {
+        let total = self.total.max(1);
+        if self.top + self.visible >= total {
+            let len = (height * self.visible).div_ceil(total).clamp(1, height);
+            return height.saturating_sub(len)..height;
+        }
+        let len = (height * self.visible / total).clamp(1, height);
+        let start = (height * self.top / total).min(height.saturating_sub(len + 1));
+        start..start + len
+    }
+
+    /// The row of a `height` tall scrollbar that `line` falls on.
+    fn row(&self, line: usize, height: usize) -> usize {
+        (line * height / self.total.max(1)).min(height.saturating_sub(1))
+    }
+}
+
+/// Draws a `height` tall scrollbar in column `x`, with each of `marks` as a line and the color of
+/// its mark. Later marks are drawn over earlier ones on the same row.
+pub fn draw(
+    screen: &mut dyn Renderer,
+    theme: &Theme,
+    x: usize,
+    height: usize,
+    scroll: Scroll,
+    marks: &[(usize, Color)],
+) {
+    if height == 0 {
+        return;
+    }
+    let (fg, bg) = theme.default_colors();
+    let track = blend(bg, fg, 0.06);
+    let thumb_bg = blend(bg, fg, 0.25);
+    let thumb = scroll.thumb(height);
+
+    let mut rows: Vec<Option<Color>> = vec![None; height];
+    for &(line, color) in marks {
+        rows[scroll.row(line, height)] = Some(color);
+    }
+
+    for (y, mark) in rows.into_iter().enumerate() {
+        let bg = if thumb.contains(&y) { thumb_bg } else { track };
+        screen.move_to(x, y);
+        match mark {
+            Some(color) => screen.print_styled("─".with(color).on(bg)),
+            None => screen.print_styled(" ".on(bg)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tui::renderer::Grid;
+
+    #[test]
+    fn test_thumb() {
+        let scroll = |top, visible, total| Scroll {
+            top,
+            visible,
+            total,
+        };
+        assert_eq!(scroll(0, 10, 5).thumb(10), 0..10);
+        assert_eq!(scroll(0, 10, 100).thumb(10), 0..1);
+        assert_eq!(scroll(50, 10, 100).thumb(10), 5..6);
+        assert_eq!(scroll(90, 10, 100).thumb(10), 9..10);
+        assert_eq!(scroll(89, 10, 100).thumb(10), 8..9);
+        assert_eq!(scroll(0, 10, 1000).thumb(10), 0..1);
+        assert_eq!(scroll(10, 10, 40).thumb(10), 2..4);
+    }
+
+    #[test]
+    fn test_draw() {
+        let theme = Theme::default();
+        let mut screen = Grid::new(2, 4);
+        let scroll = Scroll {
+            top: 0,
+            visible: 4,
+            total: 16,
+        };
+        draw(
+            &mut screen,
+            &theme,
+            1,
+            4,
+            scroll,
+            &[(9, Color::Yellow), (10, Color::Red)],
+        );
+
+        let column: String = (0..4).map(|y| screen.cell(1, y).unwrap().ch).collect();
+        assert_eq!(column, "  ─ ");
+        assert_eq!(screen.cell(1, 2).unwrap().fg, Color::Red);
+        assert_ne!(screen.cell(1, 0).unwrap().bg, screen.cell(1, 1).unwrap().bg);
+
+        // a terminal too short for any text has no rows to mark
+        draw(&mut screen, &theme, 1, 0, scroll, &[(9, Color::Yellow)]);
+    }
+}
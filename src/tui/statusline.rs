@@ -0,0 +1,407 @@
+use crossterm::style::{Color, Stylize};
+use serde::{Deserialize, Serialize};
+
+use crate::tui::{renderer::Renderer, utils::hex_to_crossterm_color};
+
+/// The powerline arrows that end the accented segments.
+const LEFT_SEPARATOR: &str = "\u{e0b0}";
+const RIGHT_SEPARATOR: &str = "\u{e0b2}";
+
+/// The values segments can show, as `{name}` in their format.
+pub const FIELDS: &[&str] = &[
+    "mode",
+    "file",
+    "dirty",
+    "readonly",
+    "noeol",
+    "autosaved",
+    "recording",
+    "plugins",
+    "language",
+    "encoding",
+    "line_ending",
+    "branch",
+    "line",
+    "column",
+    "percent",
+    "lines",
+    "search",
+];
+
+/// The `[statusline]` table of the config file, where unset keys keep their defaults:
+///
+/// ```toml
+/// [statusline]
+/// left = [" {mode} ", " {file} ", "{dirty} "]
+/// right = [" {language} ", " {line}:{column} "]
+/// background = "#444658"
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct StatuslineConfig {
+    pub left: Option<Vec<String>>,
+    pub right: Option<Vec<String>>,
+    pub foreground: Option<String>,
+    pub background: Option<String>,
+    /// Colors of the first segment on the left and the last one on the right.
+    pub accent_foreground: Option<String>,
+    pub accent_background: Option<String>,
+}
+
+/// The segments shown on each side of the statusline and their colors. Each segment is a format
+/// like `" {file} "` whose `{name}`s are replaced by values from [`FIELDS`]. Segments whose
+/// values are all empty, like `"{dirty} "` for a buffer without changes, are left out.
+#[derive(Debug, Clone)]
+pub struct Statusline {
+    left: Vec<String>,
+    right: Vec<String>,
+    fg: Color,
+    bg: Color,
+    accent_fg: Color,
+    accent_bg: Color,
+}
+
+impl Default for Statusline {
+    fn default() -> Self {
+        let segments = |segments: &[&str]| segments.iter().map(|s| s.to_string()).collect();
+        Statusline {
+            left: segments(&[
+                " {mode} ",
+                " {file} ",
+                "{dirty} ",
+                "{readonly} ",
+                "{noeol} ",
+                "{autosaved} ",
+                "{recording} ",
+                "{plugins} ",
+            ]),
+            right: segments(&[
+                " {search}",
+                " \u{e0a0} {branch}",
+                " {encoding}",
+                " {line_ending} ",
+                " {line}:{column} {percent} ",
+            ]),
+            fg: Color::White,
+            bg: Color::Rgb {
+                r: 68,
+                g: 70,
+                b: 88,
+            },
+            accent_fg: Color::Rgb { r: 0, g: 0, b: 0 },
+            accent_bg: Color::Rgb {
+                r: 178,
+                g: 145,
+                b: 236,
+            },
+        }
+    }
+}
+
+impl TryFrom<StatuslineConfig> for Statusline {
+    type Error = anyhow::Error;
+
+    fn try_from(config: StatuslineConfig) -> anyhow::Result<Self> {
+        let default = Statusline::default();
+        let color = |hex: Option<String>, default: Color| -> anyhow::Result<Color> {
+            match hex {
+                Some(hex) if hex.is_ascii() && hex.trim_start_matches('#').len() == 6 => {
+                    hex_to_crossterm_color(&hex)
+                        .map_err(|_| anyhow::anyhow!("invalid statusline color: {hex}"))
+                }
+                Some(hex) => anyhow::bail!("invalid statusline color: {hex}"),
+                None => Ok(default),
+            }
+        };
+
+        let statusline = Statusline {
+            left: config.left.unwrap_or(default.left),
+            right: config.right.unwrap_or(default.right),
+            fg: color(config.foreground, default.fg)?,
+            bg: color(config.background, default.bg)?,
+            accent_fg: color(config.accent_foreground, default.accent_fg)?,
+            accent_bg: color(config.accent_background, default.accent_bg)?,
+        };
+        for segment in statusline.left.iter().chain(&statusline.right) {
+            expand(segment, |name| {
+                FIELDS
+                    .contains(&name)
+                    .then(String::new)
+                    .ok_or_else(|| anyhow::anyhow!("unknown statusline field: {{{name}}}"))
+            })?;
+        }
+        Ok(statusline)
+    }
+}
+
+/// A segment as laid out on the statusline.
+struct Placed<'a> {
+    format: &'a str,
+    text: String,
+    /// The column the text starts at.
+    start: usize,
+    /// Whether a powerline arrow comes before the text, for the last segment on the right, or
+    /// after it, for the first one on the left.
+    separator: bool,
+}
+
+impl Statusline {
+    /// Lays out the segments of each side on a `width` wide statusline, filling them in with
+    /// `value`. The left side is cut off when they don't fit.
+    fn layout(
+        &self,
+        width: usize,
+        value: impl Fn(&str) -> String,
+    ) -> anyhow::Result<(Vec<Placed<'_>>, Vec<Placed<'_>>)> {
+        let fill = |right: bool| -> anyhow::Result<Vec<(&str, String)>> {
+            let segments = if right { &self.right } else { &self.left };
+            let mut texts = vec![];
+            for segment in segments {
+                let mut blank = true;
+                let text = expand(segment, |name| {
+                    let value = value(name);
+                    blank &= value.is_empty();
+                    Ok(value)
+                })?;
+                // segments with fields that are all empty are left out, literal ones are kept
+                if !(blank && segment.contains('{')) {
+                    texts.push((segment.as_str(), text));
+                }
+            }
+            Ok(texts)
+        };
+        let left = fill(false)?;
+        let right = fill(true)?;
+
+        // the right side goes first so the left side is cut off when they don't fit
+        let right_width: usize = right
+            .iter()
+            .map(|(_, text)| text.chars().count())
+            .sum::<usize>()
+            + usize::from(!right.is_empty());
+        let mut x = width.saturating_sub(right_width);
+        let count = right.len();
+        let right = right
+            .into_iter()
+            .enumerate()
+            .map(|(i, (format, text))| {
+                let separator = i + 1 == count;
+                x += usize::from(separator);
+                let start = x;
+                x += text.chars().count();
+                Placed {
+                    format,
+                    text,
+                    start,
+                    separator,
+                }
+            })
+            .collect();
+
+        let available = width.saturating_sub(right_width);
+        x = 0;
+        let left = left
+            .into_iter()
+            .enumerate()
+            .map(|(i, (format, text))| {
+                let text: String = text.chars().take(available.saturating_sub(x)).collect();
+                let start = x;
+                x += text.chars().count();
+                let separator = i == 0 && x < available;
+                x += usize::from(separator);
+                Placed {
+                    format,
+                    text,
+                    start,
+                    separator,
+                }
+            })
+            .collect();
+
+        Ok((left, right))
+    }
+
+    /// The names of the fields the segments show, which are all that need filling in.
+    pub fn fields(&self) -> Vec<&'static str> {
+        let mut names = vec![];
+        for segment in self.left.iter().chain(&self.right) {
+            let _ = expand(segment, |name| {
+                names.extend(FIELDS.iter().find(|&&field| field == name));
+                Ok(String::new())
+            });
+        }
+        names
+    }
+
+    /// Draws the statusline on row `y`, filling in the segments with `value`, which gets a name
+    /// from [`FIELDS`] and returns an empty string when there is nothing to show.
+    pub fn draw(
+        &self,
+        screen: &mut dyn Renderer,
+        y: usize,
+        width: usize,
+        value: impl Fn(&str) -> String,
+    ) -> anyhow::Result<()> {
+        let (left, right) = self.layout(width, value)?;
+
+        screen.move_to(0, y);
+        screen.print_styled(" ".repeat(width).on(self.bg));
+
+        for segment in &right {
+            let text = segment.text.as_str();
+            if segment.separator {
+                screen.move_to(segment.start - 1, y);
+                screen.print_styled(RIGHT_SEPARATOR.with(self.accent_bg).on(self.bg));
+                screen.print_styled(text.bold().with(self.accent_fg).on(self.accent_bg));
+            } else {
+                screen.move_to(segment.start, y);
+                screen.print_styled(text.with(self.fg).on(self.bg));
+            }
+        }
+
+        for (i, segment) in left.iter().enumerate() {
+            let text = segment.text.as_str();
+            screen.move_to(segment.start, y);
+            if i == 0 {
+                screen.print_styled(text.bold().with(self.accent_fg).on(self.accent_bg));
+                if segment.separator {
+                    screen.print_styled(LEFT_SEPARATOR.with(self.accent_bg).on(self.bg));
+                }
+            } else {
+                screen.print_styled(text.with(self.fg).on(self.bg));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The names of the fields in the segment drawn at column `x`, to tell what a click on the
+    /// statusline hits. Empty between segments or on ones with only literal text.
+    pub fn fields_at(
+        &self,
+        width: usize,
+        x: usize,
+        value: impl Fn(&str) -> String,
+    ) -> anyhow::Result<Vec<String>> {
+        let (left, right) = self.layout(width, value)?;
+        let Some(segment) = left.iter().chain(&right).find(|segment| {
+            (segment.start..segment.start + segment.text.chars().count()).contains(&x)
+        }) else {
+            return Ok(vec![]);
+        };
+
+        let mut names = vec![];
+        expand(segment.format, |name| {
+            names.push(name.to_string());
+            Ok(String::new())
+        })?;
+        Ok(names)
+    }
+}
+
+/// Replaces each `{name}` in `format` with `value(name)`. `{{` and `}}` are literal braces.
+fn expand(
+    format: &str,
+    mut value: impl FnMut(&str) -> anyhow::Result<String>,
+) -> anyhow::Result<String> {
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                text.push_str(&value(&name)?);
+            }
+            c => text.push(c),
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tui::renderer::Grid;
+
+    fn row(screen: &dyn Renderer, y: usize, width: usize) -> String {
+        (0..width).map(|x| screen.cell(x, y).unwrap().ch).collect()
+    }
+
+    #[test]
+    fn test_statusline() {
+        let statusline = Statusline::default();
+        let mut screen = Grid::new(40, 1);
+        statusline
+            .draw(&mut screen, 0, 40, |name| match name {
+                "mode" => "NORMAL".to_string(),
+                "file" => "main.rs".to_string(),
+                "dirty" => "[+]".to_string(),
+                "line_ending" => "unix".to_string(),
+                "line" => "3".to_string(),
+                "column" => "14".to_string(),
+                "percent" => "9%".to_string(),
+                _ => String::new(),
+            })
+            .unwrap();
+        assert_eq!(
+            row(&screen, 0, 40),
+            " NORMAL \u{e0b0} main.rs [+]    unix \u{e0b2} 3:14 9% "
+        );
+        assert!(statusline.fields().contains(&"search"));
+        assert!(!statusline.fields().contains(&"lines"));
+    }
+
+    #[test]
+    fn test_fields_at() {
+        let statusline = Statusline::default();
+        let value = |name: &str| match name {
+            "mode" => "NORMAL".to_string(),
+            "file" => "main.rs".to_string(),
+            "line" => "3".to_string(),
+            "column" => "14".to_string(),
+            "percent" => "9%".to_string(),
+            _ => String::new(),
+        };
+        // " NORMAL \u{e0b0} main.rs               \u{e0b2} 3:14 9% "
+        let fields = |x| statusline.fields_at(40, x, value).unwrap();
+        assert_eq!(fields(1), ["mode"]);
+        assert_eq!(fields(10), ["file"]);
+        assert!(fields(8).is_empty());
+        assert!(fields(25).is_empty());
+        assert_eq!(fields(36), ["line", "column", "percent"]);
+    }
+
+    #[test]
+    fn test_config() {
+        let config = StatuslineConfig {
+            left: Some(vec!["{{{file}}}".to_string()]),
+            right: Some(vec![]),
+            background: Some("#101010".to_string()),
+            ..Default::default()
+        };
+        let statusline = Statusline::try_from(config).unwrap();
+        let mut screen = Grid::new(10, 1);
+        statusline
+            .draw(&mut screen, 0, 10, |_| "a.rs".to_string())
+            .unwrap();
+        assert_eq!(row(&screen, 0, 10), "{a.rs}\u{e0b0}   ");
+
+        let config = StatuslineConfig {
+            right: Some(vec!["{nope}".to_string()]),
+            ..Default::default()
+        };
+        assert!(Statusline::try_from(config).is_err());
+        let config = StatuslineConfig {
+            background: Some("red".to_string()),
+            ..Default::default()
+        };
+        assert!(Statusline::try_from(config).is_err());
+    }
+}
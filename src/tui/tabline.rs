@@ -0,0 +1,112 @@
+use std::{ops::Range, path::Path};
+
+use crossterm::style::Stylize;
+
+use crate::tui::{renderer::Renderer, theme::Theme, utils::brigthen};
+
+/// An open buffer as the tabline shows it.
+#[derive(Debug, Clone)]
+pub struct Tab {
+    pub name: String,
+    pub dirty: bool,
+}
+
+impl Tab {
+    /// The text of the tab, the file name without its directories and `[+]` when modified.
+    fn label(&self) -> String {
+        let name = Path::new(&self.name).file_name().map_or_else(
+            || self.name.clone(),
+            |name| name.to_string_lossy().to_string(),
+        );
+        let dirty = if self.dirty { " [+]" } else { "" };
+        format!(" {name}{dirty} ")
+    }
+}
+
+/// The columns each tab takes on a `width` wide tabline, by index. When they don't all fit,
+/// the first ones are left out until the `current` tab does.
+fn layout(tabs: &[Tab], current: usize, width: usize) -> Vec<(usize, Range<usize>)> {
+    let widths: Vec<_> = tabs.iter().map(|tab| tab.label().chars().count()).collect();
+    let mut first = 0;
+    while first < current && widths[first..=current].iter().sum::<usize>() > width {
+        first += 1;
+    }
+
+    let mut x = 0;
+    let mut columns = vec![];
+    for (i, &tab_width) in widths.iter().enumerate().skip(first) {
+        if x >= width {
+            break;
+        }
+        columns.push((i, x..(x + tab_width).min(width)));
+        x += tab_width;
+    }
+    columns
+}
+
+/// The tab at column `x` of a `width` wide tabline.
+pub fn tab_at(tabs: &[Tab], current: usize, width: usize, x: usize) -> Option<usize> {
+    layout(tabs, current, width)
+        .into_iter()
+        .find(|(_, columns)| columns.contains(&x))
+        .map(|(i, _)| i)
+}
+
+/// Draws the tabline on the first row, the `current` tab standing out from the others.
+pub fn draw(
+    screen: &mut dyn Renderer,
+    theme: &Theme,
+    tabs: &[Tab],
+    current: usize,
+    width: usize,
+) -> anyhow::Result<()> {
+    let (fg, bg) = theme.default_colors();
+    let line_bg = brigthen(bg, 0.05)?;
+    let current_bg = brigthen(bg, 0.2)?;
+
+    screen.move_to(0, 0);
+    screen.print_styled(" ".repeat(width).on(line_bg));
+    for (i, columns) in layout(tabs, current, width) {
+        let label: String = tabs[i].label().chars().take(columns.len()).collect();
+        screen.move_to(columns.start, 0);
+        if i == current {
+            screen.print_styled(label.bold().with(fg).on(current_bg));
+        } else {
+            screen.print_styled(label.with(fg).on(line_bg));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(name: &str, dirty: bool) -> Tab {
+        Tab {
+            name: name.to_string(),
+            dirty,
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let tabs = [
+            tab("src/main.rs", false),
+            tab("README.md", true),
+            tab("No Name", false),
+        ];
+        assert_eq!(tabs[1].label(), " README.md [+] ");
+        assert_eq!(
+            layout(&tabs, 0, 80),
+            vec![(0, 0..9), (1, 9..24), (2, 24..33)]
+        );
+        assert_eq!(tab_at(&tabs, 0, 80, 10), Some(1));
+        assert_eq!(tab_at(&tabs, 0, 80, 40), None);
+
+        // the first tabs give way so the current one fits
+        assert_eq!(layout(&tabs, 2, 25), vec![(1, 0..15), (2, 15..24)]);
+        assert_eq!(layout(&tabs, 0, 12), vec![(0, 0..9), (1, 9..12)]);
+    }
+}
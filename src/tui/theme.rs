@@ -0,0 +1,1077 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use crossterm::style::{self, Attribute, Attributes};
+use once_cell::sync::OnceCell;
+use palette::{FromColor, Hsl, Srgb};
+use plist::Dictionary;
+use serde_jsonrc::{Map, Value};
+
+use crate::{
+    core::signs::SignKind,
+    error::ThemeParseError,
+    tui::{highlight::TS_TO_THEME, utils::hex_to_crossterm_color},
+};
+
+static DEFAULT_THEME: OnceCell<Theme> = OnceCell::new();
+
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub author: Option<String>,
+    pub background: String,
+    pub foreground: String,
+    pub caret: Option<String>,
+    pub invisibles: Option<String>,
+    pub settings: Vec<ThemeSetting>,
+    pub gutter_foreground: Option<String>,
+    pub gutter_background: Option<String>,
+    pub gutter_foreground_highlight: Option<String>,
+    pub gutter_background_highlight: Option<String>,
+    pub line_highlight: Option<String>,
+    pub selection: Option<String>,
+    /// The file the theme was loaded from, `None` for the built-in theme.
+    pub path: Option<PathBuf>,
+    /// Background of the bracket under the cursor and its match.
+    pub bracket_match: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThemeSetting {
+    pub scopes: Vec<String>,
+    pub settings: SettingAttributes,
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Default)]
+pub struct SettingAttributes {
+    pub background: Option<String>,
+    pub foreground: Option<String>,
+    pub font_style: Option<FontStyle>,
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontStyle {
+    Normal,
+    Bold,
+    Italic,
+    BoldItalic,
+    Underline,
+}
+
+/// The directory themes are looked up in by name, `~/.config/fed/themes`.
+pub fn themes_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("fed").join("themes"))
+}
+
+impl FontStyle {
+    /// The style as themes spell it, like `bold italic`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FontStyle::Normal => "normal",
+            FontStyle::Bold => "bold",
+            FontStyle::Italic => "italic",
+            FontStyle::BoldItalic => "bold italic",
+            FontStyle::Underline => "underline",
+        }
+    }
+
+    /// The terminal attributes to print text in this style with.
+    pub fn attributes(&self) -> Attributes {
+        match self {
+            FontStyle::Normal => Attributes::default(),
+            FontStyle::Bold => Attribute::Bold.into(),
+            FontStyle::Italic => Attribute::Italic.into(),
+            FontStyle::BoldItalic => Attributes::from(Attribute::Bold) | Attribute::Italic,
+            FontStyle::Underline => Attribute::Underlined.into(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        DEFAULT_THEME
+            .get_or_init(|| {
+                Theme::parse_tm(include_str!("../fixtures/Catppuccin-frappe.tmTheme")).unwrap()
+            })
+            .clone()
+    }
+}
+
+impl Theme {
+    pub fn default_colors(&self) -> (style::Color, style::Color) {
+        (
+            hex_to_crossterm_color(&self.foreground).unwrap(),
+            hex_to_crossterm_color(&self.background).unwrap(),
+        )
+    }
+
+    /// The color signs of `kind` are drawn in.
+    pub fn sign_color(&self, kind: SignKind) -> style::Color {
+        match kind {
+            SignKind::Error | SignKind::Removed => style::Color::Red,
+            SignKind::Warning | SignKind::Changed => style::Color::Yellow,
+            SignKind::Information => style::Color::Blue,
+            SignKind::Added => style::Color::Green,
+        }
+    }
+
+    /// The most specific rule matching `scope`, see [`Theme::scope_settings`].
+    pub fn get_scope(&self, scope: &str) -> Option<&ThemeSetting> {
+        self.matching(scope).last().map(|&(_, setting)| setting)
+    }
+
+    /// The settings for `scope`, merged from the rules matching it like TextMate does, with the
+    /// selector of the most specific one. A selector matches the scopes it's a prefix of by
+    /// whole parts, so `keyword` matches `keyword.operator` but not `keywords`. Rules with longer
+    /// selectors override the settings of shorter ones, and later rules those of earlier ones.
+    pub fn scope_settings(&self, scope: &str) -> Option<(&str, SettingAttributes)> {
+        let matching = self.matching(scope);
+        let &(selector, _) = matching.last()?;
+        let mut merged = SettingAttributes::default();
+        for (_, setting) in matching {
+            let settings = &setting.settings;
+            if settings.foreground.is_some() {
+                merged.foreground.clone_from(&settings.foreground);
+            }
+            if settings.background.is_some() {
+                merged.background.clone_from(&settings.background);
+            }
+            if settings.font_style.is_some() {
+                merged.font_style.clone_from(&settings.font_style);
+            }
+        }
+        Some((selector, merged))
+    }
+
+    /// The rules matching `scope` with the selector that matched, from the least specific to the
+    /// most.
+    fn matching(&self, scope: &str) -> Vec<(&str, &ThemeSetting)> {
+        let mut matching: Vec<_> = self
+            .settings
+            .iter()
+            .filter_map(|setting| {
+                let selector = setting
+                    .scopes
+                    .iter()
+                    .filter(|selector| selects(selector, scope))
+                    .max_by_key(|selector| specificity(selector))?;
+                Some((selector.as_str(), setting))
+            })
+            .collect();
+        // the sort is stable, so later rules stay after earlier ones as specific
+        matching.sort_by_key(|&(selector, _)| specificity(selector));
+        matching
+    }
+
+    #[allow(unused)]
+    pub fn scope_color(&self, scope: &str) -> (style::Color, style::Color) {
+        let Some(setting) = self.get_scope(scope) else {
+            return self.default_colors();
+        };
+
+        let background = setting
+            .settings
+            .background
+            .as_ref()
+            .map(|s| hex_to_crossterm_color(s).unwrap())
+            .unwrap_or_else(|| hex_to_crossterm_color(&self.background).unwrap());
+        let foreground = setting
+            .settings
+            .foreground
+            .as_ref()
+            .map(|s| hex_to_crossterm_color(s).unwrap())
+            .unwrap_or_else(|| hex_to_crossterm_color(&self.foreground).unwrap());
+
+        (background, foreground)
+    }
+
+    /// Loads a theme by name: `default` for the built-in theme, a path to a `.tmTheme`, VSCode
+    /// `.json`, Sublime `.sublime-color-scheme` or Helix `.toml` theme, or the file name of one in
+    /// the themes directory, with or without the extension.
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        if name == "default" {
+            return Ok(Theme::default());
+        }
+
+        let path = Path::new(name);
+        if path.is_file() {
+            return Self::load_file(path);
+        }
+
+        if let Some(dir) = themes_dir() {
+            for candidate in [
+                dir.join(name),
+                dir.join(format!("{name}.tmTheme")),
+                dir.join(format!("{name}.json")),
+                dir.join(format!("{name}.sublime-color-scheme")),
+                dir.join(format!("{name}.toml")),
+            ] {
+                if candidate.is_file() {
+                    return Self::load_file(&candidate);
+                }
+            }
+        }
+
+        anyhow::bail!("E185: Cannot find color scheme '{name}'")
+    }
+
+    /// Loads a theme file, telling the format apart by its extension.
+    pub fn load_file(path: &Path) -> anyhow::Result<Self> {
+        let mut theme = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("tmTheme") => Self::load_tm(path)?,
+            Some("sublime-color-scheme") => Self::load_sublime(path)?,
+            Some("toml") => Self::load_helix(path)?,
+            _ => Self::load_vscode(path)?,
+        };
+        theme.path = Some(path.to_path_buf());
+        Ok(theme)
+    }
+
+    pub fn load_vscode<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(&path)?;
+        Self::parse_vscode(&contents)
+    }
+
+    pub fn parse_vscode(contents: &str) -> anyhow::Result<Self> {
+        let mappings = [
+            ("attribute", "entity.other.attribute-name"),
+            ("boolean", "constant.language"),
+            //("carriage-return", "None"), // No direct mapping
+            ("comment", "comment"),
+            ("comment.documentation", "comment.block.documentation"),
+            ("constant", "variable.other.constant"),
+            ("constant.builtin", "*.defaultLibrary"),
+            ("constructor", "variable.function.constructor"),
+            //("constructor.builtin", "None"), // No direct mapping
+            ("embedded", "punctuation.section.embedded"),
+            ("error", "invalid"),
+            ("escape", "constant.character.escape"),
+            ("function", "entity.name.function"),
+            ("function.builtin", "support.function"),
+            ("keyword", "keyword"),
+            ("markup", "markup"),
+            ("markup.bold", "markup.bold"),
+            ("markup.heading", "heading.1.markdown"),
+            ("markup.italic", "markup.italic"),
+            ("markup.link", "markup.underline.link"),
+            //("markup.link.url", "None"), // No direct mapping
+            //("markup.list", "None"), // No direct mapping
+            //("markup.list.checked", "None"), // No direct mapping
+            //("markup.list.numbered", "None"), // No direct mapping
+            //("markup.list.unchecked", "None"), // No direct mapping
+            //("markup.list.unnumbered", "None"), // No direct mapping
+            ("markup.quote", "markup.quote"),
+            ("markup.raw", "markup.inline.raw"), // Closest match
+            //("markup.raw.block", "None"), // No direct mapping
+            //("markup.raw.inline", "None"), // No direct mapping
+            //("markup.strikethrough", "None"), // No direct mapping
+            ("module", "entity.name.tag"), // Interpretive mapping
+            ("number", "constant.numeric"),
+            ("operator", "keyword.operator"),
+            ("property", "variable.other.property"),
+            ("property.builtin", "property.defaultLibrary"),
+            ("punctuation", "punctuation"),
+            //("punctuation.bracket", "None"), // No direct mapping
+            //("punctuation.delimiter", "None"), // No direct mapping
+            //("punctuation.special", "None"), // No direct mapping
+            ("string", "string"),
+            ("string.escape", "constant.character.escape"),
+            ("string.regexp", "string.regexp"),
+            //("string.special", "None"), // No direct mapping
+            ("string.special.symbol", "constant.other.symbol"),
+            ("tag", "entity.name.tag"),
+            ("type", "support.type"),
+            ("type.builtin", "support.type.sys-types"),
+            ("variable", "variable"),
+            ("variable.builtin", "variable.defaultLibrary"),
+            ("variable.member", "variable.other.member"),
+            ("variable.parameter", "variable.parameter"),
+        ];
+
+        let theme = serde_jsonrc::from_str::<serde_jsonrc::Value>(contents)?;
+        let Some(theme) = theme.as_object() else {
+            // TODO: use a invalid field error instead
+            return Err(ThemeParseError::MissingField("theme".to_string()).into());
+        };
+
+        let mut scopes = HashMap::new();
+
+        // parses colors
+        if let Some(colors) = theme.get("colors").and_then(|v| v.as_object()) {
+            for (key, value) in colors.iter() {
+                let Some(value) = value.as_object() else {
+                    continue;
+                };
+                scopes.insert(key.clone(), value);
+            }
+        }
+
+        // parses semanticTokenColors
+        if let Some(semantic_token_colors) =
+            theme.get("semanticTokenColors").and_then(|v| v.as_object())
+        {
+            for (key, value) in semantic_token_colors.iter() {
+                let Some(value) = value.as_object() else {
+                    continue;
+                };
+                scopes.insert(key.clone(), value);
+            }
+        }
+
+        let Some(token_colors) = theme.get("tokenColors").and_then(|v| v.as_array()) else {
+            return Err(ThemeParseError::MissingField("tokenColors".to_string()).into());
+        };
+        token_colors
+            .iter()
+            .filter_map(|color| {
+                color.as_object().and_then(|info| {
+                    info["settings"].as_object().and_then(|settings| {
+                        info.get("scope").and_then(|v| {
+                            v.as_array().map(|scope| {
+                                scope
+                                    .iter()
+                                    .filter_map(|s| {
+                                        s.as_str().map(|s| {
+                                            s.split(',')
+                                                .map(|s| s.trim().to_string())
+                                                .collect::<Vec<_>>()
+                                        })
+                                    })
+                                    .flatten()
+                                    .for_each(|scope| {
+                                        scopes.insert(scope, settings);
+                                    })
+                            })
+                        })
+                    })
+                })
+            })
+            .for_each(drop);
+
+        let mut settings = Vec::new();
+        for (from, to) in mappings.iter() {
+            let from = from.to_string();
+            let to = to.to_string();
+
+            if let Some(from) = scopes.get(&from) {
+                let background = from
+                    .get("background")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                let foreground = from
+                    .get("foreground")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                let font_style =
+                    from.get("fontStyle")
+                        .and_then(|v| v.as_str())
+                        .and_then(|s| match s {
+                            "bold" => Some(FontStyle::Bold),
+                            "italic" => Some(FontStyle::Italic),
+                            "bold italic" => Some(FontStyle::BoldItalic),
+                            "underline" => Some(FontStyle::Underline),
+                            _ => None,
+                        });
+                settings.push(ThemeSetting {
+                    scopes: vec![to.clone()],
+                    settings: SettingAttributes {
+                        background,
+                        foreground,
+                        font_style,
+                    },
+                });
+                scopes.insert(to, from);
+            }
+        }
+
+        let name = theme["name"].as_str().unwrap_or("theme").to_string();
+        let author = theme
+            .get("author")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        let background = theme["colors"]["editor.background"]
+            .as_str()
+            .unwrap_or("#000000")
+            .to_string();
+        let foreground = theme["colors"]["editor.foreground"]
+            .as_str()
+            .unwrap_or("#ffffff")
+            .to_string();
+        let invisibles = theme["colors"]["editorInvisibles.foreground"]
+            .as_str()
+            .map(|s| s.to_string());
+        let line_highlight = theme["colors"]["editorLineNumber.activeForeground"]
+            .as_str()
+            .map(|s| s.to_string());
+        // let selection = theme["colors"]["editorLineNumber.foreground"]
+        //     .as_str()
+        //     .map(|s| s.to_string());
+        let gutter_foreground = theme["colors"]["editorGutter.foreground"]
+            .as_str()
+            .map(|s| s.to_string());
+        let gutter_background = theme["colors"]["editorGutter.background"]
+            .as_str()
+            .map(|s| s.to_string());
+        let bracket_match = theme["colors"]["editorBracketMatch.background"]
+            .as_str()
+            .map(|s| s.to_string());
+        Ok(Self {
+            name,
+            author,
+            background,
+            foreground,
+            invisibles,
+            line_highlight,
+            gutter_foreground,
+            gutter_background,
+            bracket_match,
+            settings,
+            ..Default::default()
+        })
+    }
+
+    pub fn load_sublime<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(&path)?;
+        Self::parse_sublime(&contents)
+    }
+
+    /// Parses a Sublime Text color scheme, a JSON file of `globals` for the editor and `rules`
+    /// for scopes, whose colors can refer to its `variables`.
+    pub fn parse_sublime(contents: &str) -> anyhow::Result<Self> {
+        let scheme = serde_jsonrc::from_str::<Value>(contents)?;
+        let Some(scheme) = scheme.as_object() else {
+            return Err(ThemeParseError::MissingField("globals".to_string()).into());
+        };
+        let variables = scheme.get("variables").and_then(|v| v.as_object());
+        let color = |value: Option<&Value>| {
+            value
+                .and_then(|v| v.as_str())
+                .and_then(|v| css_color(v, variables, 0))
+        };
+
+        let Some(globals) = scheme.get("globals").and_then(|v| v.as_object()) else {
+            return Err(ThemeParseError::MissingField("globals".to_string()).into());
+        };
+        let global = |key: &str| color(globals.get(key));
+        let Some(background) = global("background") else {
+            return Err(ThemeParseError::MissingField("background".to_string()).into());
+        };
+        let Some(foreground) = global("foreground") else {
+            return Err(ThemeParseError::MissingField("foreground".to_string()).into());
+        };
+
+        let settings = scheme
+            .get("rules")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|rule| {
+                let rule = rule.as_object()?;
+                let scope = rule.get("scope")?.as_str()?;
+                Some(ThemeSetting {
+                    scopes: scope.split(',').map(|s| s.trim().to_string()).collect(),
+                    settings: SettingAttributes {
+                        background: color(rule.get("background")),
+                        foreground: color(rule.get("foreground")),
+                        font_style: rule
+                            .get("font_style")
+                            .and_then(|v| v.as_str())
+                            .and_then(font_style),
+                    },
+                })
+            })
+            .collect();
+
+        Ok(Theme {
+            name: scheme
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("theme")
+                .to_string(),
+            author: scheme
+                .get("author")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            background,
+            foreground,
+            caret: global("caret"),
+            invisibles: global("invisibles"),
+            line_highlight: global("line_highlight"),
+            selection: global("selection"),
+            gutter_foreground: global("gutter_foreground"),
+            gutter_background: global("gutter"),
+            gutter_foreground_highlight: global("gutter_foreground_highlight"),
+            gutter_background_highlight: None,
+            path: None,
+            bracket_match: None,
+            settings,
+        })
+    }
+
+    /// Loads a Helix theme, along with the themes it `inherits` from, which are looked up next
+    /// to it.
+    pub fn load_helix<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut theme = Self::helix(&read_helix(path, 0)?);
+        if let Some(name) = path.file_stem() {
+            theme.name = name.to_string_lossy().to_string();
+        }
+        Ok(theme)
+    }
+
+    /// A Helix theme, which styles tree-sitter captures like `function.builtin` and the `ui.*`
+    /// parts of the editor with colors from its `[palette]`.
+    fn helix(table: &toml::Table) -> Self {
+        let palette = table.get("palette").and_then(|v| v.as_table());
+        // names left out of the palette are the terminal's colors, like `red` or `light-blue`
+        let color = |name: &str| match name.strip_prefix('#') {
+            Some(hex) => hex_color(hex),
+            None => match palette.and_then(|p| p.get(name)).and_then(|v| v.as_str()) {
+                Some(value) => value
+                    .strip_prefix('#')
+                    .map_or_else(|| ansi_color(value), hex_color),
+                None => ansi_color(name),
+            },
+        };
+        // a style is the color of the text, or a table with `fg`, `bg` and `modifiers`
+        let style = |key: &str| -> Option<SettingAttributes> {
+            match table.get(key)? {
+                toml::Value::String(fg) => Some(SettingAttributes {
+                    foreground: color(fg),
+                    ..Default::default()
+                }),
+                toml::Value::Table(style) => {
+                    let get = |key| style.get(key).and_then(|v| v.as_str()).and_then(color);
+                    let mut modifiers: Vec<_> = style
+                        .get("modifiers")
+                        .and_then(|v| v.as_array())
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|v| v.as_str())
+                        .collect();
+                    if style.contains_key("underline") {
+                        modifiers.push("underline");
+                    }
+                    Some(SettingAttributes {
+                        foreground: get("fg"),
+                        background: get("bg"),
+                        font_style: font_style(&modifiers.join(" ")),
+                    })
+                }
+                _ => None,
+            }
+        };
+        // like in Helix, `function.builtin` falls back to `function` when the theme leaves it out
+        let lookup = |key: &str| {
+            let mut key = key;
+            loop {
+                if let Some(style) = style(key) {
+                    return Some(style);
+                }
+                key = &key[..key.rfind('.')?];
+            }
+        };
+        let ui = |key: &str| lookup(key).unwrap_or_default();
+
+        let mut captures: Vec<_> = TS_TO_THEME.iter().collect();
+        captures.sort();
+        let mut seen = HashSet::new();
+        let settings = captures
+            .into_iter()
+            .filter(|&(_, scope)| scope != "No direct equivalent" && seen.insert(scope))
+            .filter_map(|(capture, scope)| {
+                Some(ThemeSetting {
+                    scopes: vec![scope.clone()],
+                    settings: lookup(capture)?,
+                })
+            })
+            .collect();
+
+        Theme {
+            name: "theme".to_string(),
+            author: None,
+            background: ui("ui.background")
+                .background
+                .unwrap_or_else(|| "#000000".to_string()),
+            foreground: ui("ui.text")
+                .foreground
+                .unwrap_or_else(|| "#ffffff".to_string()),
+            caret: ui("ui.cursor.primary").background,
+            invisibles: ui("ui.virtual.whitespace").foreground,
+            line_highlight: ui("ui.cursorline.primary").background,
+            selection: ui("ui.selection.primary").background,
+            gutter_foreground: ui("ui.linenr").foreground,
+            gutter_background: ui("ui.gutter").background,
+            gutter_foreground_highlight: ui("ui.linenr.selected").foreground,
+            gutter_background_highlight: ui("ui.gutter.selected").background,
+            path: None,
+            bracket_match: ui("ui.cursor.match").background,
+            settings,
+        }
+    }
+
+    pub fn load_tm<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(&path)?;
+        Self::parse_tm(&contents)
+    }
+
+    pub fn parse_tm(contents: &str) -> anyhow::Result<Self> {
+        let reader = Cursor::new(contents.as_bytes());
+        let data = plist::Value::from_reader(reader)?;
+        let data = data.as_dictionary().unwrap();
+
+        let name = data
+            .get("name")
+            // TODO use a invalid field error instead
+            .unwrap()
+            .as_string()
+            .unwrap()
+            .to_string();
+        let author = data
+            .get("author")
+            .and_then(|v| v.as_string())
+            .map(|s| s.to_string());
+
+        let Some(settings) = data.get("settings").and_then(|s| s.as_array()) else {
+            return Err(ThemeParseError::MissingDictionaryField(
+                data.clone(),
+                "settings".to_string(),
+            )
+            .into());
+        };
+
+        let (main, settings): (Vec<_>, Vec<_>) = settings
+            .iter()
+            .partition(|s| s.as_dictionary().and_then(|d| d.get("name")).is_none());
+
+        let Some(main) = main
+            .first()
+            .and_then(|s| s.as_dictionary())
+            .and_then(|s| s.get("settings"))
+            .and_then(|s| s.as_dictionary())
+        else {
+            return Err(ThemeParseError::MissingField("main".to_string()).into());
+        };
+
+        fn get_mandatory_setting(d: &Dictionary, key: &str) -> anyhow::Result<String> {
+            d.get(key)
+                .and_then(|v| v.as_string())
+                .map(|s| s.to_string())
+                .ok_or(ThemeParseError::MissingDictionaryField(d.clone(), key.to_string()).into())
+        }
+
+        fn get_setting(d: &Dictionary, key: &str) -> Option<String> {
+            d.get(key)
+                .and_then(|v| v.as_string())
+                .map(|s| s.to_string())
+        }
+
+        let background = get_mandatory_setting(main, "background")?;
+        let foreground = get_mandatory_setting(main, "foreground")?;
+
+        let caret = get_setting(main, "caret");
+        let invisibles = get_setting(main, "invisibles");
+
+        // gutter settings
+        let gutter_foreground = get_setting(main, "gutterForeground");
+        let gutter_background = get_setting(main, "gutterBackground");
+        let gutter_foregound_highlight = get_setting(main, "gutterForegroundHighlight");
+        let gutter_background_highlight = get_setting(main, "gutterBackgroundHighlight");
+        let line_highlight = get_setting(main, "lineHighlight");
+        let selection = get_setting(main, "selection");
+        let bracket_match = get_setting(main, "bracketsBackground");
+
+        // TODO: add other optional settings
+
+        let settings = settings
+            .iter()
+            .filter_map(|s| {
+                let s = s.as_dictionary().unwrap();
+                let scope = get_setting(s, "scope")?;
+
+                let settings = s.get("settings").and_then(|v| v.as_dictionary()).unwrap();
+                let background = settings
+                    .get("background")
+                    .and_then(|v| v.as_string())
+                    .map(|s| s.to_string());
+                let foreground = settings
+                    .get("foreground")
+                    .and_then(|v| v.as_string())
+                    .map(|s| s.to_string());
+                let font_style = settings
+                    .get("fontStyle")
+                    .and_then(|v| v.as_string())
+                    .and_then(|s| match s {
+                        "bold" => Some(FontStyle::Bold),
+                        "italic" => Some(FontStyle::Italic),
+                        "bold italic" => Some(FontStyle::BoldItalic),
+                        "underline" => Some(FontStyle::Underline),
+                        _ => None,
+                    });
+
+                let scopes = scope.split(",").map(|s| s.trim().to_string()).collect();
+
+                Some(ThemeSetting {
+                    scopes,
+                    settings: SettingAttributes {
+                        background,
+                        foreground,
+                        font_style,
+                    },
+                })
+            })
+            .collect();
+
+        Ok(Theme {
+            name,
+            author,
+            background,
+            foreground,
+            caret,
+            invisibles,
+            line_highlight,
+            selection,
+            gutter_foreground,
+            gutter_background,
+            gutter_foreground_highlight: gutter_foregound_highlight,
+            gutter_background_highlight,
+            path: None,
+            bracket_match,
+            settings,
+        })
+    }
+}
+
+/// Whether the theme selector `selector` matches `scope`, being it or the start of it up to a dot.
+/// Selectors of a scope within another, like `source.rust string`, or leaving scopes out, like
+/// `comment - comment.line`, never match since highlights only have the one scope.
+fn selects(selector: &str, scope: &str) -> bool {
+    if selector.is_empty() || selector.contains(' ') {
+        return false;
+    }
+    scope
+        .strip_prefix(selector)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// How specific a selector is, by the number of its parts.
+fn specificity(selector: &str) -> usize {
+    selector.split('.').count()
+}
+
+/// The font style of words like `bold italic`, as Sublime and Helix themes give it.
+fn font_style(words: &str) -> Option<FontStyle> {
+    let words: Vec<_> = words.split_whitespace().collect();
+    let bold = words.contains(&"bold");
+    let italic = words.contains(&"italic");
+    match (bold, italic) {
+        (true, true) => Some(FontStyle::BoldItalic),
+        (true, false) => Some(FontStyle::Bold),
+        (false, true) => Some(FontStyle::Italic),
+        _ if words.iter().any(|&w| w == "underline" || w == "underlined") => {
+            Some(FontStyle::Underline)
+        }
+        _ => None,
+    }
+}
+
+/// A color as `#rrggbb` from the hex digits of one, with or without alpha, which is left out.
+fn hex_color(hex: &str) -> Option<String> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex: String = match hex.len() {
+        3 | 4 => hex.chars().take(3).flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex[..6].to_string(),
+        _ => return None,
+    };
+    Some(format!("#{}", hex.to_lowercase()))
+}
+
+/// The color Helix themes mean by the name of one of the 16 terminal colors, as xterm shows it.
+fn ansi_color(name: &str) -> Option<String> {
+    let hex = match name {
+        "black" => "000000",
+        "red" => "cd0000",
+        "green" => "00cd00",
+        "yellow" => "cdcd00",
+        "blue" => "0000ee",
+        "magenta" => "cd00cd",
+        "cyan" => "00cdcd",
+        "gray" => "7f7f7f",
+        "light-red" => "ff0000",
+        "light-green" => "00ff00",
+        "light-yellow" => "ffff00",
+        "light-blue" => "5c5cff",
+        "light-magenta" => "ff00ff",
+        "light-cyan" => "00ffff",
+        "light-gray" => "e5e5e5",
+        "white" => "ffffff",
+        _ => return None,
+    };
+    Some(format!("#{hex}"))
+}
+
+/// A color of a Sublime color scheme as `#rrggbb`: hex, `rgb()`, `hsl()`, `var()` of one of the
+/// scheme's `variables`, or `color()` of any of those, whose adjusters are left out.
+fn css_color(value: &str, variables: Option<&Map<String, Value>>, depth: usize) -> Option<String> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        return hex_color(hex);
+    }
+    // variables may refer to each other, but not forever
+    if depth > 8 {
+        return None;
+    }
+    let (function, args) = value.strip_suffix(')')?.split_once('(')?;
+    let numbers = || -> Vec<f32> {
+        args.split([',', ' ', '/'])
+            .filter(|arg| !arg.is_empty())
+            .filter_map(|arg| arg.trim_end_matches(['%', 'g', 'd', 'e']).parse().ok())
+            .collect()
+    };
+    match function.trim() {
+        "var" => css_color(variables?.get(args.trim())?.as_str()?, variables, depth + 1),
+        "color" => {
+            // the color goes up to the first space outside of parentheses
+            let mut nesting = 0;
+            let end = args.trim().find(|c| {
+                match c {
+                    '(' => nesting += 1,
+                    ')' => nesting -= 1,
+                    _ => {}
+                }
+                c == ' ' && nesting == 0
+            });
+            let args = args.trim();
+            css_color(&args[..end.unwrap_or(args.len())], variables, depth + 1)
+        }
+        "rgb" | "rgba" => match numbers()[..] {
+            [r, g, b, ..] => Some(format!("#{:02x}{:02x}{:02x}", r as u8, g as u8, b as u8)),
+            _ => None,
+        },
+        "hsl" | "hsla" => match numbers()[..] {
+            [h, s, l, ..] => {
+                let (r, g, b) = Srgb::from_color(Hsl::new(h, s / 100.0, l / 100.0))
+                    .into_format::<u8>()
+                    .into_components();
+                Some(format!("#{r:02x}{g:02x}{b:02x}"))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The table of a Helix theme at `path`, merged over the tables of the themes it inherits from.
+fn read_helix(path: &Path, depth: usize) -> anyhow::Result<toml::Table> {
+    let mut table: toml::Table = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let Some(parent) = table.remove("inherits") else {
+        return Ok(table);
+    };
+    let parent = parent.as_str().unwrap_or_default();
+    anyhow::ensure!(
+        depth < 8,
+        "Theme {parent} can't be loaded, its inheritance chain is too deep"
+    );
+
+    let parent_path = path.with_file_name(format!("{parent}.toml"));
+    let mut merged = read_helix(&parent_path, depth + 1)
+        .with_context(|| format!("Cannot load {parent}, which {} inherits", path.display()))?;
+    // the palette is merged color by color, styles replace the ones inherited
+    let palette = table.remove("palette");
+    merged.extend(table);
+    if let Some(toml::Value::Table(palette)) = palette {
+        match merged.get_mut("palette") {
+            Some(toml::Value::Table(inherited)) => inherited.extend(palette),
+            _ => {
+                merged.insert("palette".to_string(), toml::Value::Table(palette));
+            }
+        }
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let theme = Theme::load_vscode("src/fixtures/tokyo-night-color-theme.json").unwrap();
+        println!("{:#?}", theme);
+    }
+
+    #[test]
+    fn test_scope_settings() {
+        let rule = |scopes: &str, foreground: Option<&str>, font_style| ThemeSetting {
+            scopes: scopes.split(',').map(|s| s.trim().to_string()).collect(),
+            settings: SettingAttributes {
+                foreground: foreground.map(str::to_string),
+                background: None,
+                font_style,
+            },
+        };
+        let theme = Theme {
+            settings: vec![
+                rule("keyword.operator", Some("#000002"), None),
+                rule("keyword, storage", Some("#000001"), None),
+                rule("comment", Some("#000003"), None),
+                rule("comment.block.documentation", None, Some(FontStyle::Italic)),
+                rule(
+                    "source.rust string, string - string.quoted",
+                    Some("#000004"),
+                    None,
+                ),
+                rule("keyword", Some("#000005"), None),
+            ],
+            ..Theme::default()
+        };
+
+        let foreground = |scope| {
+            let (_, settings) = theme.scope_settings(scope)?;
+            settings.foreground
+        };
+        assert_eq!(
+            foreground("keyword.operator.arithmetic").unwrap(),
+            "#000002"
+        );
+        // the later of two rules as specific wins
+        assert_eq!(foreground("keyword.control").unwrap(), "#000005");
+        assert_eq!(foreground("storage.type").unwrap(), "#000001");
+        assert_eq!(foreground("keywords"), None);
+        assert_eq!(foreground("string"), None);
+
+        let (selector, settings) = theme.scope_settings("comment.block.documentation").unwrap();
+        assert_eq!(selector, "comment.block.documentation");
+        assert_eq!(settings.foreground.unwrap(), "#000003");
+        assert_eq!(settings.font_style, Some(FontStyle::Italic));
+        assert_eq!(
+            theme.get_scope("comment.line").unwrap().scopes,
+            vec!["comment"]
+        );
+    }
+
+    #[test]
+    fn test_parse_sublime() {
+        let theme = Theme::parse_sublime(
+            r##"{
+                // comments and trailing commas are fine
+                "name": "Mariana",
+                "variables": {
+                    "blue": "hsl(210, 50%, 60%)",
+                    "black": "#000",
+                    "text": "var(white)",
+                    "white": "rgb(255, 255, 255)",
+                },
+                "globals": {
+                    "background": "var(black)",
+                    "foreground": "var(text)",
+                    "selection": "color(var(blue) alpha(0.5))",
+                },
+                "rules": [
+                    {
+                        "scope": "keyword, storage.type",
+                        "foreground": "var(blue)",
+                        "font_style": "italic bold",
+                    },
+                    { "scope": "comment", "foreground": "#80808080" },
+                ],
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(theme.name, "Mariana");
+        assert_eq!(theme.background, "#000000");
+        assert_eq!(theme.foreground, "#ffffff");
+        assert_eq!(theme.selection.as_deref(), Some("#6699cc"));
+        let keyword = &theme.get_scope("storage.type").unwrap().settings;
+        assert_eq!(keyword.foreground.as_deref(), Some("#6699cc"));
+        assert_eq!(keyword.font_style, Some(FontStyle::BoldItalic));
+        let comment = &theme.get_scope("comment").unwrap().settings;
+        assert_eq!(comment.foreground.as_deref(), Some("#808080"));
+        assert!(Theme::parse_sublime(r#"{ "globals": {} }"#).is_err());
+    }
+
+    #[test]
+    fn test_load_helix() {
+        let dir = std::env::temp_dir().join(format!("fed-helix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("base.toml"),
+            r##"
+            "ui.background" = { bg = "base" }
+            "ui.text" = "text"
+            "ui.linenr" = { fg = "#888" }
+            "keyword" = { fg = "red", modifiers = ["bold"] }
+            "function" = "blue"
+            "type" = "light-blue"
+
+            [palette]
+            base = "#1e1e2e"
+            text = "#cdd6f4"
+            red = "#f38ba8"
+            blue = "#89b4fa"
+            "##,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("light.toml"),
+            r##"
+            inherits = "base"
+            "comment" = { fg = "text", modifiers = ["italic"] }
+            "ui.linenr.selected" = "red"
+
+            [palette]
+            base = "#eff1f5"
+            "##,
+        )
+        .unwrap();
+
+        let theme = Theme::load(dir.join("light.toml").to_str().unwrap());
+        let missing = Theme::load_helix(dir.join("nope.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let theme = theme.unwrap();
+
+        assert_eq!(theme.name, "light");
+        assert_eq!(theme.background, "#eff1f5");
+        assert_eq!(theme.foreground, "#cdd6f4");
+        assert_eq!(theme.gutter_foreground.as_deref(), Some("#888888"));
+        assert_eq!(
+            theme.gutter_foreground_highlight.as_deref(),
+            Some("#f38ba8")
+        );
+        let keyword = &theme.get_scope("keyword").unwrap().settings;
+        assert_eq!(keyword.foreground.as_deref(), Some("#f38ba8"));
+        assert_eq!(keyword.font_style, Some(FontStyle::Bold));
+        let comment = &theme.get_scope("comment").unwrap().settings;
+        assert_eq!(comment.font_style, Some(FontStyle::Italic));
+        // `function.builtin` falls back to `function`
+        let builtin = &theme.get_scope("support.function").unwrap().settings;
+        assert_eq!(builtin.foreground.as_deref(), Some("#89b4fa"));
+        // names that aren't in the palette are terminal colors
+        let ty = &theme.get_scope("entity.name.type").unwrap().settings;
+        assert_eq!(ty.foreground.as_deref(), Some("#5c5cff"));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        assert_eq!(Theme::load("default").unwrap().name, Theme::default().name);
+        assert!(Theme::load("src/fixtures/GitHub.tmTheme").is_ok());
+        assert!(Theme::load("src/fixtures/tokyo-night-color-theme.json").is_ok());
+
+        let err = Theme::load("no-such-theme").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "E185: Cannot find color scheme 'no-such-theme'"
+        );
+    }
+}
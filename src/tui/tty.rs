@@ -0,0 +1,52 @@
+use std::{
+    io::stdout,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crossterm::{
+    cursor::SetCursorStyle,
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+
+/// Whether the terminal is set up for the editor, so it's restored only once.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Holds the terminal in the state the editor draws in: the alternate screen, raw mode, mouse
+/// capture and bracketed paste. Dropping it puts the terminal back the way it was, whether the
+/// editor quits, returns an error or panics.
+pub struct Terminal;
+
+impl Terminal {
+    pub fn enter() -> anyhow::Result<Self> {
+        ACTIVE.store(true, Ordering::SeqCst);
+        // from here on a failure restores whatever was already set up
+        let terminal = Terminal;
+        stdout().execute(EnterAlternateScreen)?;
+        terminal::enable_raw_mode()?;
+        stdout().execute(EnableMouseCapture)?;
+        stdout().execute(EnableBracketedPaste)?;
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Undoes what [`Terminal::enter`] set up, and the cursor style the editor changed. Errors are
+/// ignored since there's nothing left to do about them. Called by the panic hook too, so the
+/// panic message is printed on the main screen.
+pub fn restore() {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let _ = stdout().execute(DisableBracketedPaste);
+    let _ = stdout().execute(DisableMouseCapture);
+    let _ = stdout().execute(SetCursorStyle::DefaultUserShape);
+    let _ = terminal::disable_raw_mode();
+    let _ = stdout().execute(LeaveAlternateScreen);
+}
@@ -14,7 +14,7 @@ pub fn hex_to_crossterm_color(hex: &str) -> Result<style::Color, ParseIntError>
 }
 
 pub fn adjust_brightness(color: Color, factor: f32) -> anyhow::Result<style::Color> {
-    assert!(factor >= -1.0 && factor <= 1.0 && factor != 0.0);
+    assert!((-1.0..=1.0).contains(&factor) && factor != 0.0);
 
     let Color::Rgb { r, g, b } = color else {
         return Err(anyhow::anyhow!("Unable to fade non-rgb colors"));
@@ -43,6 +43,53 @@ pub fn darken(color: Color, factor: f32) -> anyhow::Result<style::Color> {
     adjust_brightness(color, -factor)
 }
 
+/// Mixes `factor` of `other` into `color`, leaving `color` as is when either isn't rgb.
+pub fn blend(color: Color, other: Color, factor: f32) -> Color {
+    let (
+        Color::Rgb { r, g, b },
+        Color::Rgb {
+            r: r2,
+            g: g2,
+            b: b2,
+        },
+    ) = (color, other)
+    else {
+        return color;
+    };
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * factor).round() as u8;
+    Color::Rgb {
+        r: mix(r, r2),
+        g: mix(g, g2),
+        b: mix(b, b2),
+    }
+}
+
+/// The columns `c` takes in a terminal: two for East Asian wide chars and emoji, none for
+/// combining marks and other zero width chars, one for the rest.
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036f | 0x200b..=0x200f | 0xfe00..=0xfe0f => 0,
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1f64f
+        | 0x1f900..=0x1f9ff
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// The columns `text` takes in a terminal.
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+#[allow(unused)]
 pub fn hex_to_rgb(hex: &str) -> Result<[u8; 3], ParseIntError> {
     let hex = hex.trim_start_matches('#');
 
@@ -0,0 +1,76 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Tells when files change by polling their modified times, which is cheap enough to do every
+/// time the editor is idle.
+#[derive(Debug, Default)]
+pub struct Watcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Watcher {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        Watcher {
+            files: paths
+                .into_iter()
+                .map(|path| {
+                    let modified = modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the files that were modified, created or removed since the last check.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for (path, last) in self.files.iter_mut() {
+            let modified = modified(path);
+            if modified != *last {
+                *last = modified;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+/// When `path` was last modified, `None` when it doesn't exist.
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_changed() {
+        let path = std::env::temp_dir().join(format!("fed-watcher-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut watcher = Watcher::new([path.clone()]);
+        assert!(watcher.changed().is_empty());
+
+        fs::write(&path, "a").unwrap();
+        assert_eq!(watcher.changed(), vec![path.clone()]);
+        assert!(watcher.changed().is_empty());
+
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(watcher.changed(), vec![path.clone()]);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.changed(), vec![path]);
+    }
+}
@@ -0,0 +1,81 @@
+//! The directory the editor works in, which `:cd` and `:lcd` change. Relative file names, `:grep`,
+//! the picker and shell commands all go from it.
+
+use std::path::{Component, Path, PathBuf};
+
+/// The directory `:cd` goes to for `arg`: the home directory without one, with a leading `~`
+/// standing for it.
+pub fn expand(arg: Option<&str>) -> Option<PathBuf> {
+    match arg {
+        None | Some("~") => dirs::home_dir(),
+        Some(arg) => match arg.strip_prefix("~/") {
+            Some(rest) => dirs::home_dir().map(|home| home.join(rest)),
+            None => Some(PathBuf::from(arg)),
+        },
+    }
+}
+
+/// The root of the git repository `file` is in, the nearest directory up from it with a `.git`.
+pub fn project_root(file: &Path) -> Option<PathBuf> {
+    let file = std::path::absolute(file).ok()?;
+    file.ancestors()
+        .skip(1)
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// The name of `file`, relative to `from` unless absolute, once the editor moved to `to`:
+/// relative to `to` when it's under it, absolute otherwise.
+pub fn relocate(file: &str, from: &Path, to: &Path) -> String {
+    let path = normalize(&from.join(file));
+    let path = path.strip_prefix(to).unwrap_or(&path);
+    path.to_string_lossy().to_string()
+}
+
+/// Drops the `.` and folds the `..` of `path` without looking at the file system, so
+/// `/a/b/../c` becomes `/a/c`.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_relocate() {
+        let (root, src) = (Path::new("/work"), Path::new("/work/src"));
+        assert_eq!(relocate("src/main.rs", root, src), "main.rs");
+        assert_eq!(relocate("main.rs", src, root), "src/main.rs");
+        assert_eq!(relocate("../README.md", src, root), "README.md");
+        assert_eq!(
+            relocate("./lib.rs", root, Path::new("/other")),
+            "/work/lib.rs"
+        );
+        assert_eq!(relocate("/etc/hosts", root, src), "/etc/hosts");
+    }
+
+    #[test]
+    fn test_project_root() {
+        let root = std::env::temp_dir().join(format!("fed-root-{}", std::process::id()));
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+
+        let found = project_root(&root.join("src/bin/main.rs"));
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(found, Some(root));
+        assert_eq!(expand(Some("src")), Some(PathBuf::from("src")));
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+/// A file in a fresh directory of its own, holding `text`.
+fn file(name: &str, text: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fed-batch-{}-{name}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    path
+}
+
+/// Runs `fed --batch script` on `files`, away from the user's config and log.
+fn fed(script: &str, files: &[&PathBuf]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fed"))
+        .args(["--config", "/nonexistent/fed.toml", "--log-level", "off"])
+        .args(["--batch", script])
+        .args(files)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_substitute_and_write() {
+    let path = file("a.txt", "foo one\nfoo two\n");
+    let output = fed("%s/foo/bar/g | w", &[&path]);
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "bar one\nbar two\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("written"), "{stdout}");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_script_file() {
+    let path = file("b.txt", "c\nb\na\n");
+    let script = file("b.ex", "%!sort\n2d\nwq\n");
+    let output = fed(&script.to_string_lossy(), &[&path]);
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a\nc\n");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    fs::remove_dir_all(script.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_failing_command() {
+    let path = file("c.txt", "one\n");
+    let output = fed("s/missing/x/ | w", &[&path]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("E486: Pattern not found"), "{stderr}");
+    // the commands after the failing one don't run
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one\n");
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}